@@ -69,3 +69,20 @@ pub enum SyncMode {
     /// Full synchronization (flush to disk)
     Full,
 }
+
+/// How WAL recovery treats torn and corrupted records
+///
+/// A crash in the middle of an append leaves a partially written record at
+/// the end of the log. Media errors or bugs can damage records anywhere.
+/// The modes mirror RocksDB's `WALRecoveryMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WalRecoveryMode {
+    /// Drop a torn or corrupted final record, fail on damage anywhere else
+    TolerateCorruptedTailRecords,
+    /// Fail recovery on any torn or corrupted record
+    AbsoluteConsistency,
+    /// Stop at the first damaged record and discard everything after it
+    PointInTimeRecovery,
+    /// Skip damaged records and keep replaying the rest of the log
+    SkipAnyCorruptedRecords,
+}
@@ -0,0 +1,278 @@
+//! Leveled compaction
+//!
+//! Flushes add overlapping SSTables to L0, so without compaction every read
+//! has to consult a growing number of files. Compaction merges files from
+//! one level into the next, keeping deeper levels sorted and disjoint:
+//!
+//! ```text
+//! L0: [a..z] [c..m] [b..q]        ← overlapping, newest first
+//!         ↓ merge all L0 files with overlapping L1 files
+//! L1: [a..f] [g..p] [q..z]        ← disjoint, sorted by key
+//!         ↓ merge one L1 file with overlapping L2 files
+//! L2: [a..c] [d..h] ... [w..z]    ← 10x larger than L1
+//! ```
+//!
+//! A level is picked by score. L0 scores by file count against
+//! `level0_file_num_compaction_trigger`; every other level scores by total
+//! size against `max_bytes_for_level_base * multiplier^(level - 1)`. The
+//! level with the highest score of at least 1.0 is compacted next.
+//!
+//! While merging, only the newest version of each key is kept, and a
+//! tombstone is dropped once no deeper level can hold an older version of
+//! its key.
+
+use crate::version::{FileMetaData, Version, NUM_LEVELS};
+use crate::StorageConfig;
+use ferrisdb_core::Key;
+use std::sync::Arc;
+
+/// A compaction of files from `level` into `level + 1`
+#[derive(Debug, Clone)]
+pub struct Compaction {
+    /// Level the compaction reads from
+    pub level: usize,
+    /// Files from `level`, newest first for L0
+    pub inputs: Vec<Arc<FileMetaData>>,
+    /// Files from `level + 1` overlapping the inputs
+    pub next_level_inputs: Vec<Arc<FileMetaData>>,
+    /// Version the inputs were picked from
+    version: Arc<Version>,
+}
+
+impl Compaction {
+    /// Picks the most urgent compaction for `version`
+    ///
+    /// Returns `None` if no level scores at least 1.0. Within a level above
+    /// L0, the file after `compact_pointers[level]` is picked, so repeated
+    /// compactions rotate through the key space instead of rewriting the
+    /// same range.
+    pub fn pick(
+        version: &Arc<Version>,
+        config: &StorageConfig,
+        compact_pointers: &[Option<Key>],
+    ) -> Option<Self> {
+        let (level, score) = (0..NUM_LEVELS - 1)
+            .map(|level| (level, compaction_score(version, config, level)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        if score < 1.0 {
+            return None;
+        }
+
+        let files = version.files(level);
+        let inputs = if level == 0 {
+            files.to_vec()
+        } else {
+            let next = compact_pointers
+                .get(level)
+                .and_then(Option::as_ref)
+                .and_then(|pointer| {
+                    files
+                        .iter()
+                        .find(|file| file.smallest_key.user_key > *pointer)
+                })
+                .unwrap_or(&files[0]);
+            vec![Arc::clone(next)]
+        };
+
+        let (smallest, largest) = key_range(&inputs);
+        let next_level_inputs = version.overlapping_files(level + 1, smallest, largest);
+
+        Some(Self {
+            level,
+            inputs,
+            next_level_inputs,
+            version: Arc::clone(version),
+        })
+    }
+
+    /// Returns the level the compaction writes to
+    pub fn output_level(&self) -> usize {
+        self.level + 1
+    }
+
+    /// Iterates over every input as `(level, file)`, newest first
+    pub fn input_files(&self) -> impl Iterator<Item = (usize, &Arc<FileMetaData>)> {
+        let next_level = self.output_level();
+        self.inputs
+            .iter()
+            .map(move |file| (self.level, file))
+            .chain(
+                self.next_level_inputs
+                    .iter()
+                    .map(move |file| (next_level, file)),
+            )
+    }
+
+    /// Returns the user key range covered by the inputs from `level`
+    pub fn key_range(&self) -> (&[u8], &[u8]) {
+        key_range(&self.inputs)
+    }
+
+    /// Returns true if the single input can move down without rewriting
+    ///
+    /// This holds when one file overlaps nothing in the next level, so
+    /// placing it there unchanged keeps that level disjoint.
+    pub fn is_trivial_move(&self) -> bool {
+        self.inputs.len() == 1 && self.next_level_inputs.is_empty()
+    }
+
+    /// Returns true if no level below the output can hold `user_key`
+    ///
+    /// A tombstone for such a key shadows nothing outside the compaction,
+    /// so it can be dropped once older versions in the inputs are gone.
+    pub fn is_base_level_for_key(&self, user_key: &[u8]) -> bool {
+        (self.output_level() + 1..NUM_LEVELS).all(|level| {
+            self.version
+                .files(level)
+                .iter()
+                .all(|file| !file.contains(user_key))
+        })
+    }
+}
+
+/// Returns the size limit of `level`, which must be at least 1
+pub fn max_bytes_for_level(config: &StorageConfig, level: usize) -> u64 {
+    let base = config.max_bytes_for_level_base as f64;
+    (base * config.max_bytes_for_level_multiplier.powi(level as i32 - 1)) as u64
+}
+
+/// Returns how urgently `level` needs compaction; 1.0 or more means it does
+pub fn compaction_score(version: &Version, config: &StorageConfig, level: usize) -> f64 {
+    if level == 0 {
+        let trigger = config.level0_file_num_compaction_trigger.max(1);
+        version.files(0).len() as f64 / trigger as f64
+    } else {
+        version.level_size(level) as f64 / max_bytes_for_level(config, level).max(1) as f64
+    }
+}
+
+/// Returns the user key range spanned by `files`, which must not be empty
+fn key_range(files: &[Arc<FileMetaData>]) -> (&[u8], &[u8]) {
+    let smallest = files
+        .iter()
+        .map(|file| file.smallest_key.user_key.as_slice())
+        .min()
+        .unwrap_or_default();
+    let largest = files
+        .iter()
+        .map(|file| file.largest_key.user_key.as_slice())
+        .max()
+        .unwrap_or_default();
+    (smallest, largest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sstable::InternalKey;
+    use crate::version::VersionEdit;
+
+    fn test_file(number: u64, smallest: &[u8], largest: &[u8], file_size: u64) -> FileMetaData {
+        FileMetaData {
+            number,
+            file_size,
+            smallest_key: InternalKey::new(smallest.to_vec(), 2),
+            largest_key: InternalKey::new(largest.to_vec(), 1),
+        }
+    }
+
+    fn test_config() -> StorageConfig {
+        StorageConfig {
+            level0_file_num_compaction_trigger: 2,
+            max_bytes_for_level_base: 1000,
+            max_bytes_for_level_multiplier: 10.0,
+            ..Default::default()
+        }
+    }
+
+    fn numbers(files: &[Arc<FileMetaData>]) -> Vec<u64> {
+        files.iter().map(|file| file.number).collect()
+    }
+
+    fn version_with(files: &[(usize, FileMetaData)]) -> Arc<Version> {
+        let mut edit = VersionEdit::default();
+        for (level, file) in files {
+            edit.add_file(*level, file.clone());
+        }
+        Arc::new(Version::new().apply(&edit).unwrap())
+    }
+
+    #[test]
+    fn test_level_limits() {
+        let config = test_config();
+        assert_eq!(max_bytes_for_level(&config, 1), 1000);
+        assert_eq!(max_bytes_for_level(&config, 2), 10_000);
+        assert_eq!(max_bytes_for_level(&config, 3), 100_000);
+    }
+
+    #[test]
+    fn test_pick_level0_by_file_count() {
+        let config = test_config();
+        let version = version_with(&[
+            (0, test_file(5, b"c", b"k", 100)),
+            (1, test_file(1, b"a", b"b", 100)),
+            (1, test_file(2, b"d", b"f", 100)),
+            (1, test_file(3, b"m", b"z", 100)),
+        ]);
+        assert!(Compaction::pick(&version, &config, &[]).is_none());
+
+        let version = Arc::new(
+            version
+                .apply(&{
+                    let mut edit = VersionEdit::default();
+                    edit.add_file(0, test_file(6, b"e", b"g", 100));
+                    edit
+                })
+                .unwrap(),
+        );
+        let compaction = Compaction::pick(&version, &config, &[]).unwrap();
+        assert_eq!(compaction.level, 0);
+        assert_eq!(numbers(&compaction.inputs), vec![6, 5]);
+        assert_eq!(numbers(&compaction.next_level_inputs), vec![2]);
+        assert_eq!(compaction.key_range(), (&b"c"[..], &b"k"[..]));
+        assert!(!compaction.is_trivial_move());
+    }
+
+    #[test]
+    fn test_pick_rotates_through_level() {
+        let config = test_config();
+        let version = version_with(&[
+            (1, test_file(1, b"a", b"c", 600)),
+            (1, test_file(2, b"d", b"f", 600)),
+            (2, test_file(3, b"e", b"h", 100)),
+        ]);
+        let mut pointers = vec![None; NUM_LEVELS];
+
+        let first = Compaction::pick(&version, &config, &pointers).unwrap();
+        assert_eq!(first.level, 1);
+        assert_eq!(numbers(&first.inputs), vec![1]);
+        assert!(first.is_trivial_move());
+
+        pointers[1] = Some(first.key_range().1.to_vec());
+        let second = Compaction::pick(&version, &config, &pointers).unwrap();
+        assert_eq!(numbers(&second.inputs), vec![2]);
+        assert_eq!(numbers(&second.next_level_inputs), vec![3]);
+
+        // Past the last file the pointer wraps around
+        pointers[1] = Some(second.key_range().1.to_vec());
+        let third = Compaction::pick(&version, &config, &pointers).unwrap();
+        assert_eq!(numbers(&third.inputs), vec![1]);
+    }
+
+    #[test]
+    fn test_base_level_for_key() {
+        let config = test_config();
+        let version = version_with(&[
+            (0, test_file(7, b"a", b"z", 100)),
+            (0, test_file(8, b"a", b"z", 100)),
+            (1, test_file(4, b"a", b"m", 100)),
+            (3, test_file(2, b"k", b"p", 100)),
+        ]);
+        let compaction = Compaction::pick(&version, &config, &[]).unwrap();
+        assert_eq!(compaction.output_level(), 1);
+
+        assert!(compaction.is_base_level_for_key(b"b"));
+        assert!(!compaction.is_base_level_for_key(b"m"));
+        assert!(compaction.is_base_level_for_key(b"q"));
+    }
+}
@@ -1,6 +1,6 @@
 //! Configuration for the storage engine
 
-use ferrisdb_core::{CompressionType, SyncMode};
+use ferrisdb_core::{CompressionType, SyncMode, WalRecoveryMode};
 use std::path::PathBuf;
 
 /// Configuration options for the storage engine
@@ -12,7 +12,7 @@ use std::path::PathBuf;
 ///
 /// ```
 /// use ferrisdb_storage::StorageConfig;
-/// use ferrisdb_core::{CompressionType, SyncMode};
+/// use ferrisdb_core::{CompressionType, SyncMode, WalRecoveryMode};
 ///
 /// let config = StorageConfig {
 ///     data_dir: "./data".into(),
@@ -37,9 +37,18 @@ pub struct StorageConfig {
     /// - `Full`: Sync to disk
     pub wal_sync_mode: SyncMode,
 
-    /// Maximum size of a single WAL file before rotation (in bytes)
+    /// Size at which the WAL rolls over to a new segment file (in bytes)
     pub wal_size_limit: usize,
 
+    /// Directory flushed WAL segments are moved to instead of being deleted
+    ///
+    /// Archived segments are never read by the engine again. They are kept
+    /// for backups or for shipping the log elsewhere.
+    pub wal_archive_dir: Option<PathBuf>,
+
+    /// How torn and corrupted WAL records are handled during recovery
+    pub wal_recovery_mode: WalRecoveryMode,
+
     /// Maximum size of active MemTable before flush (in bytes)
     pub memtable_size: usize,
 
@@ -49,6 +58,12 @@ pub struct StorageConfig {
     /// Size of each data block in SSTable files (in bytes)
     pub block_size: usize,
 
+    /// Number of entries between restart points in a data block
+    ///
+    /// Keys between restart points are prefix-compressed against the
+    /// previous key; lookups decode up to this many entries per seek.
+    pub block_restart_interval: usize,
+
     /// Compression algorithm for SSTable blocks
     pub compression: CompressionType,
 
@@ -61,11 +76,30 @@ pub struct StorageConfig {
     /// Size multiplier between levels (L2 = L1 * multiplier)
     pub max_bytes_for_level_multiplier: f64,
 
+    /// Size at which compaction starts a new output SSTable (in bytes)
+    pub target_file_size: u64,
+
     /// Size of the block cache for SSTable reads (in bytes)
     pub block_cache_size: usize,
 
+    /// Maximum number of SSTable files kept open at once
+    pub max_open_files: usize,
+
+    /// Read SSTables through memory maps instead of positional reads
+    ///
+    /// Avoids copying blocks out of the page cache, which helps read-heavy
+    /// workloads whose files fit in memory.
+    pub use_mmap_reads: bool,
+
     /// Bits per key for bloom filters (10 = ~1% false positive rate)
     pub bloom_filter_bits_per_key: i32,
+
+    /// Write SSTables with a partitioned index and bloom filter
+    ///
+    /// Readers then keep only a small top-level index in memory and load
+    /// partitions through the block cache, so very large files stay cheap
+    /// to hold open.
+    pub partitioned_index: bool,
 }
 
 impl Default for StorageConfig {
@@ -75,15 +109,22 @@ impl Default for StorageConfig {
             wal_dir: PathBuf::from("./data/wal"),
             wal_sync_mode: SyncMode::Normal,
             wal_size_limit: 64 * 1024 * 1024, // 64MB
-            memtable_size: 4 * 1024 * 1024,   // 4MB
+            wal_archive_dir: None,
+            wal_recovery_mode: WalRecoveryMode::TolerateCorruptedTailRecords,
+            memtable_size: 4 * 1024 * 1024, // 4MB
             max_immutable_memtables: 2,
             block_size: 4 * 1024, // 4KB
+            block_restart_interval: 16,
             compression: CompressionType::Lz4,
             level0_file_num_compaction_trigger: 4,
             max_bytes_for_level_base: 10 * 1024 * 1024, // 10MB
             max_bytes_for_level_multiplier: 10.0,
+            target_file_size: 2 * 1024 * 1024,   // 2MB
             block_cache_size: 128 * 1024 * 1024, // 128MB
+            max_open_files: 1000,
+            use_mmap_reads: false,
             bloom_filter_bits_per_key: 10,
+            partitioned_index: false,
         }
     }
 }
@@ -0,0 +1,140 @@
+//! File naming conventions for the storage engine
+//!
+//! Every file the engine creates is identified by a monotonically increasing
+//! file number shared between WAL segments, SSTables and MANIFESTs:
+//!
+//! ```text
+//! wal_dir/000007.log        ← Write-ahead log segment
+//! data_dir/000008.sst       ← SSTable
+//! data_dir/000009.tmp       ← SSTable being written, renamed once complete
+//! data_dir/MANIFEST-000010  ← Log of version edits
+//! data_dir/CURRENT          ← Name of the live MANIFEST
+//! ```
+
+use std::path::{Path, PathBuf};
+
+/// Extension used for WAL segment files
+pub const WAL_EXTENSION: &str = "log";
+
+/// Extension used for SSTable files
+pub const SSTABLE_EXTENSION: &str = "sst";
+
+/// Extension used for files that are still being written
+pub const TEMP_EXTENSION: &str = "tmp";
+
+/// Name of the file pointing at the live MANIFEST
+pub const CURRENT_FILE: &str = "CURRENT";
+
+/// Prefix of MANIFEST file names
+const MANIFEST_PREFIX: &str = "MANIFEST-";
+
+/// Returns the path of the WAL segment with the given number
+pub fn wal_file_path(dir: &Path, number: u64) -> PathBuf {
+    dir.join(format!("{:06}.{}", number, WAL_EXTENSION))
+}
+
+/// Returns the path of the SSTable with the given number
+pub fn sstable_file_path(dir: &Path, number: u64) -> PathBuf {
+    dir.join(format!("{:06}.{}", number, SSTABLE_EXTENSION))
+}
+
+/// Returns the file name of the MANIFEST with the given number
+pub fn manifest_file_name(number: u64) -> String {
+    format!("{}{:06}", MANIFEST_PREFIX, number)
+}
+
+/// Parses the number out of a MANIFEST file name
+pub fn parse_manifest_name(name: &str) -> Option<u64> {
+    name.strip_prefix(MANIFEST_PREFIX)?.parse().ok()
+}
+
+/// Returns the temporary path a file with the given number is written under
+pub fn temp_file_path(dir: &Path, number: u64) -> PathBuf {
+    dir.join(format!("{:06}.{}", number, TEMP_EXTENSION))
+}
+
+/// Flushes a directory's entries so newly created or renamed files survive a crash
+pub fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    // Directories cannot be opened as files on every platform
+    #[cfg(unix)]
+    std::fs::File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// Parses the file number out of a `<number>.<extension>` file name
+///
+/// Returns `None` if the name does not match the expected pattern.
+pub fn parse_file_number(path: &Path, extension: &str) -> Option<u64> {
+    if path.extension()? != extension {
+        return None;
+    }
+    path.file_stem()?.to_str()?.parse().ok()
+}
+
+/// Lists the numbers of all files with the given extension in `dir`
+///
+/// The result is sorted in ascending order. A missing directory is treated
+/// as empty.
+pub fn list_file_numbers(dir: &Path, extension: &str) -> std::io::Result<Vec<u64>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut numbers = Vec::new();
+    for entry in entries {
+        if let Some(number) = parse_file_number(&entry?.path(), extension) {
+            numbers.push(number);
+        }
+    }
+    numbers.sort_unstable();
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_file_paths_round_trip() {
+        let dir = Path::new("/tmp/db");
+
+        let wal = wal_file_path(dir, 7);
+        assert_eq!(wal, Path::new("/tmp/db/000007.log"));
+        assert_eq!(parse_file_number(&wal, WAL_EXTENSION), Some(7));
+        assert_eq!(parse_file_number(&wal, SSTABLE_EXTENSION), None);
+
+        let sst = sstable_file_path(dir, 1234567);
+        assert_eq!(sst, Path::new("/tmp/db/1234567.sst"));
+        assert_eq!(parse_file_number(&sst, SSTABLE_EXTENSION), Some(1234567));
+
+        let manifest = manifest_file_name(12);
+        assert_eq!(manifest, "MANIFEST-000012");
+        assert_eq!(parse_manifest_name(&manifest), Some(12));
+        assert_eq!(parse_manifest_name("000012.log"), None);
+    }
+
+    #[test]
+    fn test_list_file_numbers() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in [
+            "000003.log",
+            "000001.log",
+            "000002.sst",
+            "notes.log",
+            "LOCK",
+        ] {
+            std::fs::write(temp_dir.path().join(name), b"").unwrap();
+        }
+
+        let logs = list_file_numbers(temp_dir.path(), WAL_EXTENSION).unwrap();
+        assert_eq!(logs, vec![1, 3]);
+
+        let missing = list_file_numbers(&temp_dir.path().join("missing"), WAL_EXTENSION).unwrap();
+        assert!(missing.is_empty());
+    }
+}
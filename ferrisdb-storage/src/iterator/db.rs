@@ -0,0 +1,630 @@
+use crate::iterator::{Direction, InternalIterator, MergingIterator};
+use crate::sstable::{InternalKey, ReadOptions};
+use ferrisdb_core::{Error, Key, Operation, Result, Timestamp, Value};
+
+/// Options for a scan over the database
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IteratorOptions {
+    /// Smallest key to return (inclusive)
+    pub lower_bound: Option<Key>,
+    /// Key to stop before (exclusive)
+    pub upper_bound: Option<Key>,
+    /// Only return keys starting with this prefix
+    ///
+    /// Narrows the bounds to the keys sharing the prefix, so SSTables
+    /// holding none of them are not read at all.
+    pub prefix: Option<Key>,
+    /// Options for the SSTable reads
+    ///
+    /// Long scans should turn off `fill_cache`.
+    pub read_options: ReadOptions,
+}
+
+impl IteratorOptions {
+    /// Returns the effective `[lower, upper)` bounds, combining the prefix
+    /// with the explicit bounds
+    pub fn key_range(&self) -> (Option<Key>, Option<Key>) {
+        let Some(prefix) = &self.prefix else {
+            return (self.lower_bound.clone(), self.upper_bound.clone());
+        };
+        let lower = match &self.lower_bound {
+            Some(lower) if lower > prefix => lower.clone(),
+            _ => prefix.clone(),
+        };
+        let upper = match (&self.upper_bound, prefix_successor(prefix)) {
+            (Some(upper), Some(successor)) => Some(upper.min(&successor).clone()),
+            (upper, successor) => upper.clone().or(successor),
+        };
+        (Some(lower), upper)
+    }
+}
+
+/// Returns the smallest key greater than every key starting with `prefix`,
+/// or `None` if there is none (the prefix is all `0xff` bytes)
+fn prefix_successor(prefix: &[u8]) -> Option<Key> {
+    let end = prefix.iter().rposition(|&byte| byte != 0xff)?;
+    let mut successor = prefix[..=end].to_vec();
+    successor[end] += 1;
+    Some(successor)
+}
+
+/// A cursor over the logical contents of the database
+///
+/// Wraps a [`MergingIterator`] over every MemTable and SSTable and shows
+/// the newest version of each key no newer than the read timestamp.
+/// Deleted keys and keys outside the bounds are skipped.
+///
+/// Moving forward, the merge sits on the entry being returned. Moving
+/// backward, the versions of a key are visited oldest first, so the merge
+/// runs one key ahead and the current entry is copied out.
+///
+/// # Example
+///
+/// ```
+/// use ferrisdb_storage::{StorageConfig, StorageEngine};
+/// # let dir = tempfile::TempDir::new()?;
+/// # let config = StorageConfig {
+/// #     data_dir: dir.path().join("data"),
+/// #     wal_dir: dir.path().join("wal"),
+/// #     ..Default::default()
+/// # };
+///
+/// let engine = StorageEngine::new(config)?;
+/// engine.put(b"a".to_vec(), b"1".to_vec())?;
+/// engine.put(b"b".to_vec(), b"2".to_vec())?;
+///
+/// let mut iter = engine.iter()?;
+/// iter.seek_to_last();
+/// while iter.valid() {
+///     println!("{:?} = {:?}", iter.key(), iter.value());
+///     iter.prev();
+/// }
+/// iter.status()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct DBIterator {
+    inner: MergingIterator,
+    read_timestamp: Timestamp,
+    lower_bound: Option<Key>,
+    upper_bound: Option<Key>,
+    direction: Direction,
+    valid: bool,
+    /// Forward: the key whose older versions are being skipped. Reverse:
+    /// the current key
+    saved_key: Key,
+    /// Reverse: the current value
+    saved_value: Value,
+    /// Error from the merge that invalidated the cursor
+    error: Option<Error>,
+}
+
+impl DBIterator {
+    /// Creates an unpositioned cursor showing the entries of `inner` as of
+    /// `read_timestamp`
+    pub fn new(
+        inner: MergingIterator,
+        read_timestamp: Timestamp,
+        options: &IteratorOptions,
+    ) -> Self {
+        let (lower_bound, upper_bound) = options.key_range();
+        Self {
+            inner,
+            read_timestamp,
+            lower_bound,
+            upper_bound,
+            direction: Direction::Forward,
+            valid: false,
+            saved_key: Vec::new(),
+            saved_value: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Returns true if the cursor is positioned on a key
+    pub fn valid(&self) -> bool {
+        self.valid
+    }
+
+    /// Returns the current key
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cursor is not valid.
+    pub fn key(&self) -> &[u8] {
+        assert!(self.valid, "DBIterator is not positioned on a key");
+        match self.direction {
+            Direction::Forward => self.inner.key(),
+            Direction::Reverse => &self.saved_key,
+        }
+    }
+
+    /// Returns the value of the current key
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cursor is not valid.
+    pub fn value(&self) -> &[u8] {
+        assert!(self.valid, "DBIterator is not positioned on a key");
+        match self.direction {
+            Direction::Forward => self.inner.value(),
+            Direction::Reverse => &self.saved_value,
+        }
+    }
+
+    /// Returns the error that invalidated the cursor, if any
+    ///
+    /// The error is handed to the caller, so a second call returns `Ok`.
+    pub fn status(&mut self) -> Result<()> {
+        self.error.take().map_or(Ok(()), Err)
+    }
+
+    /// Positions the cursor on the first key
+    pub fn seek_to_first(&mut self) {
+        self.error = None;
+        self.direction = Direction::Forward;
+        match &self.lower_bound {
+            Some(lower) => self
+                .inner
+                .seek(&InternalKey::new(lower.clone(), self.read_timestamp)),
+            None => self.inner.seek_to_first(),
+        }
+        self.find_next_user_entry(false);
+    }
+
+    /// Positions the cursor on the last key
+    pub fn seek_to_last(&mut self) {
+        self.error = None;
+        self.direction = Direction::Reverse;
+        match &self.upper_bound {
+            Some(upper) => self
+                .inner
+                .seek_for_prev(&InternalKey::new(upper.clone(), Timestamp::MAX)),
+            None => self.inner.seek_to_last(),
+        }
+        self.find_prev_user_entry();
+    }
+
+    /// Positions the cursor on the first key at or after `key`
+    pub fn seek(&mut self, key: &[u8]) {
+        self.error = None;
+        self.direction = Direction::Forward;
+        let key = match &self.lower_bound {
+            Some(lower) if lower.as_slice() > key => lower.clone(),
+            _ => key.to_vec(),
+        };
+        self.inner.seek(&InternalKey::new(key, self.read_timestamp));
+        self.find_next_user_entry(false);
+    }
+
+    /// Positions the cursor on the last key at or before `key`
+    pub fn seek_for_prev(&mut self, key: &[u8]) {
+        self.error = None;
+        self.direction = Direction::Reverse;
+        // Timestamp 0 sorts after every version of the key
+        self.inner.seek_for_prev(&InternalKey::new(key.to_vec(), 0));
+        self.find_prev_user_entry();
+    }
+
+    /// Moves to the next key; does nothing if the cursor is not valid
+    pub fn next(&mut self) {
+        if !self.valid {
+            return;
+        }
+        if self.direction == Direction::Reverse {
+            // The merge is just before the current key's versions
+            self.direction = Direction::Forward;
+            if self.inner.valid() {
+                self.inner.next();
+            } else {
+                self.inner.seek_to_first();
+            }
+        } else {
+            self.saved_key.clear();
+            self.saved_key.extend_from_slice(self.inner.key());
+            self.inner.next();
+        }
+        self.find_next_user_entry(true);
+    }
+
+    /// Moves to the previous key; does nothing if the cursor is not valid
+    pub fn prev(&mut self) {
+        if !self.valid {
+            return;
+        }
+        if self.direction == Direction::Forward {
+            // Back up past every version of the current key
+            self.saved_key.clear();
+            self.saved_key.extend_from_slice(self.inner.key());
+            loop {
+                self.inner.prev();
+                if !self.inner.valid() || self.inner.key() < self.saved_key.as_slice() {
+                    break;
+                }
+            }
+            self.direction = Direction::Reverse;
+        }
+        self.find_prev_user_entry();
+    }
+
+    /// Moves the merge forward to the newest visible version of the next
+    /// live key
+    ///
+    /// With `skipping` set, versions of keys up to `saved_key` are hidden.
+    fn find_next_user_entry(&mut self, mut skipping: bool) {
+        while self.inner.valid() {
+            let key = self.inner.key();
+            if self
+                .upper_bound
+                .as_ref()
+                .is_some_and(|upper| key >= upper.as_slice())
+            {
+                self.valid = false;
+                return;
+            }
+            if self.inner.timestamp() <= self.read_timestamp
+                && !(skipping && key <= self.saved_key.as_slice())
+            {
+                match self.inner.operation() {
+                    Operation::Put => {
+                        self.valid = true;
+                        return;
+                    }
+                    Operation::Delete => {
+                        // Older versions of the key are deleted too
+                        self.saved_key.clear();
+                        self.saved_key.extend_from_slice(key);
+                        skipping = true;
+                    }
+                }
+            }
+            self.inner.next();
+        }
+        self.valid = false;
+        self.check_inner();
+    }
+
+    /// Moves the merge backward past the versions of the previous live key,
+    /// copying out its newest visible version
+    fn find_prev_user_entry(&mut self) {
+        let mut found = false;
+        while self.inner.valid() {
+            let key = self.inner.key();
+            if self
+                .upper_bound
+                .as_ref()
+                .is_some_and(|upper| key >= upper.as_slice())
+            {
+                self.inner.prev();
+                continue;
+            }
+            if self
+                .lower_bound
+                .as_ref()
+                .is_some_and(|lower| key < lower.as_slice())
+            {
+                break;
+            }
+            if self.inner.timestamp() <= self.read_timestamp {
+                if found && key < self.saved_key.as_slice() {
+                    // Every version of the saved key has been seen
+                    break;
+                }
+                // Versions arrive oldest first, so each replaces the last
+                match self.inner.operation() {
+                    Operation::Put => {
+                        self.saved_key.clear();
+                        self.saved_key.extend_from_slice(key);
+                        self.saved_value.clear();
+                        self.saved_value.extend_from_slice(self.inner.value());
+                        found = true;
+                    }
+                    Operation::Delete => found = false,
+                }
+            }
+            self.inner.prev();
+        }
+        self.valid = found;
+        self.check_inner();
+    }
+
+    /// Ends the scan if the merge stopped on an error
+    fn check_inner(&mut self) {
+        if self.inner.valid() {
+            return;
+        }
+        if let Err(e) = self.inner.status() {
+            self.error = Some(e);
+            self.valid = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iterator::VecIterator;
+    use crate::sstable::SSTableEntry;
+    use std::collections::BTreeMap;
+
+    fn entry(key: &str, timestamp: Timestamp, operation: Operation) -> SSTableEntry {
+        let value = match operation {
+            Operation::Put => format!("{}@{}", key, timestamp).into_bytes(),
+            Operation::Delete => Vec::new(),
+        };
+        SSTableEntry::new(
+            InternalKey::new(key.as_bytes().to_vec(), timestamp),
+            value,
+            operation,
+        )
+    }
+
+    /// Sources newest first, as the engine passes MemTables and SSTables
+    fn sources() -> Vec<Vec<SSTableEntry>> {
+        use Operation::{Delete, Put};
+        let mut sources = vec![
+            vec![
+                entry("apple", 40, Put),
+                entry("banana", 41, Delete),
+                entry("cherry", 42, Put),
+                entry("grape", 43, Put),
+            ],
+            vec![
+                entry("apple", 30, Delete),
+                entry("apricot", 31, Put),
+                entry("banana", 32, Put),
+                entry("cherry", 33, Delete),
+                entry("date", 34, Delete),
+                entry("fig", 35, Put),
+            ],
+            vec![
+                entry("apple", 10, Put),
+                entry("banana", 11, Put),
+                entry("date", 12, Put),
+                entry("elderberry", 13, Put),
+                entry("grape", 14, Put),
+            ],
+        ];
+        // Many versions of one key, so hiding them takes more than one step
+        sources[2].extend((1..=20).rev().map(|ts| entry("kiwi", ts, Put)));
+        sources
+    }
+
+    fn iterator(read_timestamp: Timestamp, options: &IteratorOptions) -> DBIterator {
+        let children = sources()
+            .into_iter()
+            .map(|entries| Box::new(VecIterator::new(entries)) as Box<dyn InternalIterator>)
+            .collect();
+        DBIterator::new(MergingIterator::new(children), read_timestamp, options)
+    }
+
+    /// The visible key-value pairs as of `read_timestamp`
+    fn model(read_timestamp: Timestamp, options: &IteratorOptions) -> Vec<(String, String)> {
+        let (lower, upper) = options.key_range();
+        let mut newest: BTreeMap<Key, SSTableEntry> = BTreeMap::new();
+        for entry in sources().into_iter().flatten() {
+            if entry.key.timestamp > read_timestamp {
+                continue;
+            }
+            let key = entry.key.user_key.clone();
+            if newest
+                .get(&key)
+                .is_none_or(|e| e.key.timestamp < entry.key.timestamp)
+            {
+                newest.insert(key, entry);
+            }
+        }
+        newest
+            .into_values()
+            .filter(|e| e.operation == Operation::Put)
+            .filter(|e| lower.as_ref().is_none_or(|l| e.key.user_key >= *l))
+            .filter(|e| upper.as_ref().is_none_or(|u| e.key.user_key < *u))
+            .map(|e| {
+                (
+                    String::from_utf8(e.key.user_key).unwrap(),
+                    String::from_utf8(e.value).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn current(iter: &DBIterator) -> (String, String) {
+        (
+            String::from_utf8(iter.key().to_vec()).unwrap(),
+            String::from_utf8(iter.value().to_vec()).unwrap(),
+        )
+    }
+
+    fn scan(iter: &mut DBIterator, reverse: bool) -> Vec<(String, String)> {
+        let mut keys = Vec::new();
+        if reverse {
+            iter.seek_to_last();
+        } else {
+            iter.seek_to_first();
+        }
+        while iter.valid() {
+            keys.push(current(iter));
+            if reverse {
+                iter.prev();
+            } else {
+                iter.next();
+            }
+        }
+        iter.status().unwrap();
+        if reverse {
+            keys.reverse();
+        }
+        keys
+    }
+
+    fn option_sets() -> Vec<IteratorOptions> {
+        vec![
+            IteratorOptions::default(),
+            IteratorOptions {
+                lower_bound: Some(b"b".to_vec()),
+                upper_bound: Some(b"f".to_vec()),
+                ..Default::default()
+            },
+            IteratorOptions {
+                lower_bound: Some(b"banana".to_vec()),
+                upper_bound: Some(b"fig".to_vec()),
+                ..Default::default()
+            },
+            IteratorOptions {
+                prefix: Some(b"ap".to_vec()),
+                ..Default::default()
+            },
+            IteratorOptions {
+                prefix: Some(b"d".to_vec()),
+                lower_bound: Some(b"a".to_vec()),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_resolves_versions_and_tombstones() {
+        for read_timestamp in [0, 5, 12, 20, 31, 33, 40, 41, 42, Timestamp::MAX] {
+            for options in option_sets() {
+                let expected = model(read_timestamp, &options);
+                let mut iter = iterator(read_timestamp, &options);
+                assert_eq!(scan(&mut iter, false), expected, "at {}", read_timestamp);
+                assert_eq!(scan(&mut iter, true), expected, "at {}", read_timestamp);
+            }
+        }
+
+        let expected: Vec<_> = [
+            "apple",
+            "apricot",
+            "cherry",
+            "elderberry",
+            "fig",
+            "grape",
+            "kiwi",
+        ]
+        .iter()
+        .map(|key| key.to_string())
+        .collect();
+        let keys: Vec<_> = scan(&mut iterator(Timestamp::MAX, &Default::default()), false)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn test_changes_direction() {
+        for options in option_sets() {
+            let expected = model(Timestamp::MAX, &options);
+            let mut iter = iterator(Timestamp::MAX, &options);
+            for start in 0..expected.len() {
+                iter.seek_to_first();
+                for _ in 0..start {
+                    iter.next();
+                }
+                assert_eq!(current(&iter), expected[start]);
+                iter.prev();
+                assert_eq!(
+                    iter.valid().then(|| current(&iter)).as_ref(),
+                    start.checked_sub(1).map(|i| &expected[i])
+                );
+                if start > 0 {
+                    iter.next();
+                    assert_eq!(current(&iter), expected[start]);
+                }
+
+                iter.seek_to_last();
+                for _ in start + 1..expected.len() {
+                    iter.prev();
+                }
+                assert_eq!(current(&iter), expected[start]);
+                iter.next();
+                assert_eq!(
+                    iter.valid().then(|| current(&iter)).as_ref(),
+                    expected.get(start + 1)
+                );
+                if iter.valid() {
+                    iter.prev();
+                    assert_eq!(current(&iter), expected[start]);
+                }
+            }
+
+            // Stepping past either end and back does not come back
+            iter.seek_to_first();
+            iter.prev();
+            assert!(!iter.valid());
+            iter.next();
+            assert!(!iter.valid());
+        }
+    }
+
+    #[test]
+    fn test_seeks() {
+        let mut iter = iterator(Timestamp::MAX, &Default::default());
+        for (target, at_or_after, at_or_before) in [
+            ("", Some("apple"), None),
+            ("apple", Some("apple"), Some("apple")),
+            ("apples", Some("apricot"), Some("apple")),
+            ("b", Some("cherry"), Some("apricot")),
+            ("cherry", Some("cherry"), Some("cherry")),
+            ("date", Some("elderberry"), Some("cherry")),
+            ("kiwi", Some("kiwi"), Some("kiwi")),
+            ("z", None, Some("kiwi")),
+        ] {
+            iter.seek(target.as_bytes());
+            assert_eq!(
+                iter.valid().then(|| current(&iter).0).as_deref(),
+                at_or_after
+            );
+            iter.seek_for_prev(target.as_bytes());
+            assert_eq!(
+                iter.valid().then(|| current(&iter).0).as_deref(),
+                at_or_before
+            );
+        }
+
+        // Seeks stay within the bounds
+        let options = IteratorOptions {
+            lower_bound: Some(b"b".to_vec()),
+            upper_bound: Some(b"f".to_vec()),
+            ..Default::default()
+        };
+        let mut iter = iterator(Timestamp::MAX, &options);
+        iter.seek(b"a");
+        assert_eq!(current(&iter).0, "cherry");
+        iter.seek_for_prev(b"z");
+        assert_eq!(current(&iter).0, "elderberry");
+        iter.seek_for_prev(b"apricot");
+        assert!(!iter.valid());
+    }
+
+    #[test]
+    fn test_prefix_bounds() {
+        let options = |prefix: &[u8], lower: Option<&[u8]>, upper: Option<&[u8]>| IteratorOptions {
+            prefix: Some(prefix.to_vec()),
+            lower_bound: lower.map(<[u8]>::to_vec),
+            upper_bound: upper.map(<[u8]>::to_vec),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options(b"ab", None, None).key_range(),
+            (Some(b"ab".to_vec()), Some(b"ac".to_vec()))
+        );
+        assert_eq!(
+            options(b"a\xff\xff", None, None).key_range(),
+            (Some(b"a\xff\xff".to_vec()), Some(b"b".to_vec()))
+        );
+        assert_eq!(
+            options(b"\xff", None, None).key_range(),
+            (Some(b"\xff".to_vec()), None)
+        );
+        assert_eq!(
+            options(b"ab", Some(b"abc"), Some(b"abd")).key_range(),
+            (Some(b"abc".to_vec()), Some(b"abd".to_vec()))
+        );
+        assert_eq!(
+            options(b"ab", Some(b"a"), Some(b"b")).key_range(),
+            (Some(b"ab".to_vec()), Some(b"ac".to_vec()))
+        );
+    }
+}
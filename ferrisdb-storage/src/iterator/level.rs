@@ -0,0 +1,327 @@
+use crate::iterator::InternalIterator;
+use crate::sstable::{InternalKey, ReadOptions, SSTableCursor, SSTableReader, TableCache};
+use crate::version::FileMetaData;
+use ferrisdb_core::{Error, Operation, Result, Timestamp};
+use std::sync::Arc;
+
+/// Concatenates the tables of one level ≥ 1 into a single cursor
+///
+/// Tables below L0 are sorted and disjoint, so the level reads like one
+/// long table. Only the table under the cursor is open: the next one is
+/// fetched from the [`TableCache`] when the cursor moves past either end,
+/// so a scan over a level holds one reader rather than one per file.
+///
+/// The cursor holds the `FileMetaData` of every table it may still read,
+/// which keeps the engine from deleting them after a compaction.
+pub(crate) struct LevelIterator {
+    table_cache: Arc<TableCache>,
+    /// Tables of the level in key order
+    files: Vec<Arc<FileMetaData>>,
+    options: ReadOptions,
+    /// Position in `files` of the open table
+    index: usize,
+    /// Cursor over the open table; `None` while the cursor is invalid
+    current: Option<SSTableCursor<Arc<SSTableReader>>>,
+    /// Error that invalidated the cursor
+    error: Option<Error>,
+}
+
+impl LevelIterator {
+    /// Creates an unpositioned cursor over `files`, which must be sorted
+    /// and disjoint
+    pub(crate) fn new(
+        table_cache: Arc<TableCache>,
+        files: Vec<Arc<FileMetaData>>,
+        options: ReadOptions,
+    ) -> Self {
+        Self {
+            table_cache,
+            files,
+            options,
+            index: 0,
+            current: None,
+            error: None,
+        }
+    }
+
+    fn cursor(&self) -> &SSTableCursor<Arc<SSTableReader>> {
+        self.current
+            .as_ref()
+            .expect("LevelIterator is not positioned on an entry")
+    }
+
+    /// Opens table `index`, closing the previous one first
+    ///
+    /// Returns false if there is no such table or it cannot be opened.
+    fn open(&mut self, index: usize) -> bool {
+        self.current = None;
+        self.index = index;
+        let Some(file) = self.files.get(index) else {
+            return false;
+        };
+        match self.table_cache.get(file.number) {
+            Ok(reader) => {
+                self.current = Some(SSTableCursor::new(reader, self.options));
+                true
+            }
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+
+    /// Takes the error of an exhausted table cursor, if it has one
+    fn take_cursor_error(&mut self) -> bool {
+        let Some(cursor) = self.current.as_mut() else {
+            return false;
+        };
+        match cursor.status() {
+            Ok(()) => false,
+            Err(e) => {
+                self.error = Some(e);
+                self.current = None;
+                true
+            }
+        }
+    }
+
+    /// Moves on to the first entry of the following tables while the
+    /// current one is exhausted
+    fn skip_forward(&mut self) {
+        while self.current.as_ref().is_some_and(|c| !c.valid()) {
+            if self.take_cursor_error() || !self.open(self.index + 1) {
+                self.current = None;
+                return;
+            }
+            if let Some(cursor) = self.current.as_mut() {
+                cursor.seek_to_first();
+            }
+        }
+    }
+
+    /// Moves back to the last entry of the preceding tables while the
+    /// current one is exhausted
+    fn skip_backward(&mut self) {
+        while self.current.as_ref().is_some_and(|c| !c.valid()) {
+            if self.take_cursor_error() || self.index == 0 || !self.open(self.index - 1) {
+                self.current = None;
+                return;
+            }
+            if let Some(cursor) = self.current.as_mut() {
+                cursor.seek_to_last();
+            }
+        }
+    }
+}
+
+impl InternalIterator for LevelIterator {
+    fn valid(&self) -> bool {
+        self.current.as_ref().is_some_and(|c| c.valid())
+    }
+
+    fn seek_to_first(&mut self) {
+        self.error = None;
+        if self.open(0) {
+            if let Some(cursor) = self.current.as_mut() {
+                cursor.seek_to_first();
+            }
+            self.skip_forward();
+        }
+    }
+
+    fn seek_to_last(&mut self) {
+        self.error = None;
+        if self.files.is_empty() {
+            self.current = None;
+        } else if self.open(self.files.len() - 1) {
+            if let Some(cursor) = self.current.as_mut() {
+                cursor.seek_to_last();
+            }
+            self.skip_backward();
+        }
+    }
+
+    fn seek(&mut self, target: &InternalKey) {
+        self.error = None;
+        // The first table whose largest key is not before the target
+        let index = self
+            .files
+            .partition_point(|file| file.largest_key < *target);
+        if self.open(index) {
+            if let Some(cursor) = self.current.as_mut() {
+                cursor.seek(target);
+            }
+            self.skip_forward();
+        }
+    }
+
+    fn seek_for_prev(&mut self, target: &InternalKey) {
+        self.error = None;
+        // The last table whose smallest key is not after the target
+        let index = self
+            .files
+            .partition_point(|file| file.smallest_key <= *target);
+        if index == 0 {
+            self.current = None;
+        } else if self.open(index - 1) {
+            if let Some(cursor) = self.current.as_mut() {
+                cursor.seek_for_prev(target);
+            }
+            self.skip_backward();
+        }
+    }
+
+    fn next(&mut self) {
+        if let Some(cursor) = self.current.as_mut() {
+            cursor.next();
+            self.skip_forward();
+        }
+    }
+
+    fn prev(&mut self) {
+        if let Some(cursor) = self.current.as_mut() {
+            cursor.prev();
+            self.skip_backward();
+        }
+    }
+
+    fn key(&self) -> &[u8] {
+        self.cursor().key()
+    }
+
+    fn timestamp(&self) -> Timestamp {
+        self.cursor().timestamp()
+    }
+
+    fn operation(&self) -> Operation {
+        self.cursor().operation()
+    }
+
+    fn value(&self) -> &[u8] {
+        self.cursor().value()
+    }
+
+    fn status(&mut self) -> Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        match self.current.as_mut() {
+            Some(cursor) => cursor.status(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filename;
+    use crate::sstable::{BlockCache, SSTableWriter};
+    use tempfile::TempDir;
+
+    /// Writes tables 1..=3 holding keys 00-09, 10-19 and 20-29
+    fn level(dir: &TempDir, max_open_files: usize) -> LevelIterator {
+        let mut files = Vec::new();
+        for number in 1..=3u64 {
+            let path = filename::sstable_file_path(dir.path(), number);
+            let mut writer = SSTableWriter::new(path).unwrap();
+            for i in 0..10 {
+                let key = format!("key_{:02}", (number - 1) * 10 + i).into_bytes();
+                writer
+                    .add(
+                        InternalKey::new(key, number),
+                        vec![number as u8],
+                        Operation::Put,
+                    )
+                    .unwrap();
+            }
+            let info = writer.finish().unwrap();
+            files.push(Arc::new(FileMetaData {
+                number,
+                file_size: info.file_size,
+                smallest_key: info.smallest_key,
+                largest_key: info.largest_key,
+            }));
+        }
+
+        let block_cache = Arc::new(BlockCache::new(1024 * 1024));
+        let table_cache = Arc::new(TableCache::new(dir.path(), block_cache, max_open_files));
+        LevelIterator::new(table_cache, files, ReadOptions::default())
+    }
+
+    fn key(i: usize) -> InternalKey {
+        InternalKey::new(format!("key_{:02}", i).into_bytes(), u64::MAX)
+    }
+
+    #[test]
+    fn test_scans_across_tables_in_both_directions() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut level = level(&temp_dir, 1);
+
+        let mut forward = Vec::new();
+        level.seek_to_first();
+        while level.valid() {
+            forward.push(level.key().to_vec());
+            level.next();
+        }
+        level.status().unwrap();
+        let expected: Vec<_> = (0..30).map(|i| key(i).user_key).collect();
+        assert_eq!(forward, expected);
+
+        let mut backward = Vec::new();
+        level.seek_to_last();
+        while level.valid() {
+            backward.push(level.key().to_vec());
+            level.prev();
+        }
+        level.status().unwrap();
+        backward.reverse();
+        assert_eq!(backward, expected);
+
+        assert!(level.table_cache.stats().open_files <= 1);
+    }
+
+    #[test]
+    fn test_seeks_open_the_table_holding_the_target() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut level = level(&temp_dir, 10);
+
+        level.seek(&key(15));
+        assert_eq!(level.key(), key(15).user_key);
+        assert_eq!(level.value(), &[2]);
+        assert_eq!(level.table_cache.stats().opens, 1);
+
+        // Past the end of one table lands at the start of the next
+        level.seek(&InternalKey::new(b"key_09~".to_vec(), u64::MAX));
+        assert_eq!(level.key(), key(10).user_key);
+
+        level.seek_for_prev(&InternalKey::new(b"key_09~".to_vec(), 0));
+        assert_eq!(level.key(), key(9).user_key);
+        level.next();
+        assert_eq!(level.key(), key(10).user_key);
+
+        level.seek(&key(30));
+        assert!(!level.valid());
+        level.seek_for_prev(&InternalKey::new(b"a".to_vec(), 0));
+        assert!(!level.valid());
+        level.status().unwrap();
+    }
+
+    #[test]
+    fn test_missing_table_is_reported_by_status() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut level = level(&temp_dir, 10);
+        std::fs::remove_file(filename::sstable_file_path(temp_dir.path(), 2)).unwrap();
+
+        level.seek_to_first();
+        let mut count = 0;
+        while level.valid() {
+            count += 1;
+            level.next();
+        }
+        assert_eq!(count, 10);
+        assert!(level.status().is_err());
+        assert!(level.status().is_ok());
+    }
+}
@@ -0,0 +1,446 @@
+use crate::iterator::{Direction, InternalIterator};
+use crate::sstable::InternalKey;
+use ferrisdb_core::{Error, Operation, Result, Timestamp};
+use std::cmp::Ordering;
+
+/// Merges sorted children into one cursor in internal key order
+///
+/// The valid children are kept in a binary heap keyed by their current
+/// entries: a min-heap while moving forward and a max-heap while moving
+/// backward, so each step costs O(log k) for k children. The heap holds
+/// child positions rather than copies of their keys.
+///
+/// Entries with identical internal keys are ordered by child position, so
+/// callers should pass newer sources first. Changing direction repositions
+/// every child around the current entry.
+///
+/// An error in any child ends the merge: the cursor becomes invalid and
+/// [`status`](Self::status) reports the error, since skipping a source
+/// could resurrect data it shadows.
+pub struct MergingIterator {
+    children: Vec<Box<dyn InternalIterator>>,
+    /// Positions of the valid children, ordered as a heap by `direction`
+    heap: Vec<usize>,
+    direction: Direction,
+    error: Option<Error>,
+}
+
+impl MergingIterator {
+    /// Creates an unpositioned merge over `children`, newest first
+    pub fn new(children: Vec<Box<dyn InternalIterator>>) -> Self {
+        Self {
+            heap: Vec::with_capacity(children.len()),
+            children,
+            direction: Direction::Forward,
+            error: None,
+        }
+    }
+
+    /// Returns the position of the child holding the current entry
+    fn current(&self) -> usize {
+        *self
+            .heap
+            .first()
+            .expect("MergingIterator is not positioned on an entry")
+    }
+
+    /// Orders the current entries of children `a` and `b`
+    fn compare(&self, a: usize, b: usize) -> Ordering {
+        let (x, y) = (&self.children[a], &self.children[b]);
+        x.key()
+            .cmp(y.key())
+            .then_with(|| y.timestamp().cmp(&x.timestamp()))
+            .then_with(|| a.cmp(&b))
+    }
+
+    /// Returns true if child `a` belongs above child `b` in the heap
+    fn precedes(&self, a: usize, b: usize) -> bool {
+        match self.direction {
+            Direction::Forward => self.compare(a, b) == Ordering::Less,
+            Direction::Reverse => self.compare(a, b) == Ordering::Greater,
+        }
+    }
+
+    fn sift_down(&mut self, mut slot: usize) {
+        loop {
+            let mut top = slot;
+            for child in [2 * slot + 1, 2 * slot + 2] {
+                if child < self.heap.len() && self.precedes(self.heap[child], self.heap[top]) {
+                    top = child;
+                }
+            }
+            if top == slot {
+                return;
+            }
+            self.heap.swap(slot, top);
+            slot = top;
+        }
+    }
+
+    /// Rebuilds the heap from every valid child, moving in `direction`
+    fn rebuild(&mut self, direction: Direction) {
+        self.direction = direction;
+        self.heap.clear();
+        for child in 0..self.children.len() {
+            if self.children[child].valid() {
+                self.heap.push(child);
+            } else if !self.check(child) {
+                return;
+            }
+        }
+        for slot in (0..self.heap.len() / 2).rev() {
+            self.sift_down(slot);
+        }
+    }
+
+    /// Restores the heap after its top child moved
+    fn fix_top(&mut self) {
+        let child = self.current();
+        if self.children[child].valid() {
+            self.sift_down(0);
+            return;
+        }
+        self.heap.swap_remove(0);
+        if self.check(child) {
+            self.sift_down(0);
+        }
+    }
+
+    /// Checks an invalid child for an error, ending the merge if it has one
+    fn check(&mut self, child: usize) -> bool {
+        match self.children[child].status() {
+            Ok(()) => true,
+            Err(e) => {
+                self.error = Some(e);
+                self.heap.clear();
+                false
+            }
+        }
+    }
+
+    /// Copies the current internal key
+    fn current_key(&self) -> InternalKey {
+        InternalKey::new(self.key().to_vec(), self.timestamp())
+    }
+}
+
+impl InternalIterator for MergingIterator {
+    fn valid(&self) -> bool {
+        !self.heap.is_empty()
+    }
+
+    fn seek_to_first(&mut self) {
+        self.error = None;
+        self.children.iter_mut().for_each(|c| c.seek_to_first());
+        self.rebuild(Direction::Forward);
+    }
+
+    fn seek_to_last(&mut self) {
+        self.error = None;
+        self.children.iter_mut().for_each(|c| c.seek_to_last());
+        self.rebuild(Direction::Reverse);
+    }
+
+    fn seek(&mut self, target: &InternalKey) {
+        self.error = None;
+        self.children.iter_mut().for_each(|c| c.seek(target));
+        self.rebuild(Direction::Forward);
+    }
+
+    fn seek_for_prev(&mut self, target: &InternalKey) {
+        self.error = None;
+        self.children
+            .iter_mut()
+            .for_each(|c| c.seek_for_prev(target));
+        self.rebuild(Direction::Reverse);
+    }
+
+    fn next(&mut self) {
+        if !self.valid() {
+            return;
+        }
+        if self.direction == Direction::Reverse {
+            // Move every other child to its first entry after the current
+            // one; equal keys in earlier children sort before it
+            let current = self.current();
+            let key = self.current_key();
+            for (i, child) in self.children.iter_mut().enumerate() {
+                if i == current {
+                    continue;
+                }
+                child.seek(&key);
+                if i < current && child.valid() && is_at(child.as_ref(), &key) {
+                    child.next();
+                }
+            }
+            self.children[current].next();
+            self.rebuild(Direction::Forward);
+            return;
+        }
+        let current = self.current();
+        self.children[current].next();
+        self.fix_top();
+    }
+
+    fn prev(&mut self) {
+        if !self.valid() {
+            return;
+        }
+        if self.direction == Direction::Forward {
+            // Move every other child to its last entry before the current
+            // one; equal keys in later children sort after it
+            let current = self.current();
+            let key = self.current_key();
+            for (i, child) in self.children.iter_mut().enumerate() {
+                if i == current {
+                    continue;
+                }
+                child.seek_for_prev(&key);
+                if i > current && child.valid() && is_at(child.as_ref(), &key) {
+                    child.prev();
+                }
+            }
+            self.children[current].prev();
+            self.rebuild(Direction::Reverse);
+            return;
+        }
+        let current = self.current();
+        self.children[current].prev();
+        self.fix_top();
+    }
+
+    fn key(&self) -> &[u8] {
+        self.children[self.current()].key()
+    }
+
+    fn timestamp(&self) -> Timestamp {
+        self.children[self.current()].timestamp()
+    }
+
+    fn operation(&self) -> Operation {
+        self.children[self.current()].operation()
+    }
+
+    fn value(&self) -> &[u8] {
+        self.children[self.current()].value()
+    }
+
+    fn status(&mut self) -> Result<()> {
+        self.error.take().map_or(Ok(()), Err)
+    }
+}
+
+/// Returns true if `child` is positioned exactly on `key`
+fn is_at(child: &dyn InternalIterator, key: &InternalKey) -> bool {
+    child.key() == key.user_key.as_slice() && child.timestamp() == key.timestamp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iterator::VecIterator;
+    use crate::sstable::SSTableEntry;
+
+    fn entries(keys: &[(&str, u64)]) -> Vec<SSTableEntry> {
+        keys.iter()
+            .map(|&(key, timestamp)| {
+                SSTableEntry::new(
+                    InternalKey::new(key.as_bytes().to_vec(), timestamp),
+                    format!("{}@{}", key, timestamp).into_bytes(),
+                    Operation::Put,
+                )
+            })
+            .collect()
+    }
+
+    fn sources() -> Vec<Vec<SSTableEntry>> {
+        vec![
+            entries(&[("a", 9), ("c", 8), ("e", 7)]),
+            Vec::new(),
+            entries(&[("a", 3), ("b", 2), ("c", 8), ("c", 1), ("f", 1)]),
+            entries(&[("d", 5)]),
+        ]
+    }
+
+    fn merge(sources: &[Vec<SSTableEntry>]) -> MergingIterator {
+        MergingIterator::new(
+            sources
+                .iter()
+                .map(|entries| {
+                    Box::new(VecIterator::new(entries.clone())) as Box<dyn InternalIterator>
+                })
+                .collect(),
+        )
+    }
+
+    fn position(merge: &MergingIterator) -> Option<(String, u64, String)> {
+        merge.valid().then(|| {
+            (
+                String::from_utf8(merge.key().to_vec()).unwrap(),
+                merge.timestamp(),
+                String::from_utf8(merge.value().to_vec()).unwrap(),
+            )
+        })
+    }
+
+    /// Every entry in merged order, with equal keys in source order
+    fn expected() -> Vec<(String, u64, String)> {
+        let mut all: Vec<_> = sources()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(source, entries)| entries.into_iter().map(move |e| (e, source)))
+            .collect();
+        all.sort_by(|(a, x), (b, y)| a.key.cmp(&b.key).then(x.cmp(y)));
+        all.into_iter()
+            .map(|(e, _)| {
+                (
+                    String::from_utf8(e.key.user_key).unwrap(),
+                    e.key.timestamp,
+                    String::from_utf8(e.value).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merges_in_both_directions() {
+        let mut merge = merge(&sources());
+        assert!(!merge.valid());
+
+        let mut forward = Vec::new();
+        merge.seek_to_first();
+        while let Some(entry) = position(&merge) {
+            forward.push(entry);
+            merge.next();
+        }
+        assert_eq!(forward, expected());
+
+        let mut backward = Vec::new();
+        merge.seek_to_last();
+        while let Some(entry) = position(&merge) {
+            backward.push(entry);
+            merge.prev();
+        }
+        backward.reverse();
+        assert_eq!(backward, expected());
+        merge.status().unwrap();
+    }
+
+    #[test]
+    fn test_changes_direction() {
+        let expected = expected();
+        let mut merge = merge(&sources());
+
+        // Walk forward and back from every position, including through the
+        // duplicate c@8 held by two sources
+        for start in 0..expected.len() {
+            merge.seek_to_first();
+            for _ in 0..start {
+                merge.next();
+            }
+            assert_eq!(position(&merge).as_ref(), Some(&expected[start]));
+            merge.prev();
+            assert_eq!(
+                position(&merge).as_ref(),
+                start.checked_sub(1).map(|i| &expected[i])
+            );
+            merge.seek_to_last();
+            for _ in start..expected.len() - 1 {
+                merge.prev();
+            }
+            assert_eq!(position(&merge).as_ref(), Some(&expected[start]));
+            merge.next();
+            assert_eq!(position(&merge).as_ref(), expected.get(start + 1));
+        }
+    }
+
+    #[test]
+    fn test_seeks() {
+        let mut merge = merge(&sources());
+
+        merge.seek(&InternalKey::new(b"c".to_vec(), 5));
+        assert_eq!(position(&merge).unwrap().2, "c@1");
+        merge.seek(&InternalKey::new(b"bb".to_vec(), 0));
+        assert_eq!(position(&merge).unwrap().2, "c@8");
+        merge.seek(&InternalKey::new(b"g".to_vec(), 9));
+        assert!(!merge.valid());
+
+        merge.seek_for_prev(&InternalKey::new(b"c".to_vec(), 5));
+        assert_eq!(position(&merge).unwrap().2, "c@8");
+        merge.prev();
+        assert_eq!(position(&merge).unwrap().2, "c@8");
+        merge.prev();
+        assert_eq!(position(&merge).unwrap().2, "b@2");
+        merge.seek_for_prev(&InternalKey::new(b"0".to_vec(), 0));
+        assert!(!merge.valid());
+    }
+
+    /// A child that fails once it moves past its first entry
+    struct FailingIterator {
+        inner: VecIterator,
+        error: Option<Error>,
+    }
+
+    impl InternalIterator for FailingIterator {
+        fn valid(&self) -> bool {
+            self.error.is_none() && self.inner.valid()
+        }
+        fn seek_to_first(&mut self) {
+            self.inner.seek_to_first()
+        }
+        fn seek_to_last(&mut self) {
+            self.inner.seek_to_last()
+        }
+        fn seek(&mut self, target: &InternalKey) {
+            self.inner.seek(target)
+        }
+        fn seek_for_prev(&mut self, target: &InternalKey) {
+            self.inner.seek_for_prev(target)
+        }
+        fn next(&mut self) {
+            self.error = Some(Error::Corruption("bad block".to_string()));
+        }
+        fn prev(&mut self) {
+            self.inner.prev()
+        }
+        fn key(&self) -> &[u8] {
+            self.inner.key()
+        }
+        fn timestamp(&self) -> Timestamp {
+            self.inner.timestamp()
+        }
+        fn operation(&self) -> Operation {
+            self.inner.operation()
+        }
+        fn value(&self) -> &[u8] {
+            self.inner.value()
+        }
+        fn status(&mut self) -> Result<()> {
+            self.error.take().map_or(Ok(()), Err)
+        }
+    }
+
+    #[test]
+    fn test_child_error_ends_merge() {
+        let sources = sources();
+        let mut merge = MergingIterator::new(vec![
+            Box::new(VecIterator::new(sources[0].clone())),
+            Box::new(FailingIterator {
+                inner: VecIterator::new(sources[2].clone()),
+                error: None,
+            }),
+        ]);
+
+        merge.seek_to_first();
+        assert_eq!(position(&merge).unwrap().2, "a@9");
+        merge.next();
+        assert_eq!(position(&merge).unwrap().2, "a@3");
+        merge.next();
+        assert!(!merge.valid());
+        assert!(matches!(merge.status(), Err(Error::Corruption(_))));
+
+        // Seeking starts over
+        merge.seek_to_first();
+        assert!(merge.valid());
+    }
+}
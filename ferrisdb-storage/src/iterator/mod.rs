@@ -0,0 +1,189 @@
+//! Iterators over the logical database
+//!
+//! Scans are built from three layers:
+//!
+//! ```text
+//! DBIterator          ← newest visible version per key, no tombstones
+//!     ↑
+//! MergingIterator     ← every source in internal key order
+//!     ↑
+//! MemTables, SSTables ← one InternalIterator each
+//! ```
+//!
+//! Every layer is a cursor that can seek and move in both directions, so a
+//! scan holds one position per source rather than a copy of the data. L0
+//! tables may overlap and get a cursor each; the disjoint tables of every
+//! deeper level are read through one level cursor.
+//! Internal entries are ordered by user key ascending, then timestamp
+//! descending, so the newest version of a key comes first.
+
+mod db;
+mod level;
+mod merging;
+#[cfg(test)]
+mod vec;
+
+pub use db::{DBIterator, IteratorOptions};
+pub(crate) use level::LevelIterator;
+pub use merging::MergingIterator;
+#[cfg(test)]
+pub(crate) use vec::VecIterator;
+
+use crate::memtable::SkipListIterator;
+use crate::sstable::{InternalKey, SSTableCursor, SSTableReader};
+use ferrisdb_core::{Operation, Result, Timestamp};
+use std::ops::Deref;
+
+/// Which way a cursor last moved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Forward,
+    Reverse,
+}
+
+/// A seekable, bidirectional cursor over internal entries
+///
+/// Movement does not fail outright. An error leaves the cursor invalid and
+/// is reported by [`status`](Self::status). The accessors may panic unless
+/// [`valid`](Self::valid) returns true.
+pub trait InternalIterator: Send {
+    /// Returns true if the cursor is positioned on an entry
+    fn valid(&self) -> bool;
+
+    /// Positions the cursor on the first entry
+    fn seek_to_first(&mut self);
+
+    /// Positions the cursor on the last entry
+    fn seek_to_last(&mut self);
+
+    /// Positions the cursor on the first entry at or after `target`
+    fn seek(&mut self, target: &InternalKey);
+
+    /// Positions the cursor on the last entry at or before `target`
+    fn seek_for_prev(&mut self, target: &InternalKey);
+
+    /// Moves to the next entry; does nothing if the cursor is not valid
+    fn next(&mut self);
+
+    /// Moves to the previous entry; does nothing if the cursor is not valid
+    fn prev(&mut self);
+
+    /// Returns the user key of the current entry
+    fn key(&self) -> &[u8];
+
+    /// Returns the timestamp of the current entry
+    fn timestamp(&self) -> Timestamp;
+
+    /// Returns the operation of the current entry
+    fn operation(&self) -> Operation;
+
+    /// Returns the value of the current entry
+    fn value(&self) -> &[u8];
+
+    /// Returns the error that invalidated the cursor, if any, handing it to
+    /// the caller
+    fn status(&mut self) -> Result<()>;
+}
+
+impl<R> InternalIterator for SSTableCursor<R>
+where
+    R: Deref<Target = SSTableReader> + Send,
+{
+    fn valid(&self) -> bool {
+        SSTableCursor::valid(self)
+    }
+
+    fn seek_to_first(&mut self) {
+        SSTableCursor::seek_to_first(self)
+    }
+
+    fn seek_to_last(&mut self) {
+        SSTableCursor::seek_to_last(self)
+    }
+
+    fn seek(&mut self, target: &InternalKey) {
+        SSTableCursor::seek(self, target)
+    }
+
+    fn seek_for_prev(&mut self, target: &InternalKey) {
+        SSTableCursor::seek_for_prev(self, target)
+    }
+
+    fn next(&mut self) {
+        SSTableCursor::next(self)
+    }
+
+    fn prev(&mut self) {
+        SSTableCursor::prev(self)
+    }
+
+    fn key(&self) -> &[u8] {
+        SSTableCursor::key(self)
+    }
+
+    fn timestamp(&self) -> Timestamp {
+        SSTableCursor::timestamp(self)
+    }
+
+    fn operation(&self) -> Operation {
+        SSTableCursor::operation(self)
+    }
+
+    fn value(&self) -> &[u8] {
+        SSTableCursor::value(self)
+    }
+
+    fn status(&mut self) -> Result<()> {
+        SSTableCursor::status(self)
+    }
+}
+
+impl InternalIterator for SkipListIterator {
+    fn valid(&self) -> bool {
+        SkipListIterator::valid(self)
+    }
+
+    fn seek_to_first(&mut self) {
+        SkipListIterator::seek_to_first(self)
+    }
+
+    fn seek_to_last(&mut self) {
+        SkipListIterator::seek_to_last(self)
+    }
+
+    fn seek(&mut self, target: &InternalKey) {
+        SkipListIterator::seek(self, &target.user_key, target.timestamp)
+    }
+
+    fn seek_for_prev(&mut self, target: &InternalKey) {
+        SkipListIterator::seek_for_prev(self, &target.user_key, target.timestamp)
+    }
+
+    fn next(&mut self) {
+        SkipListIterator::next(self)
+    }
+
+    fn prev(&mut self) {
+        SkipListIterator::prev(self)
+    }
+
+    fn key(&self) -> &[u8] {
+        SkipListIterator::key(self)
+    }
+
+    fn timestamp(&self) -> Timestamp {
+        SkipListIterator::timestamp(self)
+    }
+
+    fn operation(&self) -> Operation {
+        SkipListIterator::operation(self)
+    }
+
+    fn value(&self) -> &[u8] {
+        SkipListIterator::value(self)
+    }
+
+    fn status(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
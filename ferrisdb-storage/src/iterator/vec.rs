@@ -0,0 +1,82 @@
+//! In-memory cursor used by the iterator tests
+
+use super::InternalIterator;
+use crate::sstable::{InternalKey, SSTableEntry};
+use ferrisdb_core::{Operation, Result, Timestamp};
+
+/// A cursor over entries held in a sorted vector
+pub(crate) struct VecIterator {
+    entries: Vec<SSTableEntry>,
+    /// Position of the current entry; `entries.len()` when invalid
+    position: usize,
+}
+
+impl VecIterator {
+    /// Creates an unpositioned cursor over `entries`
+    ///
+    /// The entries must be in internal key order.
+    pub(crate) fn new(entries: Vec<SSTableEntry>) -> Self {
+        debug_assert!(entries.windows(2).all(|pair| pair[0].key <= pair[1].key));
+        let position = entries.len();
+        Self { entries, position }
+    }
+
+    fn current(&self) -> &SSTableEntry {
+        &self.entries[self.position]
+    }
+}
+
+impl InternalIterator for VecIterator {
+    fn valid(&self) -> bool {
+        self.position < self.entries.len()
+    }
+
+    fn seek_to_first(&mut self) {
+        self.position = 0;
+    }
+
+    fn seek_to_last(&mut self) {
+        self.position = self.entries.len().saturating_sub(1);
+    }
+
+    fn seek(&mut self, target: &InternalKey) {
+        self.position = self.entries.partition_point(|entry| entry.key < *target);
+    }
+
+    fn seek_for_prev(&mut self, target: &InternalKey) {
+        let after = self.entries.partition_point(|entry| entry.key <= *target);
+        self.position = after.checked_sub(1).unwrap_or(self.entries.len());
+    }
+
+    fn next(&mut self) {
+        if self.valid() {
+            self.position += 1;
+        }
+    }
+
+    fn prev(&mut self) {
+        if self.valid() {
+            self.position = self.position.checked_sub(1).unwrap_or(self.entries.len());
+        }
+    }
+
+    fn key(&self) -> &[u8] {
+        &self.current().key.user_key
+    }
+
+    fn timestamp(&self) -> Timestamp {
+        self.current().key.timestamp
+    }
+
+    fn operation(&self) -> Operation {
+        self.current().operation
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.current().value
+    }
+
+    fn status(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
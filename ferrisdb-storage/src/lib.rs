@@ -6,7 +6,10 @@
 //! - **Write-Ahead Log (WAL)**: Ensures durability of writes
 //! - **MemTable**: In-memory write buffer using a skip list
 //! - **SSTable**: Sorted String Table for persistent storage
+//! - **Version set**: MANIFEST log recording which SSTables are live
 //! - **Compaction**: Background process to merge and optimize SSTables
+//! - **Iterators**: Ordered scans merging the MemTables and SSTables
+//! - **Write batches**: Groups of writes logged and applied atomically
 //!
 //! # Architecture
 //!
@@ -24,14 +27,26 @@
 //! use ferrisdb_storage::{StorageEngine, StorageConfig};
 //!
 //! let config = StorageConfig::default();
-//! let engine = StorageEngine::new(config);
+//! let engine = StorageEngine::new(config)?;
+//!
+//! engine.put(b"key".to_vec(), b"value".to_vec())?;
+//! let value = engine.get(b"key")?;
+//! # Ok::<(), ferrisdb_core::Error>(())
 //! ```
 
+pub mod compaction;
 pub mod config;
+mod filename;
+pub mod iterator;
 pub mod memtable;
 pub mod sstable;
 pub mod storage_engine;
+pub mod version;
 pub mod wal;
+pub mod write_batch;
 
 pub use config::StorageConfig;
+pub use iterator::{DBIterator, IteratorOptions};
+pub use sstable::ReadOptions;
 pub use storage_engine::StorageEngine;
+pub use write_batch::WriteBatch;
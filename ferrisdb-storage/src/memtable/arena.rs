@@ -0,0 +1,351 @@
+//! Bump allocator for skip list nodes
+//!
+//! The arena hands out memory from large chunks and never frees individual
+//! allocations. Everything it allocated is released at once when the arena
+//! is dropped, which matches the life of a MemTable: entries are only ever
+//! added, and the whole table is discarded after it is flushed.
+
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::alloc::{self, Layout};
+use std::ptr::{self, NonNull};
+#[cfg(not(loom))]
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// Size of the chunks small allocations are carved from
+#[cfg(not(loom))]
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Small enough for a model-checked test to fill a chunk
+#[cfg(loom)]
+const CHUNK_SIZE: usize = 1024;
+
+/// Alignment of every chunk, which bounds the alignment of allocations
+const CHUNK_ALIGN: usize = 16;
+
+/// A block of memory owned by the arena
+struct Chunk {
+    data: NonNull<u8>,
+    capacity: usize,
+    /// Bytes of `data` handed out so far
+    offset: AtomicUsize,
+    /// The chunk allocated before this one in the same list, if any
+    older: *mut Chunk,
+}
+
+impl Chunk {
+    /// Allocates a chunk and returns it as a raw pointer for the arena's
+    /// chunk lists
+    fn allocate(capacity: usize, older: *mut Chunk) -> *mut Chunk {
+        let layout = Self::layout(capacity);
+        let data = NonNull::new(unsafe { alloc::alloc(layout) })
+            .unwrap_or_else(|| alloc::handle_alloc_error(layout));
+
+        Box::into_raw(Box::new(Self {
+            data,
+            capacity,
+            offset: AtomicUsize::new(0),
+            older,
+        }))
+    }
+
+    fn layout(capacity: usize) -> Layout {
+        Layout::from_size_align(capacity, CHUNK_ALIGN).expect("arena chunk size overflows")
+    }
+
+    /// Carves `layout` out of the unused part of the chunk
+    ///
+    /// Returns the allocation and the number of bytes it consumed,
+    /// including alignment padding, or `None` if the chunk is too full.
+    fn try_allocate(&self, layout: Layout) -> Option<(NonNull<u8>, usize)> {
+        let mut offset = self.offset.load(Ordering::Relaxed);
+        loop {
+            let start = offset.checked_next_multiple_of(layout.align())?;
+            let end = start.checked_add(layout.size())?;
+            if end > self.capacity {
+                return None;
+            }
+
+            match self.offset.compare_exchange_weak(
+                offset,
+                end,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    let ptr = unsafe { NonNull::new_unchecked(self.data.as_ptr().add(start)) };
+                    return Some((ptr, end - offset));
+                }
+                Err(current) => offset = current,
+            }
+        }
+    }
+
+    /// Frees `chunk` and every older chunk linked from it
+    ///
+    /// # Safety
+    ///
+    /// The chunks must have come from [`allocate`](Self::allocate) and must
+    /// not be used afterwards.
+    unsafe fn free_list(mut chunk: *mut Chunk) {
+        while !chunk.is_null() {
+            let owned = unsafe { Box::from_raw(chunk) };
+            chunk = owned.older;
+        }
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.data.as_ptr(), Self::layout(self.capacity)) };
+    }
+}
+
+/// A lock-free bump allocator
+///
+/// Allocation is a compare-and-swap on the current chunk's offset. When the
+/// chunk is full, threads race to install a new one with a compare-and-swap
+/// on `current`; the losers free the chunk they made and retry in the
+/// winner's. Allocations larger than a quarter of a chunk get a chunk of
+/// their own, so they neither waste the rest of the current chunk nor force
+/// it to be retired.
+pub(crate) struct Arena {
+    /// Chunk small allocations are currently carved from, linked to the
+    /// chunks it replaced
+    current: AtomicPtr<Chunk>,
+    /// Most recent chunk holding a single large allocation, linked to the
+    /// earlier ones
+    dedicated: AtomicPtr<Chunk>,
+    /// Bytes handed out, counting alignment padding and the unused tails of
+    /// retired chunks
+    memory_usage: AtomicUsize,
+}
+
+// SAFETY: chunks are only freed when the arena is dropped, and the bytes
+// of each chunk are handed out at most once.
+unsafe impl Send for Arena {}
+unsafe impl Sync for Arena {}
+
+impl Arena {
+    /// Creates an arena with one empty chunk
+    pub(crate) fn new() -> Self {
+        Self {
+            current: AtomicPtr::new(Chunk::allocate(CHUNK_SIZE, ptr::null_mut())),
+            dedicated: AtomicPtr::new(ptr::null_mut()),
+            memory_usage: AtomicUsize::new(0),
+        }
+    }
+
+    /// Allocates uninitialized memory for `layout`
+    ///
+    /// The memory stays valid until the arena is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layout` needs more than 16-byte alignment.
+    pub(crate) fn allocate(&self, layout: Layout) -> NonNull<u8> {
+        assert!(
+            layout.align() <= CHUNK_ALIGN,
+            "arena allocations are at most {}-byte aligned",
+            CHUNK_ALIGN
+        );
+
+        if layout.size() > CHUNK_SIZE / 4 {
+            return self.allocate_dedicated(layout);
+        }
+
+        loop {
+            let current = self.current.load(Ordering::Acquire);
+            let chunk = unsafe { &*current };
+            if let Some((ptr, used)) = chunk.try_allocate(layout) {
+                self.memory_usage.fetch_add(used, Ordering::Relaxed);
+                return ptr;
+            }
+            self.replace_chunk(current);
+        }
+    }
+
+    /// Returns the bytes handed out so far
+    ///
+    /// This counts node headers, towers, keys and values, the padding
+    /// between them, and the space left unused at the end of full chunks.
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.memory_usage.load(Ordering::Relaxed)
+    }
+
+    fn allocate_dedicated(&self, layout: Layout) -> NonNull<u8> {
+        let chunk = Chunk::allocate(layout.size(), ptr::null_mut());
+        let data = unsafe { (*chunk).data };
+
+        let mut older = self.dedicated.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*chunk).older = older };
+            match self.dedicated.compare_exchange_weak(
+                older,
+                chunk,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(current) => older = current,
+            }
+        }
+
+        self.memory_usage
+            .fetch_add(layout.size(), Ordering::Relaxed);
+        data
+    }
+
+    /// Replaces `full` with a new chunk, unless another thread already has
+    fn replace_chunk(&self, full: *mut Chunk) {
+        let chunk = Chunk::allocate(CHUNK_SIZE, full);
+        if self
+            .current
+            .compare_exchange(full, chunk, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            // Dropping the chunk alone leaves `full` to the winner
+            drop(unsafe { Box::from_raw(chunk) });
+            return;
+        }
+
+        // Claim the tail so nothing else is carved from the retired chunk
+        let retired = unsafe { &*full };
+        let offset = retired.offset.swap(retired.capacity, Ordering::Relaxed);
+        self.memory_usage
+            .fetch_add(retired.capacity - offset, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        unsafe {
+            Chunk::free_list(self.current.load(Ordering::Relaxed));
+            Chunk::free_list(self.dedicated.load(Ordering::Relaxed));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_allocations_are_aligned_and_counted() {
+        let arena = Arena::new();
+        assert_eq!(arena.memory_usage(), 0);
+
+        let byte = arena.allocate(Layout::new::<u8>());
+        let word = arena.allocate(Layout::new::<u64>());
+        assert_eq!(word.as_ptr() as usize % 8, 0);
+        assert_eq!(word.as_ptr() as usize - byte.as_ptr() as usize, 8);
+        // One byte, seven bytes of padding, then the word
+        assert_eq!(arena.memory_usage(), 16);
+    }
+
+    #[test]
+    fn test_full_chunks_are_retired() {
+        let arena = Arena::new();
+        let layout = Layout::from_size_align(1000, 8).unwrap();
+
+        for _ in 0..200 {
+            arena.allocate(layout);
+        }
+
+        // Each 64 KiB chunk fits 65 allocations and wastes the remainder
+        let mut chunks = 0;
+        let mut chunk = arena.current.load(Ordering::Relaxed);
+        while !chunk.is_null() {
+            chunks += 1;
+            chunk = unsafe { (*chunk).older };
+        }
+        assert_eq!(chunks, 4);
+        assert_eq!(arena.memory_usage(), 3 * CHUNK_SIZE + (200 - 3 * 65) * 1000);
+    }
+
+    #[test]
+    fn test_large_allocations_get_their_own_chunk() {
+        let arena = Arena::new();
+        let small = arena.allocate(Layout::new::<u64>());
+        let large = arena.allocate(Layout::array::<u8>(CHUNK_SIZE).unwrap());
+        let next = arena.allocate(Layout::new::<u64>());
+
+        // The current chunk keeps serving small allocations
+        assert_eq!(next.as_ptr() as usize - small.as_ptr() as usize, 8);
+        assert_ne!(large, next);
+        assert_eq!(arena.memory_usage(), 16 + CHUNK_SIZE);
+    }
+
+    #[test]
+    fn test_concurrent_allocations_do_not_overlap() {
+        let arena = Arc::new(Arena::new());
+        let layout = Layout::new::<u64>();
+
+        let handles: Vec<_> = (0..4u64)
+            .map(|thread| {
+                let arena = Arc::clone(&arena);
+                std::thread::spawn(move || {
+                    let mut addresses = Vec::new();
+                    for i in 0..20_000u64 {
+                        let ptr = arena.allocate(layout).cast::<u64>();
+                        unsafe { ptr.as_ptr().write(thread << 32 | i) };
+                        addresses.push((ptr.as_ptr() as usize, thread << 32 | i));
+                    }
+                    addresses
+                })
+            })
+            .collect();
+
+        let mut all: Vec<(usize, u64)> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        for &(address, value) in &all {
+            assert_eq!(unsafe { *(address as *const u64) }, value);
+        }
+        all.sort_unstable();
+        all.dedup_by_key(|(address, _)| *address);
+        assert_eq!(all.len(), 80_000);
+    }
+}
+
+/// Model-checked tests of concurrent allocation
+///
+/// Run with `RUSTFLAGS="--cfg loom" cargo test --release -p ferrisdb-storage --lib loom_tests`.
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::thread;
+    use std::sync::Arc;
+
+    #[test]
+    fn loom_allocations_across_a_chunk_change_do_not_overlap() {
+        let mut builder = loom::model::Builder::new();
+        builder.preemption_bound = Some(3);
+        builder.check(|| {
+            let arena = Arc::new(Arena::new());
+            let layout = Layout::from_size_align(200, 8).unwrap();
+
+            // Six allocations cannot share one 1 KiB chunk
+            let other = {
+                let arena = Arc::clone(&arena);
+                thread::spawn(move || {
+                    (0..3)
+                        .map(|_| arena.allocate(layout).as_ptr() as usize)
+                        .collect::<Vec<_>>()
+                })
+            };
+            let mut addresses: Vec<usize> = (0..3)
+                .map(|_| arena.allocate(layout).as_ptr() as usize)
+                .collect();
+            addresses.extend(other.join().unwrap());
+
+            addresses.sort_unstable();
+            assert!(addresses.windows(2).all(|pair| pair[1] - pair[0] >= 200));
+            // Usage is whole chunks that were retired plus the live one
+            assert!(arena.memory_usage() >= 6 * 200);
+            assert_eq!(arena.memory_usage() % 8, 0);
+        });
+    }
+}
@@ -5,7 +5,7 @@
 //! skip list implementation that provides:
 //!
 //! - O(log n) insert, delete, and lookup operations
-//! - Lock-free reads with epoch-based memory reclamation
+//! - Lock-free reads of nodes allocated from an arena
 //! - Support for multiple versions of the same key (MVCC)
 //! - Efficient range scans
 //!
@@ -26,9 +26,10 @@
 //! # Ok::<(), ferrisdb_core::Error>(())
 //! ```
 
+pub use self::skip_list::SkipListIterator;
+
 use self::skip_list::SkipList;
 use ferrisdb_core::{Error, Key, Operation, Result, Timestamp, Value};
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 /// In-memory write buffer using a concurrent skip list
@@ -40,6 +41,10 @@ use std::sync::Arc;
 /// - Efficient range scans
 /// - Memory usage tracking
 ///
+/// Entries are stored in an arena owned by the skip list, so
+/// [`memory_usage`](Self::memory_usage) reports the bytes actually
+/// allocated for them and the whole table is freed at once when dropped.
+///
 /// # Thread Safety
 ///
 /// MemTable is designed to be shared across multiple threads safely.
@@ -63,8 +68,6 @@ pub struct MemTable {
     /// - Background threads flush MemTable to SSTable
     /// - Iterators need concurrent access without blocking writes
     skiplist: Arc<SkipList>,
-    /// Current memory usage in bytes (approximate)
-    memory_usage: AtomicUsize,
     /// Maximum memory capacity before flush is needed
     max_size: usize,
 }
@@ -86,7 +89,6 @@ impl MemTable {
     pub fn new(max_size: usize) -> Self {
         Self {
             skiplist: Arc::new(SkipList::new()),
-            memory_usage: AtomicUsize::new(0),
             max_size,
         }
     }
@@ -107,19 +109,9 @@ impl MemTable {
     /// Returns an error if the MemTable is over capacity after the insert.
     /// Callers should flush the MemTable to disk when this occurs.
     pub fn put(&self, key: Key, value: Value, timestamp: Timestamp) -> Result<()> {
-        let size_estimate = key.len() + value.len() + 64; // 64 bytes overhead estimate
-
-        self.skiplist.insert(key, value, timestamp, Operation::Put);
-
-        let new_usage = self
-            .memory_usage
-            .fetch_add(size_estimate, Ordering::Relaxed);
-
-        if new_usage + size_estimate > self.max_size {
-            return Err(Error::MemTableFull);
-        }
-
-        Ok(())
+        self.skiplist
+            .insert(&key, &value, timestamp, Operation::Put);
+        self.check_capacity()
     }
 
     /// Marks a key as deleted (tombstone)
@@ -132,19 +124,16 @@ impl MemTable {
     /// * `key` - The key to delete
     /// * `timestamp` - MVCC timestamp for this delete operation
     pub fn delete(&self, key: Key, timestamp: Timestamp) -> Result<()> {
-        let size_estimate = key.len() + 64; // 64 bytes overhead estimate
-
         self.skiplist
-            .insert(key, Vec::new(), timestamp, Operation::Delete);
-
-        let new_usage = self
-            .memory_usage
-            .fetch_add(size_estimate, Ordering::Relaxed);
+            .insert(&key, &[], timestamp, Operation::Delete);
+        self.check_capacity()
+    }
 
-        if new_usage + size_estimate > self.max_size {
+    /// Returns `MemTableFull` if the MemTable is over capacity
+    fn check_capacity(&self) -> Result<()> {
+        if self.memory_usage() > self.max_size {
             return Err(Error::MemTableFull);
         }
-
         Ok(())
     }
 
@@ -189,15 +178,67 @@ impl MemTable {
         end_key: &[u8],
         timestamp: Timestamp,
     ) -> Vec<(Key, Value)> {
-        self.skiplist.scan(start_key, end_key, timestamp)
+        let mut results = Vec::new();
+        let mut iter = self.iter_at(timestamp);
+        iter.seek(start_key, Timestamp::MAX);
+        while iter.valid() && iter.key() < end_key {
+            if iter.operation() == Operation::Put {
+                results.push((iter.key().to_vec(), iter.value().to_vec()));
+            }
+            iter.next();
+        }
+        results
+    }
+
+    /// Returns an unpositioned cursor over every entry
+    ///
+    /// Entries are ordered by key ascending, then timestamp descending, and
+    /// include every version and tombstone. The cursor shares the MemTable's
+    /// skip list, so it streams entries without copying them and stays
+    /// usable after the MemTable handle is dropped.
+    pub fn iter(&self) -> SkipListIterator {
+        SkipListIterator::new(Arc::clone(&self.skiplist))
+    }
+
+    /// Returns an unpositioned cursor over the newest version of each key
+    /// at or before `timestamp`
+    ///
+    /// Tombstones are yielded like any other version.
+    pub fn iter_at(&self, timestamp: Timestamp) -> SkipListIterator {
+        SkipListIterator::visible_at(Arc::clone(&self.skiplist), timestamp)
     }
 
-    /// Returns the approximate memory usage in bytes
+    /// Streams every entry to `f` in sorted order
+    ///
+    /// Entries are visited by key ascending, then timestamp descending, and
+    /// include every version and tombstone. This is the order an SSTable
+    /// expects, so a flush can write entries as they are visited without
+    /// buffering the table.
     ///
-    /// This is used to determine when the MemTable should be flushed
-    /// to disk to free up memory.
+    /// # Errors
+    ///
+    /// Stops at and returns the first error returned by `f`.
+    pub fn for_each_entry<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&[u8], Timestamp, Operation, &[u8]) -> Result<()>,
+    {
+        let mut iter = self.iter();
+        iter.seek_to_first();
+        while iter.valid() {
+            f(iter.key(), iter.timestamp(), iter.operation(), iter.value())?;
+            iter.next();
+        }
+        Ok(())
+    }
+
+    /// Returns the memory used by entries in bytes
+    ///
+    /// This is the space taken from the arena: node headers, skip list
+    /// pointers, keys and values, alignment padding, and the unused ends of
+    /// full arena chunks. It is used to determine when the MemTable should
+    /// be flushed to disk to free up memory.
     pub fn memory_usage(&self) -> usize {
-        self.memory_usage.load(Ordering::Relaxed)
+        self.skiplist.memory_usage()
     }
 
     /// Returns true if the MemTable is at or over capacity
@@ -216,6 +257,7 @@ impl MemTable {
     }
 }
 
+mod arena;
 mod skip_list;
 
 #[cfg(test)]
@@ -268,7 +310,8 @@ mod tests {
 
     #[test]
     fn test_memtable_size_limit() {
-        let memtable = MemTable::new(100); // Very small limit
+        // Very small limit; the skip list's head node alone takes about 150 bytes
+        let memtable = MemTable::new(256);
 
         // First insert should succeed
         memtable
@@ -294,4 +337,71 @@ mod tests {
 
         assert!(memtable.is_full());
     }
+
+    #[test]
+    fn test_memtable_for_each_entry_in_order() {
+        let memtable = MemTable::new(1024);
+
+        memtable.put(b"b".to_vec(), b"b1".to_vec(), 1).unwrap();
+        memtable.put(b"a".to_vec(), b"a1".to_vec(), 2).unwrap();
+        memtable.put(b"b".to_vec(), b"b2".to_vec(), 3).unwrap();
+        memtable.delete(b"a".to_vec(), 4).unwrap();
+
+        let mut entries = Vec::new();
+        memtable
+            .for_each_entry(|key, timestamp, operation, value| {
+                entries.push((key.to_vec(), timestamp, operation, value.to_vec()));
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(
+            entries,
+            vec![
+                (b"a".to_vec(), 4, Operation::Delete, Vec::new()),
+                (b"a".to_vec(), 2, Operation::Put, b"a1".to_vec()),
+                (b"b".to_vec(), 3, Operation::Put, b"b2".to_vec()),
+                (b"b".to_vec(), 1, Operation::Put, b"b1".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_memtable_iter_outlives_memtable() {
+        let memtable = MemTable::new(1024);
+        memtable.put(b"a".to_vec(), b"a1".to_vec(), 1).unwrap();
+        memtable.put(b"b".to_vec(), b"b1".to_vec(), 2).unwrap();
+        memtable.delete(b"a".to_vec(), 3).unwrap();
+
+        let mut iter = memtable.iter_at(2);
+        drop(memtable);
+
+        iter.seek_to_last();
+        assert_eq!((iter.key(), iter.value()), (&b"b"[..], &b"b1"[..]));
+        iter.prev();
+        assert_eq!((iter.key(), iter.operation()), (&b"a"[..], Operation::Put));
+        iter.prev();
+        assert!(!iter.valid());
+    }
+
+    #[test]
+    fn test_memtable_memory_usage_counts_arena_bytes() {
+        let memtable = MemTable::new(1 << 20);
+        let empty = memtable.memory_usage();
+        assert!(empty > 0, "the head node lives in the arena");
+
+        // A node is a header, one pointer per level, and the key and value
+        memtable.put(b"key".to_vec(), vec![0; 1000], 1).unwrap();
+        let used = memtable.memory_usage() - empty;
+        assert!((1003 + 8..1003 + 200).contains(&used), "used {}", used);
+
+        // Usage follows the real size of entries rather than a fixed estimate
+        for i in 0..100u64 {
+            memtable
+                .put(format!("big{}", i).into_bytes(), vec![0; 4000], i + 2)
+                .unwrap();
+        }
+        assert!(memtable.memory_usage() >= empty + used + 100 * 4000);
+        assert!(memtable.memory_usage() < empty + used + 100 * 4300);
+    }
 }
@@ -1,147 +1,210 @@
 //! Lock-free skip list implementation for the MemTable
 //!
 //! This module implements a concurrent skip list that supports:
-//! - Lock-free reads using epoch-based memory reclamation
-//! - Concurrent writes with fine-grained locking
+//! - Lock-free reads and inserts of nodes allocated from an arena
 //! - Multiple versions of the same key (MVCC)
-//! - Efficient range scans
-
-use crossbeam::epoch::{self, Atomic, Guard, Owned, Shared};
-use ferrisdb_core::{Key, Operation, Timestamp, Value};
-use parking_lot::Mutex;
-use rand::{Rng, SeedableRng};
+//! - Streaming range scans in both directions
+//!
+//! Nodes are never removed, which keeps the algorithms simple: an insert
+//! links its node one level at a time with compare-and-swap, bottom level
+//! first, and a reader that sees a node at any level can follow it safely.
+
+use super::arena::Arena;
+use ferrisdb_core::{Operation, Timestamp, Value};
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+use std::alloc::Layout;
 use std::cmp::Ordering;
-use std::ops::Deref;
-use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::ptr::{self, NonNull};
+#[cfg(not(loom))]
+use std::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
 
 /// Maximum height of the skip list (affects memory usage and performance)
 const MAX_HEIGHT: usize = 12;
 
 /// Probability factor for determining node height (1/4 chance of increasing height)
-const BRANCHING_FACTOR: u32 = 4;
+const BRANCHING_FACTOR: u64 = 4;
+
+/// Seed of the height generator; heights only need to be well spread, not
+/// unpredictable
+const HEIGHT_SEED: u64 = 0x2545_f491_4f6c_dd1d;
 
-/// Internal key representation that includes metadata for MVCC
+/// A node in the skip list
+///
+/// Each node holds one version of a key and pointers to the next node at
+/// each level it participates in. A node is a single arena allocation: this
+/// header, then its tower of next pointers, then its key and value bytes.
 ///
-/// Keys in the skip list are ordered first by user key (ascending),
-/// then by timestamp (descending). This ensures that:
-/// - Keys are grouped together
-/// - Newer versions appear before older versions
-/// - Range scans are efficient
-#[derive(Debug, Clone)]
-pub struct InternalKey {
-    /// The actual user-provided key
-    pub user_key: Key,
+/// Nodes are ordered first by user key (ascending), then by timestamp
+/// (descending), so the versions of a key are adjacent and newest first.
+struct Node {
     /// Timestamp for MVCC versioning
-    pub timestamp: Timestamp,
+    timestamp: Timestamp,
     /// Operation type (Put or Delete)
-    pub operation: Operation,
+    operation: Operation,
+    /// Number of levels the node participates in
+    height: usize,
+    /// Next pointers, `height` of them
+    tower: NonNull<AtomicPtr<Node>>,
+    /// Key bytes followed by value bytes
+    data: NonNull<u8>,
+    key_len: usize,
+    value_len: usize,
 }
 
-impl InternalKey {
-    /// Creates a new internal key
-    fn new(user_key: Key, timestamp: Timestamp, operation: Operation) -> Self {
-        Self {
-            user_key,
-            timestamp,
-            operation,
-        }
-    }
+// SAFETY: a node's pointers refer to its own arena allocation, which lives
+// as long as the list. Everything but the tower is immutable once the node
+// is linked, and the tower is made of atomics.
+unsafe impl Send for Node {}
+unsafe impl Sync for Node {}
 
-    /// Compares two internal keys for ordering
+impl Node {
+    /// Allocates and initializes a node in `arena`
     ///
-    /// Keys are ordered by:
-    /// 1. User key (ascending)
-    /// 2. Timestamp (descending) - newer versions first
-    fn compare(&self, other: &Self) -> Ordering {
-        match self.user_key.cmp(&other.user_key) {
-            Ordering::Equal => {
-                // Newer timestamps come first (descending order)
-                match other.timestamp.cmp(&self.timestamp) {
-                    Ordering::Equal => Ordering::Equal,
-                    other => other,
-                }
+    /// The node is not linked at any level.
+    fn allocate(
+        arena: &Arena,
+        user_key: &[u8],
+        value: &[u8],
+        timestamp: Timestamp,
+        operation: Operation,
+        height: usize,
+    ) -> NonNull<Node> {
+        let (layout, tower_offset, data_offset) =
+            Self::layout(height, user_key.len() + value.len());
+        let base = arena.allocate(layout);
+
+        unsafe {
+            let tower = base.as_ptr().add(tower_offset).cast::<AtomicPtr<Node>>();
+            for level in 0..height {
+                tower.add(level).write(AtomicPtr::new(ptr::null_mut()));
             }
-            other => other,
+
+            let data = base.as_ptr().add(data_offset);
+            ptr::copy_nonoverlapping(user_key.as_ptr(), data, user_key.len());
+            ptr::copy_nonoverlapping(value.as_ptr(), data.add(user_key.len()), value.len());
+
+            let node = base.cast::<Node>();
+            node.as_ptr().write(Node {
+                timestamp,
+                operation,
+                height,
+                tower: NonNull::new_unchecked(tower),
+                data: NonNull::new_unchecked(data),
+                key_len: user_key.len(),
+                value_len: value.len(),
+            });
+            node
         }
     }
-}
 
-/// A node in the skip list
-///
-/// Each node contains a key-value pair and pointers to the next node
-/// at each level of the skip list. The height of a node determines
-/// how many levels it participates in.
-struct Node {
-    /// The key with version information
-    key: InternalKey,
-    /// The value associated with this key version
-    value: Value,
-    /// Next pointers for each level (height determines the vector length)
-    next: Vec<Atomic<Node>>,
-}
+    /// Returns the layout of a node and the offsets of its tower and data
+    fn layout(height: usize, data_len: usize) -> (Layout, usize, usize) {
+        let tower = Layout::array::<AtomicPtr<Node>>(height).expect("node tower overflows");
+        let data = Layout::array::<u8>(data_len).expect("node data overflows");
+        let (layout, tower_offset) = Layout::new::<Node>()
+            .extend(tower)
+            .expect("node layout overflows");
+        let (layout, data_offset) = layout.extend(data).expect("node layout overflows");
+        (layout, tower_offset, data_offset)
+    }
 
-impl Node {
-    /// Creates a new node with the specified height
-    fn new(key: InternalKey, value: Value, height: usize) -> Self {
-        let mut next = Vec::with_capacity(height);
-        for _ in 0..height {
-            next.push(Atomic::null());
-        }
+    /// Allocates the sentinel head node for the skip list
+    ///
+    /// The head node has an empty key and is never compared; searches start
+    /// from it.
+    fn allocate_head(arena: &Arena) -> NonNull<Node> {
+        Self::allocate(arena, &[], &[], 0, Operation::Put, MAX_HEIGHT)
+    }
+
+    fn user_key(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.data.as_ptr(), self.key_len) }
+    }
+
+    fn value(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.data.as_ptr().add(self.key_len), self.value_len) }
+    }
+
+    /// Returns the next pointer at `level`
+    fn next(&self, level: usize) -> &AtomicPtr<Node> {
+        assert!(level < self.height);
+        unsafe { &*self.tower.as_ptr().add(level) }
+    }
 
-        Self { key, value, next }
+    /// Returns the next node at `level`, if any
+    fn next_node(&self, level: usize) -> Option<&Node> {
+        unsafe { self.next(level).load(AtomicOrdering::Acquire).as_ref() }
     }
 
-    /// Creates a sentinel head node for the skip list
+    /// Compares this node's key with a key given as its parts
     ///
-    /// The head node has an empty key that compares less than all other keys
-    fn head(height: usize) -> Self {
-        Self::new(
-            InternalKey::new(Vec::new(), 0, Operation::Put),
-            Vec::new(),
-            height,
-        )
+    /// Keys are ordered by:
+    /// 1. User key (ascending)
+    /// 2. Timestamp (descending) - newer versions first
+    fn compare_parts(&self, user_key: &[u8], timestamp: Timestamp) -> Ordering {
+        self.user_key()
+            .cmp(user_key)
+            .then_with(|| timestamp.cmp(&self.timestamp))
     }
 }
 
 /// A concurrent skip list for storing versioned key-value pairs
 ///
 /// This skip list implementation provides:
-/// - O(log n) expected time for search, insert, and delete
-/// - Lock-free reads using epoch-based memory reclamation
+/// - O(log n) expected time for search and insert
+/// - Lock-free reads and inserts
 /// - Support for multiple versions of the same key
-/// - Efficient range scans
+/// - Streaming range scans in both directions
 ///
 /// # Thread Safety
 ///
-/// Multiple threads can read concurrently without locking. Writes use
-/// fine-grained locking to allow concurrent modifications to different
-/// parts of the list.
+/// Any number of threads can read and insert at once without locking.
+/// Inserts link a node with one compare-and-swap per level and retry a
+/// level when another insert got there first. An entry is visible to every
+/// reader from the moment its bottom-level link is made.
 ///
 /// # Memory Management
 ///
-/// Uses crossbeam's epoch-based memory reclamation to safely free
-/// nodes that are no longer reachable, avoiding the ABA problem.
+/// Nodes are allocated from an [`Arena`] and are never removed, so a node
+/// stays valid for as long as the list does. The arena releases every node
+/// at once when the list is dropped.
 pub struct SkipList {
+    /// Allocator for every node, including the head
+    arena: Arena,
     /// Sentinel head node
-    head: Atomic<Node>,
+    head: NonNull<Node>,
     /// Current height of the skip list
     height: AtomicUsize,
     /// Number of entries in the skip list
     size: AtomicUsize,
-    /// Random number generator for determining node heights
-    rng: Mutex<rand::rngs::StdRng>,
+    /// Xorshift state for determining node heights
+    rng: AtomicU64,
 }
 
+// SAFETY: `head` points into `arena`, which the list owns; nodes are
+// immutable once linked apart from their atomic towers.
+//
+// Send + Sync are required because SkipList is used within Arc<SkipList>
+// for sharing between storage engine components:
+// - Multiple threads reading from immutable MemTables during flush
+// - Background flush threads writing MemTable contents to SSTables
+// - Iterator support that outlives individual method calls
+unsafe impl Send for SkipList {}
+unsafe impl Sync for SkipList {}
+
 impl SkipList {
     /// Creates a new empty skip list
     pub fn new() -> Self {
-        let head = Node::head(MAX_HEIGHT);
+        let arena = Arena::new();
+        let head = Node::allocate_head(&arena);
 
         Self {
-            head: Atomic::new(head),
+            arena,
+            head,
             height: AtomicUsize::new(1),
             size: AtomicUsize::new(0),
-            rng: Mutex::new(rand::rngs::StdRng::from_entropy()),
+            rng: AtomicU64::new(HEIGHT_SEED),
         }
     }
 
@@ -149,12 +212,21 @@ impl SkipList {
     ///
     /// Uses geometric distribution with p = 1/4 to determine height.
     /// This gives expected height of 1.33 and keeps the skip list balanced.
+    /// The generator state is advanced with a compare-and-swap, so writers
+    /// never wait on each other for it.
     fn random_height(&self) -> usize {
-        let mut height = 1;
-        let mut rng = self.rng.lock();
+        let previous = self
+            .rng
+            .fetch_update(AtomicOrdering::Relaxed, AtomicOrdering::Relaxed, |state| {
+                Some(xorshift(state))
+            })
+            .expect("the update always succeeds");
+        let mut bits = xorshift(previous);
 
-        while height < MAX_HEIGHT && rng.gen_ratio(1, BRANCHING_FACTOR) {
+        let mut height = 1;
+        while height < MAX_HEIGHT && bits.is_multiple_of(BRANCHING_FACTOR) {
             height += 1;
+            bits /= BRANCHING_FACTOR;
         }
 
         height
@@ -162,8 +234,11 @@ impl SkipList {
 
     /// Inserts a new key-value pair with version information
     ///
-    /// This operation is thread-safe and uses compare-and-swap operations
-    /// to ensure consistency. If the same key with the same timestamp already
+    /// This operation is lock-free. The node is linked at the bottom level
+    /// first, which makes it visible, and then at each higher level. A
+    /// compare-and-swap that loses a race with another insert searches for
+    /// the new neighbours at that level, starting from the old predecessor,
+    /// and tries again. If the same key with the same timestamp already
     /// exists, it will not be updated (preserving immutability).
     ///
     /// # Arguments
@@ -172,139 +247,122 @@ impl SkipList {
     /// * `value` - The value to associate with the key
     /// * `timestamp` - Version timestamp for MVCC
     /// * `operation` - Type of operation (Put or Delete)
-    pub fn insert(&self, user_key: Key, value: Value, timestamp: Timestamp, operation: Operation) {
-        let guard = &epoch::pin();
-        let key = InternalKey::new(user_key, timestamp, operation);
+    pub fn insert(
+        &self,
+        user_key: &[u8],
+        value: &[u8],
+        timestamp: Timestamp,
+        operation: Operation,
+    ) {
         let height = self.random_height();
-
-        // Update max height if necessary
-        loop {
-            let current_height = self.height.load(AtomicOrdering::Acquire);
-            if height <= current_height {
-                break;
-            }
-
-            if self
-                .height
-                .compare_exchange(
-                    current_height,
-                    height,
-                    AtomicOrdering::Release,
-                    AtomicOrdering::Acquire,
-                )
-                .is_ok()
-            {
-                break;
-            }
+        self.height.fetch_max(height, AtomicOrdering::AcqRel);
+
+        let mut preds = [self.head(); MAX_HEIGHT];
+        let mut succs = [ptr::null_mut(); MAX_HEIGHT];
+        if self.find(user_key, timestamp, &mut preds, &mut succs) {
+            // Key already exists, we don't update in skip list
+            // (newer version should be inserted as separate entry)
+            return;
         }
 
-        let mut preds: Vec<Shared<Node>> = vec![Shared::null(); height];
-        let mut succs: Vec<Shared<Node>> = vec![Shared::null(); height];
-
-        loop {
-            if self.find(&key, &mut preds, &mut succs, guard) {
-                // Key already exists, we don't update in skip list
-                // (newer version should be inserted as separate entry)
-                break;
-            }
-
-            let new_node = Owned::new(Node::new(key.clone(), value.clone(), height));
-
-            // Set next pointers of new node
-            for (i, &succ) in succs.iter().enumerate().take(height) {
-                new_node.deref().next[i].store(succ, AtomicOrdering::Relaxed);
-            }
-
-            let new_node_shared = new_node.into_shared(guard);
-
-            // Try to link the new node
-            match unsafe { preds[0].as_ref() }.unwrap().next[0].compare_exchange(
-                succs[0],
-                new_node_shared,
-                AtomicOrdering::Release,
-                AtomicOrdering::Acquire,
-                guard,
-            ) {
-                Ok(_) => {
-                    // Successfully linked at bottom level, link other levels
-                    for i in 1..height {
-                        loop {
-                            match unsafe { preds[i].as_ref() }.unwrap().next[i].compare_exchange(
-                                succs[i],
-                                new_node_shared,
-                                AtomicOrdering::Release,
-                                AtomicOrdering::Acquire,
-                                guard,
-                            ) {
-                                Ok(_) => break,
-                                Err(_) => {
-                                    // Re-find predecessors for this level
-                                    self.find(&key, &mut preds, &mut succs, guard);
-                                }
-                            }
+        let node = Node::allocate(&self.arena, user_key, value, timestamp, operation, height);
+        let new_node = unsafe { node.as_ref() };
+
+        for level in 0..height {
+            loop {
+                // Publish the successor before the node becomes reachable
+                // at this level
+                new_node
+                    .next(level)
+                    .store(succs[level], AtomicOrdering::Relaxed);
+
+                match preds[level].next(level).compare_exchange(
+                    succs[level],
+                    node.as_ptr(),
+                    AtomicOrdering::AcqRel,
+                    AtomicOrdering::Acquire,
+                ) {
+                    Ok(_) => break,
+                    Err(_) => {
+                        let found = self.find_at_level(
+                            user_key,
+                            timestamp,
+                            level,
+                            &mut preds[level],
+                            &mut succs[level],
+                        );
+                        if found {
+                            // Only possible at the bottom level, before the
+                            // node is reachable; its arena space is wasted
+                            debug_assert_eq!(level, 0);
+                            return;
                         }
                     }
-
-                    self.size.fetch_add(1, AtomicOrdering::Relaxed);
-                    break;
-                }
-                Err(_) => {
-                    // CAS failed, retry
-                    continue;
                 }
             }
         }
+
+        self.size.fetch_add(1, AtomicOrdering::Relaxed);
     }
 
     /// Finds the predecessors and successors for a key at each level
     ///
-    /// This is the core search operation used by insert and delete.
-    /// It populates the `preds` and `succs` arrays with the nodes
-    /// before and after where the key would be inserted at each level.
+    /// This is the core search operation used by insert. It populates the
+    /// `preds` and `succs` arrays with the nodes before and after where the
+    /// key would be inserted at each level.
     ///
     /// # Arguments
     ///
-    /// * `key` - The key to search for
+    /// * `user_key`, `timestamp` - The key to search for
     /// * `preds` - Array to fill with predecessor nodes at each level
     /// * `succs` - Array to fill with successor nodes at each level
-    /// * `guard` - Epoch guard for safe memory access
     ///
     /// # Returns
     ///
     /// `true` if an exact match for the key is found, `false` otherwise
-    fn find<'g>(
-        &self,
-        key: &InternalKey,
-        preds: &mut [Shared<'g, Node>],
-        succs: &mut [Shared<'g, Node>],
-        guard: &'g Guard,
+    fn find<'a>(
+        &'a self,
+        user_key: &[u8],
+        timestamp: Timestamp,
+        preds: &mut [&'a Node; MAX_HEIGHT],
+        succs: &mut [*mut Node; MAX_HEIGHT],
     ) -> bool {
-        let mut pred = self.head.load(AtomicOrdering::Acquire, guard);
+        let mut pred = self.head();
+        let mut found = false;
 
         for level in (0..self.height.load(AtomicOrdering::Acquire)).rev() {
-            let mut curr =
-                unsafe { pred.as_ref() }.unwrap().next[level].load(AtomicOrdering::Acquire, guard);
-
-            while !curr.is_null() {
-                let curr_ref = unsafe { curr.as_ref() }.unwrap();
+            found = self.find_at_level(user_key, timestamp, level, &mut pred, &mut succs[level]);
+            preds[level] = pred;
+        }
 
-                match key.compare(&curr_ref.key) {
-                    Ordering::Greater => {
-                        pred = curr;
-                        curr = curr_ref.next[level].load(AtomicOrdering::Acquire, guard);
-                    }
-                    _ => break,
-                }
-            }
+        found
+    }
 
-            if level < preds.len() {
-                preds[level] = pred;
-                succs[level] = curr;
+    /// Advances `pred` along `level` to the last node before the key
+    ///
+    /// `pred` must already be before the key. Sets `succ` to the node after
+    /// `pred` and returns true if that node has exactly this key.
+    fn find_at_level<'a>(
+        &'a self,
+        user_key: &[u8],
+        timestamp: Timestamp,
+        level: usize,
+        pred: &mut &'a Node,
+        succ: &mut *mut Node,
+    ) -> bool {
+        loop {
+            let next = pred.next(level).load(AtomicOrdering::Acquire);
+            *succ = next;
+
+            let Some(next) = (unsafe { next.as_ref() }) else {
+                return false;
+            };
+            match next.compare_parts(user_key, timestamp) {
+                Ordering::Less => *pred = next,
+                Ordering::Equal => return true,
+                Ordering::Greater => return false,
             }
         }
-
-        !succs[0].is_null()
-            && unsafe { succs[0].as_ref() }.unwrap().key.compare(key) == Ordering::Equal
     }
 
     /// Retrieves the value for a key at a specific timestamp
@@ -324,140 +382,343 @@ impl SkipList {
     /// where operation indicates if this is a Put or Delete.
     /// `None` if the key doesn't exist or all versions are newer than the timestamp.
     pub fn get(&self, user_key: &[u8], timestamp: Timestamp) -> Option<(Value, Operation)> {
-        let guard = &epoch::pin();
-
-        // First, find the position where this key would be
-        let search_key = InternalKey::new(user_key.to_vec(), u64::MAX, Operation::Put);
-        let mut preds = vec![Shared::null(); 1];
-        let mut succs = vec![Shared::null(); 1];
+        // The first entry at or after (user_key, timestamp) is the newest
+        // visible version, if it belongs to this key
+        self.find_at_or_after(user_key, timestamp)
+            .filter(|node| node.user_key() == user_key)
+            .map(|node| (node.value().to_vec(), node.operation))
+    }
 
-        self.find(&search_key, &mut preds, &mut succs, guard);
+    /// Returns the sentinel head node
+    fn head(&self) -> &Node {
+        unsafe { self.head.as_ref() }
+    }
 
-        // Now scan from this position to find the right version
-        let mut curr = succs[0];
+    /// Returns `node`, or `None` if it is the head
+    fn non_head<'a>(&self, node: &'a Node) -> Option<&'a Node> {
+        (!ptr::eq(node, self.head())).then_some(node)
+    }
 
-        while !curr.is_null() {
-            let curr_ref = unsafe { curr.as_ref() }.unwrap();
+    /// Returns the last node ordered before `(user_key, timestamp)` and the
+    /// node that followed it at the bottom level when the search ended
+    ///
+    /// This is the predecessor search behind lookups and reverse iteration.
+    /// The predecessor is the head if every node is at or after the target.
+    /// Callers must use the successor returned here rather than reload it,
+    /// since a concurrent insert may have linked a node in between.
+    fn search(&self, user_key: &[u8], timestamp: Timestamp) -> (&Node, Option<&Node>) {
+        let mut pred = self.head();
+        let mut succ = None;
 
-            if curr_ref.key.user_key != user_key {
-                break;
+        for level in (0..self.height.load(AtomicOrdering::Acquire)).rev() {
+            succ = pred.next_node(level);
+            while let Some(next) = succ {
+                if next.compare_parts(user_key, timestamp) != Ordering::Less {
+                    break;
+                }
+                pred = next;
+                succ = next.next_node(level);
             }
+        }
+
+        (pred, succ)
+    }
+
+    /// Returns the last node ordered before `(user_key, timestamp)`, or the
+    /// head if there is none
+    fn find_less_than(&self, user_key: &[u8], timestamp: Timestamp) -> &Node {
+        self.search(user_key, timestamp).0
+    }
+
+    /// Returns the first node at or after `(user_key, timestamp)`
+    fn find_at_or_after(&self, user_key: &[u8], timestamp: Timestamp) -> Option<&Node> {
+        self.search(user_key, timestamp).1
+    }
+
+    /// Returns the last node in the list, or the head if it is empty
+    fn find_last(&self) -> &Node {
+        let mut node = self.head();
 
-            if curr_ref.key.timestamp <= timestamp {
-                return Some((curr_ref.value.clone(), curr_ref.key.operation));
+        for level in (0..self.height.load(AtomicOrdering::Acquire)).rev() {
+            while let Some(next) = node.next_node(level) {
+                node = next;
             }
+        }
+
+        node
+    }
+
+    /// Returns the bytes allocated from the arena, including the head node
+    pub fn memory_usage(&self) -> usize {
+        self.arena.memory_usage()
+    }
+
+    /// Returns the number of entries in the skip list
+    ///
+    /// Note: This counts all versions of all keys, not just unique keys.
+    #[allow(dead_code)]
+    pub fn size(&self) -> usize {
+        self.size.load(AtomicOrdering::Relaxed)
+    }
+}
+
+/// One step of Marsaglia's xorshift64 generator
+fn xorshift(mut state: u64) -> u64 {
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    state
+}
 
-            curr = curr_ref.next[0].load(AtomicOrdering::Acquire, guard);
+/// A streaming, bidirectional cursor over a skip list
+///
+/// The cursor holds the list through an `Arc`, which keeps every node in
+/// place, so it can stay on a node between movements without any guard.
+/// Reverse steps use a predecessor search from the head, which costs
+/// O(log n) instead of following a back pointer.
+///
+/// A cursor yields either every internal entry, versions and tombstones
+/// included, or only the version of each key visible at a read timestamp.
+/// Visible versions may be tombstones; skipping them is up to the caller.
+///
+/// Entries inserted while the cursor is open may or may not be seen.
+pub struct SkipListIterator {
+    list: Arc<SkipList>,
+    /// Yield only the newest version at or before this timestamp
+    read_timestamp: Option<Timestamp>,
+    /// Current node; null when the cursor is not valid
+    node: *const Node,
+}
+
+// SAFETY: `node` points into the arena of `list`, which the cursor keeps
+// alive, and the node contents the cursor reads are immutable.
+unsafe impl Send for SkipListIterator {}
+
+impl SkipListIterator {
+    /// Creates an unpositioned cursor over every entry of `list`
+    pub fn new(list: Arc<SkipList>) -> Self {
+        Self {
+            list,
+            read_timestamp: None,
+            node: ptr::null(),
+        }
+    }
+
+    /// Creates an unpositioned cursor over the entries of `list` visible at
+    /// `timestamp`, one per key
+    pub fn visible_at(list: Arc<SkipList>, timestamp: Timestamp) -> Self {
+        Self {
+            list,
+            read_timestamp: Some(timestamp),
+            node: ptr::null(),
         }
+    }
 
-        None
+    /// Returns true if the cursor is positioned on an entry
+    pub fn valid(&self) -> bool {
+        !self.node.is_null()
     }
 
-    /// Performs a range scan between start_key and end_key at a specific timestamp
+    /// Returns the user key of the current entry
     ///
-    /// Returns all key-value pairs where the key is in the range [start_key, end_key)
-    /// and the timestamp is less than or equal to the given timestamp. For keys with
-    /// multiple versions, only the most recent valid version is returned.
+    /// # Panics
     ///
-    /// Delete operations (tombstones) are filtered out from the results.
+    /// Panics if the cursor is not valid.
+    pub fn key(&self) -> &[u8] {
+        self.current().user_key()
+    }
+
+    /// Returns the timestamp of the current entry
     ///
-    /// # Arguments
+    /// # Panics
     ///
-    /// * `start_key` - The inclusive lower bound of the range
-    /// * `end_key` - The exclusive upper bound of the range
-    /// * `timestamp` - The timestamp to read at
+    /// Panics if the cursor is not valid.
+    pub fn timestamp(&self) -> Timestamp {
+        self.current().timestamp
+    }
+
+    /// Returns the operation of the current entry
     ///
-    /// # Returns
+    /// # Panics
     ///
-    /// A vector of (key, value) pairs in ascending key order
-    pub fn scan(
-        &self,
-        start_key: &[u8],
-        end_key: &[u8],
-        timestamp: Timestamp,
-    ) -> Vec<(Key, Value)> {
-        let guard = &epoch::pin();
-        let mut result = Vec::new();
-        let mut seen_keys = std::collections::HashSet::new();
+    /// Panics if the cursor is not valid.
+    pub fn operation(&self) -> Operation {
+        self.current().operation
+    }
 
-        let search_key = InternalKey::new(start_key.to_vec(), timestamp, Operation::Put);
-        let mut preds = vec![Shared::null(); 1];
-        let mut succs = vec![Shared::null(); 1];
+    /// Returns the value of the current entry
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cursor is not valid.
+    pub fn value(&self) -> &[u8] {
+        self.current().value()
+    }
 
-        self.find(&search_key, &mut preds, &mut succs, guard);
+    /// Positions the cursor on the first entry
+    pub fn seek_to_first(&mut self) {
+        let first = self.list.head().next_node(0);
+        self.node = as_ptr(self.settle_forward(first));
+    }
 
-        let mut curr = succs[0];
+    /// Positions the cursor on the last entry
+    pub fn seek_to_last(&mut self) {
+        let last = self.list.non_head(self.list.find_last());
+        self.node = as_ptr(self.settle_backward(last));
+    }
 
-        while !curr.is_null() {
-            let curr_ref = unsafe { curr.as_ref() }.unwrap();
+    /// Positions the cursor on the first entry at or after
+    /// `(user_key, timestamp)`
+    pub fn seek(&mut self, user_key: &[u8], timestamp: Timestamp) {
+        let mut node = self.list.find_at_or_after(user_key, timestamp);
 
-            if curr_ref.key.user_key.as_slice() >= end_key {
-                break;
+        // An older version of a key whose visible version comes before the
+        // target is not visible itself
+        if let (Some(found), Some(read_timestamp)) = (node, self.read_timestamp) {
+            if found.timestamp <= read_timestamp && !self.is_visible_version(found, read_timestamp)
+            {
+                node = Self::skip_key(found);
+            }
+        }
+
+        self.node = as_ptr(self.settle_forward(node));
+    }
+
+    /// Positions the cursor on the last entry at or before
+    /// `(user_key, timestamp)`
+    pub fn seek_for_prev(&mut self, user_key: &[u8], timestamp: Timestamp) {
+        let (pred, succ) = self.list.search(user_key, timestamp);
+        let node = match succ {
+            Some(next) if next.compare_parts(user_key, timestamp) == Ordering::Equal => Some(next),
+            _ => self.list.non_head(pred),
+        };
+        self.node = as_ptr(self.settle_backward(node));
+    }
+
+    /// Moves to the next entry; does nothing if the cursor is not valid
+    pub fn next(&mut self) {
+        if !self.valid() {
+            return;
+        }
+        let current = self.current();
+        let node = match self.read_timestamp {
+            None => current.next_node(0),
+            Some(_) => self.settle_forward(Self::skip_key(current)),
+        };
+        self.node = as_ptr(node);
+    }
+
+    /// Moves to the previous entry; does nothing if the cursor is not valid
+    pub fn prev(&mut self) {
+        if !self.valid() {
+            return;
+        }
+        let current = self.current();
+        let node = match self.read_timestamp {
+            None => {
+                let pred = self
+                    .list
+                    .find_less_than(current.user_key(), current.timestamp);
+                self.list.non_head(pred)
             }
+            Some(read_timestamp) => self.visible_before(current.user_key(), read_timestamp),
+        };
+        self.node = as_ptr(node);
+    }
 
-            if curr_ref.key.timestamp <= timestamp && !seen_keys.contains(&curr_ref.key.user_key) {
-                if curr_ref.key.operation == Operation::Put {
-                    result.push((curr_ref.key.user_key.clone(), curr_ref.value.clone()));
+    fn current(&self) -> &Node {
+        assert!(self.valid(), "iterator is not positioned on an entry");
+        // SAFETY: see the `Send` impl; the node lives as long as `list`
+        unsafe { &*self.node }
+    }
+
+    /// Moves forward from `node` to the first entry the cursor yields
+    ///
+    /// `node` must be the first version of its key, or be preceded only by
+    /// versions newer than the read timestamp.
+    fn settle_forward<'a>(&self, mut node: Option<&'a Node>) -> Option<&'a Node> {
+        if let Some(read_timestamp) = self.read_timestamp {
+            while let Some(found) = node {
+                if found.timestamp <= read_timestamp {
+                    break;
                 }
-                seen_keys.insert(curr_ref.key.user_key.clone());
+                node = found.next_node(0);
             }
+        }
+        node
+    }
 
-            curr = curr_ref.next[0].load(AtomicOrdering::Acquire, guard);
+    /// Moves backward from `node` to the last entry the cursor yields at or
+    /// before it
+    fn settle_backward<'a>(&'a self, node: Option<&'a Node>) -> Option<&'a Node> {
+        let (Some(found), Some(read_timestamp)) = (node, self.read_timestamp) else {
+            return node;
+        };
+        if found.timestamp <= read_timestamp {
+            // The visible version is this one or a newer one of the same key
+            self.list.find_at_or_after(found.user_key(), read_timestamp)
+        } else {
+            self.visible_before(found.user_key(), read_timestamp)
         }
+    }
 
-        result
+    /// Returns the visible version of the last key before `user_key` that
+    /// has one
+    fn visible_before<'a>(
+        &'a self,
+        mut user_key: &'a [u8],
+        read_timestamp: Timestamp,
+    ) -> Option<&'a Node> {
+        loop {
+            // The oldest version of the previous key
+            let pred = self.list.find_less_than(user_key, Timestamp::MAX);
+            let pred = self.list.non_head(pred)?;
+            user_key = pred.user_key();
+
+            let visible = self.list.find_at_or_after(user_key, read_timestamp);
+            if let Some(visible) = visible.filter(|node| node.user_key() == user_key) {
+                return Some(visible);
+            }
+        }
     }
 
-    /// Returns the number of entries in the skip list
-    ///
-    /// Note: This counts all versions of all keys, not just unique keys.
-    #[allow(dead_code)]
-    pub fn size(&self) -> usize {
-        self.size.load(AtomicOrdering::Relaxed)
+    /// Returns true if `node` is the newest version of its key at or before
+    /// `read_timestamp`
+    fn is_visible_version(&self, node: &Node, read_timestamp: Timestamp) -> bool {
+        self.list
+            .find_at_or_after(node.user_key(), read_timestamp)
+            .is_some_and(|visible| ptr::eq(visible, node))
     }
-}
 
-// SkipList automatically implements Send + Sync because:
-// - Atomic<Node> is Send + Sync (crossbeam atomics)
-// - AtomicUsize is Send + Sync (standard library)
-// - Mutex<StdRng> is Send + Sync (StdRng implements Send + Sync)
-//
-// Send + Sync are required because SkipList is used within Arc<SkipList>
-// for sharing between storage engine components:
-// - Multiple threads reading from immutable MemTables during flush
-// - Background flush threads writing MemTable contents to SSTables
-// - Iterator support that outlives individual method calls
-//
-// Using StdRng instead of ThreadRng avoids unsafe impl and leverages
-// Rust's type system to automatically prove thread safety.
-
-impl Drop for SkipList {
-    fn drop(&mut self) {
-        let guard = &epoch::pin();
-
-        let mut curr = self.head.load(AtomicOrdering::Acquire, guard);
-        while !curr.is_null() {
-            let next =
-                unsafe { curr.as_ref() }.unwrap().next[0].load(AtomicOrdering::Acquire, guard);
-            unsafe {
-                guard.defer_destroy(curr);
+    /// Returns the first node after `node` with a different user key
+    fn skip_key(node: &Node) -> Option<&Node> {
+        let mut next = node.next_node(0);
+        while let Some(found) = next {
+            if found.user_key() != node.user_key() {
+                break;
             }
-            curr = next;
+            next = found.next_node(0);
         }
+        next
     }
 }
 
+fn as_ptr(node: Option<&Node>) -> *const Node {
+    node.map_or(ptr::null(), |node| node as *const Node)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ferrisdb_core::Key;
 
     #[test]
     fn test_skiplist_basic() {
         let sl = SkipList::new();
 
-        sl.insert(b"key1".to_vec(), b"value1".to_vec(), 1, Operation::Put);
-        sl.insert(b"key2".to_vec(), b"value2".to_vec(), 2, Operation::Put);
-        sl.insert(b"key3".to_vec(), b"value3".to_vec(), 3, Operation::Put);
+        sl.insert(b"key1", b"value1", 1, Operation::Put);
+        sl.insert(b"key2", b"value2", 2, Operation::Put);
+        sl.insert(b"key3", b"value3", 3, Operation::Put);
 
         assert_eq!(sl.size(), 3);
 
@@ -473,9 +734,9 @@ mod tests {
         let sl = SkipList::new();
 
         // Insert multiple versions of the same key
-        sl.insert(b"key1".to_vec(), b"value1".to_vec(), 1, Operation::Put);
-        sl.insert(b"key1".to_vec(), b"value2".to_vec(), 3, Operation::Put);
-        sl.insert(b"key1".to_vec(), b"value3".to_vec(), 5, Operation::Put);
+        sl.insert(b"key1", b"value1", 1, Operation::Put);
+        sl.insert(b"key1", b"value2", 3, Operation::Put);
+        sl.insert(b"key1", b"value3", 5, Operation::Put);
 
         // Read at different timestamps
         let result = sl.get(b"key1", 2);
@@ -492,8 +753,8 @@ mod tests {
     fn test_skiplist_delete() {
         let sl = SkipList::new();
 
-        sl.insert(b"key1".to_vec(), b"value1".to_vec(), 1, Operation::Put);
-        sl.insert(b"key1".to_vec(), Vec::new(), 3, Operation::Delete);
+        sl.insert(b"key1", b"value1", 1, Operation::Put);
+        sl.insert(b"key1", &[], 3, Operation::Delete);
 
         // Before delete
         let result = sl.get(b"key1", 2);
@@ -503,4 +764,406 @@ mod tests {
         let result = sl.get(b"key1", 4);
         assert_eq!(result.unwrap().1, Operation::Delete);
     }
+
+    /// Builds a list of keys with a varying number of versions, returning
+    /// it with its entries in internal key order
+    fn versioned_list() -> (Arc<SkipList>, Vec<(Key, Timestamp, Operation)>) {
+        let list = Arc::new(SkipList::new());
+        let mut entries = Vec::new();
+        for i in 0..60u64 {
+            let key = format!("key_{:03}", i).into_bytes();
+            for version in 0..(i % 4) + 1 {
+                let timestamp = (i * 7 + version * 13) % 50 + 1;
+                let operation = if (i + version) % 5 == 0 {
+                    Operation::Delete
+                } else {
+                    Operation::Put
+                };
+                list.insert(&key, &timestamp.to_be_bytes(), timestamp, operation);
+                entries.push((key.clone(), timestamp, operation));
+            }
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        entries.dedup_by(|a, b| a.0 == b.0 && a.1 == b.1);
+        (list, entries)
+    }
+
+    /// The entries a cursor should yield at `read_timestamp`
+    fn expected(
+        entries: &[(Key, Timestamp, Operation)],
+        read_timestamp: Option<Timestamp>,
+    ) -> Vec<(Key, Timestamp, Operation)> {
+        let Some(read_timestamp) = read_timestamp else {
+            return entries.to_vec();
+        };
+        let mut visible: Vec<(Key, Timestamp, Operation)> = Vec::new();
+        for entry in entries {
+            if entry.1 <= read_timestamp && visible.last().is_none_or(|last| last.0 != entry.0) {
+                visible.push(entry.clone());
+            }
+        }
+        visible
+    }
+
+    fn current(iter: &SkipListIterator) -> (Key, Timestamp, Operation) {
+        assert_eq!(iter.value(), iter.timestamp().to_be_bytes());
+        (iter.key().to_vec(), iter.timestamp(), iter.operation())
+    }
+
+    fn cursor(list: &Arc<SkipList>, read_timestamp: Option<Timestamp>) -> SkipListIterator {
+        match read_timestamp {
+            Some(timestamp) => SkipListIterator::visible_at(Arc::clone(list), timestamp),
+            None => SkipListIterator::new(Arc::clone(list)),
+        }
+    }
+
+    #[test]
+    fn test_iterator_scans_both_ways() {
+        let (list, entries) = versioned_list();
+
+        for read_timestamp in [None, Some(0), Some(1), Some(20), Some(37), Some(100)] {
+            let expected = expected(&entries, read_timestamp);
+            let mut iter = cursor(&list, read_timestamp);
+
+            let mut forward = Vec::new();
+            iter.seek_to_first();
+            while iter.valid() {
+                forward.push(current(&iter));
+                iter.next();
+            }
+            assert_eq!(forward, expected, "forward at {:?}", read_timestamp);
+
+            let mut backward = Vec::new();
+            iter.seek_to_last();
+            while iter.valid() {
+                backward.push(current(&iter));
+                iter.prev();
+            }
+            backward.reverse();
+            assert_eq!(backward, expected, "backward at {:?}", read_timestamp);
+        }
+    }
+
+    #[test]
+    fn test_iterator_seeks() {
+        let (list, entries) = versioned_list();
+
+        for read_timestamp in [None, Some(20), Some(100)] {
+            let expected = expected(&entries, read_timestamp);
+            let mut iter = cursor(&list, read_timestamp);
+
+            for i in 0..62u64 {
+                let key = format!("key_{:03}", i).into_bytes();
+                for timestamp in [Timestamp::MAX, 30, 14, 0] {
+                    let target = (key.as_slice(), timestamp);
+                    let order = |entry: &(Key, Timestamp, Operation)| {
+                        entry
+                            .0
+                            .as_slice()
+                            .cmp(target.0)
+                            .then(target.1.cmp(&entry.1))
+                    };
+
+                    iter.seek(&key, timestamp);
+                    let at = expected.iter().find(|entry| order(entry) != Ordering::Less);
+                    assert_eq!(iter.valid().then(|| current(&iter)).as_ref(), at);
+
+                    iter.seek_for_prev(&key, timestamp);
+                    let at = expected
+                        .iter()
+                        .rev()
+                        .find(|entry| order(entry) != Ordering::Greater);
+                    assert_eq!(iter.valid().then(|| current(&iter)).as_ref(), at);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_iterator_changes_direction() {
+        let (list, entries) = versioned_list();
+        let expected = expected(&entries, Some(30));
+        let mut iter = cursor(&list, Some(30));
+
+        iter.seek(b"key_020", Timestamp::MAX);
+        let start = expected
+            .iter()
+            .position(|entry| entry.0.as_slice() >= b"key_020".as_slice())
+            .unwrap();
+        assert_eq!(current(&iter), expected[start]);
+
+        iter.next();
+        iter.next();
+        assert_eq!(current(&iter), expected[start + 2]);
+        iter.prev();
+        iter.prev();
+        iter.prev();
+        assert_eq!(current(&iter), expected[start - 1]);
+
+        // Stepping off either end leaves the cursor invalid
+        iter.seek_to_first();
+        iter.prev();
+        assert!(!iter.valid());
+        iter.next();
+        assert!(!iter.valid());
+    }
+
+    #[test]
+    fn test_iterator_over_empty_list() {
+        let list = Arc::new(SkipList::new());
+        for read_timestamp in [None, Some(10)] {
+            let mut iter = cursor(&list, read_timestamp);
+            assert!(!iter.valid());
+            iter.seek_to_first();
+            assert!(!iter.valid());
+            iter.seek_to_last();
+            assert!(!iter.valid());
+            iter.seek(b"key", 5);
+            assert!(!iter.valid());
+            iter.seek_for_prev(b"key", 5);
+            assert!(!iter.valid());
+        }
+    }
+
+    #[test]
+    fn test_iterator_during_inserts() {
+        let list = Arc::new(SkipList::new());
+        for i in 0..500u64 {
+            let key = format!("key_{:04}", i * 2).into_bytes();
+            list.insert(&key, &[], i + 1, Operation::Put);
+        }
+
+        let writer = {
+            let list = Arc::clone(&list);
+            std::thread::spawn(move || {
+                for i in 0..500u64 {
+                    let key = format!("key_{:04}", i * 2 + 1).into_bytes();
+                    list.insert(&key, &[], 1000 + i, Operation::Put);
+                }
+            })
+        };
+
+        // Entries written before the scan started are all seen in order,
+        // whatever is inserted around them
+        for _ in 0..5 {
+            let mut iter = SkipListIterator::visible_at(Arc::clone(&list), 500);
+            let mut keys = Vec::new();
+            iter.seek_to_last();
+            while iter.valid() {
+                keys.push(iter.key().to_vec());
+                iter.prev();
+            }
+            keys.reverse();
+            let expected: Vec<_> = (0..500u64)
+                .map(|i| format!("key_{:04}", i * 2).into_bytes())
+                .collect();
+            assert_eq!(keys, expected);
+        }
+
+        writer.join().unwrap();
+        let mut iter = SkipListIterator::new(Arc::clone(&list));
+        let mut count = 0;
+        iter.seek_to_first();
+        while iter.valid() {
+            count += 1;
+            iter.next();
+        }
+        assert_eq!(count, 1000);
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let list = Arc::new(SkipList::new());
+
+        let writers: Vec<_> = (0..4u64)
+            .map(|thread| {
+                let list = Arc::clone(&list);
+                std::thread::spawn(move || {
+                    for i in 0..2000u64 {
+                        let key = format!("key_{:05}", i * 4 + thread).into_bytes();
+                        list.insert(&key, &[], i + 1, Operation::Put);
+                        // Every writer also adds versions to a shared key
+                        list.insert(b"shared", &[], thread * 2000 + i + 1, Operation::Put);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert_eq!(list.size(), 16_000);
+        let mut iter = SkipListIterator::new(Arc::clone(&list));
+        let mut entries = Vec::new();
+        iter.seek_to_first();
+        while iter.valid() {
+            entries.push((iter.key().to_vec(), iter.timestamp()));
+            iter.next();
+        }
+        assert_eq!(entries.len(), 16_000);
+        assert!(
+            entries
+                .windows(2)
+                .all(|pair| pair[0].0 < pair[1].0
+                    || (pair[0].0 == pair[1].0 && pair[0].1 > pair[1].1))
+        );
+    }
+}
+
+/// Model-checked tests of concurrent inserts and reads
+///
+/// Run with `RUSTFLAGS="--cfg loom" cargo test --release -p ferrisdb-storage --lib loom_tests`.
+/// Loom runs each test under every interleaving of the atomic operations,
+/// up to a bound on preemptions.
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::atomic::AtomicBool;
+    use loom::thread;
+
+    fn model(f: impl Fn() + Sync + Send + 'static) {
+        let mut builder = loom::model::Builder::new();
+        builder.preemption_bound = Some(3);
+        builder.check(f);
+    }
+
+    /// Returns every entry at the bottom level, checking that each level is
+    /// strictly ordered and made of nodes from the bottom level
+    fn entries(list: &SkipList) -> Vec<(Vec<u8>, Timestamp, Vec<u8>)> {
+        let mut bottom = Vec::new();
+        let mut node = list.head().next_node(0);
+        while let Some(found) = node {
+            bottom.push(found as *const Node);
+            node = found.next_node(0);
+        }
+
+        for level in 0..MAX_HEIGHT {
+            let mut previous: Option<&Node> = None;
+            let mut node = list.head().next_node(level);
+            while let Some(found) = node {
+                assert!(bottom.contains(&(found as *const Node)));
+                if let Some(previous) = previous {
+                    assert_eq!(
+                        previous.compare_parts(found.user_key(), found.timestamp),
+                        Ordering::Less
+                    );
+                }
+                previous = Some(found);
+                node = found.next_node(level);
+            }
+        }
+
+        bottom
+            .into_iter()
+            .map(|node| {
+                let node = unsafe { &*node };
+                (
+                    node.user_key().to_vec(),
+                    node.timestamp,
+                    node.value().to_vec(),
+                )
+            })
+            .collect()
+    }
+
+    fn entry(key: &str, timestamp: Timestamp, value: &str) -> (Vec<u8>, Timestamp, Vec<u8>) {
+        (
+            key.as_bytes().to_vec(),
+            timestamp,
+            value.as_bytes().to_vec(),
+        )
+    }
+
+    #[test]
+    fn loom_concurrent_inserts_all_land_in_order() {
+        model(|| {
+            let list = Arc::new(SkipList::new());
+
+            let writer = {
+                let list = Arc::clone(&list);
+                thread::spawn(move || {
+                    list.insert(b"b", b"b1", 1, Operation::Put);
+                    list.insert(b"d", b"d3", 3, Operation::Put);
+                })
+            };
+            list.insert(b"c", b"c2", 2, Operation::Put);
+            list.insert(b"a", b"a4", 4, Operation::Delete);
+            writer.join().unwrap();
+
+            assert_eq!(
+                entries(&list),
+                vec![
+                    entry("a", 4, "a4"),
+                    entry("b", 1, "b1"),
+                    entry("c", 2, "c2"),
+                    entry("d", 3, "d3"),
+                ]
+            );
+            assert_eq!(list.size(), 4);
+        });
+    }
+
+    #[test]
+    fn loom_duplicate_inserts_keep_one_entry() {
+        model(|| {
+            let list = Arc::new(SkipList::new());
+
+            let writer = {
+                let list = Arc::clone(&list);
+                thread::spawn(move || list.insert(b"k", b"first", 1, Operation::Put))
+            };
+            list.insert(b"k", b"second", 1, Operation::Put);
+            writer.join().unwrap();
+
+            let entries = entries(&list);
+            assert_eq!(entries.len(), 1);
+            assert!(entries[0] == entry("k", 1, "first") || entries[0] == entry("k", 1, "second"));
+            assert_eq!(list.size(), 1);
+        });
+    }
+
+    #[test]
+    fn loom_reads_are_linearizable_with_inserts() {
+        model(|| {
+            let list = Arc::new(SkipList::new());
+            let inserted = Arc::new(AtomicBool::new(false));
+
+            let writer = {
+                let list = Arc::clone(&list);
+                let inserted = Arc::clone(&inserted);
+                thread::spawn(move || {
+                    list.insert(b"k", b"v1", 1, Operation::Put);
+                    inserted.store(true, AtomicOrdering::Release);
+                    list.insert(b"k", b"v2", 2, Operation::Put);
+                })
+            };
+
+            // An insert that completed before a read started is visible to
+            // it, and a read never sees a version out of order
+            let completed = inserted.load(AtomicOrdering::Acquire);
+            let latest = list.get(b"k", 10);
+            if completed {
+                assert!(latest.is_some());
+            }
+            let older = list.get(b"k", 1);
+            if latest.is_some() {
+                assert_eq!(older, Some((b"v1".to_vec(), Operation::Put)));
+            }
+
+            let mut iter = SkipListIterator::new(Arc::clone(&list));
+            let mut timestamps = Vec::new();
+            iter.seek_to_first();
+            while iter.valid() {
+                timestamps.push(iter.timestamp());
+                iter.next();
+            }
+            assert!(matches!(timestamps.as_slice(), [] | [1] | [2, 1]));
+
+            writer.join().unwrap();
+            assert_eq!(
+                entries(&list),
+                vec![entry("k", 2, "v2"), entry("k", 1, "v1")]
+            );
+        });
+    }
 }
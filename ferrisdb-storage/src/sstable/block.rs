@@ -0,0 +1,712 @@
+//! Prefix-compressed data blocks with restart points
+//!
+//! Entries in a block are sorted, so neighbouring user keys usually share a
+//! prefix. Each entry stores only the part of its user key that differs
+//! from the previous entry's:
+//!
+//! ```text
+//! ┌────────────┬──────────────┬───────────┬──────────────┬───────────┬───────────┬───────────┐
+//! │ Shared Len │ Unshared Len │ Value Len │ Unshared Key │ Timestamp │ Operation │   Value   │
+//! │  (varint)  │   (varint)   │ (varint)  │  (var len)   │ (8 bytes) │ (1 byte)  │ (var len) │
+//! └────────────┴──────────────┴───────────┴──────────────┴───────────┴───────────┴───────────┘
+//! ```
+//!
+//! Every `restart_interval` entries the shared length drops back to zero
+//! and the entry's offset is recorded as a restart point. The restart
+//! offsets follow the entries:
+//!
+//! ```text
+//! ┌───────────┬──────────────────┬─────────────────┐
+//! │  Entries  │ Restart Offsets  │  Restart Count  │
+//! │           │ (4 bytes each)   │    (4 bytes)    │
+//! └───────────┴──────────────────┴─────────────────┘
+//! ```
+//!
+//! Keys at restart points are stored whole, so a seek binary-searches them
+//! and then decodes at most one interval of entries instead of the whole
+//! block.
+
+use crate::sstable::{InternalKey, SSTableEntry};
+use ferrisdb_core::{Error, Operation, Result, Timestamp};
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Set in a data block's tag byte when the block uses this format
+///
+/// Blocks written before it existed hold an entry count followed by full
+/// entries and are re-encoded when read.
+pub(crate) const PREFIX_COMPRESSED_FLAG: u8 = 0x80;
+
+/// Builds one prefix-compressed block
+#[derive(Debug)]
+pub struct BlockBuilder {
+    buffer: Vec<u8>,
+    restarts: Vec<u32>,
+    restart_interval: usize,
+    /// Entries added since the last restart point
+    counter: usize,
+    last_key: Vec<u8>,
+}
+
+impl BlockBuilder {
+    /// Creates a builder that starts a restart point every
+    /// `restart_interval` entries
+    pub fn new(restart_interval: usize) -> Self {
+        Self {
+            buffer: Vec::new(),
+            restarts: Vec::new(),
+            restart_interval: restart_interval.max(1),
+            counter: 0,
+            last_key: Vec::new(),
+        }
+    }
+
+    /// Appends an entry; keys must be added in sorted order
+    pub fn add(&mut self, key: &InternalKey, value: &[u8], operation: Operation) {
+        let user_key = &key.user_key;
+        let shared = if self.restarts.is_empty() || self.counter == self.restart_interval {
+            self.restarts.push(self.buffer.len() as u32);
+            self.counter = 0;
+            0
+        } else {
+            self.last_key
+                .iter()
+                .zip(user_key)
+                .take_while(|(a, b)| a == b)
+                .count()
+        };
+
+        // Safe casts: keys and values are at most MAX_ENTRY_SIZE
+        put_varint32(&mut self.buffer, shared as u32);
+        put_varint32(&mut self.buffer, (user_key.len() - shared) as u32);
+        put_varint32(&mut self.buffer, value.len() as u32);
+        self.buffer.extend_from_slice(&user_key[shared..]);
+        self.buffer.extend_from_slice(&key.timestamp.to_le_bytes());
+        self.buffer.push(operation_byte(operation));
+        self.buffer.extend_from_slice(value);
+
+        self.last_key.truncate(shared);
+        self.last_key.extend_from_slice(&user_key[shared..]);
+        self.counter += 1;
+    }
+
+    /// Returns true if no entries have been added since the last finish
+    pub fn is_empty(&self) -> bool {
+        self.restarts.is_empty()
+    }
+
+    /// Returns the size the block would have if finished now
+    pub fn estimated_size(&self) -> usize {
+        self.buffer.len() + (self.restarts.len() + 1) * 4
+    }
+
+    /// Appends the restart array and returns the block, leaving the
+    /// builder empty
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut block = std::mem::take(&mut self.buffer);
+        for restart in &self.restarts {
+            block.extend_from_slice(&restart.to_le_bytes());
+        }
+        block.extend_from_slice(&(self.restarts.len() as u32).to_le_bytes());
+
+        self.restarts.clear();
+        self.counter = 0;
+        self.last_key.clear();
+        block
+    }
+}
+
+/// A decoded-on-demand data block
+#[derive(Debug)]
+pub struct Block {
+    data: Vec<u8>,
+    /// Offset of the restart array, which is where the entries end
+    restarts_offset: usize,
+    num_restarts: usize,
+}
+
+impl Block {
+    /// Wraps the contents of a block written by [`BlockBuilder`]
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Corruption` if the restart array does not fit.
+    pub fn new(data: Vec<u8>) -> Result<Self> {
+        let corruption = || Error::Corruption("Block restart array is malformed".to_string());
+        if data.len() < 4 {
+            return Err(corruption());
+        }
+        let num_restarts = u32::from_le_bytes(data[data.len() - 4..].try_into().unwrap()) as usize;
+        let restarts_offset = num_restarts
+            .checked_mul(4)
+            .and_then(|size| (data.len() - 4).checked_sub(size))
+            .ok_or_else(corruption)?;
+        if num_restarts == 0 && restarts_offset > 0 {
+            return Err(corruption());
+        }
+        Ok(Self {
+            data,
+            restarts_offset,
+            num_restarts,
+        })
+    }
+
+    /// Re-encodes entries of an older block format
+    pub fn from_entries(entries: &[SSTableEntry], restart_interval: usize) -> Result<Self> {
+        let mut builder = BlockBuilder::new(restart_interval);
+        for entry in entries {
+            builder.add(&entry.key, &entry.value, entry.operation);
+        }
+        Self::new(builder.finish())
+    }
+
+    /// Returns the size of the block contents in bytes
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns a cursor over the block, positioned before the first entry
+    pub fn iter(self: &Arc<Self>) -> BlockIter {
+        BlockIter {
+            block: Arc::clone(self),
+            current: None,
+            next_offset: 0,
+            restart_index: 0,
+            key: Vec::new(),
+            timestamp: 0,
+            operation: Operation::Put,
+            value: 0..0,
+        }
+    }
+
+    fn restart_point(&self, index: usize) -> Result<usize> {
+        let at = self.restarts_offset + index * 4;
+        let offset = u32::from_le_bytes(self.data[at..at + 4].try_into().unwrap()) as usize;
+        if offset >= self.restarts_offset {
+            return Err(Error::Corruption(format!(
+                "Restart point {} is past the end of the block entries",
+                offset
+            )));
+        }
+        Ok(offset)
+    }
+
+    /// Decodes the entry at `offset`
+    fn decode_entry(&self, offset: usize) -> Result<DecodedEntry<'_>> {
+        let entries = &self.data[..self.restarts_offset];
+        let mut data = &entries[offset..];
+        let shared = get_varint32(&mut data)? as usize;
+        let unshared = get_varint32(&mut data)? as usize;
+        let value_len = get_varint32(&mut data)? as usize;
+
+        let unshared_key = take(&mut data, unshared)?;
+        let timestamp = u64::from_le_bytes(take(&mut data, 8)?.try_into().unwrap());
+        let operation = operation_from_byte(take(&mut data, 1)?[0])?;
+        take(&mut data, value_len)?;
+
+        let value_end = entries.len() - data.len();
+        Ok(DecodedEntry {
+            shared,
+            unshared_key,
+            timestamp,
+            operation,
+            value: value_end - value_len..value_end,
+        })
+    }
+}
+
+/// The fields of one entry, with the key still delta-encoded
+struct DecodedEntry<'a> {
+    shared: usize,
+    unshared_key: &'a [u8],
+    timestamp: Timestamp,
+    operation: Operation,
+    value: Range<usize>,
+}
+
+/// A cursor over the entries of a [`Block`]
+///
+/// Holds its block through an `Arc`, so it can outlive the cache lookup
+/// that produced it.
+pub struct BlockIter {
+    block: Arc<Block>,
+    /// Offset of the current entry, or `None` when not positioned on one
+    current: Option<usize>,
+    next_offset: usize,
+    /// Restart interval containing the current entry
+    restart_index: usize,
+    key: Vec<u8>,
+    timestamp: Timestamp,
+    operation: Operation,
+    value: Range<usize>,
+}
+
+impl BlockIter {
+    /// Returns true if the cursor is positioned on an entry
+    pub fn valid(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Returns the current user key; only meaningful while valid
+    pub fn user_key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Returns the current entry's timestamp
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Returns the current entry's operation
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// Returns the current value, borrowed from the block
+    pub fn value(&self) -> &[u8] {
+        &self.block.data[self.value.clone()]
+    }
+
+    /// Copies the current entry out of the block
+    pub fn entry(&self) -> SSTableEntry {
+        SSTableEntry::new(
+            InternalKey::new(self.key.clone(), self.timestamp),
+            self.value().to_vec(),
+            self.operation,
+        )
+    }
+
+    /// Positions the cursor on the first entry
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Corruption` if the entry cannot be decoded.
+    pub fn seek_to_first(&mut self) -> Result<()> {
+        if self.block.num_restarts == 0 {
+            self.current = None;
+            return Ok(());
+        }
+        self.seek_to_restart_point(0)?;
+        self.advance()
+    }
+
+    /// Positions the cursor on the first entry at or after `target`
+    ///
+    /// Binary-searches the restart points for the last one before
+    /// `target`, then scans forward from it. The cursor is invalid if every
+    /// entry is before `target`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Corruption` if an entry cannot be decoded.
+    pub fn seek(&mut self, target: &InternalKey) -> Result<()> {
+        if self.block.num_restarts == 0 {
+            self.current = None;
+            return Ok(());
+        }
+
+        let mut left = 0;
+        let mut right = self.block.num_restarts - 1;
+        while left < right {
+            let mid = (left + right).div_ceil(2);
+            let entry = self.block.decode_entry(self.block.restart_point(mid)?)?;
+            if entry.shared != 0 {
+                return Err(Error::Corruption(
+                    "Entry at a restart point shares a key prefix".to_string(),
+                ));
+            }
+            if compare(entry.unshared_key, entry.timestamp, target) == Ordering::Less {
+                left = mid;
+            } else {
+                right = mid - 1;
+            }
+        }
+
+        self.seek_to_restart_point(left)?;
+        loop {
+            self.advance()?;
+            if !self.valid() || compare(&self.key, self.timestamp, target) != Ordering::Less {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Positions the cursor on the last entry
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Corruption` if an entry cannot be decoded.
+    pub fn seek_to_last(&mut self) -> Result<()> {
+        if self.block.num_restarts == 0 {
+            self.current = None;
+            return Ok(());
+        }
+        self.seek_to_restart_point(self.block.num_restarts - 1)?;
+        loop {
+            self.advance()?;
+            if !self.valid() || self.next_offset >= self.block.restarts_offset {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Moves to the previous entry, or invalidates the cursor before the
+    /// first
+    ///
+    /// Entries are only delta-encoded forwards, so this rescans from the
+    /// last restart point before the current entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Corruption` if an entry cannot be decoded.
+    pub fn prev(&mut self) -> Result<()> {
+        let Some(original) = self.current else {
+            return Ok(());
+        };
+
+        let mut restart = self.restart_index;
+        while self.block.restart_point(restart)? >= original {
+            if restart == 0 {
+                // Nothing before the first entry
+                self.current = None;
+                self.next_offset = self.block.restarts_offset;
+                return Ok(());
+            }
+            restart -= 1;
+        }
+
+        self.seek_to_restart_point(restart)?;
+        loop {
+            self.advance()?;
+            if !self.valid() || self.next_offset >= original {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Moves to the next entry, or invalidates the cursor after the last
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Corruption` if the entry cannot be decoded.
+    pub fn advance(&mut self) -> Result<()> {
+        let offset = self.next_offset;
+        if offset >= self.block.restarts_offset {
+            self.current = None;
+            self.next_offset = self.block.restarts_offset;
+            return Ok(());
+        }
+
+        let block = Arc::clone(&self.block);
+        let entry = block.decode_entry(offset);
+        let entry = match entry {
+            Ok(entry) if entry.shared <= self.key.len() => entry,
+            Ok(_) => return Err(self.corrupt("Entry shares more than the previous key")),
+            Err(e) => {
+                self.current = None;
+                return Err(e);
+            }
+        };
+        self.key.truncate(entry.shared);
+        self.key.extend_from_slice(entry.unshared_key);
+        self.timestamp = entry.timestamp;
+        self.operation = entry.operation;
+        self.value = entry.value.clone();
+        self.current = Some(offset);
+        self.next_offset = entry.value.end;
+
+        while self.restart_index + 1 < block.num_restarts
+            && block.restart_point(self.restart_index + 1)? <= offset
+        {
+            self.restart_index += 1;
+        }
+        Ok(())
+    }
+
+    fn seek_to_restart_point(&mut self, index: usize) -> Result<()> {
+        self.key.clear();
+        self.restart_index = index;
+        self.next_offset = self.block.restart_point(index)?;
+        self.current = None;
+        Ok(())
+    }
+
+    fn corrupt(&mut self, reason: &str) -> Error {
+        self.current = None;
+        Error::Corruption(reason.to_string())
+    }
+}
+
+impl Iterator for BlockIter {
+    type Item = Result<SSTableEntry>;
+
+    /// Yields the current entry and moves past it
+    ///
+    /// A fresh cursor starts at the first entry.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current.is_none() && self.next_offset == self.block.restarts_offset {
+            return None;
+        }
+        if self.current.is_none() {
+            if let Err(e) = self.advance() {
+                self.next_offset = self.block.restarts_offset;
+                return Some(Err(e));
+            }
+        }
+        let entry = self.entry();
+        if let Err(e) = self.advance() {
+            self.next_offset = self.block.restarts_offset;
+            return Some(Err(e));
+        }
+        Some(Ok(entry))
+    }
+}
+
+/// Orders an entry against `target` by (user key ASC, timestamp DESC)
+pub(crate) fn compare(user_key: &[u8], timestamp: Timestamp, target: &InternalKey) -> Ordering {
+    user_key
+        .cmp(&target.user_key)
+        .then_with(|| target.timestamp.cmp(&timestamp))
+}
+
+/// Encodes an operation as stored in data blocks
+pub(crate) fn operation_byte(operation: Operation) -> u8 {
+    match operation {
+        Operation::Put => 0,
+        Operation::Delete => 1,
+    }
+}
+
+/// Decodes a stored operation byte
+pub(crate) fn operation_from_byte(byte: u8) -> Result<Operation> {
+    match byte {
+        0 => Ok(Operation::Put),
+        1 => Ok(Operation::Delete),
+        _ => Err(Error::InvalidFormat(format!(
+            "Invalid operation byte: {}",
+            byte
+        ))),
+    }
+}
+
+fn put_varint32(buf: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn get_varint32(data: &mut &[u8]) -> Result<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let (&byte, rest) = data
+            .split_first()
+            .ok_or_else(|| Error::Corruption("Truncated varint in block".to_string()))?;
+        *data = rest;
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::Corruption("Varint in block is too long".to_string()))
+}
+
+/// Takes `len` bytes from the front of `data`
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if len > data.len() {
+        return Err(Error::Corruption(format!(
+            "Length {} exceeds the {} bytes left in the block",
+            len,
+            data.len()
+        )));
+    }
+    let (bytes, rest) = data.split_at(len);
+    *data = rest;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(keys: &[(&str, Timestamp)], restart_interval: usize) -> Arc<Block> {
+        let mut builder = BlockBuilder::new(restart_interval);
+        for (key, timestamp) in keys {
+            let key = InternalKey::new(key.as_bytes().to_vec(), *timestamp);
+            let value = format!("{}@{}", String::from_utf8_lossy(&key.user_key), timestamp);
+            builder.add(&key, value.as_bytes(), Operation::Put);
+        }
+        Arc::new(Block::new(builder.finish()).unwrap())
+    }
+
+    fn sample_keys() -> Vec<(String, Timestamp)> {
+        (0..100)
+            .flat_map(|i| [(format!("user:{:04}", i), 9), (format!("user:{:04}", i), 3)])
+            .collect()
+    }
+
+    fn sample_block(restart_interval: usize) -> Arc<Block> {
+        let keys = sample_keys();
+        let keys: Vec<_> = keys.iter().map(|(k, t)| (k.as_str(), *t)).collect();
+        build(&keys, restart_interval)
+    }
+
+    #[test]
+    fn test_round_trip_and_prefix_compression() {
+        let keys = sample_keys();
+        for restart_interval in [1, 2, 16, 1000] {
+            let block = sample_block(restart_interval);
+            let entries: Vec<_> = block.iter().map(|e| e.unwrap()).collect();
+            assert_eq!(entries.len(), keys.len());
+            for (entry, (key, timestamp)) in entries.iter().zip(&keys) {
+                assert_eq!(entry.key.user_key, key.as_bytes());
+                assert_eq!(entry.key.timestamp, *timestamp);
+                assert_eq!(entry.value, format!("{}@{}", key, timestamp).into_bytes());
+            }
+        }
+
+        // Sharing "user:00" saves most of every key
+        assert!(sample_block(16).size() < sample_block(1).size());
+    }
+
+    #[test]
+    fn test_seek() {
+        for restart_interval in [1, 3, 16] {
+            let block = sample_block(restart_interval);
+            let mut iter = block.iter();
+
+            // Exact match
+            iter.seek(&InternalKey::new(b"user:0042".to_vec(), 3))
+                .unwrap();
+            assert_eq!((iter.user_key(), iter.timestamp()), (&b"user:0042"[..], 3));
+            assert_eq!(iter.value(), b"user:0042@3");
+
+            // Newest version visible at a read timestamp
+            iter.seek(&InternalKey::new(b"user:0042".to_vec(), 5))
+                .unwrap();
+            assert_eq!((iter.user_key(), iter.timestamp()), (&b"user:0042"[..], 3));
+            iter.seek(&InternalKey::new(b"user:0042".to_vec(), 2))
+                .unwrap();
+            assert_eq!((iter.user_key(), iter.timestamp()), (&b"user:0043"[..], 9));
+
+            // Between keys, before the first and after the last
+            iter.seek(&InternalKey::new(b"user:0042x".to_vec(), 100))
+                .unwrap();
+            assert_eq!(iter.user_key(), b"user:0043");
+            iter.seek(&InternalKey::new(b"a".to_vec(), 0)).unwrap();
+            assert_eq!((iter.user_key(), iter.timestamp()), (&b"user:0000"[..], 9));
+            iter.seek(&InternalKey::new(b"z".to_vec(), 0)).unwrap();
+            assert!(!iter.valid());
+
+            // Iteration continues from the seek position
+            iter.seek(&InternalKey::new(b"user:0098".to_vec(), 9))
+                .unwrap();
+            let rest: Vec<_> = iter.by_ref().map(|e| e.unwrap().key).collect();
+            assert_eq!(rest.len(), 4);
+            assert_eq!(rest[3], InternalKey::new(b"user:0099".to_vec(), 3));
+        }
+    }
+
+    #[test]
+    fn test_seek_to_first_and_advance() {
+        let block = sample_block(4);
+        let mut iter = block.iter();
+        iter.seek_to_first().unwrap();
+        let mut count = 0;
+        while iter.valid() {
+            count += 1;
+            iter.advance().unwrap();
+        }
+        assert_eq!(count, 200);
+    }
+
+    #[test]
+    fn test_seek_to_last_and_prev() {
+        let keys = sample_keys();
+        for restart_interval in [1, 3, 16, 1000] {
+            let block = sample_block(restart_interval);
+            let mut iter = block.iter();
+            iter.seek_to_last().unwrap();
+            let mut backward = Vec::new();
+            while iter.valid() {
+                backward.push((
+                    String::from_utf8(iter.user_key().to_vec()).unwrap(),
+                    iter.timestamp(),
+                ));
+                iter.prev().unwrap();
+            }
+            backward.reverse();
+            assert_eq!(backward, keys);
+
+            // Changing direction returns to the same entries
+            iter.seek(&InternalKey::new(b"user:0050".to_vec(), 3))
+                .unwrap();
+            iter.prev().unwrap();
+            assert_eq!((iter.user_key(), iter.timestamp()), (&b"user:0050"[..], 9));
+            iter.advance().unwrap();
+            iter.advance().unwrap();
+            assert_eq!((iter.user_key(), iter.timestamp()), (&b"user:0051"[..], 9));
+            iter.prev().unwrap();
+            assert_eq!(iter.value(), b"user:0050@3");
+
+            // Stepping back from the first entry invalidates the cursor
+            iter.seek_to_first().unwrap();
+            iter.prev().unwrap();
+            assert!(!iter.valid());
+        }
+    }
+
+    #[test]
+    fn test_empty_block() {
+        let block = Arc::new(Block::new(BlockBuilder::new(16).finish()).unwrap());
+        let mut iter = block.iter();
+        iter.seek(&InternalKey::new(b"key".to_vec(), 1)).unwrap();
+        assert!(!iter.valid());
+        iter.seek_to_last().unwrap();
+        assert!(!iter.valid());
+        assert_eq!(block.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_malformed_blocks() {
+        assert!(matches!(Block::new(vec![1, 0]), Err(Error::Corruption(_))));
+        // Claims more restart points than the block can hold
+        assert!(matches!(
+            Block::new(100u32.to_le_bytes().to_vec()),
+            Err(Error::Corruption(_))
+        ));
+
+        // A truncated value is reported, not read past
+        let mut builder = BlockBuilder::new(16);
+        builder.add(
+            &InternalKey::new(b"key".to_vec(), 1),
+            b"value",
+            Operation::Put,
+        );
+        let full = builder.finish();
+        // Drop the restart array and the last two value bytes, then append
+        // a fresh array with the single restart point
+        let mut data = full[..full.len() - 8 - 2].to_vec();
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        let block = Arc::new(Block::new(data).unwrap());
+        assert!(matches!(
+            block.iter().next(),
+            Some(Err(Error::Corruption(_)))
+        ));
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 300, 16_383, 16_384, u32::MAX] {
+            let mut buf = Vec::new();
+            put_varint32(&mut buf, value);
+            let mut data = buf.as_slice();
+            assert_eq!(get_varint32(&mut data).unwrap(), value);
+            assert!(data.is_empty());
+        }
+        assert!(get_varint32(&mut &[0x80u8][..]).is_err());
+    }
+}
@@ -0,0 +1,365 @@
+//! Engine-wide cache of uncompressed SSTable data blocks
+//!
+//! Blocks are keyed by (file number, block offset). File numbers are never
+//! reused, so entries for deleted files can never be returned by mistake;
+//! they simply age out. Files with a partitioned index also cache their
+//! index and filter partitions here, so they compete with data blocks for
+//! the same capacity.
+//!
+//! The cache is split into shards, each with its own lock and an equal
+//! share of the capacity, so concurrent readers rarely contend. Each shard
+//! evicts its least recently used blocks once their total size exceeds its
+//! share.
+
+use crate::sstable::{Block, BloomFilter, IndexEntry};
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Default number of shards, a power of two
+const DEFAULT_SHARD_COUNT: usize = 16;
+
+/// Approximate bookkeeping cost of one cached block, on top of its entries
+const BLOCK_OVERHEAD: usize = 64;
+
+/// Identifies a data block across all SSTables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockCacheKey {
+    /// Number of the SSTable holding the block
+    pub file_number: u64,
+    /// Offset of the block within the file
+    pub block_offset: u64,
+}
+
+/// A block held by the cache
+#[derive(Debug, Clone)]
+pub enum CachedBlock {
+    /// An uncompressed data block
+    Data(Arc<Block>),
+    /// The entries of one index partition
+    IndexPartition(Arc<[IndexEntry]>),
+    /// One partition of a bloom filter
+    FilterPartition(Arc<BloomFilter>),
+}
+
+impl CachedBlock {
+    /// Returns the approximate memory used by the block
+    pub fn size(&self) -> usize {
+        match self {
+            CachedBlock::Data(block) => block.size(),
+            CachedBlock::IndexPartition(entries) => {
+                entries.iter().map(IndexEntry::serialized_size).sum()
+            }
+            CachedBlock::FilterPartition(filter) => filter.size(),
+        }
+    }
+}
+
+impl From<Arc<Block>> for CachedBlock {
+    fn from(block: Arc<Block>) -> Self {
+        CachedBlock::Data(block)
+    }
+}
+
+impl From<Arc<[IndexEntry]>> for CachedBlock {
+    fn from(entries: Arc<[IndexEntry]>) -> Self {
+        CachedBlock::IndexPartition(entries)
+    }
+}
+
+impl From<Arc<BloomFilter>> for CachedBlock {
+    fn from(filter: Arc<BloomFilter>) -> Self {
+        CachedBlock::FilterPartition(filter)
+    }
+}
+
+/// Counters describing cache effectiveness
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockCacheStats {
+    /// Lookups that found their block
+    pub hits: u64,
+    /// Lookups that did not
+    pub misses: u64,
+    /// Blocks evicted to make room
+    pub evictions: u64,
+    /// Total charge of the cached blocks in bytes
+    pub usage: usize,
+    /// Configured capacity in bytes
+    pub capacity: usize,
+}
+
+/// A sharded LRU cache of uncompressed blocks
+///
+/// # Example
+///
+/// ```
+/// use ferrisdb_storage::sstable::{Block, BlockBuilder, BlockCache, BlockCacheKey};
+/// use std::sync::Arc;
+///
+/// let cache = BlockCache::new(8 * 1024 * 1024);
+/// let key = BlockCacheKey { file_number: 7, block_offset: 0 };
+///
+/// assert!(cache.get(&key).is_none());
+/// let block = Block::new(BlockBuilder::new(16).finish())?;
+/// cache.insert(key, Arc::new(block));
+/// assert!(cache.get(&key).is_some());
+/// assert_eq!(cache.stats().hits, 1);
+/// # Ok::<(), ferrisdb_core::Error>(())
+/// ```
+pub struct BlockCache {
+    shards: Vec<Mutex<LruShard>>,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl BlockCache {
+    /// Creates a cache holding up to `capacity` bytes of uncompressed blocks
+    pub fn new(capacity: usize) -> Self {
+        Self::with_shards(capacity, DEFAULT_SHARD_COUNT)
+    }
+
+    /// Creates a cache split into `shard_count` independently locked shards
+    pub fn with_shards(capacity: usize, shard_count: usize) -> Self {
+        let shard_count = shard_count.max(1);
+        let shard_capacity = capacity.div_ceil(shard_count);
+        Self {
+            shards: (0..shard_count)
+                .map(|_| Mutex::new(LruShard::new(shard_capacity)))
+                .collect(),
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the cached block for `key`, marking it most recently used
+    pub fn get(&self, key: &BlockCacheKey) -> Option<CachedBlock> {
+        let block = self.shard(key).lock().get(key);
+        let counter = if block.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        block
+    }
+
+    /// Caches a block, evicting least recently used blocks as needed
+    ///
+    /// A block larger than a whole shard is not cached.
+    pub fn insert(&self, key: BlockCacheKey, block: impl Into<CachedBlock>) {
+        let block = block.into();
+        let charge = block_charge(&block);
+        self.shard(&key).lock().insert(key, block, charge);
+    }
+
+    /// Returns the current counters
+    pub fn stats(&self) -> BlockCacheStats {
+        let mut stats = BlockCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            capacity: self.capacity,
+            ..Default::default()
+        };
+        for shard in &self.shards {
+            let shard = shard.lock();
+            stats.usage += shard.usage;
+            stats.evictions += shard.evictions;
+        }
+        stats
+    }
+
+    fn shard(&self, key: &BlockCacheKey) -> &Mutex<LruShard> {
+        // Mix both halves so consecutive blocks of one file spread out
+        let hash = (key.file_number.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ key.block_offset)
+            .wrapping_mul(0xff51_afd7_ed55_8ccd);
+        &self.shards[(hash >> 32) as usize % self.shards.len()]
+    }
+}
+
+impl std::fmt::Debug for BlockCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockCache")
+            .field("shards", &self.shards.len())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// Returns the memory charged for a block
+fn block_charge(block: &CachedBlock) -> usize {
+    BLOCK_OVERHEAD + block.size()
+}
+
+/// One independently locked part of the cache
+///
+/// Recency is tracked with a counter: every access stamps the entry with
+/// the next tick, and `lru` orders entries by their last tick.
+struct LruShard {
+    capacity: usize,
+    usage: usize,
+    evictions: u64,
+    next_tick: u64,
+    entries: HashMap<BlockCacheKey, CacheEntry>,
+    lru: BTreeMap<u64, BlockCacheKey>,
+}
+
+struct CacheEntry {
+    block: CachedBlock,
+    charge: usize,
+    tick: u64,
+}
+
+impl LruShard {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            usage: 0,
+            evictions: 0,
+            next_tick: 0,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &BlockCacheKey) -> Option<CachedBlock> {
+        let tick = self.next_tick;
+        let cached = self.entries.get_mut(key)?;
+        self.lru.remove(&cached.tick);
+        cached.tick = tick;
+        self.lru.insert(tick, *key);
+        self.next_tick += 1;
+        Some(cached.block.clone())
+    }
+
+    fn insert(&mut self, key: BlockCacheKey, block: CachedBlock, charge: usize) {
+        if charge > self.capacity {
+            return;
+        }
+        if let Some(old) = self.entries.remove(&key) {
+            self.lru.remove(&old.tick);
+            self.usage -= old.charge;
+        }
+
+        while self.usage + charge > self.capacity {
+            let Some((_, oldest)) = self.lru.pop_first() else {
+                break;
+            };
+            let evicted = self.entries.remove(&oldest).expect("LRU entry is cached");
+            self.usage -= evicted.charge;
+            self.evictions += 1;
+        }
+
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.lru.insert(tick, key);
+        self.entries.insert(
+            key,
+            CacheEntry {
+                block,
+                charge,
+                tick,
+            },
+        );
+        self.usage += charge;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sstable::{BlockBuilder, InternalKey};
+    use ferrisdb_core::Operation;
+
+    fn key(file_number: u64, block_offset: u64) -> BlockCacheKey {
+        BlockCacheKey {
+            file_number,
+            block_offset,
+        }
+    }
+
+    fn block(value_size: usize) -> Arc<Block> {
+        let mut builder = BlockBuilder::new(16);
+        builder.add(
+            &InternalKey::new(b"key".to_vec(), 1),
+            &vec![0; value_size],
+            Operation::Put,
+        );
+        Arc::new(Block::new(builder.finish()).unwrap())
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let charge = block_charge(&block(100).into());
+        let cache = BlockCache::with_shards(3 * charge, 1);
+
+        cache.insert(key(1, 0), block(100));
+        cache.insert(key(1, 100), block(100));
+        cache.insert(key(2, 0), block(100));
+        // Touch the oldest block so the second one becomes the LRU
+        assert!(cache.get(&key(1, 0)).is_some());
+
+        cache.insert(key(2, 100), block(100));
+        assert!(cache.get(&key(1, 100)).is_none());
+        assert!(cache.get(&key(1, 0)).is_some());
+        assert!(cache.get(&key(2, 0)).is_some());
+        assert!(cache.get(&key(2, 100)).is_some());
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 4);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.usage, 3 * charge);
+    }
+
+    #[test]
+    fn test_usage_stays_within_capacity() {
+        let cache = BlockCache::with_shards(64 * 1024, 4);
+        for offset in 0..1000 {
+            cache.insert(key(offset % 7, offset), block(500));
+            assert!(cache.stats().usage <= 64 * 1024);
+        }
+        assert!(cache.stats().evictions > 0);
+
+        // Oversized blocks are not cached at all
+        cache.insert(key(99, 0), block(64 * 1024));
+        assert!(cache.get(&key(99, 0)).is_none());
+    }
+
+    #[test]
+    fn test_reinsert_replaces_block() {
+        let cache = BlockCache::with_shards(1024 * 1024, 2);
+        cache.insert(key(1, 0), block(10));
+        cache.insert(key(1, 0), block(20));
+
+        assert_eq!(cache.get(&key(1, 0)).unwrap().size(), block(20).size());
+        assert_eq!(cache.stats().usage, block_charge(&block(20).into()));
+    }
+
+    #[test]
+    fn test_concurrent_access() {
+        let cache = Arc::new(BlockCache::new(256 * 1024));
+        let handles: Vec<_> = (0..4)
+            .map(|thread| {
+                let cache = Arc::clone(&cache);
+                std::thread::spawn(move || {
+                    for offset in 0..500 {
+                        let key = key(thread, offset % 50);
+                        if cache.get(&key).is_none() {
+                            cache.insert(key, block(100));
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits + stats.misses, 2000);
+        assert!(stats.hits > stats.misses);
+    }
+}
@@ -0,0 +1,270 @@
+//! Bloom filters for SSTable point lookups
+//!
+//! Each SSTable carries one filter over the user keys it contains. A lookup
+//! for a key the filter has never seen usually answers "no" without reading
+//! a data block; a "maybe" still needs the block read.
+//!
+//! With `bits_per_key` bits for every key and `k = bits_per_key * ln 2`
+//! probes, the false positive rate is roughly `0.6185^bits_per_key`, about
+//! 1% at 10 bits per key.
+
+use crc32fast::Hasher;
+use ferrisdb_core::{Error, Result};
+
+/// Size of the hash count and checksum after the bit array
+const TRAILER_SIZE: usize = 8;
+
+/// Upper bound on probes per key; more only slows lookups down
+const MAX_HASH_COUNT: u32 = 30;
+
+/// Filters smaller than this have a poor false positive rate for few keys
+const MIN_BITS: usize = 64;
+
+/// Collects the keys of an SSTable and builds its filter block
+#[derive(Debug, Clone)]
+pub struct BloomFilterBuilder {
+    bits_per_key: usize,
+    key_hashes: Vec<u32>,
+}
+
+impl BloomFilterBuilder {
+    /// Creates a builder using `bits_per_key` bits for every added key
+    ///
+    /// With 0 bits per key the built filter is empty and matches every key.
+    pub fn new(bits_per_key: usize) -> Self {
+        Self {
+            bits_per_key,
+            key_hashes: Vec::new(),
+        }
+    }
+
+    /// Returns the policy name recorded in table properties
+    ///
+    /// `bloom:<bits per key>`, or empty when no filter is built.
+    pub fn policy_name(&self) -> String {
+        if self.bits_per_key == 0 {
+            String::new()
+        } else {
+            format!("bloom:{}", self.bits_per_key)
+        }
+    }
+
+    /// Returns true if no key has been added
+    pub fn is_empty(&self) -> bool {
+        self.key_hashes.is_empty()
+    }
+
+    /// Adds a user key to the filter
+    pub fn add_key(&mut self, user_key: &[u8]) {
+        if self.bits_per_key > 0 {
+            self.key_hashes.push(bloom_hash(user_key));
+        }
+    }
+
+    /// Serializes the filter in the Bloom Filter block format
+    ///
+    /// ```text
+    /// ┌─────────────────┬─────────────────┬─────────────┐
+    /// │   Bit Array     │   Hash Count    │  Checksum   │
+    /// │   (variable)    │    (4 bytes)    │  (4 bytes)  │
+    /// └─────────────────┴─────────────────┴─────────────┘
+    /// ```
+    ///
+    /// The checksum is a CRC32 over the bit array and hash count.
+    pub fn finish(&self) -> Vec<u8> {
+        let (bits, hash_count) = if self.key_hashes.is_empty() {
+            (Vec::new(), 0)
+        } else {
+            let num_bits = (self.key_hashes.len() * self.bits_per_key).max(MIN_BITS);
+            let mut bits = vec![0u8; num_bits.div_ceil(8)];
+            let hash_count = hash_count(self.bits_per_key);
+            let num_bits = bits.len() * 8;
+
+            for &hash in &self.key_hashes {
+                for bit in probes(hash, hash_count, num_bits) {
+                    bits[bit / 8] |= 1 << (bit % 8);
+                }
+            }
+            (bits, hash_count)
+        };
+
+        let mut block = bits;
+        block.extend_from_slice(&hash_count.to_le_bytes());
+        let mut hasher = Hasher::new();
+        hasher.update(&block);
+        block.extend_from_slice(&hasher.finalize().to_le_bytes());
+        block
+    }
+}
+
+/// A decoded filter block
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u8>,
+    hash_count: u32,
+}
+
+impl BloomFilter {
+    /// Parses a block written by [`BloomFilterBuilder::finish`]
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Corruption` if the block is truncated or its checksum
+    /// does not match.
+    pub fn decode(block: &[u8]) -> Result<Self> {
+        if block.len() < TRAILER_SIZE {
+            return Err(Error::Corruption(format!(
+                "Bloom filter block too small: {} bytes",
+                block.len()
+            )));
+        }
+
+        let (payload, checksum) = block.split_at(block.len() - 4);
+        let mut hasher = Hasher::new();
+        hasher.update(payload);
+        if hasher.finalize() != u32::from_le_bytes(checksum.try_into().unwrap()) {
+            return Err(Error::Corruption(
+                "Bloom filter checksum mismatch".to_string(),
+            ));
+        }
+
+        let (bits, hash_count) = payload.split_at(payload.len() - 4);
+        Ok(Self {
+            bits: bits.to_vec(),
+            hash_count: u32::from_le_bytes(hash_count.try_into().unwrap()),
+        })
+    }
+
+    /// Returns the size of the decoded filter in bytes
+    pub fn size(&self) -> usize {
+        self.bits.len() + std::mem::size_of::<Self>()
+    }
+
+    /// Returns false only if `user_key` was never added to the filter
+    pub fn may_contain(&self, user_key: &[u8]) -> bool {
+        if self.hash_count == 0 || self.bits.is_empty() {
+            return true;
+        }
+        let num_bits = self.bits.len() * 8;
+        probes(bloom_hash(user_key), self.hash_count, num_bits)
+            .all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+}
+
+/// Returns the number of probes that minimizes false positives
+fn hash_count(bits_per_key: usize) -> u32 {
+    // k = bits_per_key * ln(2), rounded down
+    ((bits_per_key as f64 * 0.69) as u32).clamp(1, MAX_HASH_COUNT)
+}
+
+/// Yields the bit positions probed for a key
+///
+/// Uses double hashing: probe `i` is `h + i * delta`, with `delta` derived
+/// from `h` by rotation, so one hash computation serves every probe.
+fn probes(hash: u32, hash_count: u32, num_bits: usize) -> impl Iterator<Item = usize> {
+    let delta = hash.rotate_right(17);
+    (0..hash_count).scan(hash, move |h, _| {
+        let bit = *h as usize % num_bits;
+        *h = h.wrapping_add(delta);
+        Some(bit)
+    })
+}
+
+/// 32-bit Murmur-style hash of a key
+fn bloom_hash(data: &[u8]) -> u32 {
+    const SEED: u32 = 0xbc9f_1d34;
+    const M: u32 = 0xc6a4_a793;
+
+    let mut h = SEED ^ (data.len() as u32).wrapping_mul(M);
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        h = h.wrapping_add(u32::from_le_bytes(chunk.try_into().unwrap()));
+        h = h.wrapping_mul(M);
+        h ^= h >> 16;
+    }
+
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        for (i, &byte) in rest.iter().enumerate() {
+            h = h.wrapping_add((byte as u32) << (8 * i));
+        }
+        h = h.wrapping_mul(M);
+        h ^= h >> 24;
+    }
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(keys: impl Iterator<Item = Vec<u8>>, bits_per_key: usize) -> BloomFilter {
+        let mut builder = BloomFilterBuilder::new(bits_per_key);
+        for key in keys {
+            builder.add_key(&key);
+        }
+        BloomFilter::decode(&builder.finish()).unwrap()
+    }
+
+    fn false_positive_rate(filter: &BloomFilter) -> f64 {
+        let probes = 10_000;
+        let hits = (0..probes)
+            .filter(|i| filter.may_contain(format!("missing{:06}", i).as_bytes()))
+            .count();
+        hits as f64 / probes as f64
+    }
+
+    #[test]
+    fn test_no_false_negatives() {
+        let keys = || (0..1000).map(|i| format!("key{:06}", i).into_bytes());
+        let filter = build(keys(), 10);
+        assert!(keys().all(|key| filter.may_contain(&key)));
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        for (bits_per_key, max_rate) in [(10, 0.02), (20, 0.001), (4, 0.2)] {
+            let keys = (0..10_000).map(|i| format!("key{:06}", i).into_bytes());
+            let filter = build(keys, bits_per_key);
+            let rate = false_positive_rate(&filter);
+            assert!(
+                rate <= max_rate,
+                "{} bits per key gave a false positive rate of {}",
+                bits_per_key,
+                rate
+            );
+        }
+    }
+
+    #[test]
+    fn test_small_filters_stay_useful() {
+        let filter = build((0..3).map(|i| vec![i]), 10);
+        assert!(false_positive_rate(&filter) < 0.1);
+    }
+
+    #[test]
+    fn test_disabled_filter_matches_everything() {
+        let filter = build((0..100).map(|i| vec![i]), 0);
+        assert!(filter.may_contain(b"anything"));
+
+        let empty = build(std::iter::empty(), 10);
+        assert!(empty.may_contain(b"anything"));
+    }
+
+    #[test]
+    fn test_decode_rejects_damage() {
+        let mut builder = BloomFilterBuilder::new(10);
+        builder.add_key(b"key");
+        let mut block = builder.finish();
+
+        block[0] ^= 0x01;
+        assert!(matches!(
+            BloomFilter::decode(&block),
+            Err(Error::Corruption(_))
+        ));
+        assert!(matches!(
+            BloomFilter::decode(&block[..4]),
+            Err(Error::Corruption(_))
+        ));
+    }
+}
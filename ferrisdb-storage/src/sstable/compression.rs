@@ -0,0 +1,134 @@
+//! Per-block compression for SSTable data blocks
+//!
+//! Every data block records the algorithm it was stored with in a one-byte
+//! tag, so files written under different `StorageConfig::compression`
+//! settings, or with some blocks left raw, all read back correctly.
+
+use ferrisdb_core::{CompressionType, Error, Result};
+use std::borrow::Cow;
+
+/// Compression must save at least 1/8 of a block to be worth decoding
+const MIN_SAVINGS_DIVISOR: usize = 8;
+
+/// Returns the on-disk tag for a compression type
+pub fn compression_tag(compression: CompressionType) -> u8 {
+    match compression {
+        CompressionType::None => 0,
+        CompressionType::Lz4 => 1,
+        CompressionType::Snappy => 2,
+    }
+}
+
+/// Parses an on-disk compression tag
+///
+/// # Errors
+///
+/// Returns `Error::Corruption` for an unknown tag.
+pub fn compression_from_tag(tag: u8) -> Result<CompressionType> {
+    match tag {
+        0 => Ok(CompressionType::None),
+        1 => Ok(CompressionType::Lz4),
+        2 => Ok(CompressionType::Snappy),
+        _ => Err(Error::Corruption(format!(
+            "Unknown block compression tag: {}",
+            tag
+        ))),
+    }
+}
+
+/// Compresses a block, or returns `None` if it should be stored raw
+///
+/// A block stays raw when compression is disabled or would not save at
+/// least 12.5% of its size.
+///
+/// # Errors
+///
+/// Returns an error if the compressor fails.
+pub fn compress(compression: CompressionType, raw: &[u8]) -> Result<Option<Vec<u8>>> {
+    let compressed = match compression {
+        CompressionType::None => return Ok(None),
+        CompressionType::Lz4 => lz4::block::compress(raw, None, true)?,
+        CompressionType::Snappy => snap::raw::Encoder::new()
+            .compress_vec(raw)
+            .map_err(std::io::Error::from)?,
+    };
+
+    if compressed.len() > raw.len() - raw.len() / MIN_SAVINGS_DIVISOR {
+        return Ok(None);
+    }
+    Ok(Some(compressed))
+}
+
+/// Restores a block stored with `compression`
+///
+/// Raw blocks are returned as they are, without a copy.
+///
+/// # Errors
+///
+/// Returns `Error::Corruption` if the data cannot be decompressed.
+pub fn decompress(compression: CompressionType, data: &[u8]) -> Result<Cow<'_, [u8]>> {
+    let result = match compression {
+        CompressionType::None => return Ok(Cow::Borrowed(data)),
+        CompressionType::Lz4 => lz4::block::decompress(data, None).map_err(|e| e.to_string()),
+        CompressionType::Snappy => snap::raw::Decoder::new()
+            .decompress_vec(data)
+            .map_err(|e| e.to_string()),
+    };
+    result.map(Cow::Owned).map_err(|e| {
+        Error::Corruption(format!(
+            "Failed to decompress {:?} block: {}",
+            compression, e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressible() -> Vec<u8> {
+        (0..4096).map(|i| b"abcdefgh"[i % 8]).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let raw = compressible();
+        for compression in [CompressionType::Lz4, CompressionType::Snappy] {
+            let compressed = compress(compression, &raw).unwrap().unwrap();
+            assert!(compressed.len() < raw.len() / 2);
+            assert_eq!(decompress(compression, &compressed).unwrap(), raw);
+        }
+        assert_eq!(compress(CompressionType::None, &raw).unwrap(), None);
+    }
+
+    #[test]
+    fn test_incompressible_blocks_stay_raw() {
+        // A xorshift stream has no redundancy to exploit
+        let mut state = 0x2545_f491_u32;
+        let raw: Vec<u8> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        for compression in [CompressionType::Lz4, CompressionType::Snappy] {
+            assert_eq!(compress(compression, &raw).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_tags() {
+        for compression in [
+            CompressionType::None,
+            CompressionType::Lz4,
+            CompressionType::Snappy,
+        ] {
+            let tag = compression_tag(compression);
+            assert_eq!(compression_from_tag(tag).unwrap(), compression);
+        }
+        assert!(matches!(compression_from_tag(9), Err(Error::Corruption(_))));
+        assert!(decompress(CompressionType::Snappy, b"garbage").is_err());
+    }
+}
@@ -20,6 +20,8 @@
 //! ├─────────────────┤
 //! │  Bloom Filter   │ ← Probabilistic existence filter
 //! ├─────────────────┤
+//! │   Properties    │ ← Entry counts, key range, settings
+//! ├─────────────────┤
 //! │     Footer      │ ← Metadata and magic number
 //! └─────────────────┘
 //! ```
@@ -27,13 +29,29 @@
 //! ## Data Block Format (4KB default)
 //!
 //! ```text
-//! ┌─────────────────┬─────────────────┬─────────────┐
-//! │   Entry Count   │     Entries     │  Checksum   │
-//! │    (4 bytes)    │   (variable)    │  (4 bytes)  │
-//! └─────────────────┴─────────────────┴─────────────┘
+//! ┌─────────────────┬──────────────────┬─────────────┬─────────────┐
+//! │     Entries     │  Restart Array   │     Tag     │  Checksum   │
+//! │   (variable)    │   (variable)     │  (1 byte)   │  (4 bytes)  │
+//! └─────────────────┴──────────────────┴─────────────┴─────────────┘
+//!  ╰────────── compressed together ───╯
 //! ```
 //!
-//! ## Entry Format (within Data Block)
+//! Entries are prefix-compressed against the previous key, with periodic
+//! restart points so lookups can binary-search a block; see [`block`] for
+//! the entry and restart array layout. The entries and restart array are
+//! compressed as one unit with the configured algorithm.
+//!
+//! The low bits of the tag say which algorithm the block was stored with
+//! (0 = none, 1 = LZ4, 2 = Snappy); blocks that do not compress well are
+//! stored raw. The high bit (0x80) marks the prefix-compressed format.
+//! The checksum is a CRC32 over the stored bytes and the tag. A block ends
+//! where the next one starts; the last one ends at the index block.
+//!
+//! ## Original Entry Format
+//!
+//! Blocks without the format bit were written before prefix compression.
+//! They hold a 4-byte entry count followed by full entries, and are
+//! re-encoded with restart points when read:
 //!
 //! ```text
 //! ┌──────────┬─────────────┬───────────┬──────────────┬────────────┬──────────┐
@@ -51,6 +69,8 @@
 //! └─────────────────┴─────────────────┴─────────────┘
 //! ```
 //!
+//! The checksum is a CRC32 over the entry count and entries.
+//!
 //! ## Index Entry Format
 //!
 //! ```text
@@ -60,6 +80,28 @@
 //! └─────────────┴─────────────┴────────────┘
 //! ```
 //!
+//! ## Partitioned Index
+//!
+//! Files written with `partitioned_index` (format version 3) split the
+//! index into partitions of about one block each, written in the Index
+//! Block Format after the data blocks. Each index partition has a bloom
+//! filter partition over the keys of its data blocks, written after all
+//! index partitions. The footer's index handle points at a top-level
+//! index with one entry per partition, and its bloom handle spans the
+//! filter partitions:
+//!
+//! ```text
+//! ┌───────────┬──────────┬──────────┬──────────┬──────────┬──────────┬──────────┐
+//! │  Key Len  │First Key │Index Off │Index Len │Filter Off│Filter Len│ Data End │
+//! │ (4 bytes) │(var len) │(8 bytes) │(8 bytes) │(8 bytes) │(8 bytes) │(8 bytes) │
+//! └───────────┴──────────┴──────────┴──────────┴──────────┴──────────┴──────────┘
+//! ```
+//!
+//! The top-level index is an entry count, these entries and a CRC32. Data
+//! End is where the partition's last data block ends. Readers keep only
+//! the top-level index in memory and fetch partitions through the block
+//! cache.
+//!
 //! ## Bloom Filter Format
 //!
 //! ```text
@@ -69,40 +111,58 @@
 //! └─────────────────┴─────────────────┴─────────────┘
 //! ```
 //!
-//! ## Footer Format (40 bytes)
+//! The filter covers every user key in the file. The checksum is a CRC32
+//! over the bit array and hash count. A hash count of 0 means the filter
+//! is empty and every lookup must read a data block.
+//!
+//! ## Properties Block Format
+//!
+//! Named properties summarizing the file, followed by a CRC32; see
+//! [`properties`] for the layout.
+//!
+//! ## Footer Format (60 bytes)
 //!
 //! The SSTable footer contains metadata about the file's structure and is written
 //! last during SSTable creation. This design enables single-pass sequential writes
 //! during MemTable flush - we can build the index and bloom filter as we write
 //! data blocks, then write the footer with their final positions. Reading an
-//! SSTable requires only two I/O operations: seek to end minus 40 bytes, then
+//! SSTable requires only two I/O operations: seek to end minus 60 bytes, then
 //! read the footer to locate all other components.
 //!
 //! ```text
-//! ┌─────────────┬─────────────┬─────────────┬─────────────┬─────────────┐
-//! │Index Offset │Index Length │Bloom Offset │Bloom Length │Magic Number │
-//! │  (8 bytes)  │  (8 bytes)  │  (8 bytes)  │  (8 bytes)  │  (8 bytes)  │
-//! └─────────────┴─────────────┴─────────────┴─────────────┴─────────────┘
+//! ┌─────────────┬─────────────┬─────────────┬─────────────┐
+//! │Index Offset │Index Length │Bloom Offset │Bloom Length │
+//! │  (8 bytes)  │  (8 bytes)  │  (8 bytes)  │  (8 bytes)  │
+//! ├─────────────┼─────────────┼─────────────┼─────────────┤
+//! │ Props Offset│ Props Length│   Version   │Magic Number │
+//! │  (8 bytes)  │  (8 bytes)  │  (4 bytes)  │  (8 bytes)  │
+//! └─────────────┴─────────────┴─────────────┴─────────────┘
 //! ```
 //!
-//! The fixed-size footer (40 bytes) can be located with a simple calculation,
+//! The fixed-size footer can be located with a simple calculation,
 //! and the magic number validates file integrity - incomplete writes leave no
 //! valid footer, making corruption detection straightforward.
 //!
+//! Files written before format version 2 have a 40-byte footer without
+//! the properties handle and version. Its 4 bytes before the magic number
+//! are the upper half of the bloom filter length, which is always zero,
+//! so readers treat a zero version as such a version 1 footer.
+//!
 //! # Key Invariants
 //!
 //! 1. **Sorting**: Entries sorted by (user_key ASC, timestamp DESC)
 //! 2. **Immutability**: SSTables are never modified after creation
-//! 3. **Checksums**: All blocks include CRC32 checksums
+//! 3. **Checksums**: All blocks end with a CRC32 checksum, verified on read
 //! 4. **Little Endian**: All multi-byte integers in little-endian format
 //! 5. **Magic Number**: `0x46455252_49534442` ("FERRISDB" in ASCII)
 //!
 //! # Features
 //!
 //! - Block compression (LZ4, Snappy, None)
-//! - Prefix compression for keys within blocks (future)
+//! - Prefix compression for keys within blocks
 //! - Checksums for corruption detection
-//! - Bloom filters for existence checks
+//! - Bloom filters that let point lookups skip files without the key
+//! - Optional partitioned index and filters for very large files
 
 use ferrisdb_core::{Key, Operation, Result, Timestamp, Value};
 use std::fmt;
@@ -113,12 +173,61 @@ pub const SSTABLE_MAGIC: u64 = 0x46455252_49534442;
 /// Default block size (4KB)
 pub const DEFAULT_BLOCK_SIZE: usize = 4096;
 
+/// Default number of entries between restart points in a data block
+pub const DEFAULT_BLOCK_RESTART_INTERVAL: usize = 16;
+
+/// Default bloom filter bits per user key (about 1% false positives)
+pub const DEFAULT_BLOOM_BITS_PER_KEY: usize = 10;
+
+/// Size of the CRC32 checksum that ends every block
+pub const BLOCK_TRAILER_SIZE: usize = 4;
+
+/// Format version written by this build
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Format version of files without a properties block
+pub const LEGACY_FORMAT_VERSION: u32 = 1;
+
+/// Format version of files with a partitioned index and bloom filter
+///
+/// Only files written with a partitioned index use it, so files with a
+/// full index stay readable by builds that predate partitioning.
+pub const PARTITIONED_FORMAT_VERSION: u32 = 3;
+
 /// Footer size in bytes
-pub const FOOTER_SIZE: usize = 40;
+pub const FOOTER_SIZE: usize = 60;
+
+/// Size of the footer of format version 1 files
+pub const LEGACY_FOOTER_SIZE: usize = 40;
 
 /// Maximum key or value size (16MB)
 pub const MAX_ENTRY_SIZE: usize = 16 * 1024 * 1024;
 
+/// Options for reading from an SSTable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadOptions {
+    /// Verify the checksum of every data block read from disk
+    ///
+    /// Turning this off saves a CRC32 pass per block read on hot paths, at
+    /// the cost of returning garbage instead of an error if the block is
+    /// damaged. Blocks served from the block cache are never re-verified.
+    pub verify_checksums: bool,
+    /// Add data blocks read from disk to the block cache
+    ///
+    /// Large scans should turn this off so they do not evict the blocks
+    /// point lookups depend on.
+    pub fill_cache: bool,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            verify_checksums: true,
+            fill_cache: true,
+        }
+    }
+}
+
 /// Internal key representation for SSTable entries
 ///
 /// Combines user key with MVCC timestamp for versioning.
@@ -238,49 +347,84 @@ pub struct Footer {
     pub bloom_offset: u64,
     /// Length of the bloom filter
     pub bloom_length: u64,
+    /// Offset of the properties block
+    pub properties_offset: u64,
+    /// Length of the properties block; 0 for version 1 files
+    pub properties_length: u64,
+    /// Format version the file was written with
+    pub format_version: u32,
     /// Magic number for validation
     pub magic: u64,
 }
 
 impl Footer {
-    /// Creates a new footer
+    /// Creates a new footer for the current format version
+    ///
+    /// The properties handle is empty until set with
+    /// [`with_properties`](Self::with_properties).
     pub fn new(index_offset: u64, index_length: u64, bloom_offset: u64, bloom_length: u64) -> Self {
         Self {
             index_offset,
             index_length,
             bloom_offset,
             bloom_length,
+            properties_offset: 0,
+            properties_length: 0,
+            format_version: FORMAT_VERSION,
             magic: SSTABLE_MAGIC,
         }
     }
 
-    /// Serializes the footer to bytes
-    pub fn to_bytes(&self) -> [u8; FOOTER_SIZE] {
-        let mut bytes = [0u8; FOOTER_SIZE];
+    /// Points the footer at the properties block
+    pub fn with_properties(mut self, offset: u64, length: u64) -> Self {
+        self.properties_offset = offset;
+        self.properties_length = length;
+        self
+    }
 
-        bytes[0..8].copy_from_slice(&self.index_offset.to_le_bytes());
-        bytes[8..16].copy_from_slice(&self.index_length.to_le_bytes());
-        bytes[16..24].copy_from_slice(&self.bloom_offset.to_le_bytes());
-        bytes[24..32].copy_from_slice(&self.bloom_length.to_le_bytes());
-        bytes[32..40].copy_from_slice(&self.magic.to_le_bytes());
+    /// Returns the size of this footer on disk
+    pub fn encoded_size(&self) -> usize {
+        if self.format_version == LEGACY_FORMAT_VERSION {
+            LEGACY_FOOTER_SIZE
+        } else {
+            FOOTER_SIZE
+        }
+    }
+
+    /// Serializes the footer to bytes
+    ///
+    /// A version 1 footer is written in the 40-byte legacy layout.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.encoded_size());
+
+        bytes.extend_from_slice(&self.index_offset.to_le_bytes());
+        bytes.extend_from_slice(&self.index_length.to_le_bytes());
+        bytes.extend_from_slice(&self.bloom_offset.to_le_bytes());
+        bytes.extend_from_slice(&self.bloom_length.to_le_bytes());
+        if self.format_version != LEGACY_FORMAT_VERSION {
+            bytes.extend_from_slice(&self.properties_offset.to_le_bytes());
+            bytes.extend_from_slice(&self.properties_length.to_le_bytes());
+            bytes.extend_from_slice(&self.format_version.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.magic.to_le_bytes());
 
         bytes
     }
 
-    /// Deserializes footer from bytes
+    /// Deserializes a footer from the last bytes of a file
+    ///
+    /// `bytes` may hold more than the footer; it is parsed from the end,
+    /// so callers pass the last [`FOOTER_SIZE`] bytes of the file, or the
+    /// whole file if it is shorter.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        if bytes.len() != FOOTER_SIZE {
+        if bytes.len() < LEGACY_FOOTER_SIZE {
             return Err(ferrisdb_core::Error::InvalidFormat(
                 "Invalid footer size".to_string(),
             ));
         }
 
-        let index_offset = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
-        let index_length = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
-        let bloom_offset = u64::from_le_bytes(bytes[16..24].try_into().unwrap());
-        let bloom_length = u64::from_le_bytes(bytes[24..32].try_into().unwrap());
-        let magic = u64::from_le_bytes(bytes[32..40].try_into().unwrap());
-
+        let end = bytes.len();
+        let magic = u64::from_le_bytes(bytes[end - 8..].try_into().unwrap());
         if magic != SSTABLE_MAGIC {
             return Err(ferrisdb_core::Error::InvalidFormat(format!(
                 "Invalid magic number: expected {}, got {}",
@@ -288,21 +432,62 @@ impl Footer {
             )));
         }
 
+        // Zero is the upper half of a legacy footer's bloom filter length
+        let format_version = u32::from_le_bytes(bytes[end - 12..end - 8].try_into().unwrap());
+        let (bytes, format_version) = match format_version {
+            0 => (&bytes[end - LEGACY_FOOTER_SIZE..], LEGACY_FORMAT_VERSION),
+            FORMAT_VERSION | PARTITIONED_FORMAT_VERSION if end >= FOOTER_SIZE => {
+                (&bytes[end - FOOTER_SIZE..], format_version)
+            }
+            FORMAT_VERSION | PARTITIONED_FORMAT_VERSION => {
+                return Err(ferrisdb_core::Error::InvalidFormat(
+                    "Invalid footer size".to_string(),
+                ))
+            }
+            version => {
+                return Err(ferrisdb_core::Error::InvalidFormat(format!(
+                    "Unsupported SSTable format version {}",
+                    version
+                )))
+            }
+        };
+
+        let read_u64 = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let (properties_offset, properties_length) = if format_version == LEGACY_FORMAT_VERSION {
+            (0, 0)
+        } else {
+            (read_u64(32), read_u64(40))
+        };
+
         Ok(Self {
-            index_offset,
-            index_length,
-            bloom_offset,
-            bloom_length,
+            index_offset: read_u64(0),
+            index_length: read_u64(8),
+            bloom_offset: read_u64(16),
+            bloom_length: read_u64(24),
+            properties_offset,
+            properties_length,
+            format_version,
             magic,
         })
     }
 }
 
+pub mod block;
+pub mod block_cache;
+pub mod bloom;
+mod compression;
+pub mod properties;
 pub mod reader;
+pub mod table_cache;
 pub mod writer;
 
-pub use reader::{SSTableIterator, SSTableReader, SSTableReaderInfo};
-pub use writer::{SSTableInfo, SSTableWriter};
+pub use block::{Block, BlockBuilder, BlockIter};
+pub use block_cache::{BlockCache, BlockCacheKey, BlockCacheStats, CachedBlock};
+pub use bloom::{BloomFilter, BloomFilterBuilder};
+pub use properties::TableProperties;
+pub use reader::{SSTableCursor, SSTableIterator, SSTableReader, SSTableReaderInfo};
+pub use table_cache::{TableCache, TableCacheStats};
+pub use writer::{SSTableInfo, SSTableWriter, SSTableWriterOptions};
 
 #[cfg(test)]
 mod tests {
@@ -341,14 +526,58 @@ mod tests {
         assert_eq!(deserialized.index_length, 200);
         assert_eq!(deserialized.bloom_offset, 1200);
         assert_eq!(deserialized.bloom_length, 100);
+        assert_eq!(deserialized.format_version, FORMAT_VERSION);
         assert_eq!(deserialized.magic, SSTABLE_MAGIC);
     }
 
+    #[test]
+    fn test_footer_versions() {
+        let footer = Footer::new(1000, 200, 1200, 100).with_properties(1300, 50);
+        let mut file = vec![0xAB; 100];
+        file.extend_from_slice(&footer.to_bytes());
+        let parsed = Footer::from_bytes(&file[file.len() - FOOTER_SIZE..]).unwrap();
+        assert_eq!(
+            (parsed.properties_offset, parsed.properties_length),
+            (1300, 50)
+        );
+
+        // A legacy footer parses from the tail of a full-size read
+        let legacy = Footer {
+            format_version: LEGACY_FORMAT_VERSION,
+            ..Footer::new(1000, 200, 1200, 100)
+        };
+        assert_eq!(legacy.to_bytes().len(), LEGACY_FOOTER_SIZE);
+        file.truncate(100);
+        file.extend_from_slice(&legacy.to_bytes());
+        let parsed = Footer::from_bytes(&file[file.len() - FOOTER_SIZE..]).unwrap();
+        assert_eq!(parsed.format_version, LEGACY_FORMAT_VERSION);
+        assert_eq!(parsed.encoded_size(), LEGACY_FOOTER_SIZE);
+        assert_eq!((parsed.bloom_offset, parsed.bloom_length), (1200, 100));
+        assert_eq!(parsed.properties_length, 0);
+
+        let partitioned = Footer {
+            format_version: PARTITIONED_FORMAT_VERSION,
+            ..footer.clone()
+        };
+        let parsed = Footer::from_bytes(&partitioned.to_bytes()).unwrap();
+        assert_eq!(parsed.format_version, PARTITIONED_FORMAT_VERSION);
+
+        // Versions from the future are refused rather than misread
+        let future = Footer {
+            format_version: PARTITIONED_FORMAT_VERSION + 1,
+            ..footer
+        };
+        let error = Footer::from_bytes(&future.to_bytes()).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Unsupported SSTable format version 4"));
+    }
+
     #[test]
     fn test_footer_invalid_magic() {
         let mut bytes = [0u8; FOOTER_SIZE];
         // Set invalid magic number
-        bytes[32..40].copy_from_slice(&0x12345678u64.to_le_bytes());
+        bytes[FOOTER_SIZE - 8..].copy_from_slice(&0x12345678u64.to_le_bytes());
 
         let result = Footer::from_bytes(&bytes);
         assert!(result.is_err());
@@ -476,7 +705,7 @@ mod tests {
 
         // Read and verify
         {
-            let mut reader = SSTableReader::open(&path).unwrap();
+            let reader = SSTableReader::open(&path).unwrap();
 
             // Test exact key lookups
             for (key, expected_value, _operation) in &test_entries {
@@ -503,11 +732,11 @@ mod tests {
             assert_eq!(missing, None);
 
             // Test iterator
-            let mut iter = reader.iter().unwrap();
+            let iter = reader.iter().unwrap();
             let mut count = 0;
             let mut last_key: Option<InternalKey> = None;
 
-            while let Some(entry_result) = iter.next() {
+            for entry_result in iter {
                 let entry = entry_result.unwrap();
 
                 // Verify ordering
@@ -522,10 +751,10 @@ mod tests {
             // Test range iterator
             let start_key = b"banana".to_vec();
             let end_key = b"date".to_vec();
-            let mut range_iter = reader.range_iter(Some(&start_key), Some(&end_key)).unwrap();
+            let range_iter = reader.range_iter(Some(&start_key), Some(&end_key)).unwrap();
 
             let mut range_entries = Vec::new();
-            while let Some(entry_result) = range_iter.next() {
+            for entry_result in range_iter {
                 let entry = entry_result.unwrap();
                 assert!(entry.key.user_key >= start_key);
                 assert!(entry.key.user_key < end_key);
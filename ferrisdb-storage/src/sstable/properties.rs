@@ -0,0 +1,268 @@
+//! Table properties recorded when an SSTable is written
+//!
+//! The properties block summarizes a file so compaction and tools can
+//! learn its contents from a few hundred bytes instead of scanning its
+//! data blocks. It is stored as named properties:
+//!
+//! ```text
+//! ┌─────────────────┬─────────────────┬─────────────┐
+//! │   Entry Count   │   Properties    │  Checksum   │
+//! │    (4 bytes)    │   (variable)    │  (4 bytes)  │
+//! └─────────────────┴─────────────────┴─────────────┘
+//!
+//! ┌─────────────┬────────────┬─────────────┬────────────┐
+//! │  Name Len   │    Name    │  Value Len  │   Value    │
+//! │ (4 bytes)   │ (var len)  │  (4 bytes)  │ (var len)  │
+//! └─────────────┴────────────┴─────────────┴────────────┘
+//! ```
+//!
+//! Numbers are 8-byte little-endian values and keys are an 8-byte
+//! timestamp followed by the user key. Readers skip names they do not
+//! know and leave missing properties at their defaults, so properties can
+//! be added without a new format version.
+
+use crate::sstable::compression::{compression_from_tag, compression_tag};
+use crate::sstable::InternalKey;
+use ferrisdb_core::{CompressionType, Error, Result, Timestamp};
+use std::io::Read;
+
+const ENTRY_COUNT: &str = "entry_count";
+const TOMBSTONE_COUNT: &str = "tombstone_count";
+const RAW_KEY_SIZE: &str = "raw_key_size";
+const RAW_VALUE_SIZE: &str = "raw_value_size";
+const MIN_TIMESTAMP: &str = "min_timestamp";
+const MAX_TIMESTAMP: &str = "max_timestamp";
+const SMALLEST_KEY: &str = "smallest_key";
+const LARGEST_KEY: &str = "largest_key";
+const COMPRESSION: &str = "compression";
+const FILTER_POLICY: &str = "filter_policy";
+const CREATION_TIME: &str = "creation_time";
+
+/// Summary of an SSTable's contents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableProperties {
+    /// Number of entries, tombstones included
+    pub entry_count: u64,
+    /// Number of delete entries
+    pub tombstone_count: u64,
+    /// Total size of the user keys
+    pub raw_key_size: u64,
+    /// Total size of the values
+    pub raw_value_size: u64,
+    /// Oldest timestamp in the file
+    pub min_timestamp: Timestamp,
+    /// Newest timestamp in the file
+    pub max_timestamp: Timestamp,
+    /// First key in the file
+    pub smallest_key: InternalKey,
+    /// Last key in the file
+    pub largest_key: InternalKey,
+    /// Compression the data blocks were written with
+    ///
+    /// Blocks that did not compress well are stored raw regardless.
+    pub compression: CompressionType,
+    /// Name of the filter policy, such as `bloom:10`, or empty without one
+    pub filter_policy: String,
+    /// When the file was written, in seconds since the Unix epoch
+    pub creation_time: u64,
+}
+
+impl Default for TableProperties {
+    fn default() -> Self {
+        Self {
+            entry_count: 0,
+            tombstone_count: 0,
+            raw_key_size: 0,
+            raw_value_size: 0,
+            min_timestamp: 0,
+            max_timestamp: 0,
+            smallest_key: InternalKey::new(Vec::new(), 0),
+            largest_key: InternalKey::new(Vec::new(), 0),
+            compression: CompressionType::None,
+            filter_policy: String::new(),
+            creation_time: 0,
+        }
+    }
+}
+
+impl TableProperties {
+    /// Serializes the properties without the trailing checksum
+    pub fn encode(&self) -> Vec<u8> {
+        let properties: [(&str, Vec<u8>); 11] = [
+            (ENTRY_COUNT, self.entry_count.to_le_bytes().to_vec()),
+            (TOMBSTONE_COUNT, self.tombstone_count.to_le_bytes().to_vec()),
+            (RAW_KEY_SIZE, self.raw_key_size.to_le_bytes().to_vec()),
+            (RAW_VALUE_SIZE, self.raw_value_size.to_le_bytes().to_vec()),
+            (MIN_TIMESTAMP, self.min_timestamp.to_le_bytes().to_vec()),
+            (MAX_TIMESTAMP, self.max_timestamp.to_le_bytes().to_vec()),
+            (SMALLEST_KEY, encode_key(&self.smallest_key)),
+            (LARGEST_KEY, encode_key(&self.largest_key)),
+            (COMPRESSION, vec![compression_tag(self.compression)]),
+            (FILTER_POLICY, self.filter_policy.as_bytes().to_vec()),
+            (CREATION_TIME, self.creation_time.to_le_bytes().to_vec()),
+        ];
+
+        let mut block = (properties.len() as u32).to_le_bytes().to_vec();
+        for (name, value) in properties {
+            block.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            block.extend_from_slice(name.as_bytes());
+            block.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            block.extend_from_slice(&value);
+        }
+        block
+    }
+
+    /// Parses properties encoded by [`encode`](Self::encode)
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Corruption` if the block is truncated or a known
+    /// property has a malformed value.
+    pub fn decode(mut data: &[u8]) -> Result<Self> {
+        let mut properties = Self::default();
+        let count = read_u32(&mut data)?;
+        for _ in 0..count {
+            let name_len = read_u32(&mut data)? as usize;
+            let name = take(&mut data, name_len)?;
+            let value_len = read_u32(&mut data)? as usize;
+            let value = take(&mut data, value_len)?;
+
+            let name = std::str::from_utf8(name)
+                .map_err(|_| Error::Corruption("Invalid property name".to_string()))?;
+            match name {
+                ENTRY_COUNT => properties.entry_count = decode_u64(name, value)?,
+                TOMBSTONE_COUNT => properties.tombstone_count = decode_u64(name, value)?,
+                RAW_KEY_SIZE => properties.raw_key_size = decode_u64(name, value)?,
+                RAW_VALUE_SIZE => properties.raw_value_size = decode_u64(name, value)?,
+                MIN_TIMESTAMP => properties.min_timestamp = decode_u64(name, value)?,
+                MAX_TIMESTAMP => properties.max_timestamp = decode_u64(name, value)?,
+                SMALLEST_KEY => properties.smallest_key = decode_key(name, value)?,
+                LARGEST_KEY => properties.largest_key = decode_key(name, value)?,
+                COMPRESSION => {
+                    let [tag] = value else {
+                        return Err(malformed(name));
+                    };
+                    properties.compression = compression_from_tag(*tag)?;
+                }
+                FILTER_POLICY => {
+                    properties.filter_policy =
+                        String::from_utf8(value.to_vec()).map_err(|_| malformed(name))?;
+                }
+                CREATION_TIME => properties.creation_time = decode_u64(name, value)?,
+                // Written by a newer version
+                _ => {}
+            }
+        }
+        Ok(properties)
+    }
+}
+
+fn encode_key(key: &InternalKey) -> Vec<u8> {
+    let mut bytes = key.timestamp.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&key.user_key);
+    bytes
+}
+
+fn decode_key(name: &str, value: &[u8]) -> Result<InternalKey> {
+    if value.len() < 8 {
+        return Err(malformed(name));
+    }
+    let (timestamp, user_key) = value.split_at(8);
+    Ok(InternalKey::new(
+        user_key.to_vec(),
+        u64::from_le_bytes(timestamp.try_into().unwrap()),
+    ))
+}
+
+fn decode_u64(name: &str, value: &[u8]) -> Result<u64> {
+    let bytes = value.try_into().map_err(|_| malformed(name))?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u32(data: &mut &[u8]) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    data.read_exact(&mut bytes)
+        .map_err(|_| Error::Corruption("Truncated properties block".to_string()))?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if data.len() < len {
+        return Err(Error::Corruption("Truncated properties block".to_string()));
+    }
+    let (bytes, rest) = data.split_at(len);
+    *data = rest;
+    Ok(bytes)
+}
+
+fn malformed(name: &str) -> Error {
+    Error::Corruption(format!("Malformed table property {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> TableProperties {
+        TableProperties {
+            entry_count: 120,
+            tombstone_count: 7,
+            raw_key_size: 1_440,
+            raw_value_size: 9_000,
+            min_timestamp: 3,
+            max_timestamp: 250,
+            smallest_key: InternalKey::new(b"apple".to_vec(), 17),
+            largest_key: InternalKey::new(b"zebra".to_vec(), 4),
+            compression: CompressionType::Lz4,
+            filter_policy: "bloom:10".to_string(),
+            creation_time: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let properties = properties();
+        assert_eq!(
+            TableProperties::decode(&properties.encode()).unwrap(),
+            properties
+        );
+    }
+
+    #[test]
+    fn test_skips_unknown_and_defaults_missing_properties() {
+        // A block from a newer writer with one extra and one known property
+        let mut block = 2u32.to_le_bytes().to_vec();
+        for (name, value) in [
+            ("future_property", b"anything".to_vec()),
+            (ENTRY_COUNT, 5u64.to_le_bytes().to_vec()),
+        ] {
+            block.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            block.extend_from_slice(name.as_bytes());
+            block.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            block.extend_from_slice(&value);
+        }
+
+        let decoded = TableProperties::decode(&block).unwrap();
+        assert_eq!(decoded.entry_count, 5);
+        assert_eq!(decoded.tombstone_count, 0);
+        assert!(decoded.filter_policy.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_damaged_blocks() {
+        let block = properties().encode();
+        for len in [0, 3, 10, block.len() - 1] {
+            let error = TableProperties::decode(&block[..len]).unwrap_err();
+            assert!(error.to_string().contains("Truncated properties block"));
+        }
+
+        // Shrink the entry count value from 8 bytes to 7
+        let mut block = 1u32.to_le_bytes().to_vec();
+        block.extend_from_slice(&(ENTRY_COUNT.len() as u32).to_le_bytes());
+        block.extend_from_slice(ENTRY_COUNT.as_bytes());
+        block.extend_from_slice(&7u32.to_le_bytes());
+        block.extend_from_slice(&[0; 7]);
+        let error = TableProperties::decode(&block).unwrap_err();
+        assert!(error.to_string().contains("Malformed table property"));
+    }
+}
@@ -1,11 +1,25 @@
 //! SSTable reader implementation
 
-use crate::sstable::{Footer, IndexEntry, InternalKey, SSTableEntry, FOOTER_SIZE};
+use crate::sstable::block::{
+    compare, operation_from_byte, Block, BlockIter, PREFIX_COMPRESSED_FLAG,
+};
+use crate::sstable::block_cache::{BlockCache, BlockCacheKey, CachedBlock};
+use crate::sstable::bloom::BloomFilter;
+use crate::sstable::compression::{compression_from_tag, decompress};
+use crate::sstable::{
+    Footer, IndexEntry, InternalKey, ReadOptions, SSTableEntry, TableProperties,
+    BLOCK_TRAILER_SIZE, DEFAULT_BLOCK_RESTART_INTERVAL, FOOTER_SIZE, LEGACY_FOOTER_SIZE,
+    PARTITIONED_FORMAT_VERSION,
+};
 use ferrisdb_core::{Error, Key, Operation, Result, Timestamp, Value};
-use std::collections::BTreeMap;
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
-use std::path::Path;
+use std::io::Read;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[cfg(test)]
 use crate::sstable::SSTABLE_MAGIC;
@@ -16,12 +30,21 @@ use crate::sstable::SSTABLE_MAGIC;
 /// immutable SSTable files. It uses the index to locate data blocks and
 /// supports both exact key matches and range queries.
 ///
+/// All reads are positional (`pread` on Unix), so lookups and iterators
+/// take `&self` and any number of threads can read one file at once.
+/// A reader opened with [`open_mmap`](Self::open_mmap) maps the file
+/// instead and decodes blocks straight from the mapping.
+///
+/// For files with a partitioned index only the top-level index stays in
+/// memory; index and filter partitions are read per lookup, through the
+/// block cache if the reader has one.
+///
 /// # Example
 ///
 /// ```ignore
 /// use ferrisdb_storage::sstable::reader::SSTableReader;
 ///
-/// let mut reader = SSTableReader::open("path/to/sstable.sst")?;
+/// let reader = SSTableReader::open("path/to/sstable.sst")?;
 ///
 /// // Get exact key-timestamp match
 /// if let Some(value) = reader.get(&b"key1".to_vec(), 100)? {
@@ -34,14 +57,20 @@ use crate::sstable::SSTABLE_MAGIC;
 /// }
 /// ```
 pub struct SSTableReader {
-    /// Buffered reader for the file
-    reader: BufReader<File>,
+    /// The file, shared by every lookup and iterator
+    file: Arc<TableFile>,
+    /// Path of the file, for error messages
+    path: PathBuf,
+    /// Options used by lookups that do not pass their own
+    options: ReadOptions,
     /// SSTable metadata from footer
     footer: Footer,
-    /// Index entries for efficient block lookup
-    index: Vec<IndexEntry>,
-    /// Cached data blocks (block_offset -> entries)
-    block_cache: BTreeMap<u64, Vec<SSTableEntry>>,
+    /// Index and bloom filter, or where to find their partitions
+    index: TableIndex,
+    /// Properties recorded by the writer, if the file has them
+    properties: Option<TableProperties>,
+    /// Shared cache of uncompressed blocks and this file's number in it
+    block_cache: Option<(Arc<BlockCache>, u64)>,
 }
 
 impl std::fmt::Debug for SSTableReader {
@@ -49,19 +78,29 @@ impl std::fmt::Debug for SSTableReader {
         f.debug_struct("SSTableReader")
             .field("footer", &self.footer)
             .field("index_count", &self.index.len())
-            .field("cached_blocks", &self.block_cache.len())
+            .field(
+                "partitioned",
+                &matches!(self.index, TableIndex::Partitioned(_)),
+            )
+            .field("mmap", &matches!(*self.file, TableFile::Mapped(_)))
+            .field("block_cache", &self.block_cache.is_some())
             .finish()
     }
 }
 
 impl SSTableReader {
-    /// Opens an SSTable file for reading
+    /// Opens an SSTable file for reading with default [`ReadOptions`]
     ///
     /// This method:
     /// 1. Opens the file and reads the footer
     /// 2. Validates the magic number
-    /// 3. Reads and parses the index block
-    /// 4. Prepares the reader for queries
+    /// 3. Reads, verifies and parses the index block
+    /// 4. Reads and verifies the bloom filter
+    /// 5. Prepares the reader for queries
+    ///
+    /// For a partitioned index, only the top-level index is read. The
+    /// index and bloom filter checksums are always verified, partitions
+    /// included.
     ///
     /// # Arguments
     ///
@@ -73,25 +112,100 @@ impl SSTableReader {
     /// - The file cannot be opened
     /// - The file format is invalid
     /// - The magic number doesn't match
-    /// - Index data is corrupted
+    /// - Index data or the bloom filter is corrupted
     pub fn open(path: impl AsRef<Path>) -> Result<Self> {
-        let file = File::open(path)?;
-        let mut reader = BufReader::new(file);
+        Self::open_with_options(path, ReadOptions::default())
+    }
 
-        // Read and parse footer
-        let footer = Self::read_footer(&mut reader)?;
+    /// Opens an SSTable file using `options` for lookups by default
+    ///
+    /// # Errors
+    ///
+    /// Same as [`open`](Self::open).
+    pub fn open_with_options(path: impl AsRef<Path>, options: ReadOptions) -> Result<Self> {
+        Self::open_file(path.as_ref(), options, false)
+    }
 
-        // Read and parse index
-        let index = Self::read_index(&mut reader, &footer)?;
+    /// Opens an SSTable file through a read-only memory map
+    ///
+    /// Blocks are checksummed and decompressed straight from the mapped
+    /// region instead of being read into a buffer first, which suits read-heavy
+    /// workloads whose files stay in the page cache. The footer and the
+    /// extent of every region it describes are validated against the file
+    /// length before mapping, so a damaged or truncated file fails with
+    /// `Error::Corruption` rather than faulting on access.
+    ///
+    /// The file must not be modified while mapped; SSTables never are.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`open`](Self::open), or an error if the file cannot be
+    /// mapped.
+    pub fn open_mmap(path: impl AsRef<Path>, options: ReadOptions) -> Result<Self> {
+        Self::open_file(path.as_ref(), options, true)
+    }
+
+    fn open_file(path: &Path, options: ReadOptions, mmap: bool) -> Result<Self> {
+        let path = path.to_path_buf();
+        let file = File::open(&path)?;
+        let size = file.metadata()?.len();
+
+        // Read and parse footer, then make sure everything it points at
+        // lies within the file
+        let footer = Self::read_footer(&file, size)?;
+        Self::check_footer(&footer, size, &path)?;
+
+        let file = if mmap {
+            // SAFETY: SSTables are immutable once written, and every region
+            // read from the mapping was checked against the file length
+            TableFile::Mapped(unsafe { Mmap::map(&file)? })
+        } else {
+            TableFile::Positional { file, size }
+        };
+
+        let index = if footer.format_version == PARTITIONED_FORMAT_VERSION {
+            // Partitions are read on demand
+            TableIndex::Partitioned(Self::read_partition_handles(&file, &path, &footer)?)
+        } else {
+            // Read and parse index
+            let entries = Self::read_index(&file, &path, footer.index_offset, footer.index_length)?;
+
+            // Read and verify the bloom filter
+            let filter = Self::read_filter(&file, &path, footer.bloom_offset, footer.bloom_length)?;
+
+            TableIndex::Full {
+                blocks: IndexPartition {
+                    entries: entries.into(),
+                    data_end: footer.index_offset,
+                },
+                filter,
+            }
+        };
+
+        // Version 1 files have no properties
+        let properties = Self::read_properties(&file, &path, &footer)?;
 
         Ok(Self {
-            reader,
+            file: Arc::new(file),
+            path,
+            options,
             footer,
             index,
-            block_cache: BTreeMap::new(),
+            properties,
+            block_cache: None,
         })
     }
 
+    /// Serves data blocks through a shared cache
+    ///
+    /// `file_number` identifies this file in the cache and must not be used
+    /// for any other file sharing the cache. Without a cache, every lookup
+    /// reads its block, and any index and filter partitions, from disk.
+    pub fn with_block_cache(mut self, cache: Arc<BlockCache>, file_number: u64) -> Self {
+        self.block_cache = Some((cache, file_number));
+        self
+    }
+
     /// Looks up a specific key at a specific timestamp in the SSTable
     ///
     /// Returns the value associated with the exact key-timestamp combination,
@@ -100,9 +214,11 @@ impl SSTableReader {
     ///
     /// # Performance
     ///
-    /// Uses binary search based on InternalKey ordering (user_key ASC, timestamp DESC)
-    /// to directly locate the exact key-timestamp combination in O(log n) time.
-    /// This is significantly faster than linear search for blocks with many entries.
+    /// Keys rejected by the bloom filter return without reading a block.
+    /// Otherwise, the index leads straight to the block holding the key,
+    /// whose restart points are binary-searched based on InternalKey
+    /// ordering (user_key ASC, timestamp DESC); at most one restart
+    /// interval of entries is decoded.
     ///
     /// # Arguments
     ///
@@ -112,29 +228,21 @@ impl SSTableReader {
     /// # Errors
     ///
     /// Returns an error if an I/O error occurs during lookup
-    pub fn get(&mut self, user_key: &Key, timestamp: Timestamp) -> Result<Option<Value>> {
-        // Find the block that might contain this key
-        let block_offset = match self.find_block_for_key(user_key) {
-            Some(offset) => offset,
-            None => return Ok(None), // Key is outside the range of this SSTable
-        };
-
-        // Load the block (from cache or disk)
-        let entries = self.load_block(block_offset)?;
+    pub fn get(&self, user_key: &Key, timestamp: Timestamp) -> Result<Option<Value>> {
+        let options = self.options;
+        if !self.filter_may_contain(user_key, options)? {
+            return Ok(None);
+        }
 
-        // Create target key for binary search
-        let target_key = InternalKey::new(user_key.clone(), timestamp);
+        let mut cursor = self.cursor_with_options(options);
+        cursor.seek(&InternalKey::new(user_key.clone(), timestamp));
+        cursor.status()?;
 
-        // Use binary search to find exact key match
-        match entries.binary_search_by(|entry| entry.key.cmp(&target_key)) {
-            Ok(index) => {
-                // Found exact match
-                Ok(Some(entries[index].value.clone()))
-            }
-            Err(_) => {
-                // No exact match found
-                Ok(None)
-            }
+        if cursor.valid() && cursor.key() == user_key.as_slice() && cursor.timestamp() == timestamp
+        {
+            Ok(Some(cursor.value().to_vec()))
+        } else {
+            Ok(None)
         }
     }
 
@@ -145,9 +253,11 @@ impl SSTableReader {
     ///
     /// # Performance
     ///
-    /// Uses binary search to locate the first entry for the user key, then linear
-    /// search through versions (ordered by timestamp DESC) to find the latest
-    /// version within the timestamp limit. This is optimal for MVCC workloads.
+    /// Keys rejected by the bloom filter return without reading a block.
+    /// Otherwise, seeks to `(user_key, max_timestamp)`. Versions are ordered
+    /// by timestamp DESC, so the first entry at or after that position is
+    /// the latest version within the timestamp limit, if it has the same
+    /// user key.
     ///
     /// # Arguments
     ///
@@ -158,49 +268,120 @@ impl SSTableReader {
     ///
     /// Returns (value, timestamp, operation) if found, None otherwise
     pub fn get_latest(
-        &mut self,
+        &self,
         user_key: &Key,
         max_timestamp: Timestamp,
     ) -> Result<Option<(Value, Timestamp, Operation)>> {
-        // Find the block that might contain this key
-        let block_offset = match self.find_block_for_key(user_key) {
-            Some(offset) => offset,
-            None => return Ok(None),
-        };
+        self.get_latest_with_options(user_key, max_timestamp, self.options)
+    }
 
-        // Load the block
-        let entries = self.load_block(block_offset)?;
+    /// Finds the latest version of a user key using the given options
+    ///
+    /// See [`get_latest`](Self::get_latest).
+    pub fn get_latest_with_options(
+        &self,
+        user_key: &Key,
+        max_timestamp: Timestamp,
+        options: ReadOptions,
+    ) -> Result<Option<(Value, Timestamp, Operation)>> {
+        if !self.filter_may_contain(user_key, options)? {
+            return Ok(None);
+        }
 
-        // Use binary search to find the first entry with matching user_key
-        let start_index = entries.partition_point(|entry| entry.key.user_key < *user_key);
+        // Seek to the newest visible version
+        let mut cursor = self.cursor_with_options(options);
+        cursor.seek(&InternalKey::new(user_key.clone(), max_timestamp));
+        cursor.status()?;
+
+        if cursor.valid() && cursor.key() == user_key.as_slice() {
+            Ok(Some((
+                cursor.value().to_vec(),
+                cursor.timestamp(),
+                cursor.operation(),
+            )))
+        } else {
+            Ok(None)
+        }
+    }
 
-        // Linear search through versions (timestamp DESC) for the latest valid version
-        for i in start_index..entries.len() {
-            let entry = &entries[i];
+    /// Returns false if the bloom filter rules out `user_key`
+    ///
+    /// A true result may be a false positive; the key is only known to be
+    /// present once a data block has been read. A filter partition that
+    /// cannot be read rules nothing out.
+    pub fn may_contain(&self, user_key: &[u8]) -> bool {
+        self.filter_may_contain(user_key, self.options)
+            .unwrap_or(true)
+    }
 
-            // Stop if we've moved to a different user_key
-            if entry.key.user_key != *user_key {
-                break;
-            }
+    /// Checks the bloom filter, loading the key's filter partition if the
+    /// filter is partitioned
+    fn filter_may_contain(&self, user_key: &[u8], options: ReadOptions) -> Result<bool> {
+        let handles = match &self.index {
+            TableIndex::Full { filter, .. } => return Ok(filter.may_contain(user_key)),
+            TableIndex::Partitioned(handles) => handles,
+        };
+        let number = self.find_partition(user_key);
+        // A partition starting with the key certainly holds it
+        if handles
+            .get(number + 1)
+            .is_some_and(|next| next.first_key.as_slice() == user_key)
+        {
+            return Ok(true);
+        }
+        let Some(handle) = handles.get(number) else {
+            return Ok(true);
+        };
 
-            // Check if this version is within our timestamp limit
-            if entry.key.timestamp <= max_timestamp {
-                return Ok(Some((
-                    entry.value.clone(),
-                    entry.key.timestamp,
-                    entry.operation,
-                )));
+        let key = self.cache_key(handle.filter_offset);
+        if let Some((cache, key)) = &key {
+            if let Some(CachedBlock::FilterPartition(filter)) = cache.get(key) {
+                return Ok(filter.may_contain(user_key));
             }
         }
-
-        Ok(None)
+        let filter = Arc::new(Self::read_filter(
+            &self.file,
+            &self.path,
+            handle.filter_offset,
+            handle.filter_length,
+        )?);
+        let may_contain = filter.may_contain(user_key);
+        if let Some((cache, key)) = key {
+            if options.fill_cache {
+                cache.insert(key, filter);
+            }
+        }
+        Ok(may_contain)
     }
 
     /// Creates an iterator over all entries in the SSTable
     ///
     /// The iterator yields entries in sorted order (user_key ASC, timestamp DESC).
-    pub fn iter(&mut self) -> Result<SSTableIterator> {
-        SSTableIterator::new(self)
+    pub fn iter(&self) -> Result<SSTableIterator<'_>> {
+        let options = self.options;
+        self.iter_with_options(options)
+    }
+
+    /// Creates an iterator over all entries using the given options
+    ///
+    /// Long scans can set [`ReadOptions::fill_cache`] to false so they do
+    /// not push hot blocks out of the block cache.
+    pub fn iter_with_options(&self, options: ReadOptions) -> Result<SSTableIterator<'_>> {
+        SSTableIterator::new(self, options)
+    }
+
+    /// Creates a cursor over the SSTable
+    ///
+    /// The cursor starts out invalid; position it with one of its seek
+    /// methods. To keep a cursor beyond this borrow, pass an
+    /// `Arc<SSTableReader>` to [`SSTableCursor::new`] instead.
+    pub fn cursor(&self) -> SSTableCursor<&Self> {
+        self.cursor_with_options(self.options)
+    }
+
+    /// Creates a cursor over the SSTable using the given options
+    pub fn cursor_with_options(&self, options: ReadOptions) -> SSTableCursor<&Self> {
+        SSTableCursor::new(self, options)
     }
 
     /// Creates an iterator over a range of keys
@@ -210,225 +391,660 @@ impl SSTableReader {
     /// * `start_key` - Optional start key (inclusive)
     /// * `end_key` - Optional end key (exclusive)
     pub fn range_iter(
-        &mut self,
+        &self,
         start_key: Option<&Key>,
         end_key: Option<&Key>,
-    ) -> Result<SSTableIterator> {
-        SSTableIterator::new_range(self, start_key, end_key)
+    ) -> Result<SSTableIterator<'_>> {
+        let options = self.options;
+        SSTableIterator::new_range(self, options, start_key, end_key)
     }
 
     /// Returns metadata about the SSTable
+    ///
+    /// Everything is read when the file is opened, so this touches no data
+    /// blocks.
     pub fn info(&self) -> SSTableReaderInfo {
         SSTableReaderInfo {
             index_entries: self.index.len(),
+            index_partitions: match &self.index {
+                TableIndex::Full { .. } => 0,
+                TableIndex::Partitioned(handles) => handles.len(),
+            },
             footer: self.footer.clone(),
+            properties: self.properties.clone(),
         }
     }
 
     /// Reads the footer from the end of the file
-    fn read_footer(reader: &mut BufReader<File>) -> Result<Footer> {
-        // Seek to the start of the footer (file_size - FOOTER_SIZE)
-        let file_size = reader.seek(SeekFrom::End(0))?;
-        if file_size < FOOTER_SIZE as u64 {
+    fn read_footer(file: &File, file_size: u64) -> Result<Footer> {
+        if file_size < LEGACY_FOOTER_SIZE as u64 {
             return Err(Error::InvalidFormat(
                 "File too small to contain footer".to_string(),
             ));
         }
 
-        reader.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))?;
-
-        // Read footer bytes
+        // Read enough for the current footer; older, shorter footers are
+        // parsed from the end of it
         let mut footer_bytes = [0u8; FOOTER_SIZE];
-        reader.read_exact(&mut footer_bytes)?;
+        let len = file_size.min(FOOTER_SIZE as u64);
+        let footer_bytes = &mut footer_bytes[..len as usize];
+        read_exact_at(file, footer_bytes, file_size - len)?;
 
         // Parse footer
-        Footer::from_bytes(&footer_bytes)
+        Footer::from_bytes(footer_bytes)
     }
 
-    /// Reads and parses the index block
-    fn read_index(reader: &mut BufReader<File>, footer: &Footer) -> Result<Vec<IndexEntry>> {
-        // Seek to index block
-        reader.seek(SeekFrom::Start(footer.index_offset))?;
+    /// Checks that the index, bloom filter and properties lie between the
+    /// data blocks and the footer
+    fn check_footer(footer: &Footer, file_size: u64, path: &Path) -> Result<()> {
+        let footer_offset = file_size - footer.encoded_size() as u64;
+        let regions = [
+            ("Index block", footer.index_offset, footer.index_length),
+            ("Bloom filter", footer.bloom_offset, footer.bloom_length),
+            (
+                "Properties block",
+                footer.properties_offset,
+                footer.properties_length,
+            ),
+        ];
+        for (name, offset, length) in regions {
+            if offset
+                .checked_add(length)
+                .is_none_or(|end| end > footer_offset)
+            {
+                return Err(Error::Corruption(format!(
+                    "{} at offset {} with length {} extends past the footer of {}",
+                    name,
+                    offset,
+                    length,
+                    path.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads, verifies and parses an index block or index partition
+    fn read_index(
+        file: &TableFile,
+        path: &Path,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<IndexEntry>> {
+        let block = Self::read_checked_block(file, path, offset, length, true)?;
+        let mut data = &block[..];
 
         // Read entry count
         let mut count_bytes = [0u8; 4];
-        reader.read_exact(&mut count_bytes)?;
+        data.read_exact(&mut count_bytes)?;
         let entry_count = u32::from_le_bytes(count_bytes) as usize;
 
-        let mut index_entries = Vec::with_capacity(entry_count);
+        let mut index_entries = Vec::with_capacity(entry_count.min(data.len()));
 
         // Read each index entry
         for _ in 0..entry_count {
             // Read block offset
             let mut offset_bytes = [0u8; 8];
-            reader.read_exact(&mut offset_bytes)?;
+            data.read_exact(&mut offset_bytes)?;
             let block_offset = u64::from_le_bytes(offset_bytes);
 
             // Read key length
             let mut key_len_bytes = [0u8; 4];
-            reader.read_exact(&mut key_len_bytes)?;
+            data.read_exact(&mut key_len_bytes)?;
             let key_len = u32::from_le_bytes(key_len_bytes) as usize;
 
             // Read key
-            let mut key = vec![0u8; key_len];
-            reader.read_exact(&mut key)?;
+            let key = read_bytes(&mut data, key_len)?;
 
             index_entries.push(IndexEntry::new(block_offset, key));
         }
 
-        // Read and verify checksum (placeholder for now)
-        let mut checksum_bytes = [0u8; 4];
-        reader.read_exact(&mut checksum_bytes)?;
-        let _checksum = u32::from_le_bytes(checksum_bytes);
-        // TODO: Verify checksum
-
         Ok(index_entries)
     }
 
-    /// Finds the block offset that might contain the given user key
-    fn find_block_for_key(&self, user_key: &Key) -> Option<u64> {
-        if self.index.is_empty() {
-            return None;
+    /// Reads, verifies and parses the top-level index of a partitioned
+    /// index
+    fn read_partition_handles(
+        file: &TableFile,
+        path: &Path,
+        footer: &Footer,
+    ) -> Result<Vec<PartitionHandle>> {
+        let block =
+            Self::read_checked_block(file, path, footer.index_offset, footer.index_length, true)?;
+        let mut data = &block[..];
+
+        let mut count_bytes = [0u8; 4];
+        data.read_exact(&mut count_bytes)?;
+        let count = u32::from_le_bytes(count_bytes) as usize;
+
+        let mut handles = Vec::with_capacity(count.min(data.len()));
+        for _ in 0..count {
+            let mut key_len_bytes = [0u8; 4];
+            data.read_exact(&mut key_len_bytes)?;
+            let first_key = read_bytes(&mut data, u32::from_le_bytes(key_len_bytes) as usize)?;
+
+            let mut fields = [0u64; 5];
+            for field in &mut fields {
+                let mut bytes = [0u8; 8];
+                data.read_exact(&mut bytes)?;
+                *field = u64::from_le_bytes(bytes);
+            }
+            let [index_offset, index_length, filter_offset, filter_length, data_end] = fields;
+            handles.push(PartitionHandle {
+                first_key,
+                index_offset,
+                index_length,
+                filter_offset,
+                filter_length,
+                data_end,
+            });
         }
 
-        // Find the last block whose first key is <= user_key
-        let mut result = None;
-        for entry in &self.index {
-            if entry.first_key <= *user_key {
-                result = Some(entry.block_offset);
-            } else {
-                break;
+        Ok(handles)
+    }
+
+    /// Reads a block and verifies the CRC32 in its last 4 bytes
+    ///
+    /// Returns the block without its checksum, borrowed from the mapping
+    /// for mapped files. With `verify` unset the checksum is skipped but
+    /// still stripped.
+    fn read_checked_block<'f>(
+        file: &'f TableFile,
+        path: &Path,
+        offset: u64,
+        length: u64,
+        verify: bool,
+    ) -> Result<Cow<'f, [u8]>> {
+        let corruption = |reason: &str| {
+            Error::Corruption(format!(
+                "{} in block at offset {} of {}",
+                reason,
+                offset,
+                path.display()
+            ))
+        };
+        if length < BLOCK_TRAILER_SIZE as u64 {
+            return Err(corruption("Truncated block"));
+        }
+
+        let block = Self::read_region(file, path, offset, length)?;
+        let checksum_offset = block.len() - BLOCK_TRAILER_SIZE;
+        let checksum = u32::from_le_bytes(block[checksum_offset..].try_into().unwrap());
+        let block = match block {
+            Cow::Borrowed(bytes) => Cow::Borrowed(&bytes[..checksum_offset]),
+            Cow::Owned(mut bytes) => {
+                bytes.truncate(checksum_offset);
+                Cow::Owned(bytes)
             }
+        };
+        if verify && crc32fast::hash(&block) != checksum {
+            return Err(corruption("Checksum mismatch"));
         }
+        Ok(block)
+    }
 
-        // If no block's first key is <= user_key, the key might be in the first block
-        // (if it's smaller than the first key in the file)
-        result.or(Some(self.index[0].block_offset))
+    /// Returns `length` bytes at `offset`, checking they lie within the
+    /// file
+    fn read_region<'f>(
+        file: &'f TableFile,
+        path: &Path,
+        offset: u64,
+        length: u64,
+    ) -> Result<Cow<'f, [u8]>> {
+        if offset
+            .checked_add(length)
+            .is_none_or(|end| end > file.len())
+        {
+            return Err(Error::Corruption(format!(
+                "Block extends past the end of the file in block at offset {} of {}",
+                offset,
+                path.display()
+            )));
+        }
+        Ok(file.read(offset, length as usize)?)
     }
 
-    /// Loads a data block, using cache if available
-    fn load_block(&mut self, block_offset: u64) -> Result<&Vec<SSTableEntry>> {
-        if !self.block_cache.contains_key(&block_offset) {
-            let entries = self.read_block(block_offset)?;
-            self.block_cache.insert(block_offset, entries);
+    /// Reads and decodes a bloom filter block or filter partition
+    fn read_filter(file: &TableFile, path: &Path, offset: u64, length: u64) -> Result<BloomFilter> {
+        let block = Self::read_region(file, path, offset, length)?;
+        BloomFilter::decode(&block).map_err(|e| {
+            Error::Corruption(format!(
+                "{} in block at offset {} of {}",
+                e,
+                offset,
+                path.display()
+            ))
+        })
+    }
+
+    /// Reads, verifies and parses the properties block, if the file has one
+    fn read_properties(
+        file: &TableFile,
+        path: &Path,
+        footer: &Footer,
+    ) -> Result<Option<TableProperties>> {
+        if footer.properties_length == 0 {
+            return Ok(None);
+        }
+        let block = Self::read_checked_block(
+            file,
+            path,
+            footer.properties_offset,
+            footer.properties_length,
+            true,
+        )?;
+        TableProperties::decode(&block)
+            .map(Some)
+            .map_err(|e| match e {
+                Error::Corruption(reason) => Error::Corruption(format!(
+                    "{} in block at offset {} of {}",
+                    reason,
+                    footer.properties_offset,
+                    path.display()
+                )),
+                e => e,
+            })
+    }
+
+    /// Returns the partition that might hold the first entry for
+    /// `user_key`
+    ///
+    /// That is the last one whose first key is < `user_key`, or the first
+    /// one if the key sorts before the whole file. Versions of one key can
+    /// span partitions, so a partition starting with `user_key` may be
+    /// preceded by one ending with its newer versions.
+    fn find_partition(&self, user_key: &[u8]) -> usize {
+        match &self.index {
+            TableIndex::Full { .. } => 0,
+            TableIndex::Partitioned(handles) => handles
+                .partition_point(|handle| handle.first_key.as_slice() < user_key)
+                .saturating_sub(1),
+        }
+    }
+
+    /// Finds the data block that might hold the first entry for the given
+    /// user key
+    ///
+    /// Returns the partition's number, its index entries and the block's
+    /// position among them. Versions older than the block holds continue
+    /// in the blocks that follow it.
+    fn find_block_for_key(
+        &self,
+        user_key: &Key,
+        options: ReadOptions,
+    ) -> Result<Option<(usize, IndexPartition, usize)>> {
+        if self.index.len() == 0 {
+            return Ok(None);
+        }
+        let number = self.find_partition(user_key);
+        let partition = self.load_partition(number, options)?;
+        if partition.entries.is_empty() {
+            return Ok(None);
+        }
+
+        // The last block whose first key is < user_key; the first block
+        // if the key is smaller than every first key
+        let block = partition
+            .entries
+            .partition_point(|entry| entry.first_key < *user_key)
+            .saturating_sub(1);
+        Ok(Some((number, partition, block)))
+    }
+
+    /// Returns the number of index partitions; a full index counts as one
+    fn partition_count(&self) -> usize {
+        match &self.index {
+            TableIndex::Full { .. } => 1,
+            TableIndex::Partitioned(handles) => handles.len(),
+        }
+    }
+
+    /// Returns the index entries of partition `number`, reading them
+    /// through the block cache if the index is partitioned
+    fn load_partition(&self, number: usize, options: ReadOptions) -> Result<IndexPartition> {
+        let handle = match &self.index {
+            TableIndex::Full { blocks, .. } => return Ok(blocks.clone()),
+            TableIndex::Partitioned(handles) => &handles[number],
+        };
+
+        let key = self.cache_key(handle.index_offset);
+        if let Some((cache, key)) = &key {
+            if let Some(CachedBlock::IndexPartition(entries)) = cache.get(key) {
+                return Ok(IndexPartition {
+                    entries,
+                    data_end: handle.data_end,
+                });
+            }
+        }
+        let entries: Arc<[IndexEntry]> = Self::read_index(
+            &self.file,
+            &self.path,
+            handle.index_offset,
+            handle.index_length,
+        )?
+        .into();
+        if let Some((cache, key)) = key {
+            if options.fill_cache {
+                cache.insert(key, Arc::clone(&entries));
+            }
+        }
+        Ok(IndexPartition {
+            entries,
+            data_end: handle.data_end,
+        })
+    }
+
+    /// Returns the block cache and the key of the block at `block_offset`
+    /// in it, if the reader has a cache
+    fn cache_key(&self, block_offset: u64) -> Option<(&BlockCache, BlockCacheKey)> {
+        self.block_cache.as_ref().map(|(cache, file_number)| {
+            let key = BlockCacheKey {
+                file_number: *file_number,
+                block_offset,
+            };
+            (cache.as_ref(), key)
+        })
+    }
+
+    /// Loads data block `block` of `partition`, using the block cache if
+    /// there is one
+    ///
+    /// Blocks read from disk are added to the cache unless
+    /// `options.fill_cache` is unset.
+    fn load_block(
+        &self,
+        partition: &IndexPartition,
+        block: usize,
+        options: ReadOptions,
+    ) -> Result<Arc<Block>> {
+        let block_offset = partition.entries[block].block_offset;
+        let block_end = partition.block_end(block);
+        let Some((cache, key)) = self.cache_key(block_offset) else {
+            return Ok(Arc::new(self.read_block(
+                block_offset,
+                block_end,
+                options,
+            )?));
+        };
+        if let Some(CachedBlock::Data(block)) = cache.get(&key) {
+            return Ok(block);
+        }
+
+        let block = Arc::new(self.read_block(block_offset, block_end, options)?);
+        if options.fill_cache {
+            cache.insert(key, Arc::clone(&block));
         }
-        Ok(self.block_cache.get(&block_offset).unwrap())
+        Ok(block)
     }
 
-    /// Reads a data block from disk
-    fn read_block(&mut self, block_offset: u64) -> Result<Vec<SSTableEntry>> {
-        // Seek to block
-        self.reader.seek(SeekFrom::Start(block_offset))?;
+    /// Reads the data block between `block_offset` and `block_end` from
+    /// disk and decompresses it
+    ///
+    /// Its last byte before the checksum tags its compression and format;
+    /// blocks in the original format are re-encoded with restart points so
+    /// every block is searched the same way.
+    fn read_block(&self, block_offset: u64, block_end: u64, options: ReadOptions) -> Result<Block> {
+        let block = Self::read_checked_block(
+            &self.file,
+            &self.path,
+            block_offset,
+            block_end.saturating_sub(block_offset),
+            options.verify_checksums,
+        )?;
+        let (&tag, contents) = block.split_last().ok_or_else(|| {
+            Error::Corruption(format!(
+                "Empty block at offset {} of {}",
+                block_offset,
+                self.path.display()
+            ))
+        })?;
+        let compression = compression_from_tag(tag & !PREFIX_COMPRESSED_FLAG)?;
+        let block = decompress(compression, contents)?;
+        if tag & PREFIX_COMPRESSED_FLAG != 0 {
+            return Block::new(block.into_owned()).map_err(|e| match e {
+                Error::Corruption(reason) => Error::Corruption(format!(
+                    "{} in block at offset {} of {}",
+                    reason,
+                    block_offset,
+                    self.path.display()
+                )),
+                e => e,
+            });
+        }
+        let mut data = &block[..];
 
         // Read entry count
         let mut count_bytes = [0u8; 4];
-        self.reader.read_exact(&mut count_bytes)?;
+        data.read_exact(&mut count_bytes)?;
         let entry_count = u32::from_le_bytes(count_bytes) as usize;
 
-        let mut entries = Vec::with_capacity(entry_count);
+        let mut entries = Vec::with_capacity(entry_count.min(data.len()));
 
         // Read each entry
         for _ in 0..entry_count {
-            let entry = self.read_entry()?;
+            let entry = Self::read_entry(&mut data)?;
             entries.push(entry);
         }
 
-        // Read and verify checksum (placeholder for now)
-        let mut checksum_bytes = [0u8; 4];
-        self.reader.read_exact(&mut checksum_bytes)?;
-        let _checksum = u32::from_le_bytes(checksum_bytes);
-        // TODO: Verify checksum
-
-        Ok(entries)
+        Block::from_entries(&entries, DEFAULT_BLOCK_RESTART_INTERVAL)
     }
 
-    /// Reads a single entry from the current position
-    fn read_entry(&mut self) -> Result<SSTableEntry> {
+    /// Reads a single entry in the original block format from the front of
+    /// `data`
+    fn read_entry(data: &mut &[u8]) -> Result<SSTableEntry> {
         // Read key length
         let mut key_len_bytes = [0u8; 4];
-        self.reader.read_exact(&mut key_len_bytes)?;
+        data.read_exact(&mut key_len_bytes)?;
         let key_len = u32::from_le_bytes(key_len_bytes) as usize;
 
         // Read value length
         let mut value_len_bytes = [0u8; 4];
-        self.reader.read_exact(&mut value_len_bytes)?;
+        data.read_exact(&mut value_len_bytes)?;
         let value_len = u32::from_le_bytes(value_len_bytes) as usize;
 
         // Read timestamp
         let mut timestamp_bytes = [0u8; 8];
-        self.reader.read_exact(&mut timestamp_bytes)?;
+        data.read_exact(&mut timestamp_bytes)?;
         let timestamp = u64::from_le_bytes(timestamp_bytes);
 
         // Read operation
         let mut op_byte = [0u8; 1];
-        self.reader.read_exact(&mut op_byte)?;
-        let operation = match op_byte[0] {
-            0 => Operation::Put,
-            1 => Operation::Delete,
-            _ => {
-                return Err(Error::InvalidFormat(format!(
-                    "Invalid operation byte: {}",
-                    op_byte[0]
-                )))
-            }
-        };
+        data.read_exact(&mut op_byte)?;
+        let operation = operation_from_byte(op_byte[0])?;
 
         // Read key
-        let mut user_key = vec![0u8; key_len];
-        self.reader.read_exact(&mut user_key)?;
+        let user_key = read_bytes(data, key_len)?;
 
         // Read value
-        let mut value = vec![0u8; value_len];
-        self.reader.read_exact(&mut value)?;
+        let value = read_bytes(data, value_len)?;
 
         let internal_key = InternalKey::new(user_key, timestamp);
         Ok(SSTableEntry::new(internal_key, value, operation))
     }
 }
 
+/// How a reader finds data blocks and checks the bloom filter
+enum TableIndex {
+    /// The whole index and filter, held in memory
+    Full {
+        blocks: IndexPartition,
+        filter: BloomFilter,
+    },
+    /// The top-level index of a partitioned index
+    Partitioned(Vec<PartitionHandle>),
+}
+
+impl TableIndex {
+    /// Returns the number of index entries held in memory
+    fn len(&self) -> usize {
+        match self {
+            TableIndex::Full { blocks, .. } => blocks.entries.len(),
+            TableIndex::Partitioned(handles) => handles.len(),
+        }
+    }
+}
+
+/// The index entries of a run of consecutive data blocks
+#[derive(Clone)]
+struct IndexPartition {
+    entries: Arc<[IndexEntry]>,
+    /// Where the last of the blocks ends
+    data_end: u64,
+}
+
+impl IndexPartition {
+    /// Returns where data block `block` ends: at the start of the next one,
+    /// or at the end of the partition's data for the last one
+    fn block_end(&self, block: usize) -> u64 {
+        self.entries
+            .get(block + 1)
+            .map_or(self.data_end, |entry| entry.block_offset)
+    }
+}
+
+/// Where one partition of a partitioned index and its filter are stored
+struct PartitionHandle {
+    /// First user key of the partition's first data block
+    first_key: Key,
+    index_offset: u64,
+    index_length: u64,
+    filter_offset: u64,
+    filter_length: u64,
+    /// Where the partition's last data block ends
+    data_end: u64,
+}
+
+/// The bytes behind a reader
+enum TableFile {
+    /// An open file read with positional reads, and its length
+    Positional { file: File, size: u64 },
+    /// A read-only mapping of the whole file
+    Mapped(Mmap),
+}
+
+impl TableFile {
+    /// Returns the length of the file
+    fn len(&self) -> u64 {
+        match self {
+            TableFile::Positional { size, .. } => *size,
+            TableFile::Mapped(mmap) => mmap.len() as u64,
+        }
+    }
+
+    /// Returns `length` bytes at `offset`, which must lie within the file
+    ///
+    /// Mapped files return a slice of the mapping; others read into a new
+    /// buffer.
+    fn read(&self, offset: u64, length: usize) -> std::io::Result<Cow<'_, [u8]>> {
+        match self {
+            TableFile::Positional { file, .. } => {
+                let mut buf = vec![0u8; length];
+                read_exact_at(file, &mut buf, offset)?;
+                Ok(Cow::Owned(buf))
+            }
+            TableFile::Mapped(mmap) => {
+                let start = offset as usize;
+                Ok(Cow::Borrowed(&mmap[start..start + length]))
+            }
+        }
+    }
+}
+
+/// Fills `buf` from `file` starting at `offset`, without moving a cursor
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+/// Fills `buf` from `file` starting at `offset`
+///
+/// Windows moves the file cursor on positional reads, but nothing relies
+/// on it, so concurrent reads stay correct.
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Takes `len` bytes from the front of `data`
+///
+/// Checks the length first, so a damaged length in an unverified block
+/// cannot trigger a huge allocation.
+fn read_bytes(data: &mut &[u8], len: usize) -> Result<Vec<u8>> {
+    if len > data.len() {
+        return Err(Error::Corruption(format!(
+            "Length {} exceeds the {} bytes left in the block",
+            len,
+            data.len()
+        )));
+    }
+    let (bytes, rest) = data.split_at(len);
+    *data = rest;
+    Ok(bytes.to_vec())
+}
+
 /// Iterator over SSTable entries
 pub struct SSTableIterator<'a> {
-    reader: &'a mut SSTableReader,
+    reader: &'a SSTableReader,
+    /// Index partition being read, and its entries once loaded
+    partition_idx: usize,
+    partition: Option<IndexPartition>,
+    /// Position of the current block within the partition
     current_block_idx: usize,
-    current_entry_idx: usize,
     start_key: Option<Key>,
     end_key: Option<Key>,
-    current_block_entries: Option<Vec<SSTableEntry>>,
+    options: ReadOptions,
+    current_block: Option<BlockIter>,
+    /// Error hit while moving past the last entry returned
+    error: Option<Error>,
 }
 
 impl<'a> SSTableIterator<'a> {
     /// Creates a new iterator over all entries
-    fn new(reader: &'a mut SSTableReader) -> Result<Self> {
+    fn new(reader: &'a SSTableReader, options: ReadOptions) -> Result<Self> {
         Ok(Self {
             reader,
+            partition_idx: 0,
+            partition: None,
             current_block_idx: 0,
-            current_entry_idx: 0,
             start_key: None,
             end_key: None,
-            current_block_entries: None,
+            options,
+            current_block: None,
+            error: None,
         })
     }
 
     /// Creates a new iterator over a key range
     fn new_range(
-        reader: &'a mut SSTableReader,
+        reader: &'a SSTableReader,
+        options: ReadOptions,
         start_key: Option<&Key>,
         end_key: Option<&Key>,
     ) -> Result<Self> {
-        let mut iter = Self::new(reader)?;
+        let mut iter = Self::new(reader, options)?;
         iter.start_key = start_key.cloned();
         iter.end_key = end_key.cloned();
 
         // Find the starting block if we have a start key
         if let Some(start) = start_key {
-            if let Some(block_offset) = iter.reader.find_block_for_key(start) {
-                // Find the index of this block
-                for (idx, entry) in iter.reader.index.iter().enumerate() {
-                    if entry.block_offset == block_offset {
-                        iter.current_block_idx = idx;
-                        break;
-                    }
-                }
+            if let Some((number, partition, block)) =
+                iter.reader.find_block_for_key(start, options)?
+            {
+                iter.partition_idx = number;
+                iter.partition = Some(partition);
+                iter.current_block_idx = block;
             }
         }
 
@@ -436,16 +1052,40 @@ impl<'a> SSTableIterator<'a> {
     }
 
     /// Loads the current block if needed
+    ///
+    /// With a start key, the block is entered at the first entry at or
+    /// after it rather than at its first entry.
     fn ensure_current_block(&mut self) -> Result<bool> {
-        if self.current_block_idx >= self.reader.index.len() {
-            return Ok(false); // No more blocks
-        }
+        // Move on to the next partition once this one is used up
+        let partition = loop {
+            if self.partition.is_none() {
+                if self.partition_idx >= self.reader.partition_count() {
+                    return Ok(false); // No more blocks
+                }
+                self.partition = Some(
+                    self.reader
+                        .load_partition(self.partition_idx, self.options)?,
+                );
+            }
+            let partition = self.partition.as_ref().unwrap();
+            if self.current_block_idx < partition.entries.len() {
+                break partition;
+            }
+            self.partition_idx += 1;
+            self.partition = None;
+            self.current_block_idx = 0;
+        };
 
-        if self.current_block_entries.is_none() {
-            let block_offset = self.reader.index[self.current_block_idx].block_offset;
-            let entries = self.reader.read_block(block_offset)?;
-            self.current_block_entries = Some(entries);
-            self.current_entry_idx = 0;
+        if self.current_block.is_none() {
+            let block = self
+                .reader
+                .load_block(partition, self.current_block_idx, self.options)?;
+            let mut iter = block.iter();
+            match &self.start_key {
+                Some(start) => iter.seek(&InternalKey::new(start.clone(), Timestamp::MAX))?,
+                None => iter.seek_to_first()?,
+            }
+            self.current_block = Some(iter);
         }
 
         Ok(true)
@@ -454,8 +1094,7 @@ impl<'a> SSTableIterator<'a> {
     /// Advances to the next block
     fn advance_to_next_block(&mut self) {
         self.current_block_idx += 1;
-        self.current_entry_idx = 0;
-        self.current_block_entries = None;
+        self.current_block = None;
     }
 }
 
@@ -463,6 +1102,9 @@ impl<'a> Iterator for SSTableIterator<'a> {
     type Item = Result<SSTableEntry>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
         loop {
             // Ensure we have a current block loaded
             match self.ensure_current_block() {
@@ -471,50 +1113,366 @@ impl<'a> Iterator for SSTableIterator<'a> {
                 Ok(true) => {}
             }
 
-            let entries = self.current_block_entries.as_ref().unwrap();
+            let iter = self.current_block.as_mut().unwrap();
 
             // Check if we've reached the end of current block
-            if self.current_entry_idx >= entries.len() {
+            if !iter.valid() {
                 self.advance_to_next_block();
                 continue;
             }
 
-            let entry = &entries[self.current_entry_idx];
-            self.current_entry_idx += 1;
-
-            // Check range constraints
-            if let Some(ref start) = self.start_key {
-                if entry.key.user_key < *start {
-                    continue;
-                }
-            }
-
             if let Some(ref end) = self.end_key {
-                if entry.key.user_key >= *end {
+                if iter.user_key() >= end.as_slice() {
                     return None; // Reached end of range
                 }
             }
 
-            return Some(Ok(entry.clone()));
+            let entry = iter.entry();
+            if let Err(e) = iter.advance() {
+                // Report it on the next call and skip the rest of the block
+                self.error = Some(e);
+                self.advance_to_next_block();
+            }
+            return Some(Ok(entry));
         }
     }
 }
 
+/// A seekable, bidirectional cursor over SSTable entries
+///
+/// Unlike [`SSTableIterator`], a cursor can be repositioned at any time and
+/// moved in either direction, which reverse scans and merging iterators
+/// need. Seeks use the index to go straight to the block holding the
+/// target.
+///
+/// `R` is any handle to the reader: `&SSTableReader` from
+/// [`SSTableReader::cursor`], or an `Arc<SSTableReader>` for a cursor that
+/// owns its table.
+///
+/// Movement does not fail outright. An error leaves the cursor invalid and
+/// is reported by [`status`](Self::status) until the next seek.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut cursor = reader.cursor();
+/// cursor.seek_to_last();
+/// while cursor.valid() {
+///     println!("{:?}@{} = {:?}", cursor.key(), cursor.timestamp(), cursor.value());
+///     cursor.prev();
+/// }
+/// cursor.status()?;
+/// ```
+pub struct SSTableCursor<R> {
+    reader: R,
+    options: ReadOptions,
+    /// Index partition holding the current block, once loaded
+    partition_idx: usize,
+    partition: Option<IndexPartition>,
+    /// Position of the current block within the partition
+    block_idx: usize,
+    /// The current block; `None` while the cursor is invalid
+    block: Option<BlockIter>,
+    /// Error that invalidated the cursor
+    error: Option<Error>,
+}
+
+impl<R: Deref<Target = SSTableReader>> SSTableCursor<R> {
+    /// Creates an unpositioned cursor over `reader`
+    pub fn new(reader: R, options: ReadOptions) -> Self {
+        Self {
+            reader,
+            options,
+            partition_idx: 0,
+            partition: None,
+            block_idx: 0,
+            block: None,
+            error: None,
+        }
+    }
+
+    /// Returns true if the cursor is positioned on an entry
+    pub fn valid(&self) -> bool {
+        self.block.as_ref().is_some_and(BlockIter::valid)
+    }
+
+    /// Returns the error that invalidated the cursor, if any
+    ///
+    /// The error is handed to the caller, so a second call returns `Ok`.
+    pub fn status(&mut self) -> Result<()> {
+        self.error.take().map_or(Ok(()), Err)
+    }
+
+    /// Returns the user key of the current entry
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cursor is not valid.
+    pub fn key(&self) -> &[u8] {
+        self.current().user_key()
+    }
+
+    /// Returns the timestamp of the current entry
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cursor is not valid.
+    pub fn timestamp(&self) -> Timestamp {
+        self.current().timestamp()
+    }
+
+    /// Returns the operation of the current entry
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cursor is not valid.
+    pub fn operation(&self) -> Operation {
+        self.current().operation()
+    }
+
+    /// Returns the value of the current entry
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cursor is not valid.
+    pub fn value(&self) -> &[u8] {
+        self.current().value()
+    }
+
+    /// Returns a copy of the current entry
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cursor is not valid.
+    pub fn entry(&self) -> SSTableEntry {
+        self.current().entry()
+    }
+
+    /// Positions the cursor on the first entry
+    pub fn seek_to_first(&mut self) {
+        let result = self.try_seek_to_first();
+        self.finish_seek(result);
+    }
+
+    /// Positions the cursor on the last entry
+    pub fn seek_to_last(&mut self) {
+        let result = self.try_seek_to_last();
+        self.finish_seek(result);
+    }
+
+    /// Positions the cursor on the first entry at or after `target`
+    ///
+    /// Entries are ordered by user key ascending, then timestamp
+    /// descending, so seeking to `(key, ts)` finds the newest version of
+    /// `key` no newer than `ts`, or the next key.
+    pub fn seek(&mut self, target: &InternalKey) {
+        let result = self.try_seek(target);
+        self.finish_seek(result);
+    }
+
+    /// Positions the cursor on the last entry at or before `target`
+    pub fn seek_for_prev(&mut self, target: &InternalKey) {
+        let result = self.try_seek_for_prev(target);
+        self.finish_seek(result);
+    }
+
+    /// Moves to the next entry, or invalidates the cursor after the last
+    ///
+    /// Does nothing if the cursor is not valid.
+    pub fn next(&mut self) {
+        if !self.valid() {
+            return;
+        }
+        let result = self.try_next();
+        self.finish_move(result);
+    }
+
+    /// Moves to the previous entry, or invalidates the cursor before the
+    /// first
+    ///
+    /// Does nothing if the cursor is not valid.
+    pub fn prev(&mut self) {
+        if !self.valid() {
+            return;
+        }
+        let result = self.try_prev();
+        self.finish_move(result);
+    }
+
+    fn current(&self) -> &BlockIter {
+        match &self.block {
+            Some(block) if block.valid() => block,
+            _ => panic!("SSTableCursor is not positioned on an entry"),
+        }
+    }
+
+    fn finish_seek(&mut self, result: Result<()>) {
+        self.error = None;
+        self.finish_move(result);
+    }
+
+    fn finish_move(&mut self, result: Result<()>) {
+        if let Err(e) = result {
+            self.block = None;
+            self.error = Some(e);
+        }
+    }
+
+    fn try_seek_to_first(&mut self) -> Result<()> {
+        self.block = None;
+        if !self.open_block(0, 0)? {
+            return Ok(());
+        }
+        self.block.as_mut().unwrap().seek_to_first()?;
+        self.skip_forward(None)
+    }
+
+    fn try_seek_to_last(&mut self) -> Result<()> {
+        self.block = None;
+        let partition = self.reader.partition_count().saturating_sub(1);
+        if !self.open_block(partition, usize::MAX)? {
+            return Ok(());
+        }
+        self.block.as_mut().unwrap().seek_to_last()?;
+        self.skip_backward()
+    }
+
+    fn try_seek(&mut self, target: &InternalKey) -> Result<()> {
+        self.block = None;
+        let Some((number, partition, block)) = self
+            .reader
+            .find_block_for_key(&target.user_key, self.options)?
+        else {
+            return Ok(());
+        };
+        self.partition_idx = number;
+        self.partition = Some(partition);
+        self.open_block(number, block)?;
+        self.block.as_mut().unwrap().seek(target)?;
+        self.skip_forward(Some(target))
+    }
+
+    fn try_seek_for_prev(&mut self, target: &InternalKey) -> Result<()> {
+        self.try_seek(target)?;
+        if !self.valid() {
+            return self.try_seek_to_last();
+        }
+        let block = self.block.as_ref().unwrap();
+        if compare(block.user_key(), block.timestamp(), target) == Ordering::Greater {
+            self.try_prev()?;
+        }
+        Ok(())
+    }
+
+    fn try_next(&mut self) -> Result<()> {
+        self.block.as_mut().unwrap().advance()?;
+        self.skip_forward(None)
+    }
+
+    fn try_prev(&mut self) -> Result<()> {
+        self.block.as_mut().unwrap().prev()?;
+        self.skip_backward()
+    }
+
+    /// Makes block `block` of partition `number` current, unpositioned
+    ///
+    /// `usize::MAX` selects the partition's last block. Returns false if
+    /// there is no such block.
+    fn open_block(&mut self, number: usize, block: usize) -> Result<bool> {
+        if self.partition.is_none() || self.partition_idx != number {
+            if number >= self.reader.partition_count() {
+                return Ok(false);
+            }
+            self.partition = Some(self.reader.load_partition(number, self.options)?);
+            self.partition_idx = number;
+        }
+        let partition = self.partition.as_ref().unwrap();
+        let Some(last) = partition.entries.len().checked_sub(1) else {
+            return Ok(false);
+        };
+        let block = block.min(last);
+        let data = self.reader.load_block(partition, block, self.options)?;
+        self.block_idx = block;
+        self.block = Some(data.iter());
+        Ok(true)
+    }
+
+    /// Moves past the end of exhausted blocks to the first entry of the
+    /// next block that has one, or its first entry at or after `target`
+    ///
+    /// The index only records user keys, so when the versions of one key
+    /// fill several blocks the target may lie beyond the next one.
+    fn skip_forward(&mut self, target: Option<&InternalKey>) -> Result<()> {
+        while self.block.as_ref().is_some_and(|block| !block.valid()) {
+            let opened = if self.block_idx + 1 < self.partition.as_ref().unwrap().entries.len() {
+                self.open_block(self.partition_idx, self.block_idx + 1)?
+            } else {
+                self.open_block(self.partition_idx + 1, 0)?
+            };
+            if !opened {
+                self.block = None;
+                return Ok(());
+            }
+            let block = self.block.as_mut().unwrap();
+            match target {
+                Some(target) => block.seek(target)?,
+                None => block.seek_to_first()?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves back past the start of exhausted blocks to the last entry of
+    /// the previous block that has one
+    fn skip_backward(&mut self) -> Result<()> {
+        while self.block.as_ref().is_some_and(|block| !block.valid()) {
+            let opened = if self.block_idx > 0 {
+                self.open_block(self.partition_idx, self.block_idx - 1)?
+            } else if self.partition_idx > 0 {
+                self.open_block(self.partition_idx - 1, usize::MAX)?
+            } else {
+                false
+            };
+            if !opened {
+                self.block = None;
+                return Ok(());
+            }
+            self.block.as_mut().unwrap().seek_to_last()?;
+        }
+        Ok(())
+    }
+}
+
 /// Metadata about an SSTable from reader perspective
 #[derive(Debug, Clone)]
 pub struct SSTableReaderInfo {
-    /// Number of index entries (approximately number of blocks)
+    /// Number of index entries held in memory: one per data block, or one
+    /// per partition for a partitioned index
     pub index_entries: usize,
+    /// Number of index partitions; 0 for a full index
+    pub index_partitions: usize,
     /// Footer metadata
     pub footer: Footer,
+    /// Summary of the file's contents; `None` for version 1 files
+    pub properties: Option<TableProperties>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::sstable::writer::SSTableWriter;
+    use crate::sstable::block::operation_byte;
+    use crate::sstable::bloom::BloomFilterBuilder;
+    use crate::sstable::writer::{SSTableWriter, SSTableWriterOptions};
+    use crate::sstable::{FORMAT_VERSION, LEGACY_FORMAT_VERSION};
+    use ferrisdb_core::CompressionType;
     use tempfile::TempDir;
 
+    /// Returns the index entry of data block `block` of a full index
+    fn index_entry(reader: &SSTableReader, block: usize) -> IndexEntry {
+        let partition = reader.load_partition(0, ReadOptions::default()).unwrap();
+        partition.entries[block].clone()
+    }
+
     fn create_test_sstable() -> (
         TempDir,
         std::path::PathBuf,
@@ -561,7 +1519,7 @@ mod tests {
     fn test_sstable_reader_basic() {
         let (_temp_dir, path, test_data) = create_test_sstable();
 
-        let mut reader = SSTableReader::open(&path).unwrap();
+        let reader = SSTableReader::open(&path).unwrap();
 
         // Test exact key lookups
         let result = reader
@@ -587,7 +1545,7 @@ mod tests {
     fn test_sstable_reader_get_latest() {
         let (_temp_dir, path, _test_data) = create_test_sstable();
 
-        let mut reader = SSTableReader::open(&path).unwrap();
+        let reader = SSTableReader::open(&path).unwrap();
 
         // Test getting latest version of key1
         let result = reader.get_latest(&b"key1".to_vec(), 1000).unwrap();
@@ -613,12 +1571,12 @@ mod tests {
     fn test_sstable_reader_iterator() {
         let (_temp_dir, path, test_data) = create_test_sstable();
 
-        let mut reader = SSTableReader::open(&path).unwrap();
-        let mut iter = reader.iter().unwrap();
+        let reader = SSTableReader::open(&path).unwrap();
+        let iter = reader.iter().unwrap();
 
         // Collect all entries
         let mut entries = Vec::new();
-        while let Some(entry_result) = iter.next() {
+        for entry_result in iter {
             entries.push(entry_result.unwrap());
         }
 
@@ -635,15 +1593,15 @@ mod tests {
     fn test_sstable_reader_range_iterator() {
         let (_temp_dir, path, _test_data) = create_test_sstable();
 
-        let mut reader = SSTableReader::open(&path).unwrap();
+        let reader = SSTableReader::open(&path).unwrap();
 
         // Test range from key1 to key3 (exclusive)
         let start_key = b"key1".to_vec();
         let end_key = b"key3".to_vec();
-        let mut iter = reader.range_iter(Some(&start_key), Some(&end_key)).unwrap();
+        let iter = reader.range_iter(Some(&start_key), Some(&end_key)).unwrap();
 
         let mut entries = Vec::new();
-        while let Some(entry_result) = iter.next() {
+        for entry_result in iter {
             entries.push(entry_result.unwrap());
         }
 
@@ -664,6 +1622,47 @@ mod tests {
 
         assert!(info.index_entries > 0);
         assert_eq!(info.footer.magic, SSTABLE_MAGIC);
+        assert_eq!(info.footer.format_version, FORMAT_VERSION);
+    }
+
+    #[test]
+    fn test_sstable_reader_properties() {
+        let (_temp_dir, path, test_data) = create_test_sstable();
+
+        let properties = SSTableReader::open(&path)
+            .unwrap()
+            .info()
+            .properties
+            .unwrap();
+        assert_eq!(properties.entry_count, test_data.len() as u64);
+        assert_eq!(properties.tombstone_count, 1);
+        assert_eq!(properties.raw_key_size, 16);
+        assert_eq!(properties.raw_value_size, 22);
+        assert_eq!(
+            (properties.min_timestamp, properties.max_timestamp),
+            (50, 200)
+        );
+        assert_eq!(properties.smallest_key, test_data[0].0);
+        assert_eq!(properties.largest_key, test_data[3].0);
+        assert_eq!(properties.compression, CompressionType::None);
+        assert_eq!(properties.filter_policy, "bloom:10");
+        assert!(properties.creation_time > 0);
+    }
+
+    #[test]
+    fn test_sstable_reader_rejects_damaged_properties() {
+        let (_temp_dir, path, _test_data) = create_test_sstable();
+        let footer = SSTableReader::open(&path).unwrap().info().footer;
+
+        let mut data = std::fs::read(&path).unwrap();
+        data[footer.properties_offset as usize + 6] ^= 0xFF;
+        std::fs::write(&path, data).unwrap();
+
+        let error = SSTableReader::open(&path).unwrap_err();
+        assert!(error.to_string().contains("Checksum mismatch"));
+        assert!(error
+            .to_string()
+            .contains(&footer.properties_offset.to_string()));
     }
 
     #[test]
@@ -718,7 +1717,7 @@ mod tests {
         writer.finish().unwrap();
 
         // Test reading from the SSTable
-        let mut reader = SSTableReader::open(&path).unwrap();
+        let reader = SSTableReader::open(&path).unwrap();
 
         // Test lookups throughout the range to ensure binary search works correctly
         for i in [0, 50, 100, 150, 199] {
@@ -740,4 +1739,765 @@ mod tests {
         let result = reader.get(&b"key_999999".to_vec(), 100).unwrap();
         assert_eq!(result, None);
     }
+
+    fn write_keys(path: &Path, count: usize, bloom_bits_per_key: usize) {
+        let options = SSTableWriterOptions {
+            block_size: 256,
+            bloom_bits_per_key,
+            compression: CompressionType::None,
+            ..Default::default()
+        };
+        let mut writer = SSTableWriter::with_options(path, options).unwrap();
+        for i in 0..count {
+            let key = InternalKey::new(format!("key_{:06}", i).into_bytes(), 1);
+            writer.add(key, b"value".to_vec(), Operation::Put).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_sstable_reader_bloom_filter_skips_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("bloom.sst");
+        write_keys(&path, 1000, 10);
+
+        let cache = Arc::new(BlockCache::new(1024 * 1024));
+        let reader = SSTableReader::open(&path)
+            .unwrap()
+            .with_block_cache(Arc::clone(&cache), 1);
+        for i in 0..1000 {
+            // Missing keys that fall inside the file's key range
+            let key = format!("key_{:06}x", i).into_bytes();
+            assert_eq!(reader.get(&key, 1).unwrap(), None);
+        }
+        let stats = cache.stats();
+        let false_positives = stats.hits + stats.misses;
+        assert!(
+            false_positives < 30,
+            "{} blocks read for missing keys",
+            false_positives
+        );
+
+        // Present keys are never filtered out
+        for i in 0..1000 {
+            let key = format!("key_{:06}", i).into_bytes();
+            assert!(reader.may_contain(&key));
+            assert_eq!(reader.get(&key, 1).unwrap(), Some(b"value".to_vec()));
+        }
+    }
+
+    fn partitioned_entries() -> Vec<SSTableEntry> {
+        (0..2000u64)
+            .map(|i| {
+                let key = InternalKey::new(format!("key_{:06}", i).into_bytes(), i + 1);
+                if i % 7 == 0 {
+                    SSTableEntry::new(key, Vec::new(), Operation::Delete)
+                } else {
+                    SSTableEntry::new(key, format!("value_{}", i).into_bytes(), Operation::Put)
+                }
+            })
+            .collect()
+    }
+
+    fn write_entries(path: &Path, entries: &[SSTableEntry], partitioned_index: bool) {
+        let options = SSTableWriterOptions {
+            block_size: 256,
+            partitioned_index,
+            ..Default::default()
+        };
+        let mut writer = SSTableWriter::with_options(path, options).unwrap();
+        for entry in entries {
+            writer
+                .add(entry.key.clone(), entry.value.clone(), entry.operation)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn partition_handles(reader: &SSTableReader) -> &[PartitionHandle] {
+        match &reader.index {
+            TableIndex::Partitioned(handles) => handles,
+            TableIndex::Full { .. } => panic!("index is not partitioned"),
+        }
+    }
+
+    #[test]
+    fn test_sstable_reader_partitioned_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let entries = partitioned_entries();
+        let full_path = temp_dir.path().join("full.sst");
+        let path = temp_dir.path().join("partitioned.sst");
+        write_entries(&full_path, &entries, false);
+        write_entries(&path, &entries, true);
+
+        let full = SSTableReader::open(&full_path).unwrap();
+        let cache = Arc::new(BlockCache::new(1024 * 1024));
+        let reader = SSTableReader::open(&path)
+            .unwrap()
+            .with_block_cache(Arc::clone(&cache), 1);
+
+        // Only the small top-level index stays in memory
+        let info = reader.info();
+        assert_eq!(info.footer.format_version, PARTITIONED_FORMAT_VERSION);
+        assert!(info.index_partitions > 1);
+        assert_eq!(info.index_entries, info.index_partitions);
+        assert!(info.index_entries * 8 < full.info().index_entries);
+        assert_eq!(info.properties.unwrap().entry_count, 2000);
+
+        let read: Vec<_> = reader.iter().unwrap().map(|e| e.unwrap()).collect();
+        assert_eq!(read, entries);
+        let (start, end) = (b"key_000500".to_vec(), b"key_001500".to_vec());
+        let range: Vec<_> = reader
+            .range_iter(Some(&start), Some(&end))
+            .unwrap()
+            .map(|e| e.unwrap())
+            .collect();
+        assert_eq!(range, entries[500..1500]);
+
+        for entry in &entries {
+            let (value, timestamp, operation) = reader
+                .get_latest(&entry.key.user_key, u64::MAX)
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                (value, timestamp),
+                (entry.value.clone(), entry.key.timestamp)
+            );
+            assert_eq!(operation, entry.operation);
+        }
+
+        // Filter partitions rule out missing keys across the whole file
+        let false_positives = (0..2000)
+            .filter(|i| reader.may_contain(format!("key_{:06}x", i).as_bytes()))
+            .count();
+        assert!(false_positives < 60, "{} false positives", false_positives);
+
+        // Repeated lookups find their partitions in the block cache
+        let stats = cache.stats();
+        assert!(stats.hits > stats.misses * 4);
+    }
+
+    #[test]
+    fn test_sstable_reader_partitions_are_checked_on_use() {
+        let temp_dir = TempDir::new().unwrap();
+        let entries = partitioned_entries();
+        let path = temp_dir.path().join("partitioned.sst");
+        write_entries(&path, &entries, true);
+
+        let (index_offset, filter_offset, first_keys) = {
+            let reader = SSTableReader::open(&path).unwrap();
+            let handles = partition_handles(&reader);
+            let first_keys: Vec<_> = handles.iter().map(|h| h.first_key.clone()).collect();
+            (
+                handles[1].index_offset,
+                handles[2].filter_offset,
+                first_keys,
+            )
+        };
+        damage(&path, index_offset + 4);
+        damage(&path, filter_offset + 1);
+
+        // Opening reads only the top-level index
+        let reader = SSTableReader::open(&path).unwrap();
+        let value = reader.get_latest(&first_keys[0], u64::MAX).unwrap();
+        assert!(value.is_some());
+
+        let error = reader.get_latest(&first_keys[1], u64::MAX).unwrap_err();
+        assert!(matches!(error, Error::Corruption(_)));
+        assert!(error.to_string().contains(&index_offset.to_string()));
+
+        // A partition's first key needs no filter, so look up the next one
+        let position = entries
+            .iter()
+            .position(|entry| entry.key.user_key == first_keys[2])
+            .unwrap();
+        let key = &entries[position + 1].key.user_key;
+        let error = reader.get_latest(key, u64::MAX).unwrap_err();
+        assert!(error.to_string().contains("Bloom filter checksum mismatch"));
+        assert!(reader.may_contain(key));
+
+        assert!(reader.iter().unwrap().any(|entry| entry.is_err()));
+    }
+
+    /// Returns entries where some keys have enough versions to span
+    /// several 256-byte blocks
+    fn versioned_entries() -> Vec<SSTableEntry> {
+        let mut entries = Vec::new();
+        for i in 0..300u64 {
+            let versions = if i % 25 == 0 { 40 } else { 1 + i % 3 };
+            for timestamp in (1..=versions).rev() {
+                let key = InternalKey::new(format!("key_{:06}", i).into_bytes(), timestamp * 10);
+                let value = format!("value_{}_{}", i, timestamp).into_bytes();
+                entries.push(SSTableEntry::new(key, value, Operation::Put));
+            }
+        }
+        entries
+    }
+
+    #[test]
+    fn test_sstable_reader_versions_span_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let entries = versioned_entries();
+        for partitioned_index in [false, true] {
+            let path = temp_dir.path().join(format!("{}.sst", partitioned_index));
+            write_entries(&path, &entries, partitioned_index);
+            let reader = SSTableReader::open(&path).unwrap();
+
+            for entry in &entries {
+                let key = &entry.key;
+                assert_eq!(
+                    reader.get(&key.user_key, key.timestamp).unwrap(),
+                    Some(entry.value.clone()),
+                );
+                let (value, timestamp, _) = reader
+                    .get_latest(&key.user_key, key.timestamp + 5)
+                    .unwrap()
+                    .unwrap();
+                assert_eq!((value, timestamp), (entry.value.clone(), key.timestamp));
+            }
+
+            // Older than every version of a key
+            let key = b"key_000025".to_vec();
+            assert_eq!(reader.get_latest(&key, 5).unwrap(), None);
+            let range: Vec<_> = reader
+                .range_iter(Some(&key), None)
+                .unwrap()
+                .map(|e| e.unwrap())
+                .collect();
+            assert_eq!(range[0].key, InternalKey::new(key, 400));
+        }
+    }
+
+    #[test]
+    fn test_sstable_cursor() {
+        let temp_dir = TempDir::new().unwrap();
+        let entries = versioned_entries();
+        for partitioned_index in [false, true] {
+            let path = temp_dir.path().join(format!("{}.sst", partitioned_index));
+            write_entries(&path, &entries, partitioned_index);
+            let reader = Arc::new(SSTableReader::open(&path).unwrap());
+            let mut cursor = SSTableCursor::new(Arc::clone(&reader), ReadOptions::default());
+            assert!(!cursor.valid());
+
+            let mut forward = Vec::new();
+            cursor.seek_to_first();
+            while cursor.valid() {
+                forward.push(cursor.entry());
+                cursor.next();
+            }
+            assert_eq!(forward, entries);
+
+            let mut backward = Vec::new();
+            cursor.seek_to_last();
+            while cursor.valid() {
+                backward.push(cursor.entry());
+                cursor.prev();
+            }
+            backward.reverse();
+            assert_eq!(backward, entries);
+            cursor.status().unwrap();
+
+            // Every version, plus positions between and around them
+            let mut targets = Vec::new();
+            for entry in &entries {
+                let key = &entry.key;
+                targets.push(key.clone());
+                targets.push(InternalKey::new(key.user_key.clone(), key.timestamp + 5));
+                let mut missing = key.user_key.clone();
+                missing.push(b'x');
+                targets.push(InternalKey::new(missing, 1));
+            }
+            targets.push(InternalKey::new(b"a".to_vec(), 1));
+            targets.push(InternalKey::new(b"z".to_vec(), 1));
+
+            for target in &targets {
+                let at_or_after = entries.iter().find(|entry| entry.key >= *target);
+                cursor.seek(target);
+                assert_eq!(cursor.valid().then(|| cursor.entry()).as_ref(), at_or_after);
+
+                let at_or_before = entries.iter().rev().find(|entry| entry.key <= *target);
+                cursor.seek_for_prev(target);
+                assert_eq!(
+                    cursor.valid().then(|| cursor.entry()).as_ref(),
+                    at_or_before
+                );
+            }
+
+            // Changing direction
+            let key = InternalKey::new(b"key_000100".to_vec(), 150);
+            cursor.seek(&key);
+            let position = entries.iter().position(|entry| entry.key >= key).unwrap();
+            for _ in 0..3 {
+                cursor.prev();
+            }
+            assert_eq!(cursor.entry(), entries[position - 3]);
+            for _ in 0..5 {
+                cursor.next();
+            }
+            assert_eq!(cursor.entry(), entries[position + 2]);
+
+            // Moving an invalid cursor does nothing
+            cursor.seek_to_first();
+            cursor.prev();
+            assert!(!cursor.valid());
+            cursor.next();
+            assert!(!cursor.valid());
+            cursor.status().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_sstable_reader_without_bloom_filter() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("no_bloom.sst");
+        write_keys(&path, 100, 0);
+
+        let reader = SSTableReader::open(&path).unwrap();
+        assert!(reader.may_contain(b"key_000050x"));
+        assert_eq!(reader.get(&b"key_000050x".to_vec(), 1).unwrap(), None);
+        assert_eq!(
+            reader.get(&b"key_000050".to_vec(), 1).unwrap(),
+            Some(b"value".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_sstable_reader_corrupted_bloom_filter() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("bad_bloom.sst");
+        write_keys(&path, 100, 10);
+
+        let mut data = std::fs::read(&path).unwrap();
+        let footer = Footer::from_bytes(&data[data.len() - FOOTER_SIZE..]).unwrap();
+        data[footer.bloom_offset as usize] ^= 0xFF;
+        std::fs::write(&path, &data).unwrap();
+
+        assert!(matches!(
+            SSTableReader::open(&path),
+            Err(Error::Corruption(_))
+        ));
+    }
+
+    /// Flips one byte of the file at `offset`
+    fn damage(path: &Path, offset: u64) {
+        let mut data = std::fs::read(path).unwrap();
+        data[offset as usize] ^= 0xFF;
+        std::fs::write(path, &data).unwrap();
+    }
+
+    #[test]
+    fn test_sstable_reader_detects_corrupted_data_block() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("bad_block.sst");
+        write_keys(&path, 100, 10);
+
+        let second_block = {
+            let reader = SSTableReader::open(&path).unwrap();
+            index_entry(&reader, 1).block_offset
+        };
+        // Damage the value of the first entry in the second block: three
+        // one-byte varint lengths, a 10-byte key, timestamp and operation
+        let value_offset = second_block + 3 + 10 + 8 + 1;
+        damage(&path, value_offset);
+
+        let reader = SSTableReader::open(&path).unwrap();
+        let key = format!("key_{:06}", 0).into_bytes();
+        assert_eq!(reader.get(&key, 1).unwrap(), Some(b"value".to_vec()));
+
+        let first_in_block = index_entry(&reader, 1).first_key;
+        let error = reader.get(&first_in_block, 1).unwrap_err();
+        assert!(matches!(error, Error::Corruption(_)));
+        let message = error.to_string();
+        assert!(message.contains(&second_block.to_string()));
+        assert!(message.contains("bad_block.sst"));
+
+        assert!(reader.iter().unwrap().any(|entry| entry.is_err()));
+
+        // A cursor stops at the damaged block and reports why
+        let mut cursor = reader.cursor();
+        cursor.seek_to_first();
+        let mut read = 0;
+        while cursor.valid() {
+            read += 1;
+            cursor.next();
+        }
+        assert!(read > 0);
+        assert!(matches!(cursor.status(), Err(Error::Corruption(_))));
+        cursor.status().unwrap();
+        cursor.seek_to_last();
+        assert_eq!(cursor.key(), format!("key_{:06}", 99).as_bytes());
+
+        // Skipping verification returns the damaged value instead
+        let unverified = ReadOptions {
+            verify_checksums: false,
+            ..Default::default()
+        };
+        let (value, _, _) = reader
+            .get_latest_with_options(&first_in_block, 1, unverified)
+            .unwrap()
+            .unwrap();
+        assert_ne!(value, b"value".to_vec());
+
+        let reader = SSTableReader::open_with_options(&path, unverified).unwrap();
+        assert!(reader.iter().unwrap().all(|entry| entry.is_ok()));
+    }
+
+    #[test]
+    fn test_sstable_reader_detects_corrupted_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("bad_index.sst");
+        write_keys(&path, 100, 10);
+
+        let footer = SSTableReader::open(&path).unwrap().info().footer;
+        damage(&path, footer.index_offset + 5);
+
+        let error = SSTableReader::open(&path).unwrap_err();
+        assert!(matches!(error, Error::Corruption(_)));
+        assert!(error.to_string().contains(&footer.index_offset.to_string()));
+    }
+
+    #[test]
+    fn test_sstable_reader_mixed_compression() {
+        let temp_dir = TempDir::new().unwrap();
+
+        // Alternate runs of repetitive and random values so some blocks
+        // compress and others fall back to raw
+        let mut state = 0x9e37_79b9_u32;
+        let entries: Vec<_> = (0..200)
+            .map(|i| {
+                let value: Vec<u8> = if (i / 20) % 2 == 0 {
+                    vec![b'a'; 64]
+                } else {
+                    (0..64)
+                        .map(|_| {
+                            state ^= state << 13;
+                            state ^= state >> 17;
+                            state ^= state << 5;
+                            state as u8
+                        })
+                        .collect()
+                };
+                (format!("key_{:06}", i).into_bytes(), value)
+            })
+            .collect();
+
+        for compression in [
+            CompressionType::None,
+            CompressionType::Lz4,
+            CompressionType::Snappy,
+        ] {
+            let path = temp_dir.path().join(format!("{:?}.sst", compression));
+            let options = SSTableWriterOptions {
+                block_size: 1024,
+                compression,
+                ..Default::default()
+            };
+            let mut writer = SSTableWriter::with_options(&path, options).unwrap();
+            for (key, value) in &entries {
+                let key = InternalKey::new(key.clone(), 1);
+                writer.add(key, value.clone(), Operation::Put).unwrap();
+            }
+            let info = writer.finish().unwrap();
+
+            if compression == CompressionType::None {
+                assert_eq!(info.data_size, info.raw_data_size);
+            } else {
+                let ratio = info.compression_ratio();
+                assert!(ratio < 0.9, "{:?} ratio {}", compression, ratio);
+                // Random blocks stay raw, so the ratio cannot approach
+                // that of the repetitive blocks alone
+                assert!(ratio > 0.3, "{:?} ratio {}", compression, ratio);
+            }
+
+            let reader = SSTableReader::open(&path).unwrap();
+            for (key, value) in &entries {
+                assert_eq!(reader.get(key, 1).unwrap().as_ref(), Some(value));
+            }
+            let read: Vec<_> = reader
+                .iter()
+                .unwrap()
+                .map(|entry| entry.unwrap().value)
+                .collect();
+            assert_eq!(read.len(), entries.len());
+        }
+    }
+
+    /// Writes a table in the original block format: an entry count, full
+    /// entries and a tag byte without the prefix-compressed flag
+    fn write_legacy_table(path: &Path, entries: &[SSTableEntry], entries_per_block: usize) {
+        fn push_checked(file: &mut Vec<u8>, block: &[u8]) {
+            file.extend_from_slice(block);
+            file.extend_from_slice(&crc32fast::hash(block).to_le_bytes());
+        }
+
+        let mut file = Vec::new();
+        let mut index = Vec::new();
+        let mut bloom = BloomFilterBuilder::new(10);
+        for chunk in entries.chunks(entries_per_block) {
+            index.push(IndexEntry::new(
+                file.len() as u64,
+                chunk[0].key.user_key.clone(),
+            ));
+            let mut block = (chunk.len() as u32).to_le_bytes().to_vec();
+            for entry in chunk {
+                bloom.add_key(&entry.key.user_key);
+                block.extend_from_slice(&(entry.key.user_key.len() as u32).to_le_bytes());
+                block.extend_from_slice(&(entry.value.len() as u32).to_le_bytes());
+                block.extend_from_slice(&entry.key.timestamp.to_le_bytes());
+                block.push(operation_byte(entry.operation));
+                block.extend_from_slice(&entry.key.user_key);
+                block.extend_from_slice(&entry.value);
+            }
+            block.push(0);
+            push_checked(&mut file, &block);
+        }
+
+        let index_offset = file.len() as u64;
+        let mut block = (index.len() as u32).to_le_bytes().to_vec();
+        for entry in &index {
+            block.extend_from_slice(&entry.block_offset.to_le_bytes());
+            block.extend_from_slice(&(entry.first_key.len() as u32).to_le_bytes());
+            block.extend_from_slice(&entry.first_key);
+        }
+        push_checked(&mut file, &block);
+        let index_length = file.len() as u64 - index_offset;
+
+        let bloom_offset = file.len() as u64;
+        let filter = bloom.finish();
+        file.extend_from_slice(&filter);
+        let footer = Footer {
+            format_version: LEGACY_FORMAT_VERSION,
+            ..Footer::new(
+                index_offset,
+                index_length,
+                bloom_offset,
+                filter.len() as u64,
+            )
+        };
+        file.extend_from_slice(&footer.to_bytes());
+        std::fs::write(path, file).unwrap();
+    }
+
+    #[test]
+    fn test_sstable_reader_reads_legacy_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let entries: Vec<_> = (0..300)
+            .flat_map(|i| {
+                let key = format!("user:{:06}", i).into_bytes();
+                let newest = if i % 10 == 0 {
+                    SSTableEntry::new(InternalKey::new(key.clone(), 20), vec![], Operation::Delete)
+                } else {
+                    SSTableEntry::new(
+                        InternalKey::new(key.clone(), 20),
+                        b"new".to_vec(),
+                        Operation::Put,
+                    )
+                };
+                [
+                    newest,
+                    SSTableEntry::new(InternalKey::new(key, 10), b"old".to_vec(), Operation::Put),
+                ]
+            })
+            .collect();
+
+        let legacy = temp_dir.path().join("legacy.sst");
+        write_legacy_table(&legacy, &entries, 25);
+        let current = temp_dir.path().join("current.sst");
+        let mut writer = SSTableWriter::new(&current).unwrap();
+        for entry in &entries {
+            writer
+                .add(entry.key.clone(), entry.value.clone(), entry.operation)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        // Shared "user:000" prefixes make the new format smaller
+        let size = |path: &Path| std::fs::metadata(path).unwrap().len();
+        assert!(size(&current) < size(&legacy));
+
+        let info = SSTableReader::open(&legacy).unwrap().info();
+        assert_eq!(info.footer.format_version, LEGACY_FORMAT_VERSION);
+        assert!(info.properties.is_none());
+
+        for path in [&legacy, &current] {
+            let reader = SSTableReader::open(path).unwrap();
+            let read: Vec<_> = reader.iter().unwrap().map(|e| e.unwrap()).collect();
+            assert_eq!(read, entries);
+
+            let key = b"user:000123".to_vec();
+            assert_eq!(reader.get(&key, 10).unwrap(), Some(b"old".to_vec()));
+            assert_eq!(reader.get(&key, 15).unwrap(), None);
+            let (value, timestamp, _) = reader.get_latest(&key, 15).unwrap().unwrap();
+            assert_eq!((value, timestamp), (b"old".to_vec(), 10));
+            let (_, _, operation) = reader
+                .get_latest(&b"user:000120".to_vec(), 30)
+                .unwrap()
+                .unwrap();
+            assert_eq!(operation, Operation::Delete);
+            assert_eq!(
+                reader.get_latest(&b"user:0001235".to_vec(), 30).unwrap(),
+                None
+            );
+
+            let (start, end) = (b"user:000100".to_vec(), b"user:000110".to_vec());
+            let range: Vec<_> = reader
+                .range_iter(Some(&start), Some(&end))
+                .unwrap()
+                .map(|e| e.unwrap())
+                .collect();
+            assert_eq!(range.as_slice(), &entries[200..220]);
+        }
+    }
+
+    #[test]
+    fn test_sstable_reader_mmap_matches_positional_reads() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("mapped.sst");
+        let options = SSTableWriterOptions {
+            block_size: 256,
+            compression: CompressionType::Lz4,
+            ..Default::default()
+        };
+        let mut writer = SSTableWriter::with_options(&path, options).unwrap();
+        for i in 0..500 {
+            let key = InternalKey::new(format!("key_{:06}", i).into_bytes(), 1);
+            writer.add(key, vec![b'v'; i % 50], Operation::Put).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let positional = SSTableReader::open(&path).unwrap();
+        let mapped = SSTableReader::open_mmap(&path, ReadOptions::default()).unwrap();
+        assert!(format!("{:?}", mapped).contains("mmap: true"));
+
+        let expected: Vec<_> = positional.iter().unwrap().map(|e| e.unwrap()).collect();
+        let actual: Vec<_> = mapped.iter().unwrap().map(|e| e.unwrap()).collect();
+        assert_eq!(actual, expected);
+
+        for i in (0..500).step_by(37) {
+            let key = format!("key_{:06}", i).into_bytes();
+            assert_eq!(mapped.get(&key, 1).unwrap(), Some(vec![b'v'; i % 50]));
+        }
+        assert_eq!(mapped.get(&b"key_999999".to_vec(), 1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_sstable_reader_mmap_rejects_damaged_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("damaged.sst");
+        write_keys(&path, 100, 10);
+
+        // Cutting out part of the data leaves a valid footer pointing past
+        // the end of the file
+        let data = std::fs::read(&path).unwrap();
+        let truncated = temp_dir.path().join("truncated.sst");
+        std::fs::write(&truncated, [&data[..100], &data[600..]].concat()).unwrap();
+        for result in [
+            SSTableReader::open(&truncated),
+            SSTableReader::open_mmap(&truncated, ReadOptions::default()),
+        ] {
+            let error = result.unwrap_err();
+            assert!(matches!(error, Error::Corruption(_)));
+            assert!(error.to_string().contains("extends past the footer"));
+        }
+
+        // A damaged data block is reported, not read through
+        let second_block = index_entry(&SSTableReader::open(&path).unwrap(), 1).block_offset;
+        damage(&path, second_block + 30);
+        let mapped = SSTableReader::open_mmap(&path, ReadOptions::default()).unwrap();
+        let first_in_block = index_entry(&mapped, 1).first_key;
+        assert!(matches!(
+            mapped.get(&first_in_block, 1),
+            Err(Error::Corruption(_))
+        ));
+    }
+
+    #[test]
+    fn test_sstable_reader_shares_block_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("first.sst");
+        let second = temp_dir.path().join("second.sst");
+        write_keys(&first, 100, 10);
+        write_keys(&second, 100, 10);
+
+        let cache = Arc::new(BlockCache::new(1024 * 1024));
+        let first = SSTableReader::open(&first)
+            .unwrap()
+            .with_block_cache(Arc::clone(&cache), 1);
+        let second = SSTableReader::open(&second)
+            .unwrap()
+            .with_block_cache(Arc::clone(&cache), 2);
+
+        let key = b"key_000042".to_vec();
+        assert!(first.get(&key, 1).unwrap().is_some());
+        assert!(first.get(&key, 1).unwrap().is_some());
+        // Same offset, different file: a separate entry
+        assert!(second.get(&key, 1).unwrap().is_some());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 2));
+
+        // A scan that skips filling leaves the cache as it was
+        let usage = cache.stats().usage;
+        let no_fill = ReadOptions {
+            fill_cache: false,
+            ..Default::default()
+        };
+        let scanned = first.iter_with_options(no_fill).unwrap().count();
+        assert_eq!(scanned, 100);
+        assert_eq!(cache.stats().usage, usage);
+
+        // A normal scan caches every block, so a second one reads none
+        assert_eq!(first.iter().unwrap().count(), 100);
+        assert!(cache.stats().usage > usage);
+        let misses = cache.stats().misses;
+        assert_eq!(first.iter().unwrap().count(), 100);
+        assert_eq!(cache.stats().misses, misses);
+    }
+
+    #[test]
+    fn test_sstable_reader_concurrent_reads() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("concurrent.sst");
+        write_keys(&path, 2000, 10);
+
+        let cache = Arc::new(BlockCache::new(16 * 1024));
+        let uncached = Arc::new(SSTableReader::open(&path).unwrap());
+        let cached = Arc::new(
+            SSTableReader::open(&path)
+                .unwrap()
+                .with_block_cache(Arc::clone(&cache), 1),
+        );
+
+        let handles: Vec<_> = (0..8)
+            .map(|thread| {
+                let reader = Arc::clone(if thread % 2 == 0 { &uncached } else { &cached });
+                std::thread::spawn(move || {
+                    // Interleave two live iterators with point lookups on
+                    // the same reader
+                    let mut forward = reader.iter().unwrap();
+                    let (start, end) = (b"key_001000".to_vec(), b"key_001500".to_vec());
+                    let mut range = reader.range_iter(Some(&start), Some(&end)).unwrap();
+                    for i in 0..500 {
+                        let n = (thread * 251 + i * 7) % 2000;
+                        let key = format!("key_{:06}", n).into_bytes();
+                        assert_eq!(reader.get(&key, 1).unwrap().unwrap(), b"value");
+                        assert!(reader.get(&b"missing".to_vec(), 1).unwrap().is_none());
+
+                        let entry = forward.next().unwrap().unwrap();
+                        assert_eq!(entry.key.user_key, format!("key_{:06}", i).into_bytes());
+                        let entry = range.next().unwrap().unwrap();
+                        let expected = format!("key_{:06}", 1000 + i).into_bytes();
+                        assert_eq!(entry.key.user_key, expected);
+                    }
+                    assert!(range.next().is_none());
+                    assert_eq!(forward.count(), 1500);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert!(cache.stats().hits > 0);
+        assert!(cache.stats().evictions > 0);
+    }
 }
@@ -0,0 +1,326 @@
+//! Bounded cache of open SSTable readers
+//!
+//! Each open reader holds a file descriptor plus its parsed footer, index
+//! and bloom filter. Keeping one per live file forever would exhaust file
+//! descriptors once the engine holds hundreds of tables, so the cache opens
+//! readers on first use and closes the least recently used ones beyond
+//! `max_open_files`. An evicted file is reopened on its next lookup.
+//!
+//! Readers are handed out as `Arc`s. Evicting one only drops the cache's
+//! reference: lookups and iterators still holding it keep reading, and the
+//! file is closed when the last of them finishes.
+
+use crate::filename;
+use crate::sstable::{BlockCache, ReadOptions, SSTableReader};
+use ferrisdb_core::Result;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Counters describing how often tables had to be opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableCacheStats {
+    /// Lookups served by an already open reader
+    pub hits: u64,
+    /// Lookups that had to open the file
+    pub opens: u64,
+    /// Readers closed to stay within capacity
+    pub evictions: u64,
+    /// Readers currently held by the cache
+    pub open_files: usize,
+    /// Maximum number of readers the cache holds
+    pub capacity: usize,
+}
+
+/// An LRU cache of open SSTable readers keyed by file number
+///
+/// # Example
+///
+/// ```no_run
+/// use ferrisdb_storage::sstable::{BlockCache, TableCache};
+/// use std::sync::Arc;
+///
+/// let block_cache = Arc::new(BlockCache::new(8 * 1024 * 1024));
+/// let tables = TableCache::new("./data", block_cache, 100);
+///
+/// let reader = tables.get(7)?;
+/// let latest = reader.get_latest(&b"key".to_vec(), u64::MAX)?;
+/// # Ok::<(), ferrisdb_core::Error>(())
+/// ```
+pub struct TableCache {
+    data_dir: PathBuf,
+    block_cache: Arc<BlockCache>,
+    capacity: usize,
+    mmap: bool,
+    state: Mutex<CacheState>,
+}
+
+/// Open readers and their recency, guarded by one lock
+///
+/// Recency is tracked the same way as in the block cache: every access
+/// stamps the entry with the next tick and `lru` orders entries by it.
+#[derive(Default)]
+struct CacheState {
+    next_tick: u64,
+    entries: HashMap<u64, CachedTable>,
+    lru: BTreeMap<u64, u64>,
+    hits: u64,
+    opens: u64,
+    evictions: u64,
+}
+
+struct CachedTable {
+    reader: Arc<SSTableReader>,
+    tick: u64,
+}
+
+impl TableCache {
+    /// Creates a cache for the tables in `data_dir` holding at most
+    /// `max_open_files` readers
+    ///
+    /// Readers it opens share `block_cache`.
+    pub fn new(
+        data_dir: impl Into<PathBuf>,
+        block_cache: Arc<BlockCache>,
+        max_open_files: usize,
+    ) -> Self {
+        Self {
+            data_dir: data_dir.into(),
+            block_cache,
+            capacity: max_open_files.max(1),
+            mmap: false,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Opens tables through memory maps when `enabled`
+    ///
+    /// See [`SSTableReader::open_mmap`].
+    pub fn with_mmap_reads(mut self, enabled: bool) -> Self {
+        self.mmap = enabled;
+        self
+    }
+
+    /// Returns the reader for table `number`, opening it if needed
+    ///
+    /// The file is opened without holding the cache lock, so a slow open
+    /// does not stall lookups of other tables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or its footer, index
+    /// or bloom filter is damaged.
+    pub fn get(&self, number: u64) -> Result<Arc<SSTableReader>> {
+        if let Some(reader) = self.state.lock().get(number) {
+            return Ok(reader);
+        }
+
+        let path = filename::sstable_file_path(&self.data_dir, number);
+        let reader = if self.mmap {
+            SSTableReader::open_mmap(path, ReadOptions::default())?
+        } else {
+            SSTableReader::open(path)?
+        };
+        let reader = Arc::new(reader.with_block_cache(Arc::clone(&self.block_cache), number));
+
+        let mut state = self.state.lock();
+        // Another thread may have opened the same table meanwhile
+        if let Some(existing) = state.get(number) {
+            return Ok(existing);
+        }
+        state.opens += 1;
+        state.insert(number, Arc::clone(&reader));
+        while state.entries.len() > self.capacity {
+            state.evict_oldest();
+        }
+        Ok(reader)
+    }
+
+    /// Drops the cached reader for table `number`, if any
+    ///
+    /// Called before a table is deleted so the cache does not keep its
+    /// file open.
+    pub fn evict(&self, number: u64) {
+        let mut state = self.state.lock();
+        if let Some(cached) = state.entries.remove(&number) {
+            state.lru.remove(&cached.tick);
+        }
+    }
+
+    /// Returns the current counters
+    pub fn stats(&self) -> TableCacheStats {
+        let state = self.state.lock();
+        TableCacheStats {
+            hits: state.hits,
+            opens: state.opens,
+            evictions: state.evictions,
+            open_files: state.entries.len(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+impl std::fmt::Debug for TableCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TableCache")
+            .field("data_dir", &self.data_dir)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl CacheState {
+    fn get(&mut self, number: u64) -> Option<Arc<SSTableReader>> {
+        let tick = self.next_tick;
+        let cached = self.entries.get_mut(&number)?;
+        self.lru.remove(&cached.tick);
+        cached.tick = tick;
+        self.lru.insert(tick, number);
+        self.next_tick += 1;
+        self.hits += 1;
+        Some(Arc::clone(&cached.reader))
+    }
+
+    fn insert(&mut self, number: u64, reader: Arc<SSTableReader>) {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.lru.insert(tick, number);
+        self.entries.insert(number, CachedTable { reader, tick });
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some((_, oldest)) = self.lru.pop_first() {
+            self.entries.remove(&oldest);
+            self.evictions += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sstable::{InternalKey, SSTableWriter};
+    use ferrisdb_core::Operation;
+    use tempfile::TempDir;
+
+    fn write_table(dir: &std::path::Path, number: u64) {
+        let path = filename::sstable_file_path(dir, number);
+        let mut writer = SSTableWriter::new(path).unwrap();
+        for i in 0..50 {
+            let key = InternalKey::new(format!("key_{:03}", i).into_bytes(), 1);
+            writer
+                .add(
+                    key,
+                    format!("value_{}", number).into_bytes(),
+                    Operation::Put,
+                )
+                .unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn cache(dir: &TempDir, tables: u64, max_open_files: usize) -> TableCache {
+        for number in 1..=tables {
+            write_table(dir.path(), number);
+        }
+        let block_cache = Arc::new(BlockCache::new(1024 * 1024));
+        TableCache::new(dir.path(), block_cache, max_open_files)
+    }
+
+    #[test]
+    fn test_opens_lazily_and_reuses_readers() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = cache(&temp_dir, 2, 10);
+        assert_eq!(cache.stats().open_files, 0);
+
+        let first = cache.get(1).unwrap();
+        let again = cache.get(1).unwrap();
+        assert!(Arc::ptr_eq(&first, &again));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.opens, stats.open_files), (1, 1, 1));
+        assert!(cache.get(99).is_err());
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_and_reopens() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = cache(&temp_dir, 4, 2);
+
+        cache.get(1).unwrap();
+        cache.get(2).unwrap();
+        // Touch table 1 so table 2 becomes the LRU
+        cache.get(1).unwrap();
+        cache.get(3).unwrap();
+
+        let stats = cache.stats();
+        assert_eq!((stats.open_files, stats.evictions), (2, 1));
+
+        // Table 1 is still open; table 2 is reopened transparently
+        let key = b"key_007".to_vec();
+        assert_eq!(
+            cache.get(1).unwrap().get(&key, 1).unwrap().unwrap(),
+            b"value_1"
+        );
+        assert_eq!(cache.stats().opens, 3);
+        assert_eq!(
+            cache.get(2).unwrap().get(&key, 1).unwrap().unwrap(),
+            b"value_2"
+        );
+        assert_eq!(cache.stats().opens, 4);
+
+        for number in 1..=4 {
+            cache.get(number).unwrap();
+            assert!(cache.stats().open_files <= 2);
+        }
+    }
+
+    #[test]
+    fn test_eviction_keeps_held_readers_usable() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = cache(&temp_dir, 3, 1);
+
+        let reader = cache.get(1).unwrap();
+        let mut iter = reader.iter().unwrap();
+        assert!(iter.next().is_some());
+
+        // Push table 1 out, then remove it from the cache explicitly
+        cache.get(2).unwrap();
+        cache.get(3).unwrap();
+        cache.evict(1);
+        assert_eq!(cache.stats().open_files, 1);
+
+        assert_eq!(iter.count(), 49);
+        let key = b"key_042".to_vec();
+        assert_eq!(reader.get(&key, 1).unwrap().unwrap(), b"value_1");
+    }
+
+    #[test]
+    fn test_concurrent_lookups() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = Arc::new(cache(&temp_dir, 8, 3));
+
+        let handles: Vec<_> = (0..4)
+            .map(|thread| {
+                let cache = Arc::clone(&cache);
+                std::thread::spawn(move || {
+                    let key = b"key_010".to_vec();
+                    for i in 0..200 {
+                        let number = (thread + i) % 8 + 1;
+                        let reader = cache.get(number).unwrap();
+                        let value = reader.get(&key, 1).unwrap().unwrap();
+                        assert_eq!(value, format!("value_{}", number).into_bytes());
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stats = cache.stats();
+        assert!(stats.open_files <= 3);
+        assert_eq!(stats.hits + stats.opens, 800);
+    }
+}
@@ -1,12 +1,18 @@
 //! SSTable writer implementation
 
+use crate::sstable::block::{BlockBuilder, PREFIX_COMPRESSED_FLAG};
+use crate::sstable::bloom::BloomFilterBuilder;
+use crate::sstable::compression::{compress, compression_tag};
 use crate::sstable::{
-    Footer, IndexEntry, InternalKey, SSTableEntry, DEFAULT_BLOCK_SIZE, MAX_ENTRY_SIZE,
+    Footer, IndexEntry, InternalKey, SSTableEntry, TableProperties, DEFAULT_BLOCK_RESTART_INTERVAL,
+    DEFAULT_BLOCK_SIZE, DEFAULT_BLOOM_BITS_PER_KEY, MAX_ENTRY_SIZE, PARTITIONED_FORMAT_VERSION,
 };
-use ferrisdb_core::{Error, Operation, Result, Value};
+use crate::StorageConfig;
+use ferrisdb_core::{CompressionType, Error, Key, Operation, Result, Timestamp, Value};
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Metadata about a written SSTable file
 #[derive(Debug, Clone)]
@@ -21,6 +27,60 @@ pub struct SSTableInfo {
     pub smallest_key: InternalKey,
     /// Largest key in the file
     pub largest_key: InternalKey,
+    /// Size of the data blocks before compression
+    pub raw_data_size: u64,
+    /// Size of the data blocks as stored, excluding block trailers
+    pub data_size: u64,
+}
+
+impl SSTableInfo {
+    /// Returns stored data size over raw data size; lower is better
+    pub fn compression_ratio(&self) -> f64 {
+        if self.raw_data_size == 0 {
+            return 1.0;
+        }
+        self.data_size as f64 / self.raw_data_size as f64
+    }
+}
+
+/// Options controlling how an SSTable is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SSTableWriterOptions {
+    /// Target size for data blocks in bytes
+    pub block_size: usize,
+    /// Entries between restart points within a data block
+    pub block_restart_interval: usize,
+    /// Bloom filter bits per user key; 0 writes an empty filter
+    pub bloom_bits_per_key: usize,
+    /// Compression for data blocks; `None` unless built from a config
+    pub compression: CompressionType,
+    /// Split the index and bloom filter into partitions of about
+    /// `block_size` bytes that readers load on demand
+    pub partitioned_index: bool,
+}
+
+impl Default for SSTableWriterOptions {
+    fn default() -> Self {
+        Self {
+            block_size: DEFAULT_BLOCK_SIZE,
+            block_restart_interval: DEFAULT_BLOCK_RESTART_INTERVAL,
+            bloom_bits_per_key: DEFAULT_BLOOM_BITS_PER_KEY,
+            compression: CompressionType::None,
+            partitioned_index: false,
+        }
+    }
+}
+
+impl From<&StorageConfig> for SSTableWriterOptions {
+    fn from(config: &StorageConfig) -> Self {
+        Self {
+            block_size: config.block_size,
+            block_restart_interval: config.block_restart_interval,
+            bloom_bits_per_key: config.bloom_filter_bits_per_key.max(0) as usize,
+            compression: config.compression,
+            partitioned_index: config.partitioned_index,
+        }
+    }
 }
 
 /// Writer for creating SSTable files
@@ -50,16 +110,42 @@ pub struct SSTableWriter {
     path: PathBuf,
     /// Current position in the file
     file_offset: u64,
-    /// Buffer for the current data block
-    current_block: Vec<SSTableEntry>,
-    /// Current block size in bytes
-    current_block_size: usize,
+    /// Builder for the current data block
+    block: BlockBuilder,
+    /// First user key of the current data block, for the index
+    block_first_key: Option<Key>,
     /// Maximum block size
     block_size: usize,
-    /// Index entries for all written blocks
+    /// Compression for data blocks
+    compression: CompressionType,
+    /// Data block bytes before compression
+    raw_data_size: u64,
+    /// Data block bytes as stored
+    data_size: u64,
+    /// Index entries for the written blocks of the current partition, or
+    /// of the whole file without partitioning
     index_entries: Vec<IndexEntry>,
+    /// Serialized size of `index_entries`
+    index_size: usize,
+    /// Bloom filter over the user keys of the current partition, or of
+    /// the whole file without partitioning
+    bloom: BloomFilterBuilder,
+    /// Bloom filter bits per key, for starting new partitions
+    bloom_bits_per_key: usize,
+    /// Whether the index and filter are partitioned
+    partitioned: bool,
+    /// Finished partitions, written once all data blocks are
+    partitions: Vec<PendingPartition>,
     /// Total number of entries written
     entry_count: usize,
+    /// Number of delete entries written
+    tombstone_count: u64,
+    /// Total size of the user keys written
+    raw_key_size: u64,
+    /// Total size of the values written
+    raw_value_size: u64,
+    /// Oldest and newest timestamps written
+    timestamp_range: Option<(Timestamp, Timestamp)>,
     /// Smallest key seen (for metadata)
     smallest_key: Option<InternalKey>,
     /// Largest key seen (for metadata)
@@ -71,7 +157,7 @@ pub struct SSTableWriter {
 }
 
 impl SSTableWriter {
-    /// Creates a new SSTable writer
+    /// Creates a new SSTable writer with default options
     ///
     /// # Arguments
     ///
@@ -81,6 +167,36 @@ impl SSTableWriter {
     ///
     /// Returns an error if the file cannot be created
     pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_options(path, SSTableWriterOptions::default())
+    }
+
+    /// Creates a new SSTable writer with a custom block size
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path where the SSTable file will be created
+    /// * `block_size` - Target size for data blocks in bytes
+    pub fn with_block_size(path: impl AsRef<Path>, block_size: usize) -> Result<Self> {
+        Self::with_options(
+            path,
+            SSTableWriterOptions {
+                block_size,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Creates a new SSTable writer with the given options
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path where the SSTable file will be created
+    /// * `options` - Block size and bloom filter settings
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created
+    pub fn with_options(path: impl AsRef<Path>, options: SSTableWriterOptions) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
         let file = File::create(&path)?;
         let writer = BufWriter::new(file);
@@ -89,11 +205,23 @@ impl SSTableWriter {
             writer,
             path,
             file_offset: 0,
-            current_block: Vec::new(),
-            current_block_size: 0,
-            block_size: DEFAULT_BLOCK_SIZE,
+            block: BlockBuilder::new(options.block_restart_interval),
+            block_first_key: None,
+            block_size: options.block_size,
+            compression: options.compression,
+            raw_data_size: 0,
+            data_size: 0,
             index_entries: Vec::new(),
+            index_size: 0,
+            bloom: BloomFilterBuilder::new(options.bloom_bits_per_key),
+            bloom_bits_per_key: options.bloom_bits_per_key,
+            partitioned: options.partitioned_index,
+            partitions: Vec::new(),
             entry_count: 0,
+            tombstone_count: 0,
+            raw_key_size: 0,
+            raw_value_size: 0,
+            timestamp_range: None,
             smallest_key: None,
             largest_key: None,
             last_key: None,
@@ -101,18 +229,6 @@ impl SSTableWriter {
         })
     }
 
-    /// Creates a new SSTable writer with a custom block size
-    ///
-    /// # Arguments
-    ///
-    /// * `path` - Path where the SSTable file will be created
-    /// * `block_size` - Target size for data blocks in bytes
-    pub fn with_block_size(path: impl AsRef<Path>, block_size: usize) -> Result<Self> {
-        let mut writer = Self::new(path)?;
-        writer.block_size = block_size;
-        Ok(writer)
-    }
-
     /// Adds a key-value pair with operation to the SSTable
     ///
     /// Keys must be added in sorted order according to InternalKey ordering
@@ -165,25 +281,48 @@ impl SSTableWriter {
             }
         }
 
-        // Create entry with the provided operation
+        // Create entry with the provided operation; its serialized size
+        // bounds its encoded size before prefix compression
         let entry = SSTableEntry::new(key.clone(), value, operation);
         let entry_size = entry.serialized_size();
 
+        let is_new_user_key = self
+            .last_key
+            .as_ref()
+            .is_none_or(|last| last.user_key != key.user_key);
+
         // Update metadata (clone where we need the key again)
+        if operation == Operation::Delete {
+            self.tombstone_count += 1;
+        }
+        self.raw_key_size += key_size as u64;
+        self.raw_value_size += value_size as u64;
+        let (min, max) = self
+            .timestamp_range
+            .get_or_insert((key.timestamp, key.timestamp));
+        *min = (*min).min(key.timestamp);
+        *max = (*max).max(key.timestamp);
         if self.smallest_key.is_none() {
             self.smallest_key = Some(key.clone());
         }
         self.largest_key = Some(key.clone());
 
         // Check if we need to flush the current block
-        if !self.current_block.is_empty() && self.current_block_size + entry_size > self.block_size
-        {
+        if !self.block.is_empty() && self.block.estimated_size() + entry_size > self.block_size {
             self.flush_block()?;
         }
 
+        // Older versions of a user key are already in the filter, unless
+        // the flush above started a new partition
+        if is_new_user_key || self.bloom.is_empty() {
+            self.bloom.add_key(&key.user_key);
+        }
+
         // Add to current block
-        self.current_block.push(entry);
-        self.current_block_size += entry_size;
+        if self.block.is_empty() {
+            self.block_first_key = Some(key.user_key.clone());
+        }
+        self.block.add(&entry.key, &entry.value, entry.operation);
         self.entry_count += 1;
 
         // Update last_key last to take ownership (no clone needed)
@@ -192,14 +331,24 @@ impl SSTableWriter {
         Ok(())
     }
 
+    /// Returns the approximate size the file would have if finished now
+    ///
+    /// Counts written blocks plus the pending block, but not the index and
+    /// footer. Used to cut output files at a target size.
+    pub fn estimated_size(&self) -> u64 {
+        self.file_offset + self.block.estimated_size() as u64
+    }
+
     /// Finishes writing the SSTable and returns metadata
     ///
     /// This method:
     /// 1. Flushes any remaining data block
-    /// 2. Writes the index block
-    /// 3. Writes the bloom filter (placeholder for now)
-    /// 4. Writes the footer
-    /// 5. Syncs the file to disk
+    /// 2. Writes the index block, or the index partitions
+    /// 3. Writes the bloom filter over all user keys, or the filter
+    ///    partitions followed by the top-level index
+    /// 4. Writes the properties block
+    /// 5. Writes the footer
+    /// 6. Syncs the file to disk
     ///
     /// After calling finish(), the writer cannot be used again.
     pub fn finish(mut self) -> Result<SSTableInfo> {
@@ -209,21 +358,63 @@ impl SSTableWriter {
             ));
         }
 
+        // Properties need a key and timestamp range, so empty tables are refused
+        let (Some(smallest_key), Some(largest_key), Some((min_timestamp, max_timestamp))) = (
+            self.smallest_key.take(),
+            self.largest_key.take(),
+            self.timestamp_range,
+        ) else {
+            return Err(Error::EmptyOperation(
+                "Cannot finish SSTable with no entries".to_string(),
+            ));
+        };
+
         // Flush any remaining block
-        if !self.current_block.is_empty() {
+        if !self.block.is_empty() {
             self.flush_block()?;
         }
 
-        // Write index block
-        let index_offset = self.file_offset;
-        let index_length = self.write_index_block()?;
+        let (index_offset, index_length, bloom_offset, bloom_length) = if self.partitioned {
+            self.seal_partition();
+            self.write_partitions()?
+        } else {
+            // Write index block
+            let index_offset = self.file_offset;
+            let block = encode_index(&self.index_entries);
+            let index_length = self.write_block(block)?;
+
+            // Write bloom filter
+            let bloom_offset = self.file_offset;
+            let block = self.bloom.finish();
+            let bloom_length = self.write_raw(&block)?;
+            (index_offset, index_length, bloom_offset, bloom_length)
+        };
 
-        // Write bloom filter (placeholder for now)
-        let bloom_offset = self.file_offset;
-        let bloom_length = self.write_bloom_filter()?;
+        // Write properties
+        let properties = TableProperties {
+            entry_count: self.entry_count as u64,
+            tombstone_count: self.tombstone_count,
+            raw_key_size: self.raw_key_size,
+            raw_value_size: self.raw_value_size,
+            min_timestamp,
+            max_timestamp,
+            smallest_key,
+            largest_key,
+            compression: self.compression,
+            filter_policy: self.bloom.policy_name(),
+            creation_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+        };
+        let properties_offset = self.file_offset;
+        let properties_length = self.write_block(properties.encode())?;
 
         // Write footer
-        let footer = Footer::new(index_offset, index_length, bloom_offset, bloom_length);
+        let mut footer = Footer::new(index_offset, index_length, bloom_offset, bloom_length)
+            .with_properties(properties_offset, properties_length);
+        if self.partitioned {
+            footer.format_version = PARTITIONED_FORMAT_VERSION;
+        }
         self.writer.write_all(&footer.to_bytes())?;
         self.file_offset += footer.to_bytes().len() as u64;
 
@@ -241,145 +432,156 @@ impl SSTableWriter {
             path: self.path,
             file_size: self.file_offset,
             entry_count: self.entry_count,
-            smallest_key: self.smallest_key.ok_or_else(|| {
-                Error::EmptyOperation("Cannot finish SSTable with no entries".to_string())
-            })?,
-            largest_key: self.largest_key.ok_or_else(|| {
-                Error::EmptyOperation("Cannot finish SSTable with no entries".to_string())
-            })?,
+            smallest_key: properties.smallest_key,
+            largest_key: properties.largest_key,
+            raw_data_size: self.raw_data_size,
+            data_size: self.data_size,
         })
     }
 
     /// Flushes the current block to disk
     fn flush_block(&mut self) -> Result<()> {
-        if self.current_block.is_empty() {
+        let Some(first_key) = self.block_first_key.take() else {
             return Ok(());
-        }
-
-        // Remember the first key for the index
-        let first_key = self.current_block[0].key.user_key.clone();
+        };
         let block_offset = self.file_offset;
 
-        // Write block header (entry count - u32 supports up to 4B entries per block)
-        let entry_count = self.current_block.len() as u32;
-        self.writer.write_all(&entry_count.to_le_bytes())?;
-        self.file_offset += 4;
-
-        // Write entries
-        for entry in &self.current_block {
-            Self::write_entry(&mut self.writer, &mut self.file_offset, entry)?;
-        }
+        // Entries and restart points
+        let block = self.block.finish();
 
-        // Calculate and write checksum (placeholder - just use 0 for now)
-        let checksum: u32 = 0; // TODO: Implement actual CRC32
-        self.writer.write_all(&checksum.to_le_bytes())?;
-        self.file_offset += 4;
+        // Store compressed only if that saves space, and tag the block so
+        // the reader knows which
+        let raw_size = block.len() as u64;
+        let (mut block, compression) = match compress(self.compression, &block)? {
+            Some(compressed) => (compressed, self.compression),
+            None => (block, CompressionType::None),
+        };
+        self.raw_data_size += raw_size;
+        self.data_size += block.len() as u64;
+        block.push(compression_tag(compression) | PREFIX_COMPRESSED_FLAG);
+        self.write_block(block)?;
 
         // Add index entry
-        self.index_entries
-            .push(IndexEntry::new(block_offset, first_key));
+        let entry = IndexEntry::new(block_offset, first_key);
+        self.index_size += entry.serialized_size();
+        self.index_entries.push(entry);
 
-        // Clear current block
-        self.current_block.clear();
-        self.current_block_size = 0;
+        if self.partitioned && self.index_size >= self.block_size {
+            self.seal_partition();
+        }
 
         Ok(())
     }
 
-    /// Writes a single entry to the current block
-    fn write_entry(
-        writer: &mut BufWriter<File>,
-        file_offset: &mut u64,
-        entry: &SSTableEntry,
-    ) -> Result<()> {
-        // Write key length (safe cast: MAX_ENTRY_SIZE is 16MB, well within u32)
-        let key_len = entry.key.user_key.len() as u32;
-        writer.write_all(&key_len.to_le_bytes())?;
-        *file_offset += 4;
-
-        // Write value length (safe cast: MAX_ENTRY_SIZE is 16MB, well within u32)
-        let value_len = entry.value.len() as u32;
-        writer.write_all(&value_len.to_le_bytes())?;
-        *file_offset += 4;
-
-        // Write timestamp
-        writer.write_all(&entry.key.timestamp.to_le_bytes())?;
-        *file_offset += 8;
-
-        // Write operation
-        let op_byte = match entry.operation {
-            Operation::Put => 0u8,
-            Operation::Delete => 1u8,
+    /// Ends the current index partition and starts a new one
+    ///
+    /// The partition's filter covers exactly the keys of its data blocks.
+    fn seal_partition(&mut self) {
+        let Some(first) = self.index_entries.first() else {
+            return;
         };
-        writer.write_all(&[op_byte])?;
-        *file_offset += 1;
-
-        // Write key
-        writer.write_all(&entry.key.user_key)?;
-        *file_offset += entry.key.user_key.len() as u64;
-
-        // Write value
-        writer.write_all(&entry.value)?;
-        *file_offset += entry.value.len() as u64;
-
-        Ok(())
+        let first_key = first.first_key.clone();
+        let bloom = std::mem::replace(
+            &mut self.bloom,
+            BloomFilterBuilder::new(self.bloom_bits_per_key),
+        );
+        self.partitions.push(PendingPartition {
+            first_key,
+            index: encode_index(&self.index_entries),
+            filter: bloom.finish(),
+            data_end: self.file_offset,
+        });
+        self.index_entries.clear();
+        self.index_size = 0;
     }
 
-    /// Writes the index block and returns its length
-    fn write_index_block(&mut self) -> Result<u64> {
-        let start_offset = self.file_offset;
-
-        // Write entry count
-        let entry_count = self.index_entries.len() as u32;
-        self.writer.write_all(&entry_count.to_le_bytes())?;
-        self.file_offset += 4;
-
-        // Write each index entry
-        for entry in &self.index_entries {
-            // Write block offset
-            self.writer.write_all(&entry.block_offset.to_le_bytes())?;
-            self.file_offset += 8;
-
-            // Write key length
-            let key_len = entry.first_key.len() as u32;
-            self.writer.write_all(&key_len.to_le_bytes())?;
-            self.file_offset += 4;
-
-            // Write key
-            self.writer.write_all(&entry.first_key)?;
-            self.file_offset += entry.first_key.len() as u64;
+    /// Writes the index partitions, the filter partitions and the
+    /// top-level index
+    ///
+    /// Returns the top-level index handle and the extent of the filter
+    /// partitions, for the footer.
+    fn write_partitions(&mut self) -> Result<(u64, u64, u64, u64)> {
+        let mut partitions = std::mem::take(&mut self.partitions);
+
+        let mut index_handles = Vec::with_capacity(partitions.len());
+        for partition in &mut partitions {
+            let offset = self.file_offset;
+            let length = self.write_block(std::mem::take(&mut partition.index))?;
+            index_handles.push((offset, length));
         }
 
-        // Write checksum (placeholder)
-        let checksum: u32 = 0; // TODO: Implement actual CRC32
-        self.writer.write_all(&checksum.to_le_bytes())?;
-        self.file_offset += 4;
+        let bloom_offset = self.file_offset;
+        let mut block = (partitions.len() as u32).to_le_bytes().to_vec();
+        for (partition, (index_offset, index_length)) in partitions.iter().zip(index_handles) {
+            let filter_offset = self.file_offset;
+            let filter_length = self.write_raw(&partition.filter)?;
+
+            block.extend_from_slice(&(partition.first_key.len() as u32).to_le_bytes());
+            block.extend_from_slice(&partition.first_key);
+            for value in [
+                index_offset,
+                index_length,
+                filter_offset,
+                filter_length,
+                partition.data_end,
+            ] {
+                block.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        let bloom_length = self.file_offset - bloom_offset;
 
-        Ok(self.file_offset - start_offset)
+        let index_offset = self.file_offset;
+        let index_length = self.write_block(block)?;
+        Ok((index_offset, index_length, bloom_offset, bloom_length))
     }
 
-    /// Writes a placeholder bloom filter and returns its length
-    fn write_bloom_filter(&mut self) -> Result<u64> {
-        let start_offset = self.file_offset;
+    /// Appends a CRC32 of `block` to it, writes it and returns its length
+    fn write_block(&mut self, mut block: Vec<u8>) -> Result<u64> {
+        let checksum = crc32fast::hash(&block);
+        block.extend_from_slice(&checksum.to_le_bytes());
+
+        self.writer.write_all(&block)?;
+        self.file_offset += block.len() as u64;
+        Ok(block.len() as u64)
+    }
 
-        // For now, just write a minimal bloom filter structure
-        // TODO: Implement actual bloom filter
+    /// Writes a block that carries its own checksum and returns its length
+    fn write_raw(&mut self, block: &[u8]) -> Result<u64> {
+        self.writer.write_all(block)?;
+        self.file_offset += block.len() as u64;
+        Ok(block.len() as u64)
+    }
+}
 
-        // Write empty bit array (just 8 bytes of zeros)
-        self.writer.write_all(&[0u8; 8])?;
-        self.file_offset += 8;
+/// An index partition and its filter, encoded and waiting to be written
+struct PendingPartition {
+    /// First user key of the partition's first data block
+    first_key: Key,
+    /// Index entries in the Index Block Format, without the checksum
+    index: Vec<u8>,
+    /// The partition's bloom filter block
+    filter: Vec<u8>,
+    /// Where the partition's last data block ends
+    data_end: u64,
+}
 
-        // Write hash count (0 for placeholder)
-        self.writer.write_all(&0u32.to_le_bytes())?;
-        self.file_offset += 4;
+/// Encodes index entries in the Index Block Format, without the checksum
+fn encode_index(entries: &[IndexEntry]) -> Vec<u8> {
+    let mut block = Vec::new();
 
-        // Write checksum (placeholder)
-        let checksum: u32 = 0;
-        self.writer.write_all(&checksum.to_le_bytes())?;
-        self.file_offset += 4;
+    // Entry count
+    let entry_count = entries.len() as u32;
+    block.extend_from_slice(&entry_count.to_le_bytes());
 
-        Ok(self.file_offset - start_offset)
+    // Each index entry: block offset, key length, key
+    for entry in entries {
+        block.extend_from_slice(&entry.block_offset.to_le_bytes());
+        let key_len = entry.first_key.len() as u32;
+        block.extend_from_slice(&key_len.to_le_bytes());
+        block.extend_from_slice(&entry.first_key);
     }
+
+    block
 }
 
 #[cfg(test)]
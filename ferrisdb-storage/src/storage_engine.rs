@@ -1,6 +1,29 @@
 //! Main storage engine implementation
 
+use crate::compaction::Compaction;
+use crate::filename::{self, SSTABLE_EXTENSION, TEMP_EXTENSION, WAL_EXTENSION};
+use crate::iterator::{
+    DBIterator, InternalIterator, IteratorOptions, LevelIterator, MergingIterator,
+};
+use crate::memtable::MemTable;
+use crate::sstable::{
+    BlockCache, BlockCacheStats, InternalKey, ReadOptions, SSTableCursor, SSTableReader,
+    SSTableWriter, SSTableWriterOptions, TableCache, TableCacheStats,
+};
+use crate::version::{FileMetaData, Version, VersionEdit, VersionSet, NUM_LEVELS};
+use crate::wal::{WALManager, WALWriterStats};
+use crate::write_batch::WriteBatch;
 use crate::StorageConfig;
+use ferrisdb_core::{Error, Key, Operation, Result, Timestamp, Value};
+use log::{error, info, warn};
+use parking_lot::{Condvar, Mutex, RwLock};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Number of merged entries between checks for shutdown and pending flushes
+const COMPACTION_CHECK_INTERVAL: usize = 1024;
 
 /// The main storage engine for FerrisDB
 ///
@@ -16,19 +39,158 @@ use crate::StorageConfig;
 /// - On-disk SSTables organized in levels
 /// - Background compaction to optimize read performance
 ///
+/// # Versioning
+///
+/// Every write is assigned a timestamp from a single monotonic counter owned
+/// by the engine. The timestamp doubles as the sequence number: it orders
+/// records in the WAL and versions keys in the MemTable and SSTables.
+///
+/// # Flushing
+///
+/// Once the active MemTable reaches `memtable_size`, the next write switches
+/// it into a queue of immutable MemTables and starts a new WAL segment. A
+/// background thread writes immutable MemTables, oldest first, to new L0
+/// SSTables and deletes the WAL segments they came from once the SSTable is
+/// durable. Writes stall while `max_immutable_memtables` are waiting to be
+/// flushed.
+///
+/// # Compaction
+///
+/// When no flush is pending, the same background thread runs leveled
+/// compactions (see [`crate::compaction`]) until every level is within its
+/// limits. Each compaction is recorded as a single version edit that
+/// swaps its input files for its outputs.
+///
+/// # Persistence
+///
+/// The set of live SSTables is tracked by a [`VersionSet`] and its MANIFEST.
+/// Every flush is recorded as a version edit, together with the last
+/// timestamp it covers and the oldest WAL segment still needed, so a
+/// reopened engine sees exactly the same files and replays only unflushed
+/// WAL segments.
+///
 /// # Example
 ///
 /// ```no_run
 /// use ferrisdb_storage::{StorageEngine, StorageConfig};
 ///
 /// let config = StorageConfig::default();
-/// let engine = StorageEngine::new(config);
+/// let engine = StorageEngine::new(config)?;
 ///
-/// // TODO: Add methods for get/put/delete operations
+/// engine.put(b"user:123".to_vec(), b"John Doe".to_vec())?;
+/// assert_eq!(engine.get(b"user:123")?, Some(b"John Doe".to_vec()));
+///
+/// engine.delete(b"user:123".to_vec())?;
+/// assert_eq!(engine.get(b"user:123")?, None);
+/// # Ok::<(), ferrisdb_core::Error>(())
 /// ```
 pub struct StorageEngine {
-    #[allow(dead_code)] // TODO: Remove when implementing engine
+    /// State shared with the background thread
+    inner: Arc<EngineInner>,
+    /// Background thread flushing immutable MemTables and compacting SSTables
+    background_thread: Option<JoinHandle<()>>,
+}
+
+/// Engine state shared between the public handle and background work
+struct EngineInner {
     config: StorageConfig,
+    /// Write path state
+    ///
+    /// The lock is held while a write is given its timestamps and its WAL
+    /// record is queued, so the order of records in the log always matches
+    /// timestamp order. It is released before the record is written, so
+    /// concurrent writers share a group commit. Writers stalled on
+    /// back-pressure wait while holding it.
+    writer: Mutex<WriterState>,
+    /// Segmented write-ahead log
+    ///
+    /// Records are queued and segments rolled over only with the writer
+    /// lock held.
+    wal: WALManager,
+    /// Newest timestamp whose write has been applied to the MemTable
+    ///
+    /// Writes are applied concurrently but published in timestamp order,
+    /// so every older write is visible too. Scans read as of this
+    /// timestamp.
+    last_timestamp: AtomicU64,
+    /// Held while advancing `last_timestamp`
+    publish: Mutex<()>,
+    /// Signalled when `last_timestamp` advances
+    published: Condvar,
+    /// MemTables and SSTables visible to readers
+    tables: RwLock<Tables>,
+    /// Live files and the MANIFEST recording them
+    ///
+    /// Also hands out file numbers. When both are needed, this lock is taken
+    /// before `tables` so a new version is installed in the order it was
+    /// logged. The WAL takes this lock to number new segments.
+    versions: Arc<Mutex<VersionSet>>,
+    /// Uncompressed data blocks shared by every SSTable reader
+    block_cache: Arc<BlockCache>,
+    /// Open readers for live SSTables, bounded by `max_open_files`
+    table_cache: Arc<TableCache>,
+    /// Compaction inputs waiting to be deleted
+    ///
+    /// A file leaves the current version before it is deleted. Older
+    /// versions and iterators still holding its `FileMetaData` may open it,
+    /// so it is only deleted once this list holds the last reference.
+    obsolete_files: Mutex<Vec<Arc<FileMetaData>>>,
+    /// Coordination state for the background thread
+    background: Mutex<BackgroundState>,
+    /// Signalled when a MemTable is queued for flushing or on shutdown
+    work_requested: Condvar,
+    /// Signalled when a flush or compaction finishes or fails
+    work_completed: Condvar,
+}
+
+/// State owned by the write path
+struct WriterState {
+    /// Next timestamp to hand out
+    next_timestamp: Timestamp,
+}
+
+/// A MemTable together with where its writes end in the WAL
+///
+/// Both fields are fixed when the MemTable is switched out. Once it and all
+/// older MemTables have been flushed, no WAL segment numbered up to
+/// `wal_number` and no write up to `last_timestamp` needs replaying.
+#[derive(Clone)]
+struct MemTableHandle {
+    memtable: Arc<MemTable>,
+    /// Newest WAL segment holding its writes
+    wal_number: u64,
+    /// Newest timestamp it can hold
+    last_timestamp: Timestamp,
+}
+
+/// The set of tables a read searches, in the order it searches them
+struct Tables {
+    /// Active MemTable receiving all writes
+    active: MemTableHandle,
+    /// MemTables waiting to be flushed, newest first
+    immutables: VecDeque<MemTableHandle>,
+    /// Live SSTables
+    version: Arc<Version>,
+}
+
+/// A job for the background thread
+enum BackgroundWork {
+    /// Flush the oldest immutable MemTable
+    Flush(MemTableHandle),
+    /// Run a compaction
+    Compaction(Compaction),
+}
+
+/// State guarded by the background lock
+#[derive(Default)]
+struct BackgroundState {
+    /// Set when the engine is dropped
+    shutting_down: bool,
+    /// First error hit by the background thread
+    ///
+    /// Once set, background work stops and later writes fail, since the
+    /// MemTable queue can no longer drain.
+    error: Option<String>,
 }
 
 impl StorageEngine {
@@ -38,7 +200,7 @@ impl StorageEngine {
     /// 1. Create necessary directories
     /// 2. Recover from existing WAL if present
     /// 3. Load existing SSTables
-    /// 4. Start background compaction threads
+    /// 4. Start the background flush and compaction thread
     ///
     /// # Errors
     ///
@@ -46,8 +208,2118 @@ impl StorageEngine {
     /// - Directory creation fails
     /// - WAL recovery fails
     /// - Corruption is detected during recovery
-    pub fn new(config: StorageConfig) -> Self {
-        // TODO: Implement full initialization
-        Self { config }
+    pub fn new(config: StorageConfig) -> Result<Self> {
+        std::fs::create_dir_all(&config.data_dir)?;
+        std::fs::create_dir_all(&config.wal_dir)?;
+
+        // A crash during a flush leaves a partially written table behind
+        for number in filename::list_file_numbers(&config.data_dir, TEMP_EXTENSION)? {
+            let path = filename::temp_file_path(&config.data_dir, number);
+            warn!("Removing unfinished file {}", path.display());
+            std::fs::remove_file(path)?;
+        }
+
+        let mut versions = VersionSet::recover(&config.data_dir)?;
+        let sstable_numbers = filename::list_file_numbers(&config.data_dir, SSTABLE_EXTENSION)?;
+        let wal_numbers = filename::list_file_numbers(&config.wal_dir, WAL_EXTENSION)?;
+        for &number in sstable_numbers.iter().chain(wal_numbers.iter()) {
+            versions.mark_file_number_used(number);
+        }
+
+        if versions.has_manifest() {
+            Self::remove_orphan_sstables(&config, &versions, &sstable_numbers)?;
+        } else if !sstable_numbers.is_empty() {
+            Self::adopt_sstables(&config, &mut versions, &sstable_numbers)?;
+        }
+
+        let version = versions.current();
+        let block_cache = Arc::new(BlockCache::new(config.block_cache_size));
+        let table_cache = TableCache::new(
+            &config.data_dir,
+            Arc::clone(&block_cache),
+            config.max_open_files,
+        )
+        .with_mmap_reads(config.use_mmap_reads);
+
+        // Numbering new segments needs the version set while the engine runs
+        let versions = Arc::new(Mutex::new(versions));
+        let mut wal = WALManager::open(
+            &config.wal_dir,
+            config.wal_sync_mode,
+            config.wal_size_limit as u64,
+            {
+                let versions = Arc::clone(&versions);
+                move || versions.lock().new_file_number()
+            },
+        )?;
+        if let Some(archive_dir) = &config.wal_archive_dir {
+            wal = wal.with_archive_dir(archive_dir);
+        }
+
+        // Segments below the log number were flushed before the last shutdown
+        wal.remove_segments_before(versions.lock().log_number())?;
+
+        // Replay the remaining WAL segments into a fresh MemTable before
+        // accepting writes. Recovery keeps everything in one MemTable; it is
+        // flushed once the engine is running. The recovered segments stay on
+        // disk until then.
+        let memtable = MemTable::new(config.memtable_size);
+        let recovered = wal.recover(config.wal_recovery_mode, |batch| {
+            EngineInner::apply(&memtable, batch)
+        })?;
+        let last_timestamp = versions.lock().last_sequence().max(recovered);
+
+        // Persist the recovered state; this also starts a fresh MANIFEST
+        versions.lock().log_and_apply(VersionEdit {
+            last_sequence: Some(last_timestamp),
+            ..Default::default()
+        })?;
+
+        let inner = Arc::new(EngineInner {
+            writer: Mutex::new(WriterState {
+                next_timestamp: last_timestamp + 1,
+            }),
+            last_timestamp: AtomicU64::new(last_timestamp),
+            publish: Mutex::new(()),
+            published: Condvar::new(),
+            tables: RwLock::new(Tables {
+                active: MemTableHandle {
+                    memtable: Arc::new(memtable),
+                    wal_number: wal.active_number(),
+                    last_timestamp,
+                },
+                immutables: VecDeque::new(),
+                version,
+            }),
+            wal,
+            versions,
+            block_cache,
+            table_cache: Arc::new(table_cache),
+            obsolete_files: Mutex::new(Vec::new()),
+            background: Mutex::new(BackgroundState::default()),
+            work_requested: Condvar::new(),
+            work_completed: Condvar::new(),
+            config,
+        });
+
+        let background_thread = {
+            let inner = Arc::clone(&inner);
+            thread::Builder::new()
+                .name("ferrisdb-background".to_string())
+                .spawn(move || inner.run_background_thread())?
+        };
+
+        Ok(Self {
+            inner,
+            background_thread: Some(background_thread),
+        })
+    }
+
+    /// Inserts or overwrites the value for a key
+    ///
+    /// The write is appended to the WAL before it is applied to the MemTable,
+    /// so once this returns the write survives a crash (subject to the
+    /// configured `wal_sync_mode`).
+    ///
+    /// Blocks while `max_immutable_memtables` MemTables are waiting to be
+    /// flushed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WAL append fails or a background flush has
+    /// failed. In that case the write has not been applied.
+    pub fn put(&self, key: Key, value: Value) -> Result<()> {
+        let mut batch = WriteBatch::new();
+        batch.put(key, value);
+        self.inner.write(batch)
+    }
+
+    /// Deletes a key
+    ///
+    /// Deletion writes a tombstone that shadows older versions of the key in
+    /// the MemTable and SSTables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WAL append fails or a background flush has
+    /// failed. In that case the delete has not been applied.
+    pub fn delete(&self, key: Key) -> Result<()> {
+        let mut batch = WriteBatch::new();
+        batch.delete(key);
+        self.inner.write(batch)
+    }
+
+    /// Applies every write in a batch atomically
+    ///
+    /// The batch gets consecutive timestamps in the order its writes were
+    /// added and is appended to the WAL as one record, so recovery replays
+    /// all of it or none of it. Readers see none of the batch until all of
+    /// it has been applied. An empty batch is a no-op.
+    ///
+    /// Blocks while `max_immutable_memtables` MemTables are waiting to be
+    /// flushed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WAL append fails or a background flush has
+    /// failed. In that case none of the batch has been applied.
+    pub fn write(&self, batch: WriteBatch) -> Result<()> {
+        self.inner.write(batch)
+    }
+
+    /// Retrieves the latest value for a key
+    ///
+    /// Checks the active MemTable, then immutable MemTables from newest to
+    /// oldest, then the SSTables whose key range covers the key, level by
+    /// level. The first version found wins; a tombstone hides any older
+    /// versions.
+    ///
+    /// # Returns
+    ///
+    /// - `Some(value)` if the key exists
+    /// - `None` if the key was never written or has been deleted
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading an SSTable fails, including
+    /// `Error::Corruption` if a block fails its checksum.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Value>> {
+        self.get_with_options(key, ReadOptions::default())
+    }
+
+    /// Retrieves the latest value for a key using the given read options
+    ///
+    /// See [`get`](Self::get).
+    pub fn get_with_options(&self, key: &[u8], options: ReadOptions) -> Result<Option<Value>> {
+        // Writes newer than this belong to a batch that is still being
+        // applied
+        let read_timestamp = self.inner.last_timestamp.load(Ordering::Acquire);
+
+        // Take a snapshot so a concurrent flush cannot move the key between
+        // tables while it is being searched. Readers are fetched under the
+        // lock too, so a compaction cannot delete a file before it is open.
+        let (memtables, sstables) = {
+            let tables = self.inner.tables.read();
+            let memtables: Vec<_> = std::iter::once(&tables.active)
+                .chain(tables.immutables.iter())
+                .map(|handle| Arc::clone(&handle.memtable))
+                .collect();
+            let sstables: Vec<_> = tables
+                .version
+                .files_for_key(key)
+                .iter()
+                .map(|file| self.inner.table_cache.get(file.number))
+                .collect::<Result<_>>()?;
+            (memtables, sstables)
+        };
+
+        for memtable in &memtables {
+            if let Some((value, operation)) = memtable.get(key, read_timestamp) {
+                return Ok(Self::visible_value(value, operation));
+            }
+        }
+
+        let key = key.to_vec();
+        for sstable in &sstables {
+            let found = sstable.get_latest_with_options(&key, read_timestamp, options)?;
+            if let Some((value, _, operation)) = found {
+                return Ok(Self::visible_value(value, operation));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Creates an iterator over every key in the database
+    ///
+    /// See [`iter_with_options`](Self::iter_with_options).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an L0 SSTable cannot be opened.
+    pub fn iter(&self) -> Result<DBIterator> {
+        self.iter_with_options(IteratorOptions::default())
+    }
+
+    /// Creates an iterator over the keys selected by `options`
+    ///
+    /// The iterator sees the database as of its creation: later writes are
+    /// hidden, and the SSTables it reads are not deleted even if a
+    /// compaction replaces them. MemTables are read in place and kept alive
+    /// after a flush. L0 tables are opened up front; each deeper level is
+    /// read one table at a time, opened through the table cache as the
+    /// iterator reaches it, so a scan stays within `max_open_files`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an L0 SSTable cannot be opened. Deeper tables
+    /// that cannot be opened are reported by [`DBIterator::status`].
+    pub fn iter_with_options(&self, options: IteratorOptions) -> Result<DBIterator> {
+        let read_timestamp = self.inner.last_timestamp.load(Ordering::Acquire);
+        let (lower, upper) = options.key_range();
+        let overlaps = |file: &&Arc<FileMetaData>| {
+            lower
+                .as_ref()
+                .is_none_or(|lower| file.largest_key.user_key >= *lower)
+                && upper
+                    .as_ref()
+                    .is_none_or(|upper| file.smallest_key.user_key < *upper)
+        };
+
+        // The version keeps its files from being deleted, so tables can be
+        // opened after the lock is released
+        let (memtables, version) = {
+            let tables = self.inner.tables.read();
+            let memtables: Vec<_> = std::iter::once(&tables.active)
+                .chain(tables.immutables.iter())
+                .map(|handle| Arc::clone(&handle.memtable))
+                .collect();
+            (memtables, Arc::clone(&tables.version))
+        };
+
+        // Newest first: MemTables, then L0 newest first, then deeper levels
+        let mut children: Vec<Box<dyn InternalIterator>> = Vec::new();
+        for memtable in &memtables {
+            children.push(Box::new(memtable.iter()));
+        }
+        for file in version.files(0).iter().filter(overlaps) {
+            let sstable = self.inner.table_cache.get(file.number)?;
+            children.push(Box::new(SSTableCursor::new(sstable, options.read_options)));
+        }
+        for level in 1..NUM_LEVELS {
+            let files: Vec<_> = version
+                .files(level)
+                .iter()
+                .filter(overlaps)
+                .cloned()
+                .collect();
+            if !files.is_empty() {
+                children.push(Box::new(LevelIterator::new(
+                    Arc::clone(&self.inner.table_cache),
+                    files,
+                    options.read_options,
+                )));
+            }
+        }
+
+        Ok(DBIterator::new(
+            MergingIterator::new(children),
+            read_timestamp,
+            &options,
+        ))
+    }
+
+    /// Flushes the active MemTable and waits until every MemTable is on disk
+    ///
+    /// Once this returns, all writes acknowledged before the call are stored
+    /// in SSTables and their WAL segments have been deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if starting a new WAL segment or the background
+    /// flush fails.
+    pub fn flush(&self) -> Result<()> {
+        {
+            let mut writer = self.inner.writer.lock();
+            let is_empty = self.inner.tables.read().active.memtable.entry_count() == 0;
+            if !is_empty {
+                self.inner.make_room_for_write(&mut writer, true)?;
+            }
+        }
+        self.inner.wait_for_flushes()
+    }
+
+    /// Returns hit, miss and usage counters for the block cache
+    pub fn block_cache_stats(&self) -> BlockCacheStats {
+        self.inner.block_cache.stats()
+    }
+
+    /// Returns open and eviction counters for the table cache
+    pub fn table_cache_stats(&self) -> TableCacheStats {
+        self.inner.table_cache.stats()
+    }
+
+    /// Returns group commit counters for the WAL
+    ///
+    /// Counts every segment written since the engine was opened. The
+    /// average number of writes committed per WAL write is
+    /// `records / groups`.
+    pub fn wal_stats(&self) -> WALWriterStats {
+        self.inner.wal.stats()
+    }
+
+    /// Returns the configuration the engine was opened with
+    pub fn config(&self) -> &StorageConfig {
+        &self.inner.config
+    }
+
+    /// Deletes SSTables that are not part of the recovered version
+    ///
+    /// These are left behind by a crash between writing a table and logging
+    /// the edit that adds it.
+    fn remove_orphan_sstables(
+        config: &StorageConfig,
+        versions: &VersionSet,
+        sstable_numbers: &[u64],
+    ) -> Result<()> {
+        let version = versions.current();
+        for &number in sstable_numbers {
+            if !version.all_files().any(|(_, file)| file.number == number) {
+                let path = filename::sstable_file_path(&config.data_dir, number);
+                warn!("Removing SSTable {} missing from MANIFEST", path.display());
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Records SSTables from a data directory that has no MANIFEST yet
+    ///
+    /// Each table is scanned once for its key range and largest timestamp,
+    /// then added to L0. L0 is searched in file number order, so reads see
+    /// the tables exactly as before.
+    fn adopt_sstables(
+        config: &StorageConfig,
+        versions: &mut VersionSet,
+        sstable_numbers: &[u64],
+    ) -> Result<()> {
+        let mut edit = VersionEdit::default();
+        let mut last_timestamp = 0;
+
+        for &number in sstable_numbers {
+            let path = filename::sstable_file_path(&config.data_dir, number);
+            let reader = SSTableReader::open(&path)?;
+            let mut range: Option<(InternalKey, InternalKey)> = None;
+            if let Some(properties) = reader.info().properties {
+                // Recorded by the writer; no need to scan the file
+                last_timestamp = last_timestamp.max(properties.max_timestamp);
+                if properties.entry_count > 0 {
+                    range = Some((properties.smallest_key, properties.largest_key));
+                }
+            } else {
+                for entry in reader.iter()? {
+                    let key = entry?.key;
+                    last_timestamp = last_timestamp.max(key.timestamp);
+                    match &mut range {
+                        Some((_, largest)) => *largest = key,
+                        None => range = Some((key.clone(), key)),
+                    }
+                }
+            }
+
+            if let Some((smallest_key, largest_key)) = range {
+                info!("Adopting {} into L0", path.display());
+                edit.add_file(
+                    0,
+                    FileMetaData {
+                        number,
+                        file_size: std::fs::metadata(&path)?.len(),
+                        smallest_key,
+                        largest_key,
+                    },
+                );
+            }
+        }
+
+        edit.last_sequence = Some(last_timestamp);
+        versions.log_and_apply(edit)
+    }
+
+    /// Maps a found version to what a reader should see
+    fn visible_value(value: Value, operation: Operation) -> Option<Value> {
+        match operation {
+            Operation::Put => Some(value),
+            Operation::Delete => None,
+        }
+    }
+}
+
+impl Drop for StorageEngine {
+    /// Stops the background thread
+    ///
+    /// MemTables still waiting to be flushed are not written out; their WAL
+    /// segments are replayed on the next open. A compaction in progress is
+    /// abandoned and its partial output removed on the next open.
+    fn drop(&mut self) {
+        self.inner.background.lock().shutting_down = true;
+        self.inner.work_requested.notify_all();
+        if let Some(handle) = self.background_thread.take() {
+            if handle.join().is_err() {
+                error!("Background thread panicked");
+            }
+        }
+    }
+}
+
+impl EngineInner {
+    /// Assigns timestamps, logs the batch and applies it to the MemTable
+    fn write(&self, mut batch: WriteBatch) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let (pending, memtable) = {
+            let mut writer = self.writer.lock();
+            self.make_room_for_write(&mut writer, false)?;
+
+            batch.set_base_timestamp(writer.next_timestamp);
+            let pending = self.wal.queue_batch(&batch)?;
+            writer.next_timestamp += batch.len() as u64;
+            (pending, Arc::clone(&self.tables.read().active.memtable))
+        };
+
+        // Writers that queued while a group was being written wait here
+        // together, and one of them writes and syncs all of their records
+        let first_timestamp = batch.base_timestamp();
+        let last_timestamp = batch.last_timestamp();
+        let result = pending.wait().and_then(|()| Self::apply(&memtable, batch));
+
+        // A failed write still gives up its timestamps, so later writes are
+        // not held back waiting for it
+        self.publish(first_timestamp, last_timestamp);
+        result
+    }
+
+    /// Makes writes up to `last` visible once every write before `first` is
+    fn publish(&self, first: Timestamp, last: Timestamp) {
+        let mut guard = self.publish.lock();
+        while self.last_timestamp.load(Ordering::Acquire) != first - 1 {
+            self.published.wait(&mut guard);
+        }
+        self.last_timestamp.store(last, Ordering::Release);
+        self.published.notify_all();
+    }
+
+    /// Blocks until every write that has been given timestamps is published
+    ///
+    /// Must be called with the writer lock held, so no new write can start.
+    fn wait_for_writes_in_flight(&self, writer: &WriterState) {
+        let mut guard = self.publish.lock();
+        while self.last_timestamp.load(Ordering::Acquire) != writer.next_timestamp - 1 {
+            self.published.wait(&mut guard);
+        }
+    }
+
+    /// Applies a logged batch to a MemTable
+    ///
+    /// A full MemTable still takes every write. It is switched out before
+    /// the next write rather than after this one, so a failed switch never
+    /// fails a logged write.
+    fn apply(memtable: &MemTable, batch: WriteBatch) -> Result<()> {
+        for entry in batch.into_entries() {
+            let result = match entry.operation {
+                Operation::Put => memtable.put(entry.key, entry.value, entry.timestamp),
+                Operation::Delete => memtable.delete(entry.key, entry.timestamp),
+            };
+            match result {
+                Ok(()) | Err(Error::MemTableFull) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Ensures the active MemTable can take another write
+    ///
+    /// If the active MemTable is full (or `force` is set), it is queued for
+    /// flushing and replaced by an empty one backed by a new WAL segment.
+    /// Blocks while the immutable queue is at `max_immutable_memtables`.
+    ///
+    /// Must be called with the writer lock held.
+    fn make_room_for_write(&self, writer: &mut WriterState, mut force: bool) -> Result<()> {
+        let max_immutables = self.config.max_immutable_memtables.max(1);
+        let mut background = self.background.lock();
+        loop {
+            if let Some(error) = &background.error {
+                return Err(Error::StorageEngine(format!(
+                    "background work failed: {}",
+                    error
+                )));
+            }
+
+            let (is_full, queued) = {
+                let tables = self.tables.read();
+                (tables.active.memtable.is_full(), tables.immutables.len())
+            };
+            if !force && !is_full {
+                return Ok(());
+            }
+            if queued >= max_immutables {
+                warn!("Stalling writes until {} queued MemTables flush", queued);
+                self.work_completed.wait(&mut background);
+                continue;
+            }
+
+            drop(background);
+            self.switch_memtable(writer)?;
+            background = self.background.lock();
+            force = false;
+        }
+    }
+
+    /// Moves the active MemTable to the immutable queue and starts a new WAL segment
+    ///
+    /// Writes still being applied to the active MemTable finish first, so
+    /// a flush never misses one.
+    fn switch_memtable(&self, writer: &mut WriterState) -> Result<()> {
+        self.wait_for_writes_in_flight(writer);
+        let full_wal_number = self.wal.active_number();
+        let wal_number = self.wal.roll_over()?;
+
+        let active = MemTableHandle {
+            memtable: Arc::new(MemTable::new(self.config.memtable_size)),
+            wal_number,
+            last_timestamp: writer.next_timestamp - 1,
+        };
+        {
+            let mut tables = self.tables.write();
+            let mut full = std::mem::replace(&mut tables.active, active);
+            full.wal_number = full_wal_number;
+            full.last_timestamp = writer.next_timestamp - 1;
+            tables.immutables.push_front(full);
+        }
+
+        let _background = self.background.lock();
+        self.work_requested.notify_one();
+        Ok(())
+    }
+
+    /// Blocks until the immutable queue is empty
+    fn wait_for_flushes(&self) -> Result<()> {
+        let mut background = self.background.lock();
+        loop {
+            if let Some(error) = &background.error {
+                return Err(Error::StorageEngine(format!(
+                    "background work failed: {}",
+                    error
+                )));
+            }
+            if self.tables.read().immutables.is_empty() {
+                return Ok(());
+            }
+            self.work_completed.wait(&mut background);
+        }
+    }
+
+    /// Body of the background thread
+    ///
+    /// Flushes immutable MemTables oldest first, and compacts while nothing
+    /// is waiting to be flushed, until shutdown or the first error.
+    fn run_background_thread(&self) {
+        let mut compact_pointers = vec![None; NUM_LEVELS];
+        loop {
+            let work = {
+                let mut background = self.background.lock();
+                loop {
+                    if background.shutting_down || background.error.is_some() {
+                        return;
+                    }
+                    if let Some(work) = self.pick_background_work(&compact_pointers) {
+                        break work;
+                    }
+                    self.work_requested.wait(&mut background);
+                }
+            };
+
+            let result = match work {
+                BackgroundWork::Flush(oldest) => self.flush_memtable(&oldest),
+                BackgroundWork::Compaction(compaction) => {
+                    if compaction.level > 0 {
+                        compact_pointers[compaction.level] =
+                            Some(compaction.key_range().1.to_vec());
+                    }
+                    self.compact(compaction)
+                }
+            };
+
+            let mut background = self.background.lock();
+            if let Err(e) = result {
+                error!("Background work failed: {}", e);
+                background.error = Some(e.to_string());
+            }
+            self.work_completed.notify_all();
+        }
+    }
+
+    /// Returns the next job for the background thread, flushes first
+    fn pick_background_work(&self, compact_pointers: &[Option<Key>]) -> Option<BackgroundWork> {
+        let tables = self.tables.read();
+        if let Some(oldest) = tables.immutables.back() {
+            return Some(BackgroundWork::Flush(oldest.clone()));
+        }
+        Compaction::pick(&tables.version, &self.config, compact_pointers)
+            .map(BackgroundWork::Compaction)
+    }
+
+    /// Flushes every queued MemTable and wakes stalled writers
+    ///
+    /// Called between chunks of a long compaction so writes do not stall
+    /// behind it.
+    fn flush_pending_memtables(&self) -> Result<()> {
+        loop {
+            let Some(oldest) = self.tables.read().immutables.back().cloned() else {
+                return Ok(());
+            };
+            self.flush_memtable(&oldest)?;
+            let _background = self.background.lock();
+            self.work_completed.notify_all();
+        }
+    }
+
+    /// Runs a compaction and installs its result
+    ///
+    /// Input entries are merged newest first. Only the newest version of
+    /// each key is written, and tombstones are dropped where
+    /// [`Compaction::is_base_level_for_key`] allows. Output is split into
+    /// files of about `target_file_size`, always between two keys so the
+    /// output level stays disjoint.
+    ///
+    /// Inputs are deleted once the edit replacing them is in the MANIFEST
+    /// and no version or iterator that could still open them is alive.
+    fn compact(&self, compaction: Compaction) -> Result<()> {
+        let output_level = compaction.output_level();
+        let mut edit = VersionEdit::default();
+        for (level, file) in compaction.input_files() {
+            edit.delete_file(level, file.number);
+        }
+
+        if compaction.is_trivial_move() {
+            let file = &compaction.inputs[0];
+            info!(
+                "Moving SSTable {} from L{} to L{}",
+                file.number, compaction.level, output_level
+            );
+            edit.add_file(output_level, FileMetaData::clone(file));
+            return self.install_compaction(edit, Vec::new());
+        }
+
+        info!(
+            "Compacting {} files from L{} with {} files from L{}",
+            compaction.inputs.len(),
+            compaction.level,
+            compaction.next_level_inputs.len(),
+            output_level
+        );
+
+        // Share the open readers; one-off compaction reads stay out of the
+        // block cache so they do not evict the working set
+        let read_options = ReadOptions {
+            fill_cache: false,
+            ..Default::default()
+        };
+        let children = compaction
+            .input_files()
+            .map(|(_, file)| {
+                let reader = self.table_cache.get(file.number)?;
+                Ok(Box::new(SSTableCursor::new(reader, read_options)) as Box<dyn InternalIterator>)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut merge = MergingIterator::new(children);
+
+        let mut outputs = Vec::new();
+        let mut output: Option<(u64, SSTableWriter)> = None;
+        let mut last_user_key: Option<Key> = None;
+
+        merge.seek_to_first();
+        for i in 0.. {
+            if i > 0 {
+                merge.next();
+            }
+            if !merge.valid() {
+                break;
+            }
+
+            if i % COMPACTION_CHECK_INTERVAL == 0 {
+                if self.background.lock().shutting_down {
+                    info!("Abandoning compaction for shutdown");
+                    return Ok(());
+                }
+                self.flush_pending_memtables()?;
+            }
+
+            // Entries arrive newest first for each key; older versions are
+            // shadowed
+            if last_user_key.as_deref() == Some(merge.key()) {
+                continue;
+            }
+            last_user_key = Some(merge.key().to_vec());
+
+            if merge.operation() == Operation::Delete
+                && compaction.is_base_level_for_key(merge.key())
+            {
+                continue;
+            }
+
+            if let Some((number, writer)) = output.take() {
+                if writer.estimated_size() >= self.config.target_file_size {
+                    outputs.push(self.finish_compaction_output(number, writer)?);
+                } else {
+                    output = Some((number, writer));
+                }
+            }
+            let (_, writer) = match output.as_mut() {
+                Some(output) => output,
+                None => {
+                    let number = self.versions.lock().new_file_number();
+                    let path = filename::temp_file_path(&self.config.data_dir, number);
+                    let writer = SSTableWriter::with_options(
+                        path,
+                        SSTableWriterOptions::from(&self.config),
+                    )?;
+                    output.insert((number, writer))
+                }
+            };
+            writer.add(
+                InternalKey::new(merge.key().to_vec(), merge.timestamp()),
+                merge.value().to_vec(),
+                merge.operation(),
+            )?;
+        }
+        merge.status()?;
+        if let Some((number, writer)) = output.take() {
+            outputs.push(self.finish_compaction_output(number, writer)?);
+        }
+        filename::sync_dir(&self.config.data_dir)?;
+
+        for file in outputs {
+            edit.add_file(output_level, file);
+        }
+        let inputs: Vec<_> = compaction
+            .input_files()
+            .map(|(_, file)| Arc::clone(file))
+            .collect();
+        // The compaction pins its inputs through them and its version
+        drop(compaction);
+        self.install_compaction(edit, inputs)
+    }
+
+    /// Finishes one compaction output and moves it to its final name
+    fn finish_compaction_output(&self, number: u64, writer: SSTableWriter) -> Result<FileMetaData> {
+        let info = writer.finish()?;
+        let path = filename::sstable_file_path(&self.config.data_dir, number);
+        std::fs::rename(&info.path, &path)?;
+
+        let file = FileMetaData {
+            number,
+            file_size: info.file_size,
+            smallest_key: info.smallest_key,
+            largest_key: info.largest_key,
+        };
+        // Open the table once so a broken output fails the compaction
+        // rather than a later read
+        self.table_cache.get(number)?;
+        Ok(file)
+    }
+
+    /// Logs a compaction's edit, installs the new version and deletes its
+    /// inputs once nothing reads them
+    fn install_compaction(&self, edit: VersionEdit, inputs: Vec<Arc<FileMetaData>>) -> Result<()> {
+        {
+            let mut versions = self.versions.lock();
+            versions.log_and_apply(edit)?;
+            self.tables.write().version = versions.current();
+        }
+
+        self.obsolete_files.lock().extend(inputs);
+        self.delete_obsolete_files();
+        Ok(())
+    }
+
+    /// Deletes the compaction inputs no version or iterator refers to
+    ///
+    /// Inputs still in use stay queued for a later flush or compaction.
+    /// They are already gone from the MANIFEST, so one left behind, or one
+    /// that fails to delete, is only wasted space until the next open
+    /// removes it.
+    fn delete_obsolete_files(&self) {
+        self.obsolete_files.lock().retain(|file| {
+            // Nothing else holds the metadata, so nothing can open the file
+            if Arc::strong_count(file) > 1 {
+                return true;
+            }
+            self.table_cache.evict(file.number);
+            let path = filename::sstable_file_path(&self.config.data_dir, file.number);
+            if let Err(e) = std::fs::remove_file(&path) {
+                warn!(
+                    "Failed to remove compacted SSTable {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+            false
+        });
+    }
+
+    /// Writes an immutable MemTable to a new L0 SSTable
+    ///
+    /// The table is written under a temporary name and renamed once it is
+    /// synced, so a crash never leaves a partial `.sst` file behind. Only
+    /// after the edit adding the table is in the MANIFEST is the MemTable
+    /// dropped from the queue and its WAL segments deleted.
+    fn flush_memtable(&self, handle: &MemTableHandle) -> Result<()> {
+        let data_dir = &self.config.data_dir;
+        let mut edit = VersionEdit {
+            log_number: Some(handle.wal_number + 1),
+            ..Default::default()
+        };
+        if handle.memtable.entry_count() > 0 {
+            let number = self.versions.lock().new_file_number();
+            let temp_path = filename::temp_file_path(data_dir, number);
+            let path = filename::sstable_file_path(data_dir, number);
+
+            let mut writer =
+                SSTableWriter::with_options(&temp_path, SSTableWriterOptions::from(&self.config))?;
+            let mut last_timestamp = 0;
+            let mut entries = handle.memtable.iter();
+            entries.seek_to_first();
+            while entries.valid() {
+                last_timestamp = last_timestamp.max(entries.timestamp());
+                writer.add(
+                    InternalKey::new(entries.key().to_vec(), entries.timestamp()),
+                    entries.value().to_vec(),
+                    entries.operation(),
+                )?;
+                entries.next();
+            }
+            let info = writer.finish()?;
+            std::fs::rename(&temp_path, &path)?;
+            filename::sync_dir(data_dir)?;
+
+            info!(
+                "Flushed {} entries ({} bytes, compression ratio {:.2}) to {}",
+                info.entry_count,
+                info.file_size,
+                info.compression_ratio(),
+                path.display()
+            );
+            edit.last_sequence = Some(last_timestamp);
+            edit.add_file(
+                0,
+                FileMetaData {
+                    number,
+                    file_size: info.file_size,
+                    smallest_key: info.smallest_key,
+                    largest_key: info.largest_key,
+                },
+            );
+            // Open the table once so a broken file fails the flush rather
+            // than a later read
+            self.table_cache.get(number)?;
+        }
+
+        {
+            let mut versions = self.versions.lock();
+            versions.log_and_apply(edit)?;
+
+            let mut tables = self.tables.write();
+            let flushed = tables.immutables.pop_back();
+            debug_assert!(flushed.is_some_and(|f| Arc::ptr_eq(&f.memtable, &handle.memtable)));
+            tables.version = versions.current();
+        }
+
+        // Older MemTables were flushed first, so every write up to this
+        // one's last is now covered by SSTables. The MANIFEST's log number
+        // already says so, and segments left behind are removed on the next
+        // open.
+        if let Err(e) = self.wal.remove_flushed(handle.last_timestamp) {
+            warn!("Failed to release flushed WAL segments: {}", e);
+        }
+        self.delete_obsolete_files();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::{WALEntry, WALReader, WALWriter};
+    use ferrisdb_core::WalRecoveryMode;
+    use tempfile::TempDir;
+
+    fn test_config(temp_dir: &TempDir) -> StorageConfig {
+        StorageConfig {
+            data_dir: temp_dir.path().join("data"),
+            wal_dir: temp_dir.path().join("wal"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_put_get_delete() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = StorageEngine::new(test_config(&temp_dir)).unwrap();
+
+        engine.put(b"key1".to_vec(), b"value1".to_vec()).unwrap();
+        engine.put(b"key2".to_vec(), b"value2".to_vec()).unwrap();
+        assert_eq!(engine.get(b"key1").unwrap(), Some(b"value1".to_vec()));
+        assert_eq!(engine.get(b"key2").unwrap(), Some(b"value2".to_vec()));
+        assert_eq!(engine.get(b"missing").unwrap(), None);
+
+        engine.put(b"key1".to_vec(), b"updated".to_vec()).unwrap();
+        assert_eq!(engine.get(b"key1").unwrap(), Some(b"updated".to_vec()));
+
+        engine.delete(b"key1".to_vec()).unwrap();
+        assert_eq!(engine.get(b"key1").unwrap(), None);
+        assert_eq!(engine.get(b"key2").unwrap(), Some(b"value2".to_vec()));
+    }
+
+    #[test]
+    fn test_writes_are_logged_to_wal() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        let engine = StorageEngine::new(config.clone()).unwrap();
+
+        engine.put(b"key1".to_vec(), b"value1".to_vec()).unwrap();
+        engine.delete(b"key1".to_vec()).unwrap();
+
+        let wal_numbers = filename::list_file_numbers(&config.wal_dir, WAL_EXTENSION).unwrap();
+        assert_eq!(wal_numbers.len(), 1);
+
+        let path = filename::wal_file_path(&config.wal_dir, wal_numbers[0]);
+        let entries = crate::wal::WALReader::new(path)
+            .unwrap()
+            .read_all()
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].operation, Operation::Put);
+        assert_eq!(entries[1].operation, Operation::Delete);
+        assert!(entries[0].timestamp < entries[1].timestamp);
+    }
+
+    #[test]
+    fn test_reads_fall_through_to_sstables() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        std::fs::create_dir_all(&config.data_dir).unwrap();
+
+        // Older table: "a" and "b"
+        let mut writer =
+            SSTableWriter::new(filename::sstable_file_path(&config.data_dir, 1)).unwrap();
+        writer
+            .add(
+                InternalKey::new(b"a".to_vec(), 1),
+                b"a1".to_vec(),
+                Operation::Put,
+            )
+            .unwrap();
+        writer
+            .add(
+                InternalKey::new(b"b".to_vec(), 2),
+                b"b1".to_vec(),
+                Operation::Put,
+            )
+            .unwrap();
+        writer.finish().unwrap();
+
+        // Newer table overwrites "a" and deletes "b"
+        let mut writer =
+            SSTableWriter::new(filename::sstable_file_path(&config.data_dir, 2)).unwrap();
+        writer
+            .add(
+                InternalKey::new(b"a".to_vec(), 3),
+                b"a2".to_vec(),
+                Operation::Put,
+            )
+            .unwrap();
+        writer
+            .add(
+                InternalKey::new(b"b".to_vec(), 4),
+                Vec::new(),
+                Operation::Delete,
+            )
+            .unwrap();
+        writer.finish().unwrap();
+
+        // Tables written before the MANIFEST existed are adopted into L0
+        let engine = StorageEngine::new(config.clone()).unwrap();
+        assert!(config.data_dir.join(filename::CURRENT_FILE).exists());
+        assert_eq!(engine.inner.tables.read().version.files(0).len(), 2);
+        assert_eq!(engine.get(b"a").unwrap(), Some(b"a2".to_vec()));
+        assert_eq!(engine.get(b"b").unwrap(), None);
+
+        // New writes get timestamps above everything on disk and win
+        engine.put(b"b".to_vec(), b"b2".to_vec()).unwrap();
+        assert_eq!(engine.get(b"b").unwrap(), Some(b"b2".to_vec()));
+        assert_eq!(engine.inner.writer.lock().next_timestamp, 6);
+    }
+
+    #[test]
+    fn test_recovery_replays_wal() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+
+        {
+            let engine = StorageEngine::new(config.clone()).unwrap();
+            engine.put(b"key1".to_vec(), b"value1".to_vec()).unwrap();
+            engine.put(b"key2".to_vec(), b"value2".to_vec()).unwrap();
+            engine.put(b"key1".to_vec(), b"updated".to_vec()).unwrap();
+            engine.delete(b"key2".to_vec()).unwrap();
+        }
+
+        let engine = StorageEngine::new(config.clone()).unwrap();
+        assert_eq!(engine.get(b"key1").unwrap(), Some(b"updated".to_vec()));
+        assert_eq!(engine.get(b"key2").unwrap(), None);
+        assert_eq!(engine.inner.writer.lock().next_timestamp, 5);
+
+        // Writes after recovery continue the timestamp sequence and survive
+        // another restart together with the recovered data
+        engine.put(b"key2".to_vec(), b"again".to_vec()).unwrap();
+        drop(engine);
+
+        let engine = StorageEngine::new(config).unwrap();
+        assert_eq!(engine.get(b"key1").unwrap(), Some(b"updated".to_vec()));
+        assert_eq!(engine.get(b"key2").unwrap(), Some(b"again".to_vec()));
+        assert_eq!(engine.inner.writer.lock().next_timestamp, 6);
+    }
+
+    #[test]
+    fn test_recovery_rejects_corrupted_wal() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+
+        {
+            let engine = StorageEngine::new(config.clone()).unwrap();
+            engine.put(b"key1".to_vec(), b"value1".to_vec()).unwrap();
+            engine.put(b"key2".to_vec(), b"value2".to_vec()).unwrap();
+        }
+
+        // Flip a byte inside the first record's payload
+        let wal_numbers = filename::list_file_numbers(&config.wal_dir, WAL_EXTENSION).unwrap();
+        let path = filename::wal_file_path(&config.wal_dir, wal_numbers[0]);
+        let mut data = std::fs::read(&path).unwrap();
+        data[12] ^= 0xFF;
+        std::fs::write(&path, data).unwrap();
+
+        let result = StorageEngine::new(config);
+        assert!(matches!(result, Err(Error::Corruption(_))));
+    }
+
+    /// Returns the path of the oldest WAL segment
+    fn first_wal_segment(config: &StorageConfig) -> std::path::PathBuf {
+        let wal_numbers = filename::list_file_numbers(&config.wal_dir, WAL_EXTENSION).unwrap();
+        filename::wal_file_path(&config.wal_dir, wal_numbers[0])
+    }
+
+    /// Returns the bytes that appending `batch` to the WAL segment at `path`
+    /// would add, leaving the segment unchanged
+    fn framed_record(path: &std::path::Path, batch: &WriteBatch) -> Vec<u8> {
+        let original = std::fs::read(path).unwrap();
+        WALWriter::new(path, ferrisdb_core::SyncMode::Normal, u64::MAX)
+            .unwrap()
+            .append_batch(batch)
+            .unwrap();
+        let data = std::fs::read(path).unwrap();
+        std::fs::write(path, &original).unwrap();
+        data[original.len()..].to_vec()
+    }
+
+    #[test]
+    fn test_recovery_truncates_torn_tail() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+
+        let valid_length = {
+            let engine = StorageEngine::new(config.clone()).unwrap();
+            engine.put(b"key1".to_vec(), b"value1".to_vec()).unwrap();
+            engine.put(b"key2".to_vec(), b"value2".to_vec()).unwrap();
+            engine.inner.wal.active_size()
+        };
+
+        // Simulate a crash halfway through a third append
+        let path = first_wal_segment(&config);
+        let entry = WALEntry::new_put(b"key3".to_vec(), b"value3".to_vec(), 3);
+        let torn = framed_record(&path, &WriteBatch::from(entry));
+        let mut data = std::fs::read(&path).unwrap();
+        data.extend_from_slice(&torn[..torn.len() / 2]);
+        std::fs::write(&path, data).unwrap();
+
+        let engine = StorageEngine::new(config.clone()).unwrap();
+        assert_eq!(engine.get(b"key2").unwrap(), Some(b"value2".to_vec()));
+        assert_eq!(engine.get(b"key3").unwrap(), None);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), valid_length);
+
+        // The same torn tail fails recovery under absolute consistency
+        drop(engine);
+        let mut data = std::fs::read(&path).unwrap();
+        data.extend_from_slice(&torn[..torn.len() / 2]);
+        std::fs::write(&path, data).unwrap();
+
+        let strict = StorageConfig {
+            wal_recovery_mode: WalRecoveryMode::AbsoluteConsistency,
+            ..config
+        };
+        assert!(matches!(
+            StorageEngine::new(strict),
+            Err(Error::Corruption(_))
+        ));
+    }
+
+    #[test]
+    fn test_recovery_point_in_time_discards_later_segments() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig {
+            wal_recovery_mode: WalRecoveryMode::PointInTimeRecovery,
+            ..test_config(&temp_dir)
+        };
+
+        // Each open starts a new segment
+        let mut record_len = 0;
+        for session in 0..2 {
+            let engine = StorageEngine::new(config.clone()).unwrap();
+            for i in 0..3 {
+                let key = format!("s{}_key{}", session, i).into_bytes();
+                engine.put(key, b"value".to_vec()).unwrap();
+                if session == 0 && i == 0 {
+                    record_len = engine.inner.wal.active_size() as usize;
+                }
+            }
+        }
+
+        // Damage the second record of the first segment
+        let path = first_wal_segment(&config);
+        let mut data = std::fs::read(&path).unwrap();
+        data[record_len + 10] ^= 0xFF;
+        std::fs::write(&path, data).unwrap();
+
+        let engine = StorageEngine::new(config.clone()).unwrap();
+        assert_eq!(engine.get(b"s0_key0").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(engine.get(b"s0_key1").unwrap(), None);
+        assert_eq!(engine.get(b"s0_key2").unwrap(), None);
+        assert_eq!(engine.get(b"s1_key0").unwrap(), None);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), record_len as u64);
+
+        // Timestamps of the discarded records are not handed out again: the
+        // MANIFEST recorded timestamp 3 when the second session opened
+        assert_eq!(engine.inner.writer.lock().next_timestamp, 4);
+
+        // The recovered state is stable across another restart
+        engine.put(b"after".to_vec(), b"value".to_vec()).unwrap();
+        drop(engine);
+        let engine = StorageEngine::new(config).unwrap();
+        assert_eq!(engine.get(b"s0_key0").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(engine.get(b"s1_key0").unwrap(), None);
+        assert_eq!(engine.get(b"after").unwrap(), Some(b"value".to_vec()));
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = Arc::new(StorageEngine::new(test_config(&temp_dir)).unwrap());
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let engine = Arc::clone(&engine);
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        let key = format!("t{}_key{}", t, i).into_bytes();
+                        let value = format!("value{}", i).into_bytes();
+                        engine.put(key, value).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        for t in 0..4 {
+            for i in 0..100 {
+                let key = format!("t{}_key{}", t, i).into_bytes();
+                let value = format!("value{}", i).into_bytes();
+                assert_eq!(engine.get(&key).unwrap(), Some(value));
+            }
+        }
+        assert_eq!(engine.inner.writer.lock().next_timestamp, 401);
+    }
+
+    #[test]
+    fn test_concurrent_writes_share_wal_groups() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig {
+            wal_sync_mode: ferrisdb_core::SyncMode::Full,
+            ..test_config(&temp_dir)
+        };
+        let engine = Arc::new(StorageEngine::new(config).unwrap());
+
+        // Block the first leader until every writer has queued its record
+        let wal_writer = engine.inner.wal.active_writer();
+        let file = wal_writer.lock_file();
+        let handles: Vec<_> = (0..8u64)
+            .map(|i| {
+                let engine = Arc::clone(&engine);
+                thread::spawn(move || {
+                    engine
+                        .put(i.to_be_bytes().to_vec(), b"value".to_vec())
+                        .unwrap()
+                })
+            })
+            .collect();
+        while engine.inner.writer.lock().next_timestamp < 9 {
+            thread::yield_now();
+        }
+        drop(file);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // The first leader wrote what was queued when it started, and one
+        // of the writers waiting behind it wrote the rest with one sync
+        let stats = engine.wal_stats();
+        assert_eq!(stats.records, 8);
+        assert!(stats.groups <= 2);
+        assert!(stats.max_group_size >= 4);
+        assert_eq!(stats.syncs, stats.groups);
+        for i in 0..8u64 {
+            assert_eq!(
+                engine.get(&i.to_be_bytes()).unwrap(),
+                Some(b"value".to_vec())
+            );
+        }
+        assert_eq!(engine.inner.last_timestamp.load(Ordering::Acquire), 8);
+    }
+
+    #[test]
+    fn test_write_batch() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        let engine = StorageEngine::new(config.clone()).unwrap();
+        engine.put(b"key2".to_vec(), b"old".to_vec()).unwrap();
+
+        let mut batch = WriteBatch::new();
+        batch.put(b"key1".to_vec(), b"value1".to_vec());
+        batch.delete(b"key2".to_vec());
+        batch.put(b"key3".to_vec(), b"first".to_vec());
+        batch.put(b"key3".to_vec(), b"second".to_vec());
+        engine.write(batch).unwrap();
+        engine.write(WriteBatch::new()).unwrap();
+
+        let check = |engine: &StorageEngine| {
+            assert_eq!(engine.get(b"key1").unwrap(), Some(b"value1".to_vec()));
+            assert_eq!(engine.get(b"key2").unwrap(), None);
+            assert_eq!(engine.get(b"key3").unwrap(), Some(b"second".to_vec()));
+        };
+        check(&engine);
+        assert_eq!(engine.inner.writer.lock().next_timestamp, 6);
+
+        // The batch is one WAL record and replays the same way
+        let mut reader = WALReader::new(first_wal_segment(&config)).unwrap();
+        assert_eq!(reader.read_batch().unwrap().unwrap().len(), 1);
+        assert_eq!(reader.read_batch().unwrap().unwrap().base_timestamp(), 2);
+        assert!(reader.read_batch().unwrap().is_none());
+
+        drop(engine);
+        let engine = StorageEngine::new(config).unwrap();
+        check(&engine);
+        assert_eq!(engine.inner.writer.lock().next_timestamp, 6);
+    }
+
+    #[test]
+    fn test_recovery_drops_torn_batch() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+
+        let valid_length = {
+            let engine = StorageEngine::new(config.clone()).unwrap();
+            engine.put(b"key0".to_vec(), b"value0".to_vec()).unwrap();
+            engine.inner.wal.active_size()
+        };
+
+        // Simulate a crash partway through appending a batch, at any byte
+        let mut batch = WriteBatch::new();
+        for i in 1..4 {
+            batch.put(format!("key{}", i).into_bytes(), b"value".to_vec());
+        }
+        batch.set_base_timestamp(2);
+        let path = first_wal_segment(&config);
+        let torn = framed_record(&path, &batch);
+        let data = std::fs::read(&path).unwrap();
+
+        for cut in [1, 12, torn.len() / 2, torn.len() - 1] {
+            let mut damaged = data.clone();
+            damaged.extend_from_slice(&torn[..cut]);
+            std::fs::write(&path, damaged).unwrap();
+
+            let engine = StorageEngine::new(config.clone()).unwrap();
+            assert_eq!(engine.get(b"key0").unwrap(), Some(b"value0".to_vec()));
+            for i in 1..4 {
+                let key = format!("key{}", i).into_bytes();
+                assert_eq!(engine.get(&key).unwrap(), None, "cut at {}", cut);
+            }
+            assert_eq!(std::fs::metadata(&path).unwrap().len(), valid_length);
+        }
+    }
+
+    #[test]
+    fn test_readers_see_whole_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = Arc::new(StorageEngine::new(test_config(&temp_dir)).unwrap());
+
+        // Every batch moves both keys to the same value
+        let writer = {
+            let engine = Arc::clone(&engine);
+            thread::spawn(move || {
+                for i in 0..500u32 {
+                    let mut batch = WriteBatch::new();
+                    batch.put(b"a".to_vec(), i.to_be_bytes().to_vec());
+                    batch.put(b"b".to_vec(), i.to_be_bytes().to_vec());
+                    engine.write(batch).unwrap();
+                }
+            })
+        };
+
+        while !writer.is_finished() {
+            let mut iter = engine.iter().unwrap();
+            iter.seek_to_first();
+            let mut values = Vec::new();
+            while iter.valid() {
+                values.push(iter.value().to_vec());
+                iter.next();
+            }
+            assert!(values.is_empty() || values == [values[0].clone(), values[0].clone()]);
+
+            // Once a point read sees part of a batch, all of it is visible
+            let a = engine.get(b"a").unwrap();
+            let b = engine.get(b"b").unwrap();
+            if let Some(a) = a {
+                assert!(b.unwrap() >= a);
+            }
+        }
+        writer.join().unwrap();
+    }
+
+    /// Config with a tiny MemTable so a handful of writes trigger a flush
+    fn small_memtable_config(temp_dir: &TempDir) -> StorageConfig {
+        StorageConfig {
+            memtable_size: 1024,
+            // Keep flushed tables in L0 so their shape is predictable
+            level0_file_num_compaction_trigger: 1000,
+            ..test_config(temp_dir)
+        }
+    }
+
+    #[test]
+    fn test_flush_writes_sstable_and_deletes_wal() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = small_memtable_config(&temp_dir);
+        let engine = StorageEngine::new(config.clone()).unwrap();
+
+        for i in 0..200 {
+            let key = format!("key{:03}", i).into_bytes();
+            engine.put(key, format!("value{}", i).into_bytes()).unwrap();
+        }
+        engine.delete(b"key007".to_vec()).unwrap();
+        engine.flush().unwrap();
+
+        let sstables = filename::list_file_numbers(&config.data_dir, SSTABLE_EXTENSION).unwrap();
+        assert!(sstables.len() > 1);
+        assert!(
+            filename::list_file_numbers(&config.data_dir, TEMP_EXTENSION)
+                .unwrap()
+                .is_empty()
+        );
+
+        // Only the segment backing the new, empty MemTable is left
+        let wal_numbers = filename::list_file_numbers(&config.wal_dir, WAL_EXTENSION).unwrap();
+        assert_eq!(wal_numbers.len(), 1);
+        assert_eq!(wal_numbers[0], engine.inner.tables.read().active.wal_number);
+
+        let check = |engine: &StorageEngine| {
+            for i in 0..200 {
+                let key = format!("key{:03}", i).into_bytes();
+                let expected = (i != 7).then(|| format!("value{}", i).into_bytes());
+                assert_eq!(engine.get(&key).unwrap(), expected);
+            }
+        };
+        check(&engine);
+
+        // The flushed data survives a restart without any WAL to replay
+        drop(engine);
+        let engine = StorageEngine::new(config).unwrap();
+        check(&engine);
+        assert_eq!(engine.inner.writer.lock().next_timestamp, 202);
+    }
+
+    #[test]
+    fn test_wal_rolls_over_full_segments() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig {
+            wal_size_limit: 256,
+            wal_archive_dir: Some(temp_dir.path().join("archive")),
+            ..test_config(&temp_dir)
+        };
+
+        let check = |engine: &StorageEngine| {
+            for i in 0..50 {
+                let key = format!("key{:03}", i).into_bytes();
+                assert_eq!(engine.get(&key).unwrap(), Some(b"value".to_vec()));
+            }
+        };
+        {
+            let engine = StorageEngine::new(config.clone()).unwrap();
+            for i in 0..50 {
+                let key = format!("key{:03}", i).into_bytes();
+                engine.put(key, b"value".to_vec()).unwrap();
+            }
+            let segments = engine.inner.wal.segments();
+            assert!(segments.len() > 5);
+            assert!(segments
+                .windows(2)
+                .all(|pair| pair[0].min_timestamp < pair[1].min_timestamp));
+
+            // WAL counters cover every segment, not just the active one
+            let stats = engine.wal_stats();
+            assert_eq!(stats.records, 50);
+            assert_eq!(stats.groups, 50);
+        }
+
+        // Every segment is replayed after a restart
+        let engine = StorageEngine::new(config.clone()).unwrap();
+        check(&engine);
+
+        // Once flushed, the segments are archived and the log reads back whole
+        engine.flush().unwrap();
+        let wal_numbers = filename::list_file_numbers(&config.wal_dir, WAL_EXTENSION).unwrap();
+        assert_eq!(wal_numbers, vec![engine.inner.wal.active_number()]);
+
+        let archive_dir = config.wal_archive_dir.as_ref().unwrap();
+        let archived = WALReader::open_dir(archive_dir, WalRecoveryMode::AbsoluteConsistency)
+            .unwrap()
+            .read_all()
+            .unwrap();
+        let timestamps: Vec<_> = archived.iter().map(|entry| entry.timestamp).collect();
+        assert_eq!(timestamps, (1..=50).collect::<Vec<_>>());
+
+        drop(engine);
+        check(&StorageEngine::new(config).unwrap());
+    }
+
+    #[test]
+    fn test_writes_stall_until_flushed() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig {
+            max_immutable_memtables: 1,
+            ..small_memtable_config(&temp_dir)
+        };
+        let engine = Arc::new(StorageEngine::new(config.clone()).unwrap());
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let engine = Arc::clone(&engine);
+                thread::spawn(move || {
+                    for i in 0..200 {
+                        let key = format!("t{}_key{}", t, i).into_bytes();
+                        engine.put(key, format!("value{}", i).into_bytes()).unwrap();
+                        assert!(engine.inner.tables.read().immutables.len() <= 1);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        drop(Arc::try_unwrap(engine).ok().unwrap());
+
+        let engine = StorageEngine::new(config).unwrap();
+        for t in 0..4 {
+            for i in 0..200 {
+                let key = format!("t{}_key{}", t, i).into_bytes();
+                assert_eq!(
+                    engine.get(&key).unwrap(),
+                    Some(format!("value{}", i).into_bytes())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_failed_flush_stops_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = small_memtable_config(&temp_dir);
+        let engine = StorageEngine::new(config.clone()).unwrap();
+
+        engine.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+        std::fs::remove_dir_all(&config.data_dir).unwrap();
+        assert!(matches!(engine.flush(), Err(Error::StorageEngine(_))));
+
+        // Data stays readable from memory and the WAL is kept
+        assert_eq!(engine.get(b"key").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(
+            filename::list_file_numbers(&config.wal_dir, WAL_EXTENSION)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // Later writes fail instead of piling up in memory
+        assert!(matches!(
+            engine.put(b"other".to_vec(), b"value".to_vec()),
+            Err(Error::StorageEngine(_))
+        ));
+    }
+
+    #[test]
+    fn test_failed_wal_cleanup_keeps_writes_going() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = small_memtable_config(&temp_dir);
+        let engine = StorageEngine::new(config.clone()).unwrap();
+        engine.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+
+        // The flushed segment cannot be removed once it is already gone
+        for number in filename::list_file_numbers(&config.wal_dir, WAL_EXTENSION).unwrap() {
+            std::fs::remove_file(filename::wal_file_path(&config.wal_dir, number)).unwrap();
+        }
+        engine.flush().unwrap();
+
+        engine.put(b"other".to_vec(), b"value".to_vec()).unwrap();
+        assert_eq!(engine.get(b"key").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(engine.get(b"other").unwrap(), Some(b"value".to_vec()));
+    }
+
+    /// Returns `(level, number, smallest, largest)` for every live file
+    fn lsm_shape(engine: &StorageEngine) -> Vec<(usize, u64, InternalKey, InternalKey)> {
+        let version = engine.inner.tables.read().version.clone();
+        version
+            .all_files()
+            .map(|(level, file)| {
+                (
+                    level,
+                    file.number,
+                    file.smallest_key.clone(),
+                    file.largest_key.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reopen_restores_lsm_shape() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = small_memtable_config(&temp_dir);
+
+        let shape = {
+            let engine = StorageEngine::new(config.clone()).unwrap();
+            for i in 0..100 {
+                let key = format!("key{:03}", i).into_bytes();
+                engine.put(key, b"value".to_vec()).unwrap();
+            }
+            engine.flush().unwrap();
+            lsm_shape(&engine)
+        };
+        assert!(shape.len() > 1);
+
+        let engine = StorageEngine::new(config.clone()).unwrap();
+        assert_eq!(lsm_shape(&engine), shape);
+        assert_eq!(engine.inner.writer.lock().next_timestamp, 101);
+
+        // Each open starts a fresh MANIFEST and removes the old one
+        let manifests: Vec<_> = std::fs::read_dir(&config.data_dir)
+            .unwrap()
+            .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+            .filter(|name| filename::parse_manifest_name(name).is_some())
+            .collect();
+        assert_eq!(manifests.len(), 1);
+        let current =
+            std::fs::read_to_string(config.data_dir.join(filename::CURRENT_FILE)).unwrap();
+        assert_eq!(current.trim_end(), manifests[0]);
+    }
+
+    #[test]
+    fn test_reopen_removes_orphans_and_flushed_wal() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = small_memtable_config(&temp_dir);
+
+        let (shape, log_number) = {
+            let engine = StorageEngine::new(config.clone()).unwrap();
+            for i in 0..50 {
+                let key = format!("key{:03}", i).into_bytes();
+                engine.put(key, b"value".to_vec()).unwrap();
+            }
+            engine.flush().unwrap();
+            let log_number = engine.inner.versions.lock().log_number();
+            (lsm_shape(&engine), log_number)
+        };
+
+        // A table written just before a crash, never added to the MANIFEST
+        let (_, number, _, _) = &shape[0];
+        let orphan = filename::sstable_file_path(&config.data_dir, 1000);
+        std::fs::copy(
+            filename::sstable_file_path(&config.data_dir, *number),
+            &orphan,
+        )
+        .unwrap();
+
+        // A WAL segment whose deletion was interrupted after its flush
+        let stale = filename::wal_file_path(&config.wal_dir, log_number - 1);
+        let writer = WALWriter::new(&stale, config.wal_sync_mode, 1024).unwrap();
+        writer
+            .append(&WALEntry::new_put(b"key000".to_vec(), b"stale".to_vec(), 1))
+            .unwrap();
+        drop(writer);
+
+        let engine = StorageEngine::new(config).unwrap();
+        assert_eq!(lsm_shape(&engine), shape);
+        assert!(!orphan.exists());
+        assert!(!stale.exists());
+        assert_eq!(engine.get(b"key000").unwrap(), Some(b"value".to_vec()));
+    }
+
+    fn compaction_config(temp_dir: &TempDir) -> StorageConfig {
+        StorageConfig {
+            memtable_size: 1024,
+            level0_file_num_compaction_trigger: 2,
+            max_bytes_for_level_base: 64 * 1024,
+            target_file_size: 1024,
+            ..test_config(temp_dir)
+        }
+    }
+
+    /// Waits until nothing is left to flush or compact
+    fn wait_for_compactions(engine: &StorageEngine) {
+        let inner = &engine.inner;
+        let pointers = vec![None; NUM_LEVELS];
+        for _ in 0..1000 {
+            {
+                let tables = inner.tables.read();
+                if tables.immutables.is_empty()
+                    && Compaction::pick(&tables.version, &inner.config, &pointers).is_none()
+                {
+                    return;
+                }
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("compactions did not finish");
+    }
+
+    /// Returns every entry stored in the live SSTables
+    fn sstable_entries(engine: &StorageEngine) -> Vec<crate::sstable::SSTableEntry> {
+        let version = engine.inner.tables.read().version.clone();
+        let mut entries = Vec::new();
+        for (_, file) in version.all_files() {
+            let path = filename::sstable_file_path(&engine.config().data_dir, file.number);
+            let reader = SSTableReader::open(path).unwrap();
+            for entry in reader.iter().unwrap() {
+                entries.push(entry.unwrap());
+            }
+        }
+        entries
+    }
+
+    #[test]
+    fn test_compaction_merges_level0_into_level1() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = compaction_config(&temp_dir);
+
+        let shape = {
+            let engine = StorageEngine::new(config.clone()).unwrap();
+            for round in 0..3 {
+                for i in 0..100 {
+                    let key = format!("key{:03}", i).into_bytes();
+                    engine
+                        .put(key, format!("value{}-{}", i, round).into_bytes())
+                        .unwrap();
+                }
+            }
+            for i in (0..100).step_by(10) {
+                engine.delete(format!("key{:03}", i).into_bytes()).unwrap();
+            }
+            engine.flush().unwrap();
+            wait_for_compactions(&engine);
+
+            let version = engine.inner.tables.read().version.clone();
+            assert!(version.files(0).len() < 2);
+            let level1 = version.files(1);
+            assert!(level1.len() > 1);
+            for pair in level1.windows(2) {
+                assert!(pair[0].largest_key.user_key < pair[1].smallest_key.user_key);
+            }
+
+            // Replaced inputs are gone from disk
+            let mut live: Vec<u64> = version.all_files().map(|(_, file)| file.number).collect();
+            live.sort_unstable();
+            assert_eq!(
+                filename::list_file_numbers(&config.data_dir, SSTABLE_EXTENSION).unwrap(),
+                live
+            );
+
+            // Only the newest version of each key survives
+            assert_eq!(
+                sstable_entries(&engine)
+                    .iter()
+                    .filter(|entry| entry.key.user_key == b"key005")
+                    .count(),
+                1
+            );
+            lsm_shape(&engine)
+        };
+
+        let engine = StorageEngine::new(config).unwrap();
+        assert_eq!(lsm_shape(&engine), shape);
+        for i in 0..100 {
+            let key = format!("key{:03}", i).into_bytes();
+            let expected = (i % 10 != 0).then(|| format!("value{}-2", i).into_bytes());
+            assert_eq!(engine.get(&key).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_compaction_drops_obsolete_tombstones() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig {
+            level0_file_num_compaction_trigger: 1,
+            ..compaction_config(&temp_dir)
+        };
+        let engine = StorageEngine::new(config).unwrap();
+
+        for i in 0..100 {
+            let key = format!("key{:03}", i).into_bytes();
+            engine.put(key, b"value".to_vec()).unwrap();
+        }
+        engine.flush().unwrap();
+        for i in 0..50 {
+            engine.delete(format!("key{:03}", i).into_bytes()).unwrap();
+        }
+        engine.flush().unwrap();
+        wait_for_compactions(&engine);
+
+        // Nothing lies below L1, so the tombstones and the puts they shadow
+        // are both gone
+        let version = engine.inner.tables.read().version.clone();
+        assert!(version.files(0).is_empty());
+        let entries = sstable_entries(&engine);
+        assert_eq!(entries.len(), 50);
+        assert!(entries
+            .iter()
+            .all(|entry| entry.operation == Operation::Put
+                && entry.key.user_key >= b"key050".to_vec()));
+        assert_eq!(engine.get(b"key010").unwrap(), None);
+        assert_eq!(engine.get(b"key060").unwrap(), Some(b"value".to_vec()));
+    }
+
+    #[test]
+    fn test_reads_use_block_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = StorageEngine::new(test_config(&temp_dir)).unwrap();
+        for i in 0..100 {
+            let key = format!("key{:03}", i).into_bytes();
+            engine.put(key, b"value".to_vec()).unwrap();
+        }
+        engine.flush().unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(engine.get(b"key050").unwrap(), Some(b"value".to_vec()));
+        }
+        let stats = engine.block_cache_stats();
+        assert_eq!((stats.hits, stats.misses), (2, 1));
+        assert!(stats.usage > 0);
+        assert_eq!(stats.capacity, engine.config().block_cache_size);
+    }
+
+    #[test]
+    fn test_table_cache_bounds_open_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig {
+            max_open_files: 2,
+            ..small_memtable_config(&temp_dir)
+        };
+        let engine = StorageEngine::new(config).unwrap();
+        for i in 0..200 {
+            let key = format!("key{:03}", i).into_bytes();
+            engine.put(key, vec![b'v'; 32]).unwrap();
+        }
+        engine.flush().unwrap();
+        assert!(lsm_shape(&engine).len() > 4);
+
+        // Every table is searched and reopened as needed, never holding
+        // more than two at once
+        for _ in 0..2 {
+            for i in 0..200 {
+                let key = format!("key{:03}", i).into_bytes();
+                assert_eq!(engine.get(&key).unwrap(), Some(vec![b'v'; 32]));
+                assert!(engine.table_cache_stats().open_files <= 2);
+            }
+        }
+        let stats = engine.table_cache_stats();
+        assert!(stats.evictions > 0);
+        assert_eq!(stats.capacity, 2);
+    }
+
+    #[test]
+    fn test_mmap_reads() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig {
+            use_mmap_reads: true,
+            ..small_memtable_config(&temp_dir)
+        };
+        let engine = StorageEngine::new(config.clone()).unwrap();
+        for i in 0..100 {
+            let key = format!("key{:03}", i).into_bytes();
+            engine.put(key, format!("value{}", i).into_bytes()).unwrap();
+        }
+        engine.delete(b"key042".to_vec()).unwrap();
+        engine.flush().unwrap();
+        drop(engine);
+
+        let engine = StorageEngine::new(config).unwrap();
+        assert_eq!(engine.get(b"key007").unwrap(), Some(b"value7".to_vec()));
+        assert_eq!(engine.get(b"key042").unwrap(), None);
+        assert_eq!(engine.get(b"key099").unwrap(), Some(b"value99".to_vec()));
+    }
+
+    #[test]
+    fn test_partitioned_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig {
+            partitioned_index: true,
+            block_size: 128,
+            ..small_memtable_config(&temp_dir)
+        };
+        let engine = StorageEngine::new(config.clone()).unwrap();
+        for i in 0..500 {
+            let key = format!("key{:03}", i).into_bytes();
+            engine.put(key, format!("value{}", i).into_bytes()).unwrap();
+        }
+        engine.delete(b"key042".to_vec()).unwrap();
+        engine.flush().unwrap();
+        drop(engine);
+
+        for entry in std::fs::read_dir(&config.data_dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path
+                .extension()
+                .is_some_and(|ext| ext == filename::SSTABLE_EXTENSION)
+            {
+                let info = SSTableReader::open(&path).unwrap().info();
+                assert_eq!(
+                    info.footer.format_version,
+                    crate::sstable::PARTITIONED_FORMAT_VERSION
+                );
+            }
+        }
+
+        let engine = StorageEngine::new(config).unwrap();
+        for i in (0..500).filter(|&i| i != 42) {
+            let key = format!("key{:03}", i).into_bytes();
+            assert_eq!(
+                engine.get(&key).unwrap(),
+                Some(format!("value{}", i).into_bytes())
+            );
+        }
+        assert_eq!(engine.get(b"key042").unwrap(), None);
+        assert_eq!(engine.get(b"key500").unwrap(), None);
+    }
+
+    /// Collects every key-value pair an iterator returns, in both directions
+    fn scan_both_ways(engine: &StorageEngine, options: IteratorOptions) -> Vec<(Key, Value)> {
+        let mut iter = engine.iter_with_options(options).unwrap();
+        let mut forward = Vec::new();
+        iter.seek_to_first();
+        while iter.valid() {
+            forward.push((iter.key().to_vec(), iter.value().to_vec()));
+            iter.next();
+        }
+        iter.status().unwrap();
+
+        let mut backward = Vec::new();
+        iter.seek_to_last();
+        while iter.valid() {
+            backward.push((iter.key().to_vec(), iter.value().to_vec()));
+            iter.prev();
+        }
+        iter.status().unwrap();
+        backward.reverse();
+        assert_eq!(forward, backward);
+        forward
+    }
+
+    #[test]
+    fn test_iterator_merges_all_tables() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = StorageEngine::new(compaction_config(&temp_dir)).unwrap();
+        let mut model = std::collections::BTreeMap::new();
+        for round in 0..4 {
+            for i in (0..150).filter(|i| i % (round + 2) != 0) {
+                let key = format!("key{:03}", i).into_bytes();
+                let value = format!("value{}-{}", i, round).into_bytes();
+                engine.put(key.clone(), value.clone()).unwrap();
+                model.insert(key, value);
+            }
+            for i in (0..150).step_by(7 + round) {
+                let key = format!("key{:03}", i).into_bytes();
+                engine.delete(key.clone()).unwrap();
+                model.remove(&key);
+            }
+        }
+        // Leave entries in the MemTable as well as in several levels
+        wait_for_compactions(&engine);
+        engine.put(b"key000".to_vec(), b"fresh".to_vec()).unwrap();
+        model.insert(b"key000".to_vec(), b"fresh".to_vec());
+        assert!(lsm_shape(&engine).len() > 1);
+
+        let expected: Vec<_> = model.clone().into_iter().collect();
+        assert_eq!(
+            scan_both_ways(&engine, IteratorOptions::default()),
+            expected
+        );
+
+        let options = IteratorOptions {
+            lower_bound: Some(b"key040".to_vec()),
+            upper_bound: Some(b"key100".to_vec()),
+            ..Default::default()
+        };
+        let expected: Vec<_> = model
+            .range(b"key040".to_vec()..b"key100".to_vec())
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        assert_eq!(scan_both_ways(&engine, options), expected);
+
+        let options = IteratorOptions {
+            prefix: Some(b"key12".to_vec()),
+            ..Default::default()
+        };
+        let expected: Vec<_> = model
+            .iter()
+            .filter(|(k, _)| k.starts_with(b"key12"))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        assert_eq!(scan_both_ways(&engine, options), expected);
+    }
+
+    #[test]
+    fn test_iterator_stays_within_max_open_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig {
+            max_open_files: 2,
+            ..compaction_config(&temp_dir)
+        };
+        let engine = StorageEngine::new(config).unwrap();
+        for round in 0..3 {
+            for i in 0..200 {
+                let key = format!("key{:03}", i).into_bytes();
+                engine
+                    .put(key, format!("value{}-{}", i, round).into_bytes())
+                    .unwrap();
+            }
+        }
+        engine.flush().unwrap();
+        wait_for_compactions(&engine);
+        let shape = lsm_shape(&engine);
+        assert!(shape.iter().filter(|(level, ..)| *level > 0).count() > 4);
+
+        // Each level holds one table open at a time, so the scan reopens
+        // tables as it moves instead of holding every one
+        let mut iter = engine.iter().unwrap();
+        let mut count = 0;
+        iter.seek_to_first();
+        while iter.valid() {
+            let key = format!("key{:03}", count).into_bytes();
+            assert_eq!(iter.key(), key.as_slice());
+            assert_eq!(iter.value(), format!("value{}-2", count).as_bytes());
+            assert!(engine.table_cache_stats().open_files <= 2);
+            count += 1;
+            iter.next();
+        }
+        iter.status().unwrap();
+        assert_eq!(count, 200);
+
+        iter.seek_to_last();
+        while iter.valid() {
+            count -= 1;
+            assert!(engine.table_cache_stats().open_files <= 2);
+            iter.prev();
+        }
+        iter.status().unwrap();
+        assert_eq!(count, 0);
+        assert!(engine.table_cache_stats().evictions > 0);
+    }
+
+    #[test]
+    fn test_iterator_keeps_compacted_tables() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = compaction_config(&temp_dir);
+        let engine = StorageEngine::new(config.clone()).unwrap();
+        let write_round = |round: usize| {
+            for i in 0..200 {
+                let key = format!("key{:03}", i).into_bytes();
+                engine
+                    .put(key, format!("value{}-{}", i, round).into_bytes())
+                    .unwrap();
+            }
+            engine.flush().unwrap();
+            wait_for_compactions(&engine);
+        };
+        write_round(0);
+        let before = lsm_shape(&engine);
+        let mut iter = engine.iter().unwrap();
+
+        // Rewriting every key replaces the tables the iterator has not
+        // opened yet
+        write_round(1);
+        write_round(2);
+        let live: Vec<u64> = lsm_shape(&engine).iter().map(|f| f.1).collect();
+        assert!(before.iter().any(|f| f.0 > 0 && !live.contains(&f.1)));
+
+        let mut count = 0;
+        iter.seek_to_first();
+        while iter.valid() {
+            assert_eq!(iter.value(), format!("value{}-0", count).as_bytes());
+            count += 1;
+            iter.next();
+        }
+        iter.status().unwrap();
+        assert_eq!(count, 200);
+
+        // Once the iterator is gone the next flush deletes what it held
+        drop(iter);
+        engine.put(b"other".to_vec(), b"value".to_vec()).unwrap();
+        engine.flush().unwrap();
+        wait_for_compactions(&engine);
+        let mut live: Vec<u64> = lsm_shape(&engine).iter().map(|f| f.1).collect();
+        live.sort_unstable();
+        assert_eq!(
+            filename::list_file_numbers(&config.data_dir, SSTABLE_EXTENSION).unwrap(),
+            live
+        );
+    }
+
+    #[test]
+    fn test_iterator_reads_a_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = StorageEngine::new(compaction_config(&temp_dir)).unwrap();
+        for i in 0..100 {
+            let key = format!("key{:03}", i).into_bytes();
+            engine.put(key, b"old".to_vec()).unwrap();
+        }
+        engine.flush().unwrap();
+
+        let mut iter = engine.iter().unwrap();
+
+        // Overwrite everything and let compaction replace the files the
+        // iterator is reading
+        for i in 0..100 {
+            let key = format!("key{:03}", i).into_bytes();
+            engine.put(key, b"new".to_vec()).unwrap();
+        }
+        engine.delete(b"key050".to_vec()).unwrap();
+        engine.put(b"key100".to_vec(), b"new".to_vec()).unwrap();
+        engine.flush().unwrap();
+        wait_for_compactions(&engine);
+
+        let mut count = 0;
+        iter.seek_to_first();
+        while iter.valid() {
+            assert_eq!(iter.value(), b"old");
+            count += 1;
+            iter.next();
+        }
+        iter.status().unwrap();
+        assert_eq!(count, 100);
+
+        let mut iter = engine.iter().unwrap();
+        iter.seek(b"key050");
+        assert_eq!((iter.key(), iter.value()), (&b"key051"[..], &b"new"[..]));
+        iter.seek_to_last();
+        assert_eq!(iter.key(), b"key100");
     }
 }
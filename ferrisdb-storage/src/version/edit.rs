@@ -0,0 +1,230 @@
+use super::FileMetaData;
+use crate::sstable::InternalKey;
+use bytes::{Buf, BufMut, BytesMut};
+use ferrisdb_core::{Error, Result, Timestamp};
+
+/// Tags identifying each field of an encoded edit
+const TAG_LOG_NUMBER: u8 = 1;
+const TAG_NEXT_FILE_NUMBER: u8 = 2;
+const TAG_LAST_SEQUENCE: u8 = 3;
+const TAG_DELETED_FILE: u8 = 4;
+const TAG_NEW_FILE: u8 = 5;
+
+/// A change from one [`Version`](super::Version) to the next
+///
+/// Edits are appended to the MANIFEST and replayed in order on open. Every
+/// field is optional, so an edit only carries what changed.
+///
+/// # Binary Format
+///
+/// An edit is a sequence of tagged fields. Integers are little-endian and
+/// keys are length-prefixed:
+///
+/// ```text
+/// LogNumber       : tag(1) | number(8)
+/// NextFileNumber  : tag(2) | number(8)
+/// LastSequence    : tag(3) | timestamp(8)
+/// DeletedFile     : tag(4) | level(4) | number(8)
+/// NewFile         : tag(5) | level(4) | number(8) | file_size(8)
+///                 | smallest_key | largest_key
+/// Key             : key_len(4) | user_key | timestamp(8)
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionEdit {
+    /// WAL segments numbered below this hold only flushed writes
+    pub log_number: Option<u64>,
+    /// Next unused file number
+    pub next_file_number: Option<u64>,
+    /// Largest timestamp assigned to a write
+    pub last_sequence: Option<Timestamp>,
+    /// Files removed from the version as `(level, file number)`
+    pub deleted_files: Vec<(usize, u64)>,
+    /// Files added to the version as `(level, metadata)`
+    pub new_files: Vec<(usize, FileMetaData)>,
+}
+
+impl VersionEdit {
+    /// Records a new file at `level`
+    pub fn add_file(&mut self, level: usize, file: FileMetaData) {
+        self.new_files.push((level, file));
+    }
+
+    /// Records the removal of file `number` from `level`
+    pub fn delete_file(&mut self, level: usize, number: u64) {
+        self.deleted_files.push((level, number));
+    }
+
+    /// Encodes the edit into its binary format
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+
+        if let Some(number) = self.log_number {
+            buf.put_u8(TAG_LOG_NUMBER);
+            buf.put_u64_le(number);
+        }
+        if let Some(number) = self.next_file_number {
+            buf.put_u8(TAG_NEXT_FILE_NUMBER);
+            buf.put_u64_le(number);
+        }
+        if let Some(sequence) = self.last_sequence {
+            buf.put_u8(TAG_LAST_SEQUENCE);
+            buf.put_u64_le(sequence);
+        }
+        for &(level, number) in &self.deleted_files {
+            buf.put_u8(TAG_DELETED_FILE);
+            buf.put_u32_le(level as u32);
+            buf.put_u64_le(number);
+        }
+        for (level, file) in &self.new_files {
+            buf.put_u8(TAG_NEW_FILE);
+            buf.put_u32_le(*level as u32);
+            buf.put_u64_le(file.number);
+            buf.put_u64_le(file.file_size);
+            put_key(&mut buf, &file.smallest_key);
+            put_key(&mut buf, &file.largest_key);
+        }
+
+        buf.to_vec()
+    }
+
+    /// Decodes an edit from its binary format
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Corruption` if the data is truncated or contains an
+    /// unknown tag.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut cursor = data;
+        let mut edit = Self::default();
+
+        while cursor.has_remaining() {
+            match cursor.get_u8() {
+                TAG_LOG_NUMBER => edit.log_number = Some(get_u64(&mut cursor)?),
+                TAG_NEXT_FILE_NUMBER => edit.next_file_number = Some(get_u64(&mut cursor)?),
+                TAG_LAST_SEQUENCE => edit.last_sequence = Some(get_u64(&mut cursor)?),
+                TAG_DELETED_FILE => {
+                    let level = get_level(&mut cursor)?;
+                    let number = get_u64(&mut cursor)?;
+                    edit.deleted_files.push((level, number));
+                }
+                TAG_NEW_FILE => {
+                    let level = get_level(&mut cursor)?;
+                    let file = FileMetaData {
+                        number: get_u64(&mut cursor)?,
+                        file_size: get_u64(&mut cursor)?,
+                        smallest_key: get_key(&mut cursor)?,
+                        largest_key: get_key(&mut cursor)?,
+                    };
+                    edit.new_files.push((level, file));
+                }
+                tag => {
+                    return Err(Error::Corruption(format!(
+                        "Unknown version edit tag {}",
+                        tag
+                    )))
+                }
+            }
+        }
+
+        Ok(edit)
+    }
+}
+
+fn put_key(buf: &mut BytesMut, key: &InternalKey) {
+    buf.put_u32_le(key.user_key.len() as u32);
+    buf.put_slice(&key.user_key);
+    buf.put_u64_le(key.timestamp);
+}
+
+fn truncated() -> Error {
+    Error::Corruption("Version edit truncated".to_string())
+}
+
+fn get_u64(cursor: &mut &[u8]) -> Result<u64> {
+    if cursor.remaining() < 8 {
+        return Err(truncated());
+    }
+    Ok(cursor.get_u64_le())
+}
+
+fn get_level(cursor: &mut &[u8]) -> Result<usize> {
+    if cursor.remaining() < 4 {
+        return Err(truncated());
+    }
+    let level = cursor.get_u32_le() as usize;
+    if level >= super::NUM_LEVELS {
+        return Err(Error::Corruption(format!(
+            "Version edit level {} out of range",
+            level
+        )));
+    }
+    Ok(level)
+}
+
+fn get_key(cursor: &mut &[u8]) -> Result<InternalKey> {
+    if cursor.remaining() < 4 {
+        return Err(truncated());
+    }
+    let key_len = cursor.get_u32_le() as usize;
+    if cursor.remaining() < key_len {
+        return Err(truncated());
+    }
+    let user_key = cursor[..key_len].to_vec();
+    cursor.advance(key_len);
+    let timestamp = get_u64(cursor)?;
+    Ok(InternalKey::new(user_key, timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_file(number: u64, smallest: &[u8], largest: &[u8]) -> FileMetaData {
+        FileMetaData {
+            number,
+            file_size: 4096,
+            smallest_key: InternalKey::new(smallest.to_vec(), 10),
+            largest_key: InternalKey::new(largest.to_vec(), 3),
+        }
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let mut edit = VersionEdit {
+            log_number: Some(7),
+            next_file_number: Some(12),
+            last_sequence: Some(345),
+            ..Default::default()
+        };
+        edit.delete_file(0, 4);
+        edit.delete_file(1, 5);
+        edit.add_file(1, test_file(11, b"apple", b"mango"));
+        edit.add_file(2, test_file(10, b"", b"zebra"));
+
+        let decoded = VersionEdit::decode(&edit.encode()).unwrap();
+        assert_eq!(decoded, edit);
+
+        assert_eq!(
+            VersionEdit::decode(&VersionEdit::default().encode()).unwrap(),
+            VersionEdit::default()
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_data() {
+        let mut edit = VersionEdit::default();
+        edit.add_file(0, test_file(3, b"a", b"b"));
+        let encoded = edit.encode();
+
+        for len in 1..encoded.len() {
+            assert!(matches!(
+                VersionEdit::decode(&encoded[..len]),
+                Err(Error::Corruption(_))
+            ));
+        }
+        assert!(matches!(
+            VersionEdit::decode(&[99]),
+            Err(Error::Corruption(_))
+        ));
+    }
+}
@@ -0,0 +1,184 @@
+use super::VersionEdit;
+use crc32fast::Hasher;
+use ferrisdb_core::{Error, Result};
+use log::warn;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Size of the length and checksum header in front of each record
+const HEADER_SIZE: usize = 8;
+
+/// Appends version edits to a MANIFEST file
+///
+/// Each edit is framed like a WAL record:
+///
+/// ```text
+/// +------------+------------+-----------------+
+/// | Length(4B) | CRC32(4B)  | VersionEdit     |
+/// +------------+------------+-----------------+
+/// ```
+///
+/// The length covers only the edit and the checksum is computed over it.
+pub struct ManifestWriter {
+    writer: BufWriter<File>,
+}
+
+impl ManifestWriter {
+    /// Creates a new, empty MANIFEST at `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file already exists or cannot be created.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    /// Appends an edit and syncs it to disk
+    pub fn append(&mut self, edit: &VersionEdit) -> Result<()> {
+        let payload = edit.encode();
+        let mut hasher = Hasher::new();
+        hasher.update(&payload);
+
+        self.writer
+            .write_all(&(payload.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&hasher.finalize().to_le_bytes())?;
+        self.writer.write_all(&payload)?;
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+        Ok(())
+    }
+}
+
+/// Reads every edit from a MANIFEST
+///
+/// A crash in the middle of an append leaves a torn or corrupted final
+/// record. Such a tail is dropped with a warning, since the edit it held
+/// never took effect. Damage before the last record means committed edits
+/// are lost and is reported as corruption.
+///
+/// # Errors
+///
+/// Returns `Error::Corruption` for damage before the final record.
+pub fn read_manifest(path: impl AsRef<Path>) -> Result<Vec<VersionEdit>> {
+    let path = path.as_ref();
+    let data = std::fs::read(path)?;
+    let mut edits = Vec::new();
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let remaining = data.len() - offset;
+        if remaining < HEADER_SIZE {
+            warn!(
+                "Dropping torn record at offset {} in {}",
+                offset,
+                path.display()
+            );
+            break;
+        }
+
+        let header = &data[offset..offset + HEADER_SIZE];
+        let length = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if length > remaining - HEADER_SIZE {
+            warn!(
+                "Dropping torn record at offset {} in {}",
+                offset,
+                path.display()
+            );
+            break;
+        }
+
+        let end = offset + HEADER_SIZE + length;
+        let payload = &data[offset + HEADER_SIZE..end];
+        let mut hasher = Hasher::new();
+        hasher.update(payload);
+        if hasher.finalize() != checksum {
+            if end == data.len() {
+                warn!(
+                    "Dropping corrupted final record at offset {} in {}",
+                    offset,
+                    path.display()
+                );
+                break;
+            }
+            return Err(Error::Corruption(format!(
+                "MANIFEST checksum mismatch at offset {} in {}",
+                offset,
+                path.display()
+            )));
+        }
+
+        edits.push(VersionEdit::decode(payload)?);
+        offset = end;
+    }
+
+    Ok(edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_edits(path: &Path, count: u64) -> Vec<VersionEdit> {
+        let mut writer = ManifestWriter::create(path).unwrap();
+        (0..count)
+            .map(|i| {
+                let edit = VersionEdit {
+                    next_file_number: Some(i + 2),
+                    last_sequence: Some(i * 10),
+                    ..Default::default()
+                };
+                writer.append(&edit).unwrap();
+                edit
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_read_written_edits() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("MANIFEST-000001");
+        let edits = write_edits(&path, 3);
+
+        assert_eq!(read_manifest(&path).unwrap(), edits);
+        assert!(ManifestWriter::create(&path).is_err());
+    }
+
+    #[test]
+    fn test_torn_tail_is_dropped() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("MANIFEST-000001");
+        let edits = write_edits(&path, 3);
+        let data = std::fs::read(&path).unwrap();
+        let record_len = data.len() / 3;
+
+        // Cut anywhere inside the last record
+        for cut in 2 * record_len + 1..data.len() {
+            std::fs::write(&path, &data[..cut]).unwrap();
+            assert_eq!(read_manifest(&path).unwrap(), edits[..2]);
+        }
+
+        // Garbage in the final record
+        let mut damaged = data.clone();
+        *damaged.last_mut().unwrap() ^= 0xFF;
+        std::fs::write(&path, &damaged).unwrap();
+        assert_eq!(read_manifest(&path).unwrap(), edits[..2]);
+    }
+
+    #[test]
+    fn test_corruption_before_tail_is_an_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("MANIFEST-000001");
+        write_edits(&path, 3);
+
+        let mut data = std::fs::read(&path).unwrap();
+        data[HEADER_SIZE] ^= 0xFF;
+        std::fs::write(&path, &data).unwrap();
+        assert!(matches!(read_manifest(&path), Err(Error::Corruption(_))));
+    }
+}
@@ -0,0 +1,242 @@
+//! Tracking of the live SSTables across restarts
+//!
+//! A [`Version`] describes the shape of the LSM tree at a point in time:
+//! which SSTable files are live, the level each belongs to and the range of
+//! keys it covers. Changes are described by [`VersionEdit`]s, which the
+//! [`VersionSet`] appends to a MANIFEST log before applying them:
+//!
+//! ```text
+//! data_dir/CURRENT          ← Name of the live MANIFEST
+//! data_dir/MANIFEST-000004  ← Snapshot of the tree, then one edit per change
+//! ```
+//!
+//! On open, the edits in the MANIFEST named by CURRENT are replayed to
+//! rebuild the last version, so the engine reopens with exactly the same
+//! files in the same levels. The first edit after open starts a fresh
+//! MANIFEST holding a snapshot, and CURRENT is switched to it atomically.
+
+mod edit;
+mod manifest;
+mod set;
+
+pub use edit::VersionEdit;
+pub use set::VersionSet;
+
+use crate::sstable::InternalKey;
+use ferrisdb_core::{Error, Result};
+use std::sync::Arc;
+
+/// Number of levels in the LSM tree
+pub const NUM_LEVELS: usize = 7;
+
+/// Metadata about a live SSTable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMetaData {
+    /// File number, shared with WAL segments and other SSTables
+    pub number: u64,
+    /// Size of the file in bytes
+    pub file_size: u64,
+    /// First key in the file
+    pub smallest_key: InternalKey,
+    /// Last key in the file
+    pub largest_key: InternalKey,
+}
+
+impl FileMetaData {
+    /// Returns true if `user_key` falls within the file's key range
+    pub fn contains(&self, user_key: &[u8]) -> bool {
+        self.smallest_key.user_key.as_slice() <= user_key
+            && user_key <= self.largest_key.user_key.as_slice()
+    }
+
+    /// Returns true if the file's key range intersects `[smallest, largest]`
+    pub fn overlaps(&self, smallest: &[u8], largest: &[u8]) -> bool {
+        self.smallest_key.user_key.as_slice() <= largest
+            && smallest <= self.largest_key.user_key.as_slice()
+    }
+}
+
+/// An immutable snapshot of the live SSTables, grouped by level
+///
+/// Files in L0 may overlap and are ordered newest first. Files in every
+/// other level cover disjoint key ranges and are ordered by key.
+#[derive(Debug, Clone)]
+pub struct Version {
+    levels: Vec<Vec<Arc<FileMetaData>>>,
+}
+
+impl Version {
+    /// Creates a version with no files
+    pub fn new() -> Self {
+        Self {
+            levels: vec![Vec::new(); NUM_LEVELS],
+        }
+    }
+
+    /// Returns the files in `level`
+    pub fn files(&self, level: usize) -> &[Arc<FileMetaData>] {
+        &self.levels[level]
+    }
+
+    /// Returns the total size of the files in `level` in bytes
+    pub fn level_size(&self, level: usize) -> u64 {
+        self.levels[level].iter().map(|file| file.file_size).sum()
+    }
+
+    /// Returns the files in `level` whose key range intersects `[smallest, largest]`
+    pub fn overlapping_files(
+        &self,
+        level: usize,
+        smallest: &[u8],
+        largest: &[u8],
+    ) -> Vec<Arc<FileMetaData>> {
+        self.levels[level]
+            .iter()
+            .filter(|file| file.overlaps(smallest, largest))
+            .cloned()
+            .collect()
+    }
+
+    /// Iterates over every file as `(level, file)`
+    pub fn all_files(&self) -> impl Iterator<Item = (usize, &Arc<FileMetaData>)> {
+        self.levels
+            .iter()
+            .enumerate()
+            .flat_map(|(level, files)| files.iter().map(move |file| (level, file)))
+    }
+
+    /// Returns the files that may contain `user_key`, in the order to search them
+    ///
+    /// Every overlapping L0 file is returned newest first, followed by at
+    /// most one file from each deeper level.
+    pub fn files_for_key(&self, user_key: &[u8]) -> Vec<Arc<FileMetaData>> {
+        let mut files: Vec<_> = self.levels[0]
+            .iter()
+            .filter(|file| file.contains(user_key))
+            .cloned()
+            .collect();
+
+        for level in &self.levels[1..] {
+            let index =
+                level.partition_point(|file| file.largest_key.user_key.as_slice() < user_key);
+            if let Some(file) = level.get(index).filter(|file| file.contains(user_key)) {
+                files.push(Arc::clone(file));
+            }
+        }
+        files
+    }
+
+    /// Returns the version that results from applying `edit`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Corruption` if the edit deletes a file that is not
+    /// part of this version or names a level that does not exist.
+    pub fn apply(&self, edit: &VersionEdit) -> Result<Version> {
+        let mut levels = self.levels.clone();
+
+        for &(level, number) in &edit.deleted_files {
+            let files = levels.get_mut(level).ok_or_else(|| bad_level(level))?;
+            let position = files
+                .iter()
+                .position(|file| file.number == number)
+                .ok_or_else(|| {
+                    Error::Corruption(format!("Deleted file {} is not in level {}", number, level))
+                })?;
+            files.remove(position);
+        }
+
+        for (level, file) in &edit.new_files {
+            let files = levels.get_mut(*level).ok_or_else(|| bad_level(*level))?;
+            files.push(Arc::new(file.clone()));
+        }
+
+        levels[0].sort_by_key(|file| std::cmp::Reverse(file.number));
+        for files in &mut levels[1..] {
+            files.sort_by(|a, b| a.smallest_key.cmp(&b.smallest_key));
+        }
+
+        Ok(Version { levels })
+    }
+}
+
+impl Default for Version {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn bad_level(level: usize) -> Error {
+    Error::Corruption(format!("Level {} out of range", level))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_file(number: u64, smallest: &[u8], largest: &[u8]) -> FileMetaData {
+        FileMetaData {
+            number,
+            file_size: 1024,
+            smallest_key: InternalKey::new(smallest.to_vec(), 2),
+            largest_key: InternalKey::new(largest.to_vec(), 1),
+        }
+    }
+
+    fn numbers(files: &[Arc<FileMetaData>]) -> Vec<u64> {
+        files.iter().map(|file| file.number).collect()
+    }
+
+    #[test]
+    fn test_apply_orders_levels() {
+        let mut edit = VersionEdit::default();
+        edit.add_file(0, test_file(3, b"a", b"z"));
+        edit.add_file(0, test_file(7, b"c", b"f"));
+        edit.add_file(1, test_file(5, b"m", b"p"));
+        edit.add_file(1, test_file(4, b"a", b"k"));
+        let version = Version::new().apply(&edit).unwrap();
+
+        assert_eq!(numbers(version.files(0)), vec![7, 3]);
+        assert_eq!(numbers(version.files(1)), vec![4, 5]);
+        assert_eq!(version.all_files().count(), 4);
+
+        let mut edit = VersionEdit::default();
+        edit.delete_file(0, 3);
+        edit.add_file(1, test_file(8, b"q", b"z"));
+        let version = version.apply(&edit).unwrap();
+        assert_eq!(numbers(version.files(0)), vec![7]);
+        assert_eq!(numbers(version.files(1)), vec![4, 5, 8]);
+
+        // Deleting a file that is not there means the MANIFEST is damaged
+        let mut edit = VersionEdit::default();
+        edit.delete_file(2, 4);
+        assert!(matches!(version.apply(&edit), Err(Error::Corruption(_))));
+    }
+
+    #[test]
+    fn test_files_for_key() {
+        let mut edit = VersionEdit::default();
+        edit.add_file(0, test_file(10, b"d", b"h"));
+        edit.add_file(0, test_file(11, b"a", b"e"));
+        edit.add_file(1, test_file(5, b"a", b"c"));
+        edit.add_file(1, test_file(6, b"d", b"k"));
+        edit.add_file(2, test_file(2, b"f", b"z"));
+        let version = Version::new().apply(&edit).unwrap();
+
+        assert_eq!(numbers(&version.files_for_key(b"e")), vec![11, 10, 6]);
+        assert_eq!(numbers(&version.files_for_key(b"g")), vec![10, 6, 2]);
+        assert_eq!(numbers(&version.files_for_key(b"b")), vec![11, 5]);
+        assert!(version.files_for_key(b"0").is_empty());
+
+        assert_eq!(
+            numbers(&version.overlapping_files(0, b"e", b"f")),
+            vec![11, 10]
+        );
+        assert_eq!(
+            numbers(&version.overlapping_files(1, b"c", b"d")),
+            vec![5, 6]
+        );
+        assert!(version.overlapping_files(2, b"a", b"e").is_empty());
+        assert_eq!(version.level_size(1), 2048);
+    }
+}
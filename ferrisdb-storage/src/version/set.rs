@@ -0,0 +1,361 @@
+use super::manifest::{read_manifest, ManifestWriter};
+use super::{FileMetaData, Version, VersionEdit};
+use crate::filename::{self, CURRENT_FILE};
+use ferrisdb_core::{Error, Result, Timestamp};
+use log::{info, warn};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The current [`Version`] plus the MANIFEST that makes it durable
+///
+/// Besides the live files, the version set tracks the counters that must
+/// survive a restart: the next file number, the last timestamp assigned to
+/// a write and the oldest WAL segment that still holds unflushed writes.
+///
+/// # Example
+///
+/// ```no_run
+/// use ferrisdb_storage::version::{VersionEdit, VersionSet};
+///
+/// let mut versions = VersionSet::recover("./data")?;
+/// let number = versions.new_file_number();
+///
+/// let edit = VersionEdit {
+///     log_number: Some(number),
+///     ..Default::default()
+/// };
+/// versions.log_and_apply(edit)?;
+/// # Ok::<(), ferrisdb_core::Error>(())
+/// ```
+pub struct VersionSet {
+    /// Directory holding CURRENT and the MANIFEST files
+    dir: PathBuf,
+    /// The latest version
+    current: Arc<Version>,
+    /// Next unused file number
+    next_file_number: u64,
+    /// Largest timestamp recorded so far
+    last_sequence: Timestamp,
+    /// WAL segments numbered below this hold only flushed writes
+    log_number: u64,
+    /// Whether CURRENT existed when the set was recovered
+    has_manifest: bool,
+    /// The MANIFEST new edits are appended to
+    ///
+    /// `None` until the first edit after open, which writes a fresh MANIFEST
+    /// rather than appending to the one that was recovered.
+    manifest: Option<ManifestWriter>,
+}
+
+impl VersionSet {
+    /// Loads the version recorded in `dir`
+    ///
+    /// Replays the MANIFEST named by CURRENT. A directory without CURRENT is
+    /// a new database and yields an empty version.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if CURRENT or the MANIFEST cannot be read, or
+    /// `Error::Corruption` if either is damaged beyond a torn final record.
+    pub fn recover(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let mut versions = Self {
+            dir,
+            current: Arc::new(Version::new()),
+            next_file_number: 1,
+            last_sequence: 0,
+            log_number: 0,
+            has_manifest: false,
+            manifest: None,
+        };
+
+        let current = match std::fs::read_to_string(versions.dir.join(CURRENT_FILE)) {
+            Ok(current) => current,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(versions),
+            Err(e) => return Err(e.into()),
+        };
+        let name = current.trim_end();
+        let manifest_number = filename::parse_manifest_name(name).ok_or_else(|| {
+            Error::Corruption(format!("CURRENT names an invalid MANIFEST: {:?}", name))
+        })?;
+
+        let mut version = Version::new();
+        for edit in read_manifest(versions.dir.join(name))? {
+            version = version.apply(&edit)?;
+            versions.absorb_counters(&edit);
+        }
+        info!(
+            "Recovered {} with {} live files",
+            name,
+            version.all_files().count()
+        );
+
+        versions.current = Arc::new(version);
+        versions.mark_file_number_used(manifest_number);
+        versions.has_manifest = true;
+        Ok(versions)
+    }
+
+    /// Returns true if the set was recovered from an existing MANIFEST
+    pub fn has_manifest(&self) -> bool {
+        self.has_manifest
+    }
+
+    /// Returns the latest version
+    pub fn current(&self) -> Arc<Version> {
+        Arc::clone(&self.current)
+    }
+
+    /// Allocates a new file number
+    pub fn new_file_number(&mut self) -> u64 {
+        let number = self.next_file_number;
+        self.next_file_number += 1;
+        number
+    }
+
+    /// Ensures `number` is never handed out by [`new_file_number`](Self::new_file_number)
+    ///
+    /// Used for files created without an edit, such as WAL segments, which
+    /// the MANIFEST may not know about.
+    pub fn mark_file_number_used(&mut self, number: u64) {
+        self.next_file_number = self.next_file_number.max(number + 1);
+    }
+
+    /// Returns the largest timestamp recorded in an edit
+    pub fn last_sequence(&self) -> Timestamp {
+        self.last_sequence
+    }
+
+    /// Returns the number of the oldest WAL segment that may hold unflushed writes
+    pub fn log_number(&self) -> u64 {
+        self.log_number
+    }
+
+    /// Durably records `edit` and makes the resulting version current
+    ///
+    /// The edit is stamped with the next file number before it is written.
+    /// The first call after open writes the whole resulting state into a new
+    /// MANIFEST, points CURRENT at it and removes older MANIFESTs, so a
+    /// MANIFEST never grows across restarts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the edit does not apply to the current version or
+    /// cannot be written. The current version is unchanged in that case.
+    pub fn log_and_apply(&mut self, mut edit: VersionEdit) -> Result<()> {
+        let version = self.current.apply(&edit)?;
+
+        if let Some(manifest) = self.manifest.as_mut() {
+            edit.next_file_number = Some(self.next_file_number);
+            if let Err(e) = manifest.append(&edit) {
+                // The tail of this MANIFEST is now unknown; start a new one
+                // on the next edit
+                self.manifest = None;
+                return Err(e);
+            }
+        } else {
+            let manifest_number = self.new_file_number();
+            edit.next_file_number = Some(self.next_file_number);
+            self.write_snapshot(manifest_number, &version, &edit)?;
+        }
+
+        self.absorb_counters(&edit);
+        self.current = Arc::new(version);
+        Ok(())
+    }
+
+    /// Starts a new MANIFEST holding `version` and switches CURRENT to it
+    fn write_snapshot(
+        &mut self,
+        manifest_number: u64,
+        version: &Version,
+        edit: &VersionEdit,
+    ) -> Result<()> {
+        let mut snapshot = VersionEdit {
+            log_number: Some(edit.log_number.unwrap_or(0).max(self.log_number)),
+            next_file_number: edit.next_file_number,
+            last_sequence: Some(edit.last_sequence.unwrap_or(0).max(self.last_sequence)),
+            ..Default::default()
+        };
+        for (level, file) in version.all_files() {
+            snapshot.add_file(level, FileMetaData::clone(file));
+        }
+
+        let name = filename::manifest_file_name(manifest_number);
+        let mut manifest = ManifestWriter::create(self.dir.join(&name))?;
+        manifest.append(&snapshot)?;
+        self.set_current(manifest_number, &name)?;
+        self.remove_stale_manifests(&name);
+
+        self.manifest = Some(manifest);
+        Ok(())
+    }
+
+    /// Atomically points CURRENT at the named MANIFEST
+    fn set_current(&self, manifest_number: u64, name: &str) -> Result<()> {
+        let temp_path = filename::temp_file_path(&self.dir, manifest_number);
+        let mut file = std::fs::File::create(&temp_path)?;
+        file.write_all(format!("{}\n", name).as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, self.dir.join(CURRENT_FILE))?;
+        filename::sync_dir(&self.dir)?;
+        Ok(())
+    }
+
+    /// Removes every MANIFEST other than the live one
+    ///
+    /// Failures only leave garbage behind, so they are logged and ignored.
+    fn remove_stale_manifests(&self, live: &str) {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Failed to list {}: {}", self.dir.display(), e);
+                return;
+            }
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else { continue };
+            if name != live && filename::parse_manifest_name(name).is_some() {
+                if let Err(e) = std::fs::remove_file(entry.path()) {
+                    warn!("Failed to remove {}: {}", name, e);
+                }
+            }
+        }
+    }
+
+    /// Folds the counters carried by an edit into the set
+    fn absorb_counters(&mut self, edit: &VersionEdit) {
+        if let Some(number) = edit.log_number {
+            self.log_number = self.log_number.max(number);
+        }
+        if let Some(number) = edit.next_file_number {
+            self.next_file_number = self.next_file_number.max(number);
+        }
+        if let Some(sequence) = edit.last_sequence {
+            self.last_sequence = self.last_sequence.max(sequence);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sstable::InternalKey;
+    use tempfile::TempDir;
+
+    fn test_file(number: u64) -> FileMetaData {
+        FileMetaData {
+            number,
+            file_size: 100,
+            smallest_key: InternalKey::new(b"a".to_vec(), 5),
+            largest_key: InternalKey::new(b"z".to_vec(), 1),
+        }
+    }
+
+    fn manifests(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| filename::parse_manifest_name(name).is_some())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_new_database_is_empty() {
+        let temp_dir = TempDir::new().unwrap();
+        let versions = VersionSet::recover(temp_dir.path()).unwrap();
+
+        assert!(!versions.has_manifest());
+        assert_eq!(versions.current().all_files().count(), 0);
+        assert_eq!(versions.last_sequence(), 0);
+        assert_eq!(versions.log_number(), 0);
+    }
+
+    #[test]
+    fn test_recover_replays_edits() {
+        let temp_dir = TempDir::new().unwrap();
+
+        {
+            let mut versions = VersionSet::recover(temp_dir.path()).unwrap();
+            let first = versions.new_file_number();
+            let mut edit = VersionEdit {
+                log_number: Some(first),
+                last_sequence: Some(10),
+                ..Default::default()
+            };
+            edit.add_file(0, test_file(first));
+            versions.log_and_apply(edit).unwrap();
+
+            let second = versions.new_file_number();
+            let mut edit = VersionEdit {
+                last_sequence: Some(20),
+                ..Default::default()
+            };
+            edit.delete_file(0, first);
+            edit.add_file(1, test_file(second));
+            versions.log_and_apply(edit).unwrap();
+        }
+
+        let mut versions = VersionSet::recover(temp_dir.path()).unwrap();
+        assert!(versions.has_manifest());
+        assert!(versions.current().files(0).is_empty());
+        assert_eq!(versions.current().files(1).len(), 1);
+        assert_eq!(versions.last_sequence(), 20);
+        assert_eq!(versions.log_number(), 1);
+        let next = versions.new_file_number();
+        assert!(next > versions.current().files(1)[0].number);
+
+        // The first edit after reopening moves to a fresh MANIFEST
+        let before = manifests(temp_dir.path());
+        versions.log_and_apply(VersionEdit::default()).unwrap();
+        let after = manifests(temp_dir.path());
+        assert_eq!(after.len(), 1);
+        assert_ne!(before, after);
+
+        let versions = VersionSet::recover(temp_dir.path()).unwrap();
+        assert_eq!(versions.current().files(1).len(), 1);
+        assert_eq!(versions.last_sequence(), 20);
+    }
+
+    #[test]
+    fn test_recover_ignores_torn_manifest_tail() {
+        let temp_dir = TempDir::new().unwrap();
+
+        {
+            let mut versions = VersionSet::recover(temp_dir.path()).unwrap();
+            for sequence in [10, 20] {
+                let number = versions.new_file_number();
+                let mut edit = VersionEdit {
+                    last_sequence: Some(sequence),
+                    ..Default::default()
+                };
+                edit.add_file(0, test_file(number));
+                versions.log_and_apply(edit).unwrap();
+            }
+        }
+
+        // Tear the last edit in half
+        let path = temp_dir.path().join(&manifests(temp_dir.path())[0]);
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[..data.len() - 10]).unwrap();
+
+        let versions = VersionSet::recover(temp_dir.path()).unwrap();
+        assert_eq!(versions.current().files(0).len(), 1);
+        assert_eq!(versions.last_sequence(), 10);
+    }
+
+    #[test]
+    fn test_recover_rejects_bad_current() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join(CURRENT_FILE), "garbage\n").unwrap();
+
+        assert!(matches!(
+            VersionSet::recover(temp_dir.path()),
+            Err(Error::Corruption(_))
+        ));
+    }
+}
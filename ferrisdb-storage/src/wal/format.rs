@@ -0,0 +1,187 @@
+//! Physical layout of WAL files
+//!
+//! A WAL file is a sequence of fixed-size blocks. Each logical record (an
+//! encoded [`WALEntry`](super::WALEntry) or [`WriteBatch`](crate::WriteBatch))
+//! is stored as one or more fragments, and no fragment crosses a block
+//! boundary:
+//!
+//! ```text
+//! +-----------+------------+----------+---------------+
+//! | CRC32(4B) | Length(2B) | Type(1B) | Payload(var)  |
+//! +-----------+------------+----------+---------------+
+//! ```
+//!
+//! The checksum covers the type and payload. A record that fits in the rest
+//! of the current block is written as one FULL fragment; otherwise it is
+//! split into a FIRST fragment, any number of MIDDLE fragments and a LAST
+//! fragment. When fewer than [`HEADER_SIZE`] bytes are left in a block they
+//! are filled with zeros and the next fragment starts the next block.
+//!
+//! Because every block starts with a fragment header, a reader that meets
+//! a corrupted fragment can always pick up again at the next block.
+
+use crc32fast::Hasher;
+
+/// Size of a physical block
+pub(crate) const BLOCK_SIZE: usize = 32 * 1024;
+
+/// Size of the checksum, length and type fields of a fragment
+pub(crate) const HEADER_SIZE: usize = 4 + 2 + 1;
+
+/// What part of a logical record a fragment holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FragmentType {
+    /// The whole record
+    Full = 1,
+    /// The start of a record continued in the next fragments
+    First = 2,
+    /// A piece of a record with fragments before and after it
+    Middle = 3,
+    /// The end of a record
+    Last = 4,
+}
+
+impl FragmentType {
+    pub(crate) fn from_u8(tag: u8) -> Option<Self> {
+        match tag {
+            1 => Some(Self::Full),
+            2 => Some(Self::First),
+            3 => Some(Self::Middle),
+            4 => Some(Self::Last),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the checksum stored in the header of a fragment
+pub(crate) fn fragment_checksum(tag: u8, payload: &[u8]) -> u32 {
+    let mut hasher = Hasher::new();
+    hasher.update(&[tag]);
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+/// Appends `record` to `out` as fragments, starting `block_offset` bytes
+/// into a block
+///
+/// `block_offset` is advanced to where the next record would start.
+pub(crate) fn frame(record: &[u8], block_offset: &mut usize, out: &mut Vec<u8>) {
+    let mut rest = record;
+    let mut first = true;
+    loop {
+        let left = BLOCK_SIZE - *block_offset;
+        if left < HEADER_SIZE {
+            out.resize(out.len() + left, 0);
+            *block_offset = 0;
+            continue;
+        }
+
+        let length = rest.len().min(left - HEADER_SIZE);
+        let last = length == rest.len();
+        let tag = match (first, last) {
+            (true, true) => FragmentType::Full,
+            (true, false) => FragmentType::First,
+            (false, false) => FragmentType::Middle,
+            (false, true) => FragmentType::Last,
+        } as u8;
+        let (payload, remainder) = rest.split_at(length);
+
+        out.extend_from_slice(&fragment_checksum(tag, payload).to_le_bytes());
+        out.extend_from_slice(&(length as u16).to_le_bytes());
+        out.push(tag);
+        out.extend_from_slice(payload);
+        *block_offset += HEADER_SIZE + length;
+
+        if last {
+            return;
+        }
+        rest = remainder;
+        first = false;
+    }
+}
+
+/// Returns the number of bytes [`frame`] would append for a record of
+/// `length` bytes starting `block_offset` bytes into a block
+pub(crate) fn framed_size(mut length: usize, mut block_offset: usize) -> usize {
+    let mut size = 0;
+    loop {
+        let left = BLOCK_SIZE - block_offset;
+        if left < HEADER_SIZE {
+            size += left;
+            block_offset = 0;
+            continue;
+        }
+
+        let fragment = length.min(left - HEADER_SIZE);
+        size += HEADER_SIZE + fragment;
+        block_offset += HEADER_SIZE + fragment;
+        length -= fragment;
+        if length == 0 {
+            return size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_record_is_one_fragment() {
+        let mut offset = 0;
+        let mut out = Vec::new();
+        frame(b"hello", &mut offset, &mut out);
+
+        assert_eq!(out.len(), HEADER_SIZE + 5);
+        assert_eq!(offset, out.len());
+        assert_eq!(out[6], FragmentType::Full as u8);
+        assert_eq!(&out[4..6], &5u16.to_le_bytes());
+        assert_eq!(&out[..4], &fragment_checksum(1, b"hello").to_le_bytes());
+    }
+
+    #[test]
+    fn test_large_record_spans_blocks() {
+        let record = vec![7u8; 2 * BLOCK_SIZE];
+        let mut offset = 100;
+        let mut out = Vec::new();
+        frame(&record, &mut offset, &mut out);
+
+        // FIRST fills the rest of the first block, MIDDLE the whole second
+        // block and LAST starts the third
+        let first_end = BLOCK_SIZE - 100;
+        assert_eq!(out[6], FragmentType::First as u8);
+        assert_eq!(out[first_end + 6], FragmentType::Middle as u8);
+        assert_eq!(out[first_end + BLOCK_SIZE + 6], FragmentType::Last as u8);
+        assert_eq!(out.len(), framed_size(record.len(), 100));
+        assert_eq!(offset, (100 + out.len()) % BLOCK_SIZE);
+    }
+
+    #[test]
+    fn test_short_block_tail_is_padded() {
+        let mut offset = BLOCK_SIZE - 3;
+        let mut out = Vec::new();
+        frame(b"x", &mut offset, &mut out);
+
+        assert_eq!(&out[..3], &[0, 0, 0]);
+        assert_eq!(out[3 + 6], FragmentType::Full as u8);
+        assert_eq!(offset, HEADER_SIZE + 1);
+        assert_eq!(out.len(), framed_size(1, BLOCK_SIZE - 3));
+    }
+
+    #[test]
+    fn test_record_ending_at_block_boundary() {
+        let record = vec![1u8; BLOCK_SIZE - HEADER_SIZE];
+        let mut offset = 0;
+        let mut out = Vec::new();
+        frame(&record, &mut offset, &mut out);
+
+        assert_eq!(out.len(), BLOCK_SIZE);
+        assert_eq!(out[6], FragmentType::Full as u8);
+        assert_eq!(offset, BLOCK_SIZE);
+
+        // The next record starts a new block without padding
+        frame(b"next", &mut offset, &mut out);
+        assert_eq!(out.len(), BLOCK_SIZE + HEADER_SIZE + 4);
+        assert_eq!(offset, HEADER_SIZE + 4);
+    }
+}
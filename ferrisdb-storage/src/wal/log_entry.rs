@@ -17,6 +17,9 @@ use ferrisdb_core::{Error, Key, Operation, Result, Timestamp, Value};
 /// | Key(var)   | Val Len(4B)| Value(var) |
 /// +------------+------------+------------+
 /// ```
+///
+/// In the log file the encoded entry is the payload of one or more block
+/// fragments, which carry a checksum of their own.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WALEntry {
     /// Timestamp when this operation occurred
@@ -0,0 +1,649 @@
+use super::{format, WALEntry, WALReader, WALWriter, WALWriterStats};
+use crate::filename::{self, WAL_EXTENSION};
+use crate::write_batch::WriteBatch;
+use ferrisdb_core::{Error, Result, SyncMode, Timestamp, WalRecoveryMode};
+use log::{info, warn};
+use parking_lot::{Mutex, RwLock};
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A Write-Ahead Log split across numbered segment files
+///
+/// Appends go to the active segment, `000123.log` in the WAL directory.
+/// When a record would take the active segment past the segment size, the
+/// manager rolls over to a new segment with a fresh file number and appends
+/// there, so a full segment never fails a write. Records are logged in the
+/// order they are queued, and no record is queued to an older segment once
+/// a newer one has been started.
+///
+/// The manager tracks the smallest timestamp logged to each segment. Once
+/// every write up to some timestamp is in SSTables,
+/// [`remove_flushed`](Self::remove_flushed) deletes the segments holding
+/// nothing newer, or moves them to an archive directory if one is set.
+///
+/// File numbers come from a caller-supplied allocator, so segments can
+/// share a number space with other files.
+///
+/// # Example
+///
+/// ```no_run
+/// use ferrisdb_storage::wal::{WALEntry, WALManager};
+/// use ferrisdb_core::SyncMode;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// let next_number = AtomicU64::new(1);
+/// let wal = WALManager::open("path/to/wal", SyncMode::Normal, 64 * 1024 * 1024, move || {
+///     next_number.fetch_add(1, Ordering::Relaxed)
+/// })?;
+///
+/// wal.append(&WALEntry::new_put(b"key".to_vec(), b"value".to_vec(), 1))?;
+///
+/// // Once timestamp 1 is in an SSTable, no older segment is needed
+/// wal.remove_flushed(1)?;
+/// # Ok::<(), ferrisdb_core::Error>(())
+/// ```
+pub struct WALManager {
+    dir: PathBuf,
+    sync_mode: SyncMode,
+    segment_size: u64,
+    /// Where flushed segments are moved instead of being deleted
+    archive_dir: Option<PathBuf>,
+    /// Hands out file numbers for new segments
+    new_file_number: Box<dyn Fn() -> u64 + Send + Sync>,
+    /// Segment receiving appends
+    ///
+    /// Appends hold the read lock while their record is queued and tracked.
+    /// A retired segment's writer lives on in any [`PendingAppend`] still
+    /// waiting on it, so records queued before a roll-over are still written.
+    active: RwLock<ActiveSegment>,
+    /// Every segment on disk, oldest first, ending with the active one
+    segments: Mutex<SegmentList>,
+    /// Group commit counters shared by the writers of every segment
+    stats: Arc<Mutex<WALWriterStats>>,
+}
+
+/// The segment receiving appends
+struct ActiveSegment {
+    number: u64,
+    writer: Arc<WALWriter>,
+}
+
+/// A record queued in the WAL that may not have been written yet
+///
+/// Returned by [`WALManager::queue_batch`]. Concurrent queued records are
+/// written together by whichever waiting thread leads the next group
+/// commit on their segment.
+#[must_use = "a queued record is only guaranteed to be written once waited on"]
+pub struct PendingAppend {
+    /// Writer of the segment holding the record, and the record's ticket;
+    /// `None` for an empty batch
+    commit: Option<(Arc<WALWriter>, u64)>,
+}
+
+impl PendingAppend {
+    /// Waits until the record has been written and synced as the sync mode
+    /// requires
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing the record's group failed.
+    pub fn wait(self) -> Result<()> {
+        match self.commit {
+            Some((writer, ticket)) => writer.wait_for_commit(ticket),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A segment file and the timestamps logged to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentInfo {
+    /// File number of the segment
+    pub number: u64,
+    /// Smallest timestamp logged to the segment; `None` if it holds no
+    /// writes or has not been recovered yet
+    pub min_timestamp: Option<Timestamp>,
+}
+
+/// Segment bookkeeping, guarded by `WALManager::segments`
+struct SegmentList {
+    segments: Vec<SegmentInfo>,
+    /// Newest timestamp logged to any segment
+    last_timestamp: Option<Timestamp>,
+}
+
+impl SegmentList {
+    /// Records that timestamps `first..=last` were logged to segment `number`
+    fn track(&mut self, number: u64, first: Timestamp, last: Timestamp) {
+        if let Some(segment) = self.segments.iter_mut().rev().find(|s| s.number == number) {
+            segment.min_timestamp = Some(segment.min_timestamp.map_or(first, |min| min.min(first)));
+        }
+        self.last_timestamp = Some(self.last_timestamp.map_or(last, |newest| newest.max(last)));
+    }
+}
+
+impl WALManager {
+    /// Opens the WAL in `dir` and starts a new active segment
+    ///
+    /// Existing segments are kept but not written to again. Call
+    /// [`recover`](Self::recover) to replay them.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory holding the segments
+    /// * `sync_mode` - Durability level for writes
+    /// * `segment_size` - Size at which the log rolls over to a new segment
+    /// * `new_file_number` - Returns an unused file number on each call
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be listed or the new segment
+    /// cannot be created.
+    pub fn open(
+        dir: impl AsRef<Path>,
+        sync_mode: SyncMode,
+        segment_size: u64,
+        new_file_number: impl Fn() -> u64 + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+
+        let mut segments: Vec<_> = filename::list_file_numbers(&dir, WAL_EXTENSION)?
+            .into_iter()
+            .map(|number| SegmentInfo {
+                number,
+                min_timestamp: None,
+            })
+            .collect();
+
+        let stats = Arc::default();
+        let number = new_file_number();
+        let writer = WALWriter::new(
+            filename::wal_file_path(&dir, number),
+            sync_mode,
+            segment_size,
+        )?
+        .with_stats(Arc::clone(&stats));
+        let writer = Arc::new(writer);
+        segments.push(SegmentInfo {
+            number,
+            min_timestamp: None,
+        });
+
+        Ok(Self {
+            dir,
+            sync_mode,
+            segment_size,
+            archive_dir: None,
+            new_file_number: Box::new(new_file_number),
+            active: RwLock::new(ActiveSegment { number, writer }),
+            segments: Mutex::new(SegmentList {
+                segments,
+                last_timestamp: None,
+            }),
+            stats,
+        })
+    }
+
+    /// Moves flushed segments to `dir` instead of deleting them
+    pub fn with_archive_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.archive_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Replays the segments that existed when the WAL was opened
+    ///
+    /// Batches are passed to `apply` oldest first. Damaged records are
+    /// handled according to `recovery_mode`. A segment with a dropped tail
+    /// is truncated back to its last valid record so the garbage is not
+    /// replayed again on the next open. In point-in-time mode, damage in one
+    /// segment also discards every later segment.
+    ///
+    /// Returns the newest timestamp replayed, or 0 if there was nothing to
+    /// replay.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a segment cannot be read, if it is damaged in a
+    /// way the recovery mode does not tolerate, or if `apply` fails.
+    pub fn recover<F>(&self, recovery_mode: WalRecoveryMode, mut apply: F) -> Result<Timestamp>
+    where
+        F: FnMut(WriteBatch) -> Result<()>,
+    {
+        let active = self.active.read().number;
+        let numbers: Vec<u64> = self
+            .segments
+            .lock()
+            .segments
+            .iter()
+            .map(|segment| segment.number)
+            .filter(|&number| number != active)
+            .collect();
+
+        let mut last_timestamp = 0;
+        for (i, &number) in numbers.iter().enumerate() {
+            let path = self.segment_path(number);
+            let mut reader = WALReader::with_recovery_mode(&path, recovery_mode)?;
+            let mut recovered = 0;
+            while let Some(batch) = reader.read_batch()? {
+                if batch.is_empty() {
+                    continue;
+                }
+                let (first, last) = (batch.base_timestamp(), batch.last_timestamp());
+                recovered += batch.len();
+                apply(batch)?;
+                self.segments.lock().track(number, first, last);
+                last_timestamp = last_timestamp.max(last);
+            }
+            info!("Recovered {} entries from {}", recovered, path.display());
+
+            let stats = reader.stats();
+            if stats.dropped_bytes == 0 {
+                continue;
+            }
+            warn!(
+                "Dropped {} records ({} bytes) from {}",
+                stats.dropped_records,
+                stats.dropped_bytes,
+                path.display()
+            );
+
+            let file = OpenOptions::new().write(true).open(&path)?;
+            if file.metadata()?.len() > stats.valid_length {
+                file.set_len(stats.valid_length)?;
+                file.sync_all()?;
+            }
+
+            if stats.stopped_early {
+                let mut segments = self.segments.lock();
+                for &later in &numbers[i + 1..] {
+                    let later_path = self.segment_path(later);
+                    warn!(
+                        "Discarding {} after point-in-time recovery",
+                        later_path.display()
+                    );
+                    std::fs::remove_file(later_path)?;
+                    segments.segments.retain(|segment| segment.number != later);
+                }
+                break;
+            }
+        }
+        Ok(last_timestamp)
+    }
+
+    /// Appends an entry to the active segment, rolling over if it is full
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::EntrySizeExceeded` if the record is larger than a
+    /// whole segment, or an error if the write or a new segment fails.
+    pub fn append(&self, entry: &WALEntry) -> Result<()> {
+        self.queue_record(&entry.encode(), entry.timestamp, entry.timestamp)?
+            .wait()
+    }
+
+    /// Appends a batch to the active segment as a single record, rolling
+    /// over if the segment is full
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::EntrySizeExceeded` if the record is larger than a
+    /// whole segment, or an error if the write or a new segment fails.
+    pub fn append_batch(&self, batch: &WriteBatch) -> Result<()> {
+        self.queue_batch(batch)?.wait()
+    }
+
+    /// Queues a batch as a single record without waiting for it to be
+    /// written
+    ///
+    /// Records are logged in the order they are queued, so a caller can
+    /// queue under its own lock to keep the log in timestamp order, then
+    /// wait outside the lock so concurrent batches share a write and sync.
+    /// An empty batch queues nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::EntrySizeExceeded` if the record is larger than a
+    /// whole segment, or an error if an earlier write failed or a new
+    /// segment cannot be created.
+    pub fn queue_batch(&self, batch: &WriteBatch) -> Result<PendingAppend> {
+        if batch.is_empty() {
+            return Ok(PendingAppend { commit: None });
+        }
+        let encoded = batch.encode();
+        self.queue_record(&encoded, batch.base_timestamp(), batch.last_timestamp())
+    }
+
+    fn queue_record(
+        &self,
+        encoded: &[u8],
+        first: Timestamp,
+        last: Timestamp,
+    ) -> Result<PendingAppend> {
+        // A new segment starts at a block boundary
+        let size = format::framed_size(encoded.len(), 0);
+        if size as u64 > self.segment_size {
+            return Err(Error::EntrySizeExceeded {
+                size,
+                max_size: self.segment_size as usize,
+            });
+        }
+
+        loop {
+            let full = {
+                let active = self.active.read();
+                if let Some(ticket) = active.writer.try_queue_record(encoded)? {
+                    self.segments.lock().track(active.number, first, last);
+                    return Ok(PendingAppend {
+                        commit: Some((Arc::clone(&active.writer), ticket)),
+                    });
+                }
+                active.number
+            };
+            self.roll_over_from(full)?;
+        }
+    }
+
+    /// Starts a new active segment and returns its number
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the new segment cannot be created.
+    pub fn roll_over(&self) -> Result<u64> {
+        let mut active = self.active.write();
+        self.start_segment(&mut active)?;
+        Ok(active.number)
+    }
+
+    /// Rolls over unless another append already replaced segment `full`
+    fn roll_over_from(&self, full: u64) -> Result<()> {
+        let mut active = self.active.write();
+        if active.number == full {
+            self.start_segment(&mut active)?;
+        }
+        Ok(())
+    }
+
+    fn start_segment(&self, active: &mut ActiveSegment) -> Result<()> {
+        let number = (self.new_file_number)();
+        let writer = WALWriter::new(self.segment_path(number), self.sync_mode, self.segment_size)?
+            .with_stats(Arc::clone(&self.stats));
+        let writer = Arc::new(writer);
+        self.segments.lock().segments.push(SegmentInfo {
+            number,
+            min_timestamp: None,
+        });
+        *active = ActiveSegment { number, writer };
+        Ok(())
+    }
+
+    /// Deletes or archives the segments whose writes are all at or before
+    /// `flushed_through`
+    ///
+    /// Every write up to `flushed_through` must already be in SSTables, and
+    /// the segments that existed when the WAL was opened must have been
+    /// recovered. A segment holds nothing as new as the smallest timestamp
+    /// of the next segment with writes, so segments are released oldest
+    /// first until one may still be needed. The active segment is never
+    /// released.
+    ///
+    /// Returns the numbers of the segments released.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a segment cannot be deleted or moved.
+    pub fn remove_flushed(&self, flushed_through: Timestamp) -> Result<Vec<u64>> {
+        let mut list = self.segments.lock();
+        let retired = list.segments.len() - 1;
+        let count = (0..retired)
+            .take_while(|&i| {
+                let newest = list.segments[i + 1..]
+                    .iter()
+                    .find_map(|segment| segment.min_timestamp)
+                    .map(|min| min.saturating_sub(1))
+                    .or(list.last_timestamp)
+                    .unwrap_or(0);
+                newest <= flushed_through
+            })
+            .count();
+        self.release_oldest(&mut list, count)
+    }
+
+    /// Deletes or archives every segment numbered below `number`
+    ///
+    /// Used when another record, such as a MANIFEST, already says which
+    /// segments have been flushed. The active segment is never released.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a segment cannot be deleted or moved.
+    pub fn remove_segments_before(&self, number: u64) -> Result<Vec<u64>> {
+        let mut list = self.segments.lock();
+        let retired = list.segments.len() - 1;
+        let count = list.segments[..retired]
+            .iter()
+            .take_while(|segment| segment.number < number)
+            .count();
+        self.release_oldest(&mut list, count)
+    }
+
+    /// Releases the `count` oldest segments
+    fn release_oldest(&self, list: &mut SegmentList, count: usize) -> Result<Vec<u64>> {
+        let mut released = Vec::with_capacity(count);
+        for _ in 0..count {
+            let number = list.segments[0].number;
+            self.release(number)?;
+            list.segments.remove(0);
+            released.push(number);
+        }
+        Ok(released)
+    }
+
+    /// Deletes a segment, or moves it to the archive directory
+    fn release(&self, number: u64) -> Result<()> {
+        let path = self.segment_path(number);
+        match &self.archive_dir {
+            Some(archive_dir) => {
+                std::fs::create_dir_all(archive_dir)?;
+                let archived = filename::wal_file_path(archive_dir, number);
+                info!(
+                    "Archiving flushed WAL segment {} to {}",
+                    path.display(),
+                    archived.display()
+                );
+                std::fs::rename(path, archived)?;
+                filename::sync_dir(archive_dir)?;
+            }
+            None => {
+                info!("Removing flushed WAL segment {}", path.display());
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the segments on disk, oldest first, ending with the active one
+    pub fn segments(&self) -> Vec<SegmentInfo> {
+        self.segments.lock().segments.clone()
+    }
+
+    /// Returns the number of the active segment
+    pub fn active_number(&self) -> u64 {
+        self.active.read().number
+    }
+
+    /// Returns the size of the active segment
+    pub fn active_size(&self) -> u64 {
+        self.active.read().writer.size()
+    }
+
+    /// Returns group commit counters for every segment written since the
+    /// WAL was opened
+    pub fn stats(&self) -> WALWriterStats {
+        *self.stats.lock()
+    }
+
+    /// Returns the writer of the active segment
+    #[cfg(test)]
+    pub(crate) fn active_writer(&self) -> Arc<WALWriter> {
+        Arc::clone(&self.active.read().writer)
+    }
+
+    /// Forces a sync of the active segment to disk
+    pub fn sync(&self) -> Result<()> {
+        self.active.read().writer.sync()
+    }
+
+    /// Returns the directory holding the segments
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn segment_path(&self, number: u64) -> PathBuf {
+        filename::wal_file_path(&self.dir, number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use tempfile::TempDir;
+
+    /// Opens a manager whose file numbers continue from `next`
+    fn open(dir: &Path, segment_size: u64, next: &Arc<AtomicU64>) -> WALManager {
+        let next = Arc::clone(next);
+        WALManager::open(dir, SyncMode::Normal, segment_size, move || {
+            next.fetch_add(1, Ordering::Relaxed)
+        })
+        .unwrap()
+    }
+
+    fn put(timestamp: Timestamp) -> WALEntry {
+        WALEntry::new_put(
+            format!("key{:03}", timestamp).into_bytes(),
+            b"value".to_vec(),
+            timestamp,
+        )
+    }
+
+    #[test]
+    fn test_full_segments_roll_over() {
+        let temp_dir = TempDir::new().unwrap();
+        let next = Arc::new(AtomicU64::new(1));
+        let wal = open(temp_dir.path(), 240, &next);
+
+        // Each record is 43 bytes framed, so five fit in a segment
+        for timestamp in 1..=12 {
+            wal.append(&put(timestamp)).unwrap();
+        }
+
+        let segments = wal.segments();
+        let numbers: Vec<_> = segments.iter().map(|s| s.number).collect();
+        assert_eq!(numbers, vec![1, 2, 3]);
+        let mins: Vec<_> = segments.iter().map(|s| s.min_timestamp).collect();
+        assert_eq!(mins, vec![Some(1), Some(6), Some(11)]);
+        assert_eq!(wal.active_number(), 3);
+        assert!(filename::wal_file_path(temp_dir.path(), 3).exists());
+
+        // Counters carry over from the segments rolled away from
+        let stats = wal.stats();
+        assert_eq!(stats.records, 12);
+        assert_eq!(stats.groups, 12);
+
+        // The segments read back as one log
+        let entries = WALReader::open_dir(temp_dir.path(), WalRecoveryMode::AbsoluteConsistency)
+            .unwrap()
+            .read_all()
+            .unwrap();
+        assert_eq!(entries, (1..=12).map(put).collect::<Vec<_>>());
+
+        // A record that could never fit is rejected rather than rolled over
+        let huge = WALEntry::new_put(b"key".to_vec(), vec![0; 240], 13);
+        assert!(matches!(
+            wal.append(&huge),
+            Err(Error::EntrySizeExceeded { .. })
+        ));
+        assert_eq!(wal.active_number(), 3);
+    }
+
+    #[test]
+    fn test_remove_flushed_segments() {
+        let temp_dir = TempDir::new().unwrap();
+        let next = Arc::new(AtomicU64::new(1));
+        let wal = open(temp_dir.path(), 240, &next);
+        for timestamp in 1..=12 {
+            wal.append(&put(timestamp)).unwrap();
+        }
+
+        // Segment 1 holds timestamps 1-5 and segment 2 holds 6-10
+        assert!(wal.remove_flushed(4).unwrap().is_empty());
+        assert_eq!(wal.remove_flushed(9).unwrap(), vec![1]);
+        assert_eq!(wal.remove_flushed(12).unwrap(), vec![2]);
+        assert!(!filename::wal_file_path(temp_dir.path(), 1).exists());
+        assert!(!filename::wal_file_path(temp_dir.path(), 2).exists());
+
+        // The active segment stays even once everything is flushed
+        let numbers: Vec<_> = wal.segments().iter().map(|s| s.number).collect();
+        assert_eq!(numbers, vec![3]);
+        assert!(wal.remove_flushed(Timestamp::MAX).unwrap().is_empty());
+
+        // An empty segment is released with the segments before it
+        wal.roll_over().unwrap();
+        assert_eq!(wal.remove_flushed(12).unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn test_flushed_segments_are_archived() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_dir = temp_dir.path().join("wal");
+        let archive_dir = temp_dir.path().join("archive");
+        let next = Arc::new(AtomicU64::new(1));
+        let wal = open(&wal_dir, 240, &next).with_archive_dir(&archive_dir);
+        for timestamp in 1..=6 {
+            wal.append(&put(timestamp)).unwrap();
+        }
+
+        assert_eq!(wal.remove_flushed(6).unwrap(), vec![1]);
+        assert!(!filename::wal_file_path(&wal_dir, 1).exists());
+
+        let archived = WALReader::new(filename::wal_file_path(&archive_dir, 1))
+            .unwrap()
+            .read_all()
+            .unwrap();
+        assert_eq!(archived, (1..=5).map(put).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_recover_existing_segments() {
+        let temp_dir = TempDir::new().unwrap();
+        let next = Arc::new(AtomicU64::new(1));
+        {
+            let wal = open(temp_dir.path(), 240, &next);
+            for timestamp in 1..=7 {
+                wal.append(&put(timestamp)).unwrap();
+            }
+        }
+
+        // Reopening starts a new segment after the existing ones
+        let wal = open(temp_dir.path(), 240, &next);
+        assert_eq!(wal.active_number(), 3);
+        let mut replayed = Vec::new();
+        let last = wal
+            .recover(WalRecoveryMode::AbsoluteConsistency, |batch| {
+                replayed.extend(batch.into_entries());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(last, 7);
+        assert_eq!(replayed, (1..=7).map(put).collect::<Vec<_>>());
+
+        let mins: Vec<_> = wal.segments().iter().map(|s| s.min_timestamp).collect();
+        assert_eq!(mins, vec![Some(1), Some(6), None]);
+
+        assert_eq!(wal.remove_segments_before(2).unwrap(), vec![1]);
+        assert_eq!(wal.remove_segments_before(u64::MAX).unwrap(), vec![2]);
+        assert_eq!(wal.active_number(), 3);
+    }
+}
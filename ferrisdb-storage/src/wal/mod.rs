@@ -9,6 +9,17 @@
 //! - Operation type (Put or Delete)
 //! - Key and value data
 //!
+//! Records are stored as fragments in fixed-size 32 KiB blocks, so a
+//! record can be larger than a block and a reader that finds damage can
+//! resume at the next block boundary.
+//!
+//! Damaged records found while reading a log are handled according to a
+//! [`WalRecoveryMode`](ferrisdb_core::WalRecoveryMode).
+//!
+//! A [`WALManager`] spreads the log over numbered segment files, rolling
+//! over to a new segment when the active one is full and releasing
+//! segments once their writes have been flushed.
+//!
 //! # Example
 //!
 //! ```no_run
@@ -26,10 +37,13 @@
 //! # Ok::<(), ferrisdb_core::Error>(())
 //! ```
 
+mod format;
 mod log_entry;
+mod manager;
 mod reader;
 mod writer;
 
 pub use log_entry::WALEntry;
-pub use reader::WALReader;
-pub use writer::WALWriter;
+pub use manager::{PendingAppend, SegmentInfo, WALManager};
+pub use reader::{WALReader, WALRecoveryStats};
+pub use writer::{WALWriter, WALWriterStats};
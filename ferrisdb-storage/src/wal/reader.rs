@@ -1,8 +1,12 @@
+use super::format::{self, FragmentType, BLOCK_SIZE, HEADER_SIZE};
 use super::WALEntry;
-use ferrisdb_core::Result;
+use crate::filename::{self, WAL_EXTENSION};
+use crate::write_batch::WriteBatch;
+use ferrisdb_core::{Error, Result, WalRecoveryMode};
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::{BufReader, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Reader for the Write-Ahead Log
 ///
@@ -10,6 +14,17 @@ use std::path::Path;
 /// checksums and handles partial entries at the end of the file (which may
 /// occur if the process crashed during a write).
 ///
+/// How torn and corrupted records are treated is controlled by a
+/// [`WalRecoveryMode`]. Whatever the reader skips is reported through
+/// [`WALReader::stats`].
+///
+/// A record may hold a [`WriteBatch`]. [`read_batch`](WALReader::read_batch)
+/// returns whole records, while [`read_entry`](WALReader::read_entry) and the
+/// iterator return the entries of a batch one at a time.
+///
+/// [`open_dir`](WALReader::open_dir) reads every segment in a directory as
+/// one log, oldest first.
+///
 /// # Example
 ///
 /// ```no_run
@@ -30,49 +45,372 @@ use std::path::Path;
 /// # Ok::<(), ferrisdb_core::Error>(())
 /// ```
 pub struct WALReader {
-    reader: BufReader<File>,
+    /// Segment being read; `None` until the first segment of a directory
+    /// is opened
+    reader: Option<BufReader<File>>,
+    /// Segments still to be read after the current one, oldest first
+    segments: VecDeque<PathBuf>,
+    /// How torn and corrupted records are handled
+    recovery_mode: WalRecoveryMode,
+    /// Offset of the next unread byte
+    offset: u64,
+    /// Total size of the file when it was opened
+    file_size: u64,
+    /// What has been skipped so far
+    stats: WALRecoveryStats,
+    /// Set once the reader has given up on the rest of the file
+    stopped: bool,
+    /// Entries of the last batch not yet returned by `read_entry`
+    pending: VecDeque<WALEntry>,
+}
+
+/// Summary of the records a [`WALReader`] did not return
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WALRecoveryStats {
+    /// Offset just past the last valid record
+    ///
+    /// Everything after this offset was either dropped or skipped, so the
+    /// file can be truncated to this length. When reading a directory, this
+    /// refers to the segment being read.
+    pub valid_length: u64,
+    /// Number of bytes that were not replayed
+    pub dropped_bytes: u64,
+    /// Number of torn or corrupted records that were not replayed
+    pub dropped_records: u64,
+    /// Whether reading stopped at a damaged record before the end of the file
+    ///
+    /// Only set in [`WalRecoveryMode::PointInTimeRecovery`] when the damage
+    /// is not confined to the tail. Later segments of a directory are not
+    /// read either.
+    pub stopped_early: bool,
+}
+
+/// Where damage sits in the file
+enum Damage {
+    /// The damage runs up to or past the end of the file
+    Tail,
+    /// Data the reader can resume from follows the damage
+    Middle,
 }
 
 impl WALReader {
     /// Creates a new WAL reader
     ///
+    /// Uses [`WalRecoveryMode::TolerateCorruptedTailRecords`], which drops a
+    /// damaged final record and fails on damage anywhere else.
+    ///
     /// # Errors
     ///
     /// Returns an error if the file cannot be opened.
     pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_recovery_mode(path, WalRecoveryMode::TolerateCorruptedTailRecords)
+    }
+
+    /// Creates a new WAL reader with the given recovery mode
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub fn with_recovery_mode(
+        path: impl AsRef<Path>,
+        recovery_mode: WalRecoveryMode,
+    ) -> Result<Self> {
+        let mut reader = Self::unopened(VecDeque::new(), recovery_mode);
+        reader.open_segment(path.as_ref())?;
+        Ok(reader)
+    }
+
+    /// Creates a reader over every WAL segment in `dir`
+    ///
+    /// Segments are read in file number order as one log. Damage in one
+    /// segment is handled as if the segment were the whole file, so a torn
+    /// tail is dropped and reading continues with the next segment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be listed.
+    pub fn open_dir(dir: impl AsRef<Path>, recovery_mode: WalRecoveryMode) -> Result<Self> {
+        let dir = dir.as_ref();
+        let segments = filename::list_file_numbers(dir, WAL_EXTENSION)?
+            .into_iter()
+            .map(|number| filename::wal_file_path(dir, number))
+            .collect();
+        Ok(Self::unopened(segments, recovery_mode))
+    }
+
+    /// Creates a reader that will read `segments` in turn
+    fn unopened(segments: VecDeque<PathBuf>, recovery_mode: WalRecoveryMode) -> Self {
+        Self {
+            reader: None,
+            segments,
+            recovery_mode,
+            offset: 0,
+            file_size: 0,
+            stats: WALRecoveryStats::default(),
+            stopped: false,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Starts reading the segment at `path`
+    fn open_segment(&mut self, path: &Path) -> Result<()> {
         let file = File::open(path)?;
-        Ok(Self {
-            reader: BufReader::new(file),
-        })
+        self.file_size = file.metadata()?.len();
+        self.reader = Some(BufReader::new(file));
+        self.offset = 0;
+        self.stats.valid_length = 0;
+        self.stopped = false;
+        Ok(())
+    }
+
+    /// Moves on to the next segment, if there is one to read
+    fn advance_segment(&mut self) -> Result<bool> {
+        if self.stats.stopped_early {
+            return Ok(false);
+        }
+        match self.segments.pop_front() {
+            Some(path) => {
+                self.open_segment(&path)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn reader(&mut self) -> &mut BufReader<File> {
+        self.reader
+            .as_mut()
+            .expect("a segment is open while bytes remain")
     }
 
     /// Reads the next entry from the WAL
     ///
-    /// Returns `Ok(None)` when the end of file is reached.
+    /// Returns `Ok(None)` when the end of file is reached. Torn and corrupted
+    /// records are skipped, end the log, or fail the read depending on the
+    /// recovery mode. The entries of a batch are returned one at a time.
     ///
     /// # Errors
     ///
     /// Returns an error if:
     /// - An I/O error occurs
-    /// - Corruption is detected (checksum mismatch)
-    /// - The entry format is invalid
+    /// - Corruption is detected that the recovery mode does not tolerate
     pub fn read_entry(&mut self) -> Result<Option<WALEntry>> {
-        // Read length
-        let mut length_buf = [0u8; 4];
-        match self.reader.read_exact(&mut length_buf) {
-            Ok(_) => {}
-            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
-            Err(e) => return Err(e.into()),
+        while self.pending.is_empty() {
+            match self.read_batch()? {
+                Some(batch) => self.pending.extend(batch.into_entries()),
+                None => return Ok(None),
+            }
+        }
+        Ok(self.pending.pop_front())
+    }
+
+    /// Reads the next record from the WAL as a batch
+    ///
+    /// A record holding a single entry is returned as a batch of one. Damage
+    /// anywhere in a record drops the whole batch, so recovery replays a
+    /// batch completely or not at all. Entries of an earlier batch not yet
+    /// returned by [`read_entry`](Self::read_entry) are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - An I/O error occurs
+    /// - Corruption is detected that the recovery mode does not tolerate
+    pub fn read_batch(&mut self) -> Result<Option<WriteBatch>> {
+        self.pending.clear();
+        loop {
+            let Some((start, record)) = self.read_record()? else {
+                return Ok(None);
+            };
+
+            match WriteBatch::decode(&record) {
+                Ok(batch) => {
+                    self.stats.valid_length = self.offset;
+                    return Ok(Some(batch));
+                }
+                Err(Error::Corruption(reason)) => {
+                    self.handle_damage(start, self.offset, &reason)?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Reassembles the next logical record from its fragments
+    ///
+    /// Returns the offset the record starts at along with its bytes. Damaged
+    /// fragments are handed to [`handle_damage`](Self::handle_damage), which
+    /// either fails the read or leaves the reader past the damage.
+    fn read_record(&mut self) -> Result<Option<(u64, Vec<u8>)>> {
+        let mut record = Vec::new();
+        // Where the record being reassembled starts, once its FIRST
+        // fragment has been read
+        let mut record_start = None;
+
+        loop {
+            if self.stopped || self.offset == self.file_size {
+                if let Some(start) = record_start.take() {
+                    self.handle_damage(start, self.file_size, "record cut short at end of file")?;
+                }
+                if self.advance_segment()? {
+                    continue;
+                }
+                return Ok(None);
+            }
+
+            let block_end = (self.offset / BLOCK_SIZE as u64 + 1) * BLOCK_SIZE as u64;
+            let block_left = block_end - self.offset;
+
+            // The writer pads a block tail too short for a header with zeros
+            if block_left < HEADER_SIZE as u64 {
+                self.skip_to(block_end.min(self.file_size))?;
+                continue;
+            }
+
+            let fragment_start = self.offset;
+            let damage_start = record_start.unwrap_or(fragment_start);
+
+            // A header cut short can only be a torn final write
+            if self.file_size - self.offset < HEADER_SIZE as u64 {
+                self.handle_damage(damage_start, self.file_size, "truncated fragment header")?;
+                record_start = None;
+                continue;
+            }
+
+            let mut header = [0u8; HEADER_SIZE];
+            self.reader().read_exact(&mut header)?;
+            self.offset += HEADER_SIZE as u64;
+            let checksum = u32::from_le_bytes(header[..4].try_into().unwrap());
+            let length = u16::from_le_bytes([header[4], header[5]]) as u64;
+            let tag = header[6];
+
+            // No fragment crosses a block boundary, so the length is corrupt
+            // and nothing more in this block can be trusted
+            if length > block_left - HEADER_SIZE as u64 {
+                self.skip_to(block_end.min(self.file_size))?;
+                self.handle_damage(
+                    damage_start,
+                    self.offset,
+                    "fragment crosses a block boundary",
+                )?;
+                record_start = None;
+                continue;
+            }
+
+            // A fragment extending past the end of the file is a torn write,
+            // or a corrupted length that cannot be told apart from one
+            if length > self.file_size - self.offset {
+                self.handle_damage(
+                    damage_start,
+                    self.file_size,
+                    "fragment extends past end of file",
+                )?;
+                record_start = None;
+                continue;
+            }
+
+            let mut payload = vec![0u8; length as usize];
+            self.reader().read_exact(&mut payload)?;
+            self.offset += length;
+
+            let fragment_type = match FragmentType::from_u8(tag) {
+                Some(fragment_type) if checksum == format::fragment_checksum(tag, &payload) => {
+                    fragment_type
+                }
+                _ => {
+                    // The length fit in the block, so try the next fragment
+                    // right after this one; if the length was damaged too,
+                    // the next header fails its checks and the block is
+                    // abandoned
+                    self.handle_damage(damage_start, self.offset, "fragment checksum mismatch")?;
+                    record_start = None;
+                    continue;
+                }
+            };
+
+            // A new record starting means the one being reassembled lost
+            // its last fragment
+            if let (FragmentType::Full | FragmentType::First, Some(start)) =
+                (fragment_type, record_start)
+            {
+                self.handle_damage(start, fragment_start, "record missing its last fragment")?;
+                record_start = None;
+                if self.stopped {
+                    continue;
+                }
+            }
+
+            match (fragment_type, record_start) {
+                (FragmentType::Full, _) => return Ok(Some((fragment_start, payload))),
+                (FragmentType::First, _) => {
+                    record = payload;
+                    record_start = Some(fragment_start);
+                }
+                (FragmentType::Middle | FragmentType::Last, None) => {
+                    self.handle_damage(
+                        fragment_start,
+                        self.offset,
+                        "fragment without a record start",
+                    )?;
+                }
+                (FragmentType::Middle, Some(_)) => record.extend_from_slice(&payload),
+                (FragmentType::Last, Some(start)) => {
+                    record.extend_from_slice(&payload);
+                    return Ok(Some((start, record)));
+                }
+            }
         }
+    }
 
-        let length = u32::from_le_bytes(length_buf) as usize;
+    /// Moves the read position forward to `offset`
+    fn skip_to(&mut self, offset: u64) -> Result<()> {
+        let distance = offset - self.offset;
+        self.reader().seek_relative(distance as i64)?;
+        self.offset = offset;
+        Ok(())
+    }
 
-        // Read the rest of the entry
-        let mut data = vec![0u8; length + 4];
-        data[..4].copy_from_slice(&length_buf);
-        self.reader.read_exact(&mut data[4..])?;
+    /// Applies the recovery mode to damaged bytes from `start` to `end`
+    ///
+    /// The reader must already be positioned at `end`. Damage reaching the
+    /// end of the file is treated as a torn tail.
+    fn handle_damage(&mut self, start: u64, end: u64, reason: &str) -> Result<()> {
+        let damage = if end >= self.file_size {
+            Damage::Tail
+        } else {
+            Damage::Middle
+        };
+
+        match (self.recovery_mode, damage) {
+            (WalRecoveryMode::AbsoluteConsistency, _)
+            | (WalRecoveryMode::TolerateCorruptedTailRecords, Damage::Middle) => {
+                return Err(Error::Corruption(format!(
+                    "WAL record at offset {}: {}",
+                    start, reason
+                )));
+            }
+            (WalRecoveryMode::SkipAnyCorruptedRecords, Damage::Middle) => {
+                // Reading resumes right after the damage
+                self.stats.dropped_bytes += end - start;
+                self.stats.dropped_records += 1;
+                return Ok(());
+            }
+            (WalRecoveryMode::PointInTimeRecovery, Damage::Middle) => {
+                self.stats.stopped_early = true;
+            }
+            (_, Damage::Tail) => {}
+        }
 
-        Ok(Some(WALEntry::decode(&data)?))
+        // Everything from the damage to the end of the file is dropped
+        self.stats.dropped_bytes += self.file_size - start;
+        self.stats.dropped_records += 1;
+        self.stopped = true;
+        Ok(())
+    }
+
+    /// Returns what the reader has skipped so far
+    pub fn stats(&self) -> WALRecoveryStats {
+        self.stats
     }
 
     /// Reads all remaining entries from the WAL
@@ -172,4 +510,342 @@ mod tests {
         assert_eq!(entries[1].operation, ferrisdb_core::Operation::Delete);
         assert_eq!(entries[1].value, Vec::<u8>::new());
     }
+
+    #[test]
+    fn test_wal_reader_torn_tail() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+
+        let mut record_ends = Vec::new();
+        {
+            let writer = WALWriter::new(&wal_path, SyncMode::Normal, 1024 * 1024).unwrap();
+            for i in 0..3 {
+                let entry = WALEntry::new_put(
+                    format!("key{}", i).into_bytes(),
+                    format!("value{}", i).into_bytes(),
+                    i as u64,
+                );
+                writer.append(&entry).unwrap();
+                record_ends.push(writer.size());
+            }
+        }
+
+        // Cut the last record at every possible byte, including its header
+        let data = std::fs::read(&wal_path).unwrap();
+        for cut in record_ends[1] + 1..record_ends[2] {
+            std::fs::write(&wal_path, &data[..cut as usize]).unwrap();
+
+            let mut reader = WALReader::new(&wal_path).unwrap();
+            let entries = reader.read_all().unwrap();
+            assert_eq!(entries.len(), 2, "cut at {}", cut);
+            assert_eq!(reader.stats().valid_length, record_ends[1]);
+        }
+    }
+
+    #[test]
+    fn test_wal_reader_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+
+        let mut batch = WriteBatch::new();
+        batch.put(b"key1".to_vec(), b"value1".to_vec());
+        batch.delete(b"key2".to_vec());
+        batch.set_base_timestamp(2);
+
+        let batch_end = {
+            let writer = WALWriter::new(&wal_path, SyncMode::Normal, 1024 * 1024).unwrap();
+            writer
+                .append(&WALEntry::new_put(b"key0".to_vec(), b"value0".to_vec(), 1))
+                .unwrap();
+            writer.append_batch(&batch).unwrap();
+            let batch_end = writer.size();
+            writer
+                .append(&WALEntry::new_put(b"key3".to_vec(), b"value3".to_vec(), 4))
+                .unwrap();
+            batch_end
+        };
+
+        // Entries of a batch are returned one at a time
+        let entries = WALReader::new(&wal_path).unwrap().read_all().unwrap();
+        let timestamps: Vec<_> = entries.iter().map(|entry| entry.timestamp).collect();
+        assert_eq!(timestamps, vec![1, 2, 3, 4]);
+        assert_eq!(entries[2].operation, ferrisdb_core::Operation::Delete);
+
+        // Whole records come back as batches
+        let mut reader = WALReader::new(&wal_path).unwrap();
+        assert_eq!(reader.read_batch().unwrap().unwrap().len(), 1);
+        assert_eq!(reader.read_batch().unwrap().unwrap(), batch);
+        assert_eq!(reader.read_batch().unwrap().unwrap().len(), 1);
+        assert!(reader.read_batch().unwrap().is_none());
+
+        // A torn batch is dropped as a whole
+        let data = std::fs::read(&wal_path).unwrap();
+        std::fs::write(&wal_path, &data[..batch_end as usize - 1]).unwrap();
+        let mut reader = WALReader::new(&wal_path).unwrap();
+        assert_eq!(reader.read_all().unwrap().len(), 1);
+        assert_eq!(reader.stats().dropped_records, 1);
+    }
+
+    /// Writes three records and returns the file contents and record end offsets
+    fn write_three_records(wal_path: &Path) -> (Vec<u8>, Vec<u64>) {
+        if wal_path.exists() {
+            std::fs::remove_file(wal_path).unwrap();
+        }
+        let writer = WALWriter::new(wal_path, SyncMode::Normal, 1024 * 1024).unwrap();
+        let mut record_ends = Vec::new();
+        for i in 0..3 {
+            let entry = WALEntry::new_put(
+                format!("key{}", i).into_bytes(),
+                format!("value{}", i).into_bytes(),
+                i as u64,
+            );
+            writer.append(&entry).unwrap();
+            record_ends.push(writer.size());
+        }
+        drop(writer);
+        (std::fs::read(wal_path).unwrap(), record_ends)
+    }
+
+    /// Reads `data` back with the given recovery mode
+    fn read_with_mode(
+        wal_path: &Path,
+        data: &[u8],
+        mode: WalRecoveryMode,
+    ) -> Result<(Vec<WALEntry>, WALRecoveryStats)> {
+        std::fs::write(wal_path, data).unwrap();
+        let mut reader = WALReader::with_recovery_mode(wal_path, mode).unwrap();
+        let entries = reader.read_all()?;
+        Ok((entries, reader.stats()))
+    }
+
+    #[test]
+    fn test_recovery_mode_tolerate_corrupted_tail() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let (data, ends) = write_three_records(&wal_path);
+        let mode = WalRecoveryMode::TolerateCorruptedTailRecords;
+
+        // A corrupted final record is dropped
+        let mut tail = data.clone();
+        *tail.last_mut().unwrap() ^= 0xFF;
+        let (entries, stats) = read_with_mode(&wal_path, &tail, mode).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(stats.valid_length, ends[1]);
+        assert_eq!(stats.dropped_bytes, ends[2] - ends[1]);
+        assert_eq!(stats.dropped_records, 1);
+        assert!(!stats.stopped_early);
+
+        // Damage followed by valid records is an error
+        let mut middle = data.clone();
+        middle[ends[0] as usize + 10] ^= 0xFF;
+        let result = read_with_mode(&wal_path, &middle, mode);
+        assert!(matches!(result, Err(Error::Corruption(_))));
+    }
+
+    #[test]
+    fn test_recovery_mode_absolute_consistency() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let (data, ends) = write_three_records(&wal_path);
+        let mode = WalRecoveryMode::AbsoluteConsistency;
+
+        let (entries, stats) = read_with_mode(&wal_path, &data, mode).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            stats,
+            WALRecoveryStats {
+                valid_length: ends[2],
+                ..Default::default()
+            }
+        );
+
+        // Even a torn final write fails recovery
+        let result = read_with_mode(&wal_path, &data[..data.len() - 1], mode);
+        assert!(matches!(result, Err(Error::Corruption(_))));
+    }
+
+    #[test]
+    fn test_recovery_mode_point_in_time() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let (mut data, ends) = write_three_records(&wal_path);
+
+        data[ends[0] as usize + 10] ^= 0xFF;
+        let (entries, stats) =
+            read_with_mode(&wal_path, &data, WalRecoveryMode::PointInTimeRecovery).unwrap();
+
+        // Nothing after the first damaged record is replayed
+        assert_eq!(entries.len(), 1);
+        assert_eq!(stats.valid_length, ends[0]);
+        assert_eq!(stats.dropped_bytes, ends[2] - ends[0]);
+        assert_eq!(stats.dropped_records, 1);
+        assert!(stats.stopped_early);
+    }
+
+    #[test]
+    fn test_recovery_mode_skip_any_corrupted() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let (mut data, ends) = write_three_records(&wal_path);
+
+        // Corrupt the middle record and tear the last one
+        data[ends[0] as usize + 10] ^= 0xFF;
+        data.truncate(ends[2] as usize - 1);
+        let (entries, stats) =
+            read_with_mode(&wal_path, &data, WalRecoveryMode::SkipAnyCorruptedRecords).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, b"key0");
+        assert_eq!(stats.valid_length, ends[0]);
+        assert_eq!(stats.dropped_bytes, ends[2] - 1 - ends[0]);
+        assert_eq!(stats.dropped_records, 2);
+
+        // With an intact tail the records around the damage are all replayed
+        let (mut data, _) = write_three_records(&wal_path);
+        data[ends[0] as usize + 10] ^= 0xFF;
+        let (entries, stats) =
+            read_with_mode(&wal_path, &data, WalRecoveryMode::SkipAnyCorruptedRecords).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].key, b"key2");
+        assert_eq!(stats.valid_length, ends[2]);
+        assert_eq!(stats.dropped_records, 1);
+    }
+
+    #[test]
+    fn test_large_records_span_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+
+        let entries: Vec<_> = [10, 3 * BLOCK_SIZE, 20, BLOCK_SIZE - 30, 5]
+            .iter()
+            .enumerate()
+            .map(|(i, &size)| WALEntry::new_put(vec![i as u8], vec![i as u8; size], i as u64))
+            .collect();
+        {
+            let writer = WALWriter::new(&wal_path, SyncMode::Normal, 1024 * 1024).unwrap();
+            for entry in &entries {
+                writer.append(entry).unwrap();
+            }
+            assert!(writer.size() > 4 * BLOCK_SIZE as u64);
+        }
+
+        let mut reader =
+            WALReader::with_recovery_mode(&wal_path, WalRecoveryMode::AbsoluteConsistency).unwrap();
+        assert_eq!(reader.read_all().unwrap(), entries);
+    }
+
+    #[test]
+    fn test_corrupted_length_resyncs_at_next_block() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+
+        let mut record_starts = Vec::new();
+        {
+            let writer = WALWriter::new(&wal_path, SyncMode::Normal, 1024 * 1024).unwrap();
+            for i in 0..1000u64 {
+                record_starts.push(writer.size());
+                let entry = WALEntry::new_put(i.to_be_bytes().to_vec(), b"value".to_vec(), i);
+                writer.append(&entry).unwrap();
+            }
+        }
+
+        // A length pointing far past the fragment used to derail everything
+        // after it; now only the rest of the first block is lost
+        let mut data = std::fs::read(&wal_path).unwrap();
+        data[4..6].copy_from_slice(&u16::MAX.to_le_bytes());
+        let (entries, stats) =
+            read_with_mode(&wal_path, &data, WalRecoveryMode::SkipAnyCorruptedRecords).unwrap();
+
+        // The record straddling the boundary loses its first fragment
+        let first_whole = record_starts
+            .iter()
+            .position(|&start| start >= BLOCK_SIZE as u64)
+            .unwrap();
+        let timestamps: Vec<_> = entries.iter().map(|e| e.timestamp).collect();
+        assert_eq!(timestamps, (first_whole as u64..1000).collect::<Vec<_>>());
+        assert_eq!(stats.dropped_records, 2);
+        assert!(!stats.stopped_early);
+
+        let result = read_with_mode(
+            &wal_path,
+            &data,
+            WalRecoveryMode::TolerateCorruptedTailRecords,
+        );
+        assert!(matches!(result, Err(Error::Corruption(_))));
+    }
+
+    #[test]
+    fn test_random_corruption_does_not_stop_recovery() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            if wal_path.exists() {
+                std::fs::remove_file(&wal_path).unwrap();
+            }
+
+            // Mostly small records, with the odd one spanning several blocks
+            let mut written = Vec::new();
+            {
+                let writer = WALWriter::new(&wal_path, SyncMode::Normal, u64::MAX).unwrap();
+                for timestamp in 0..300 {
+                    let size = if rng.gen_bool(0.02) {
+                        rng.gen_range(BLOCK_SIZE..3 * BLOCK_SIZE)
+                    } else {
+                        rng.gen_range(0..400)
+                    };
+                    let entry = WALEntry::new_put(
+                        format!("key{}", timestamp).into_bytes(),
+                        vec![rng.gen(); size],
+                        timestamp,
+                    );
+                    let start = writer.size();
+                    writer.append(&entry).unwrap();
+                    written.push((entry, start, writer.size()));
+                }
+            }
+
+            let mut data = std::fs::read(&wal_path).unwrap();
+            let mut damaged_blocks = Vec::new();
+            for _ in 0..rng.gen_range(1..6) {
+                let offset = rng.gen_range(0..data.len());
+                data[offset] ^= rng.gen_range(1..=u8::MAX);
+                damaged_blocks.push(offset / BLOCK_SIZE);
+            }
+
+            let (entries, _) =
+                read_with_mode(&wal_path, &data, WalRecoveryMode::SkipAnyCorruptedRecords).unwrap();
+
+            // Everything returned was written, in order
+            let mut remaining = written.iter().map(|(entry, _, _)| entry);
+            for entry in &entries {
+                assert!(remaining.any(|e| e == entry), "seed {}", seed);
+            }
+
+            // Every record in blocks untouched by the damage is recovered
+            for (entry, start, end) in &written {
+                let blocks = *start as usize / BLOCK_SIZE..=(*end as usize - 1) / BLOCK_SIZE;
+                if !damaged_blocks.iter().any(|block| blocks.contains(block)) {
+                    assert!(
+                        entries.contains(entry),
+                        "seed {}: lost record at {}",
+                        seed,
+                        start
+                    );
+                }
+            }
+
+            // Point-in-time recovery returns an intact prefix
+            let (prefix, _) =
+                read_with_mode(&wal_path, &data, WalRecoveryMode::PointInTimeRecovery).unwrap();
+            assert!(written
+                .iter()
+                .zip(&prefix)
+                .all(|((expected, _, _), entry)| expected == entry));
+        }
+    }
 }
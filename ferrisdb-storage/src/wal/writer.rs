@@ -1,11 +1,15 @@
+use super::format::{self, BLOCK_SIZE};
 use super::WALEntry;
+use crate::write_batch::WriteBatch;
 use ferrisdb_core::{Error, Result, SyncMode};
-use parking_lot::Mutex;
+use parking_lot::{Condvar, Mutex};
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write};
+use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Writer for the Write-Ahead Log
 ///
@@ -13,10 +17,23 @@ use std::sync::Arc;
 /// guarantees. It tracks the file size and returns an error when the size
 /// limit is reached, indicating that rotation is needed.
 ///
+/// Records are split into fragments laid out in 32 KiB blocks, so a record
+/// may be larger than a block. The size limit counts the fragment headers
+/// and block padding as well as the records.
+///
 /// # Thread Safety
 ///
 /// The writer is thread-safe and can be shared across multiple threads.
-/// Internal locking ensures that entries are written atomically.
+/// Concurrent appends are committed in groups: records queue up while a
+/// write is in progress, and the next thread to find no write in progress
+/// becomes the leader. The leader writes every queued record with a single
+/// `write_all` and at most one sync, then wakes the other threads in the
+/// group with the result. Each record is written whole, in the order its
+/// append was queued. [`stats`](WALWriter::stats) reports group sizes and
+/// sync latency.
+///
+/// A failed write leaves the end of the log in an unknown state, so every
+/// later append fails too.
 ///
 /// # Example
 ///
@@ -38,9 +55,71 @@ use std::sync::Arc;
 pub struct WALWriter {
     file: Arc<Mutex<BufWriter<File>>>,
     path: PathBuf,
+    /// Bytes written to the file
     size: AtomicU64,
     sync_mode: SyncMode,
     size_limit: u64,
+    /// Records waiting to be written and the results of written groups
+    queue: Mutex<CommitQueue>,
+    /// Signalled when a group has been written
+    committed: Condvar,
+    /// Group commit counters, shared by every segment of a
+    /// [`WALManager`](super::WALManager)
+    stats: Arc<Mutex<WALWriterStats>>,
+}
+
+/// Counters describing group commit
+///
+/// The average group size is `records / groups`, and the average sync
+/// latency is `sync_micros / syncs`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WALWriterStats {
+    /// Records written
+    pub records: u64,
+    /// Groups written, each with a single write
+    pub groups: u64,
+    /// Most records written in one group
+    pub max_group_size: u64,
+    /// Syncs to disk, from groups and from [`WALWriter::sync`]
+    pub syncs: u64,
+    /// Total time spent in syncs, in microseconds
+    pub sync_micros: u64,
+    /// Longest single sync, in microseconds
+    pub max_sync_micros: u64,
+}
+
+impl WALWriterStats {
+    /// Counts a sync that took `elapsed`
+    fn record_sync(&mut self, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        self.syncs += 1;
+        self.sync_micros += micros;
+        self.max_sync_micros = self.max_sync_micros.max(micros);
+    }
+}
+
+/// Group commit state, guarded by `WALWriter::queue`
+#[derive(Default)]
+struct CommitQueue {
+    /// Fragments of the records waiting for a leader, back to back
+    pending: Vec<u8>,
+    /// Offset into its block of the end of the queued records
+    ///
+    /// Records are framed when they are queued, which is the order they
+    /// are written in.
+    block_offset: usize,
+    /// Tickets of the records in `pending`, in order
+    tickets: Vec<u64>,
+    /// Ticket for the next append
+    next_ticket: u64,
+    /// Set while a leader is writing a group
+    leader_active: bool,
+    /// Bytes queued or being written, counted against the size limit
+    unwritten_bytes: u64,
+    /// Results for followers whose group has been written
+    results: HashMap<u64, Result<()>>,
+    /// Why an earlier write failed, if one did
+    failure: Option<String>,
 }
 
 impl WALWriter {
@@ -70,9 +149,22 @@ impl WALWriter {
             size: AtomicU64::new(size),
             sync_mode,
             size_limit,
+            queue: Mutex::new(CommitQueue {
+                block_offset: (size % BLOCK_SIZE as u64) as usize,
+                ..CommitQueue::default()
+            }),
+            committed: Condvar::new(),
+            stats: Arc::default(),
         })
     }
 
+    /// Counts this writer's groups and syncs in `stats`, which may be shared
+    /// with other writers
+    pub(super) fn with_stats(mut self, stats: Arc<Mutex<WALWriterStats>>) -> Self {
+        self.stats = stats;
+        self
+    }
+
     /// Appends an entry to the WAL
     ///
     /// The entry is encoded and written to the file. Depending on the
@@ -84,32 +176,162 @@ impl WALWriter {
     /// - The entry would exceed the size limit
     /// - An I/O error occurs during write
     pub fn append(&self, entry: &WALEntry) -> Result<()> {
-        let encoded = entry.encode();
-        let entry_size = encoded.len() as u64;
+        self.append_record(&entry.encode())
+    }
 
-        // Check if we need to rotate
-        if self.size.load(Ordering::Relaxed) + entry_size > self.size_limit {
-            return Err(Error::StorageEngine(
+    /// Appends a batch to the WAL as a single record
+    ///
+    /// The record is written and synced like an entry, so either the whole
+    /// batch is recovered after a crash or none of it is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The record would exceed the size limit
+    /// - An I/O error occurs during write
+    pub fn append_batch(&self, batch: &WriteBatch) -> Result<()> {
+        self.append_record(&batch.encode())
+    }
+
+    /// Queues an encoded record and waits until its group has been written
+    fn append_record(&self, encoded: &[u8]) -> Result<()> {
+        match self.try_queue_record(encoded)? {
+            Some(ticket) => self.wait_for_commit(ticket),
+            None => Err(Error::StorageEngine(
                 "WAL file size limit reached".to_string(),
-            ));
+            )),
+        }
+    }
+
+    /// Queues an encoded record if it fits within the size limit
+    ///
+    /// Returns the ticket to pass to [`wait_for_commit`](Self::wait_for_commit),
+    /// or `None`, without queueing anything, if the record would take the
+    /// file past the limit. Records are written in the order they are
+    /// queued, but a queued record is only guaranteed to be written once
+    /// its ticket has been waited on.
+    pub(super) fn try_queue_record(&self, encoded: &[u8]) -> Result<Option<u64>> {
+        let mut queue = self.queue.lock();
+        if let Some(reason) = &queue.failure {
+            return Err(Self::failed_error(reason));
+        }
+        let record_size = format::framed_size(encoded.len(), queue.block_offset) as u64;
+
+        // Check if we need to rotate
+        let size = self.size.load(Ordering::Relaxed) + queue.unwritten_bytes;
+        if size + record_size > self.size_limit {
+            return Ok(None);
+        }
+
+        let ticket = queue.next_ticket;
+        queue.next_ticket += 1;
+        let CommitQueue {
+            pending,
+            block_offset,
+            ..
+        } = &mut *queue;
+        format::frame(encoded, block_offset, pending);
+        queue.tickets.push(ticket);
+        queue.unwritten_bytes += record_size;
+        Ok(Some(ticket))
+    }
+
+    /// Waits until the record queued with `ticket` has been written
+    ///
+    /// If no group is being written, the caller becomes the leader and
+    /// writes every queued record, including ones queued by threads that
+    /// have not started waiting yet.
+    pub(super) fn wait_for_commit(&self, ticket: u64) -> Result<()> {
+        let mut queue = self.queue.lock();
+
+        // Results are posted before the leader steps down, so a record
+        // without a result is still queued once no leader is active
+        loop {
+            if let Some(result) = queue.results.remove(&ticket) {
+                return result;
+            }
+            if !queue.leader_active {
+                break;
+            }
+            self.committed.wait(&mut queue);
+        }
+
+        queue.leader_active = true;
+        let group = std::mem::take(&mut queue.pending);
+        let tickets = std::mem::take(&mut queue.tickets);
+        let failure = queue.failure.clone();
+        drop(queue);
+
+        let outcome = match failure {
+            Some(reason) => Err(Self::failed_error(&reason)),
+            None => self.write_group(&group),
+        };
+
+        let mut queue = self.queue.lock();
+        queue.unwritten_bytes -= group.len() as u64;
+        let result = match outcome {
+            Ok(sync_time) => {
+                self.size.fetch_add(group.len() as u64, Ordering::Relaxed);
+                let mut stats = self.stats.lock();
+                stats.records += tickets.len() as u64;
+                stats.groups += 1;
+                stats.max_group_size = stats.max_group_size.max(tickets.len() as u64);
+                if let Some(elapsed) = sync_time {
+                    stats.record_sync(elapsed);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                queue.failure.get_or_insert_with(|| e.to_string());
+                Err(e)
+            }
+        };
+
+        for &follower in tickets.iter().filter(|&&t| t != ticket) {
+            let copy = match &result {
+                Ok(()) => Ok(()),
+                Err(e) => Err(Self::copy_error(e)),
+            };
+            queue.results.insert(follower, copy);
         }
+        queue.leader_active = false;
+        self.committed.notify_all();
+        result
+    }
 
+    /// Writes a group of records and applies the sync mode
+    ///
+    /// Returns how long the sync took, if there was one.
+    fn write_group(&self, group: &[u8]) -> Result<Option<Duration>> {
         let mut file = self.file.lock();
-        file.write_all(&encoded)?;
+        file.write_all(group)?;
 
         match self.sync_mode {
-            SyncMode::None => {}
+            SyncMode::None => Ok(None),
             SyncMode::Normal => {
                 file.flush()?;
+                Ok(None)
             }
             SyncMode::Full => {
                 file.flush()?;
+                let start = Instant::now();
                 file.get_ref().sync_all()?;
+                Ok(Some(start.elapsed()))
             }
         }
+    }
 
-        self.size.fetch_add(entry_size, Ordering::Relaxed);
-        Ok(())
+    /// The error returned once an earlier write has failed
+    fn failed_error(reason: &str) -> Error {
+        Error::StorageEngine(format!("WAL write failed earlier: {}", reason))
+    }
+
+    /// Duplicates a group's error for each follower
+    fn copy_error(error: &Error) -> Error {
+        match error {
+            Error::Io(e) => Error::Io(io::Error::new(e.kind(), e.to_string())),
+            other => Error::StorageEngine(other.to_string()),
+        }
     }
 
     /// Forces a sync of all buffered data to disk
@@ -117,12 +339,22 @@ impl WALWriter {
     /// This ensures durability by flushing the buffer and calling
     /// fsync on the underlying file.
     pub fn sync(&self) -> Result<()> {
-        let mut file = self.file.lock();
-        file.flush()?;
-        file.get_ref().sync_all()?;
+        let elapsed = {
+            let mut file = self.file.lock();
+            file.flush()?;
+            let start = Instant::now();
+            file.get_ref().sync_all()?;
+            start.elapsed()
+        };
+        self.stats.lock().record_sync(elapsed);
         Ok(())
     }
 
+    /// Returns group commit counters
+    pub fn stats(&self) -> WALWriterStats {
+        *self.stats.lock()
+    }
+
     /// Returns the current size of the WAL file
     pub fn size(&self) -> u64 {
         self.size.load(Ordering::Relaxed)
@@ -132,6 +364,12 @@ impl WALWriter {
     pub fn path(&self) -> &Path {
         &self.path
     }
+
+    /// Locks the file, so the next leader blocks before writing its group
+    #[cfg(test)]
+    pub(crate) fn lock_file(&self) -> parking_lot::MutexGuard<'_, BufWriter<File>> {
+        self.file.lock()
+    }
 }
 
 #[cfg(test)]
@@ -173,4 +411,101 @@ mod tests {
         let result = writer.append(&entry);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_concurrent_appends_share_groups() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let writer = Arc::new(WALWriter::new(&wal_path, SyncMode::Full, 1024 * 1024).unwrap());
+
+        let append = |timestamp: u64| {
+            let writer = Arc::clone(&writer);
+            std::thread::spawn(move || {
+                let key = format!("key{}", timestamp).into_bytes();
+                writer.append(&WALEntry::new_put(key, b"value".to_vec(), timestamp))
+            })
+        };
+
+        // Hold up the first leader's write so the other appends queue
+        // behind it
+        let file = writer.file.lock();
+        let first = append(0);
+        while !writer.queue.lock().leader_active {
+            std::thread::yield_now();
+        }
+        let followers: Vec<_> = (1..=8).map(append).collect();
+        while writer.queue.lock().tickets.len() < 8 {
+            std::thread::yield_now();
+        }
+        drop(file);
+
+        first.join().unwrap().unwrap();
+        for follower in followers {
+            follower.join().unwrap().unwrap();
+        }
+
+        // The queued appends were written by one leader with one sync
+        let stats = writer.stats();
+        assert_eq!(stats.records, 9);
+        assert_eq!(stats.groups, 2);
+        assert_eq!(stats.max_group_size, 8);
+        assert_eq!(stats.syncs, 2);
+        assert!(stats.max_sync_micros <= stats.sync_micros);
+
+        let entries = crate::wal::WALReader::new(&wal_path)
+            .unwrap()
+            .read_all()
+            .unwrap();
+        let mut timestamps: Vec<_> = entries.iter().map(|entry| entry.timestamp).collect();
+        assert_eq!(timestamps[0], 0);
+        timestamps.sort_unstable();
+        assert_eq!(timestamps, (0..=8).collect::<Vec<_>>());
+        assert_eq!(writer.size(), std::fs::metadata(&wal_path).unwrap().len());
+    }
+
+    #[test]
+    fn test_concurrent_appends_are_all_written() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let writer = Arc::new(WALWriter::new(&wal_path, SyncMode::Full, 1024 * 1024).unwrap());
+
+        let handles: Vec<_> = (0..8u64)
+            .map(|thread| {
+                let writer = Arc::clone(&writer);
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        let key = format!("t{}_key{}", thread, i).into_bytes();
+                        let entry = WALEntry::new_put(key, b"value".to_vec(), thread * 100 + i);
+                        writer.append(&entry).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stats = writer.stats();
+        assert_eq!(stats.records, 200);
+        assert_eq!(stats.syncs, stats.groups);
+        assert!(stats.groups <= 200);
+
+        // Each thread's records are in the order it appended them
+        let entries = crate::wal::WALReader::new(&wal_path)
+            .unwrap()
+            .read_all()
+            .unwrap();
+        assert_eq!(entries.len(), 200);
+        for thread in 0..8 {
+            let timestamps: Vec<_> = entries
+                .iter()
+                .map(|entry| entry.timestamp)
+                .filter(|timestamp| timestamp / 100 == thread)
+                .collect();
+            assert_eq!(
+                timestamps,
+                (0..25).map(|i| thread * 100 + i).collect::<Vec<_>>()
+            );
+        }
+    }
 }
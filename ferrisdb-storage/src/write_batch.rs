@@ -0,0 +1,384 @@
+//! Atomic groups of writes
+//!
+//! A [`WriteBatch`] collects puts and deletes that must take effect
+//! together. The storage engine assigns the batch a run of consecutive
+//! timestamps, logs it as a single WAL record and applies it to the
+//! MemTable before publishing any of it, so readers and recovery see either
+//! every write in the batch or none of them.
+
+use crate::wal::WALEntry;
+use bytes::{Buf, BufMut, BytesMut};
+use crc32fast::Hasher;
+use ferrisdb_core::{Error, Key, Operation, Result, Timestamp, Value};
+use serde::{Deserialize, Serialize};
+
+/// Operation tag marking a WAL record as a batch
+///
+/// Single entries use the tags of their operation, so a batch record can
+/// never be mistaken for one.
+const BATCH_TAG: u8 = 3;
+
+/// Size of the length, checksum, base timestamp, tag and count fields
+const HEADER_SIZE: usize = 4 + 4 + 8 + 1 + 4;
+
+/// A group of puts and deletes applied atomically
+///
+/// Writes are applied in the order they were added; the first gets the
+/// batch's base timestamp and each later one the next timestamp after it,
+/// so a later write to the same key wins.
+///
+/// # Binary Format
+///
+/// A batch is logged as one checksummed WAL record:
+///
+/// ```text
+/// +------------+------------+------------+-------+------------+
+/// | Length(4B) | CRC32(4B)  | Base(8B)   | 3(1B) | Count(4B)  |
+/// +------------+------------+------------+-------+------------+
+/// | Op(1B) | Key Len(4B) | Key(var) | Val Len(4B) | Value(var) |  × Count
+/// +--------+-------------+----------+-------------+------------+
+/// ```
+///
+/// A batch holding a single write is logged as a plain [`WALEntry`] record.
+/// [`encode`](Self::encode) produces the logical record the WAL stores in
+/// block fragments, which is also the form a replication layer should ship;
+/// the type implements serde's traits as well for callers that already have
+/// a wire format.
+///
+/// # Example
+///
+/// ```no_run
+/// use ferrisdb_storage::{StorageConfig, StorageEngine, WriteBatch};
+///
+/// let engine = StorageEngine::new(StorageConfig::default())?;
+///
+/// let mut batch = WriteBatch::new();
+/// batch.put(b"account:alice".to_vec(), b"90".to_vec());
+/// batch.put(b"account:bob".to_vec(), b"110".to_vec());
+/// batch.delete(b"transfer:pending".to_vec());
+/// engine.write(batch)?;
+/// # Ok::<(), ferrisdb_core::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteBatch {
+    /// Timestamp of the first write; assigned by the engine
+    base_timestamp: Timestamp,
+    writes: Vec<BatchWrite>,
+}
+
+/// One write in a batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct BatchWrite {
+    operation: Operation,
+    key: Key,
+    /// Empty for deletes
+    value: Value,
+}
+
+impl WriteBatch {
+    /// Creates an empty batch
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a write of `value` to `key`
+    pub fn put(&mut self, key: Key, value: Value) {
+        self.writes.push(BatchWrite {
+            operation: Operation::Put,
+            key,
+            value,
+        });
+    }
+
+    /// Adds a deletion of `key`
+    pub fn delete(&mut self, key: Key) {
+        self.writes.push(BatchWrite {
+            operation: Operation::Delete,
+            key,
+            value: Vec::new(),
+        });
+    }
+
+    /// Removes every write from the batch
+    pub fn clear(&mut self) {
+        self.writes.clear();
+    }
+
+    /// Returns the number of writes in the batch
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    /// Returns true if the batch holds no writes
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Returns the timestamp of the first write in the batch
+    pub fn base_timestamp(&self) -> Timestamp {
+        self.base_timestamp
+    }
+
+    /// Sets the timestamp of the first write in the batch
+    ///
+    /// The storage engine overwrites this when the batch is written.
+    pub fn set_base_timestamp(&mut self, timestamp: Timestamp) {
+        self.base_timestamp = timestamp;
+    }
+
+    /// Returns the timestamp of the last write in the batch
+    ///
+    /// For an empty batch this is one before the base timestamp.
+    pub fn last_timestamp(&self) -> Timestamp {
+        (self.base_timestamp + self.writes.len() as u64).wrapping_sub(1)
+    }
+
+    /// Returns the writes in order, each with its timestamp
+    pub fn entries(&self) -> impl Iterator<Item = WALEntry> + '_ {
+        (self.base_timestamp..)
+            .zip(&self.writes)
+            .map(|(timestamp, write)| WALEntry {
+                timestamp,
+                operation: write.operation,
+                key: write.key.clone(),
+                value: write.value.clone(),
+            })
+    }
+
+    /// Consumes the batch, returning the writes in order, each with its
+    /// timestamp
+    pub fn into_entries(self) -> impl Iterator<Item = WALEntry> {
+        (self.base_timestamp..)
+            .zip(self.writes)
+            .map(|(timestamp, write)| WALEntry {
+                timestamp,
+                operation: write.operation,
+                key: write.key,
+                value: write.value,
+            })
+    }
+
+    /// Encodes the batch as a WAL record
+    ///
+    /// All integers are encoded in little-endian format.
+    pub fn encode(&self) -> Vec<u8> {
+        if let [write] = self.writes.as_slice() {
+            let entry = WALEntry {
+                timestamp: self.base_timestamp,
+                operation: write.operation,
+                key: write.key.clone(),
+                value: write.value.clone(),
+            };
+            return entry.encode();
+        }
+
+        let mut buf = BytesMut::new();
+
+        // Reserve space for length and checksum
+        buf.put_u32_le(0);
+        buf.put_u32_le(0);
+
+        buf.put_u64_le(self.base_timestamp);
+        buf.put_u8(BATCH_TAG);
+        buf.put_u32_le(self.writes.len() as u32);
+
+        for write in &self.writes {
+            buf.put_u8(match write.operation {
+                Operation::Put => 1,
+                Operation::Delete => 2,
+            });
+            buf.put_u32_le(write.key.len() as u32);
+            buf.put_slice(&write.key);
+            buf.put_u32_le(write.value.len() as u32);
+            buf.put_slice(&write.value);
+        }
+
+        let total_len = buf.len() - 4;
+        buf[0..4].copy_from_slice(&(total_len as u32).to_le_bytes());
+
+        let mut hasher = Hasher::new();
+        hasher.update(&buf[8..]);
+        let checksum = hasher.finalize();
+        buf[4..8].copy_from_slice(&checksum.to_le_bytes());
+
+        buf.to_vec()
+    }
+
+    /// Decodes a WAL record into a batch
+    ///
+    /// A record holding a single [`WALEntry`] becomes a batch of one.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Corruption` if:
+    /// - The data is too small
+    /// - The length doesn't match
+    /// - The checksum is invalid
+    /// - An operation type is unknown
+    /// - The writes do not fill the record exactly
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < HEADER_SIZE || data[16] != BATCH_TAG {
+            return WALEntry::decode(data).map(Self::from);
+        }
+
+        let mut cursor = data;
+
+        let length = cursor.get_u32_le() as usize;
+        if data.len() != length + 4 {
+            return Err(Error::Corruption("WAL batch length mismatch".to_string()));
+        }
+
+        let expected_checksum = cursor.get_u32_le();
+        let mut hasher = Hasher::new();
+        hasher.update(&data[8..]);
+        if expected_checksum != hasher.finalize() {
+            return Err(Error::Corruption("WAL batch checksum mismatch".to_string()));
+        }
+
+        let base_timestamp = cursor.get_u64_le();
+        cursor.advance(1);
+        let count = cursor.get_u32_le() as usize;
+
+        // Every write takes at least nine bytes, which bounds a corrupted count
+        let mut writes = Vec::with_capacity(count.min(cursor.len() / 9));
+        for _ in 0..count {
+            if cursor.len() < 5 {
+                return Err(Error::Corruption("WAL batch truncated".to_string()));
+            }
+            let operation = match cursor.get_u8() {
+                1 => Operation::Put,
+                2 => Operation::Delete,
+                _ => return Err(Error::Corruption("Invalid operation type".to_string())),
+            };
+            let key = Self::decode_slice(&mut cursor, "Key length exceeds data")?;
+            if cursor.len() < 4 {
+                return Err(Error::Corruption("WAL batch truncated".to_string()));
+            }
+            let value = Self::decode_slice(&mut cursor, "Value length exceeds data")?;
+            writes.push(BatchWrite {
+                operation,
+                key,
+                value,
+            });
+        }
+
+        if !cursor.is_empty() {
+            return Err(Error::Corruption("WAL batch has trailing data".to_string()));
+        }
+
+        Ok(Self {
+            base_timestamp,
+            writes,
+        })
+    }
+
+    /// Reads a length-prefixed byte string; the caller has checked that the
+    /// length prefix is present
+    fn decode_slice(cursor: &mut &[u8], overflow: &str) -> Result<Vec<u8>> {
+        let len = cursor.get_u32_le() as usize;
+        if cursor.len() < len {
+            return Err(Error::Corruption(overflow.to_string()));
+        }
+        let bytes = cursor[..len].to_vec();
+        cursor.advance(len);
+        Ok(bytes)
+    }
+}
+
+impl From<WALEntry> for WriteBatch {
+    /// Wraps a single entry, keeping its timestamp as the base timestamp
+    fn from(entry: WALEntry) -> Self {
+        Self {
+            base_timestamp: entry.timestamp,
+            writes: vec![BatchWrite {
+                operation: entry.operation,
+                key: entry.key,
+                value: entry.value,
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_batch() -> WriteBatch {
+        let mut batch = WriteBatch::new();
+        batch.put(b"key1".to_vec(), b"value1".to_vec());
+        batch.delete(b"key2".to_vec());
+        batch.put(b"key1".to_vec(), b"value2".to_vec());
+        batch.set_base_timestamp(10);
+        batch
+    }
+
+    #[test]
+    fn test_entries_get_consecutive_timestamps() {
+        let batch = sample_batch();
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch.last_timestamp(), 12);
+
+        let entries: Vec<_> = batch.entries().collect();
+        assert_eq!(
+            entries,
+            vec![
+                WALEntry::new_put(b"key1".to_vec(), b"value1".to_vec(), 10),
+                WALEntry::new_delete(b"key2".to_vec(), 11),
+                WALEntry::new_put(b"key1".to_vec(), b"value2".to_vec(), 12),
+            ]
+        );
+        assert_eq!(batch.clone().into_entries().collect::<Vec<_>>(), entries);
+    }
+
+    #[test]
+    fn test_encode_decode_batch() {
+        let batch = sample_batch();
+        assert_eq!(WriteBatch::decode(&batch.encode()).unwrap(), batch);
+
+        let empty = WriteBatch::new();
+        assert_eq!(WriteBatch::decode(&empty.encode()).unwrap(), empty);
+    }
+
+    #[test]
+    fn test_single_write_is_a_plain_entry() {
+        let mut batch = WriteBatch::new();
+        batch.put(b"key".to_vec(), b"value".to_vec());
+        batch.set_base_timestamp(7);
+
+        let encoded = batch.encode();
+        assert_eq!(
+            WALEntry::decode(&encoded).unwrap(),
+            WALEntry::new_put(b"key".to_vec(), b"value".to_vec(), 7)
+        );
+        assert_eq!(WriteBatch::decode(&encoded).unwrap(), batch);
+    }
+
+    #[test]
+    fn test_corruption_detection() {
+        let encoded = sample_batch().encode();
+
+        // Flipping any byte after the length prefix breaks the checksum or
+        // the length check
+        for i in 4..encoded.len() {
+            let mut corrupted = encoded.clone();
+            corrupted[i] ^= 0xFF;
+            assert!(
+                matches!(WriteBatch::decode(&corrupted), Err(Error::Corruption(_))),
+                "byte {}",
+                i
+            );
+        }
+
+        assert!(matches!(
+            WriteBatch::decode(&encoded[..encoded.len() - 1]),
+            Err(Error::Corruption(_))
+        ));
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let batch = sample_batch();
+        let bytes = bincode::serialize(&batch).unwrap();
+        assert_eq!(bincode::deserialize::<WriteBatch>(&bytes).unwrap(), batch);
+    }
+}